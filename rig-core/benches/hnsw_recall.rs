@@ -0,0 +1,171 @@
+//! Compare the recall and speed of approximate (HNSW) vector searches with the exact
+//! (brute-force) search of `InMemoryVectorStore`.
+//!
+//! Run with `cargo bench -p rig-core --bench hnsw_recall`. The size of the corpus can be
+//! changed with the `HNSW_BENCH_DOCUMENTS` and `HNSW_BENCH_DIMS` environment variables.
+use std::time::{Duration, Instant};
+
+use rig::{
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel},
+    vector_store::{hnsw::HnswParams, in_memory_store::InMemoryVectorStore, VectorStoreIndex},
+    OneOrMany,
+};
+
+const NUM_QUERIES: usize = 200;
+const NUM_CLUSTERS: usize = 100;
+const TOP_N: usize = 10;
+
+/// Embedding model parsing queries written as comma separated numbers
+#[derive(Clone)]
+struct ParseModel(usize);
+
+impl EmbeddingModel for ParseModel {
+    const MAX_DOCUMENTS: usize = 1;
+
+    fn ndims(&self) -> usize {
+        self.0
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(texts
+            .into_iter()
+            .map(|text| Embedding {
+                vec: text.split(',').map(|x| x.parse().unwrap()).collect(),
+                document: text,
+            })
+            .collect())
+    }
+}
+
+/// xorshift64 generator of numbers in `[-1, 1]`
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    fn vector(&mut self, dims: usize) -> Vec<f64> {
+        (0..dims).map(|_| self.next()).collect()
+    }
+}
+
+/// Random vectors grouped around cluster centers, like embeddings of related documents
+fn clustered_vectors(random: &mut Random, centers: &[Vec<f64>], count: usize) -> Vec<Vec<f64>> {
+    (0..count)
+        .map(|i| {
+            let center = &centers[i % centers.len()];
+            center.iter().map(|x| x + 0.3 * random.next()).collect()
+        })
+        .collect()
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+async fn run_queries(
+    store: InMemoryVectorStore<String>,
+    dims: usize,
+    queries: &[String],
+) -> (Vec<Vec<String>>, Duration) {
+    let index = store.index(ParseModel(dims));
+
+    let start = Instant::now();
+    let mut results = Vec::with_capacity(queries.len());
+    for query in queries {
+        let ids = index.top_n_ids(query, TOP_N).await.unwrap();
+        results.push(ids.into_iter().map(|(_, id)| id).collect());
+    }
+
+    (results, start.elapsed() / queries.len() as u32)
+}
+
+#[tokio::main]
+async fn main() {
+    let num_documents = env_usize("HNSW_BENCH_DOCUMENTS", 20_000);
+    let dims = env_usize("HNSW_BENCH_DIMS", 64);
+
+    let mut random = Random(0x2545f4914f6cdd1d);
+    let centers = (0..NUM_CLUSTERS)
+        .map(|_| random.vector(dims))
+        .collect::<Vec<_>>();
+    let documents = clustered_vectors(&mut random, &centers, num_documents)
+        .into_iter()
+        .enumerate()
+        .map(|(i, vec)| {
+            let id = format!("doc{i}");
+            let embedding = Embedding {
+                document: id.clone(),
+                vec,
+            };
+            (id.clone(), id, OneOrMany::one(embedding))
+        })
+        .collect::<Vec<_>>();
+    let queries = clustered_vectors(&mut random, &centers, NUM_QUERIES)
+        .into_iter()
+        .map(|query| {
+            query
+                .iter()
+                .map(f64::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>();
+
+    let store = InMemoryVectorStore::from_documents_with_ids(documents);
+
+    println!("{num_documents} documents, {dims} dimensions, {NUM_QUERIES} queries, top {TOP_N}\n");
+    println!(
+        "{:<32} {:>12} {:>14} {:>10}",
+        "index", "build", "query", "recall"
+    );
+
+    let (exact, query_time) = run_queries(store.clone(), dims, &queries).await;
+    println!(
+        "{:<32} {:>12} {:>14?} {:>10.3}",
+        "brute force", "-", query_time, 1.0
+    );
+
+    for (m, ef_construction, ef_search) in [
+        (8, 100, 16),
+        (16, 200, 32),
+        (16, 200, 64),
+        (16, 200, 128),
+        (32, 400, 128),
+    ] {
+        let params = HnswParams::default()
+            .m(m)
+            .ef_construction(ef_construction)
+            .ef_search(ef_search);
+
+        let start = Instant::now();
+        let approximate_store = store.clone().with_hnsw(params);
+        let build_time = start.elapsed();
+
+        let (approximate, query_time) = run_queries(approximate_store, dims, &queries).await;
+        let found = exact
+            .iter()
+            .zip(&approximate)
+            .map(|(exact, approximate)| approximate.iter().filter(|id| exact.contains(id)).count())
+            .sum::<usize>();
+        let recall = found as f64 / (NUM_QUERIES * TOP_N) as f64;
+
+        println!(
+            "{:<32} {:>12?} {:>14?} {:>10.3}",
+            format!("hnsw m={m} ef_c={ef_construction} ef={ef_search}"),
+            build_time,
+            query_time,
+            recall
+        );
+    }
+}
@@ -1,84 +1,7 @@
-use rig::{
-    agent::Agent,
-    completion::{self, Completion, PromptError, ToolDefinition},
-    message::{AssistantContent, Message, ToolCall, ToolFunction, ToolResultContent, UserContent},
-    providers::anthropic,
-    tool::Tool,
-    OneOrMany,
-};
+use rig::{completion::ToolDefinition, providers::anthropic, tool::Tool};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-struct MultiTurnAgent<M: rig::completion::CompletionModel> {
-    agent: Agent<M>,
-    chat_history: Vec<completion::Message>,
-}
-
-impl<M: rig::completion::CompletionModel> MultiTurnAgent<M> {
-    async fn multi_turn_prompt(
-        &mut self,
-        prompt: impl Into<Message> + Send,
-    ) -> Result<String, PromptError> {
-        let mut current_prompt: Message = prompt.into();
-        loop {
-            println!("Current Prompt: {:?}\n", current_prompt);
-            let resp = self
-                .agent
-                .completion(current_prompt.clone(), self.chat_history.clone())
-                .await?
-                .send()
-                .await?;
-
-            let mut final_text = None;
-
-            for content in resp.choice.into_iter() {
-                match content {
-                    AssistantContent::Text(text) => {
-                        println!("Intermediate Response: {:?}\n", text.text);
-                        final_text = Some(text.text.clone());
-                        self.chat_history.push(current_prompt.clone());
-                        let response_message = Message::Assistant {
-                            content: OneOrMany::one(AssistantContent::text(&text.text)),
-                        };
-                        self.chat_history.push(response_message);
-                    }
-                    AssistantContent::ToolCall(content) => {
-                        self.chat_history.push(current_prompt.clone());
-                        let tool_call_msg = AssistantContent::ToolCall(content.clone());
-                        println!("Tool Call Msg: {:?}\n", tool_call_msg);
-
-                        self.chat_history.push(Message::Assistant {
-                            content: OneOrMany::one(tool_call_msg),
-                        });
-
-                        let ToolCall {
-                            id,
-                            function: ToolFunction { name, arguments },
-                        } = content;
-
-                        let tool_result =
-                            self.agent.tools.call(&name, arguments.to_string()).await?;
-
-                        current_prompt = Message::User {
-                            content: OneOrMany::one(UserContent::tool_result(
-                                id,
-                                OneOrMany::one(ToolResultContent::text(tool_result)),
-                            )),
-                        };
-
-                        final_text = None;
-                        break;
-                    }
-                }
-            }
-
-            if let Some(text) = final_text {
-                return Ok(text);
-            }
-        }
-    }
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // tracing_subscriber::registry()
@@ -109,21 +32,27 @@ async fn main() -> anyhow::Result<()> {
         .tool(Divide)
         .build();
 
-    let mut agent = MultiTurnAgent {
-        agent: calculator_rag,
-        chat_history: Vec::new(),
-    };
+    let mut chat_history = Vec::new();
 
-    // Prompt the agent and print the response
-    let result = agent
-        .multi_turn_prompt("Calculate 5 - 2 = ?. Describe the result to me.")
+    // Prompt the agent and print the response. The agent will keep calling tools and
+    // feeding the results back to the model for up to 20 turns.
+    let result = calculator_rag
+        .multi_turn_chat(
+            "Calculate 5 - 2 = ?. Describe the result to me.",
+            &mut chat_history,
+            20,
+        )
         .await?;
 
     println!("\n\nOpenAI Calculator Agent: {}", result);
 
     // Prompt the agent again and print the response
-    let result = agent
-        .multi_turn_prompt("Calculate (3 + 5) / 9  = ?. Describe the result to me.")
+    let result = calculator_rag
+        .multi_turn_chat(
+            "Calculate (3 + 5) / 9  = ?. Describe the result to me.",
+            &mut chat_history,
+            20,
+        )
         .await?;
 
     println!("\n\nOpenAI Calculator Agent: {}", result);
@@ -106,22 +106,25 @@
 //! let response = agent.prompt("What does \"glarb-glarb\" mean?").await
 //!     .expect("Failed to prompt the agent");
 //! ```
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use futures::{stream, StreamExt, TryStreamExt};
 
 use crate::{
     completion::{
-        Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder, Document,
-        Message, Prompt, PromptError,
+        Chat, Completion, CompletionError, CompletionModel, CompletionModelDyn,
+        CompletionRequestBuilder, Document, Message, Prompt, PromptError,
     },
-    message::AssistantContent,
+    message::{AssistantContent, ToolCall, ToolResultContent, UserContent},
+    rerank::RerankerDyn,
     streaming::{
-        StreamingChat, StreamingCompletion, StreamingCompletionModel, StreamingPrompt,
-        StreamingResult,
+        StreamingChat, StreamingCompletion, StreamingCompletionModel, StreamingCompletionModelDyn,
+        StreamingPrompt, StreamingResult,
     },
-    tool::{Tool, ToolSet},
+    tokenizer::Tokenizer,
+    tool::{Tool, ToolSet, ToolSetError},
     vector_store::{VectorStoreError, VectorStoreIndexDyn},
+    OneOrMany,
 };
 
 /// Struct representing an LLM agent. An agent is an LLM model combined with a preamble
@@ -161,10 +164,27 @@ pub struct Agent<M: CompletionModel> {
     additional_params: Option<serde_json::Value>,
     /// List of vector store, with the sample number
     dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// Reranker applied to the dynamic context, with the number of candidates fetched
+    /// from each vector store
+    reranker: Option<(usize, Box<dyn RerankerDyn>)>,
     /// Dynamic tools
     dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
     /// Actual tool implementations
     pub tools: ToolSet,
+    /// Maximum number of turns when running tool calls in a loop. If `None`, tool calls
+    /// are executed once and their output is returned as the response.
+    max_turns: Option<usize>,
+    /// Maximum number of tool calls executed concurrently. If `None`, all the tool calls
+    /// of a response are executed at once.
+    tool_concurrency: Option<usize>,
+    /// JSON schema the responses of the model must conform to
+    output_schema: Option<serde_json::Value>,
+    /// Context window requests are trimmed to fit in, if any
+    context_window: Option<u64>,
+    /// Tokenizer used to count the tokens of requests. If `None`, tokens are estimated.
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    /// Whether the reasoning content of the chat history is removed from requests
+    strip_reasoning: bool,
 }
 
 impl<M: CompletionModel> Completion<M> for Agent<M> {
@@ -174,7 +194,10 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
         chat_history: Vec<Message>,
     ) -> Result<CompletionRequestBuilder<M>, CompletionError> {
         let prompt = prompt.into();
-        let rag_text = prompt.rag_text().clone();
+        // Tool result prompts have no text to RAG with, so fall back to the last user text
+        let rag_text = prompt
+            .rag_text()
+            .or_else(|| chat_history.iter().rev().find_map(Message::rag_text));
 
         let completion_request = self
             .model
@@ -184,37 +207,76 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
             .temperature_opt(self.temperature)
             .max_tokens_opt(self.max_tokens)
             .additional_params_opt(self.additional_params.clone())
+            .output_schema_opt(self.output_schema.clone())
+            .context_window_opt(self.context_window)
+            .tokenizer_opt(self.tokenizer.clone())
+            .strip_reasoning(self.strip_reasoning)
             .documents(self.static_context.clone());
 
         let agent = match &rag_text {
             Some(text) => {
                 let dynamic_context = stream::iter(self.dynamic_context.iter())
                     .then(|(num_sample, index)| async {
-                        Ok::<_, VectorStoreError>(
-                            index
-                                .top_n(text, *num_sample)
-                                .await?
+                        // Over-fetch candidates when reranking, only `num_sample` are kept
+                        let num_candidates = match &self.reranker {
+                            Some((num_candidates, _)) => (*num_candidates).max(*num_sample),
+                            None => *num_sample,
+                        };
+
+                        let docs = index
+                            .top_n(text, num_candidates)
+                            .await
+                            .map_err(|e| CompletionError::RequestError(Box::new(e)))?
+                            .into_iter()
+                            .map(|(_, id, doc)| {
+                                // Pretty print the document if possible for better readability
+                                let text = serde_json::to_string_pretty(&doc)
+                                    .unwrap_or_else(|_| doc.to_string());
+
+                                Document {
+                                    id,
+                                    text,
+                                    additional_props: HashMap::new(),
+                                }
+                            })
+                            .collect::<Vec<_>>();
+
+                        // Documents are returned with their relevance, used to drop the least
+                        // relevant ones first if the request exceeds the context window
+                        match &self.reranker {
+                            Some((_, reranker)) => {
+                                let texts = docs.iter().map(|doc| doc.text.clone()).collect();
+                                let ranking = reranker
+                                    .rerank(text, texts, *num_sample)
+                                    .await
+                                    .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+
+                                Ok::<_, CompletionError>(
+                                    ranking
+                                        .into_iter()
+                                        .filter_map(|result| {
+                                            Some((
+                                                docs.get(result.index)?.clone(),
+                                                result.relevance_score,
+                                            ))
+                                        })
+                                        .collect::<Vec<_>>(),
+                                )
+                            }
+                            // The scores of different vector stores are not comparable (e.g.:
+                            // similarities and distances), so the reciprocal rank is used instead
+                            None => Ok(docs
                                 .into_iter()
-                                .map(|(_, id, doc)| {
-                                    // Pretty print the document if possible for better readability
-                                    let text = serde_json::to_string_pretty(&doc)
-                                        .unwrap_or_else(|_| doc.to_string());
-
-                                    Document {
-                                        id,
-                                        text,
-                                        additional_props: HashMap::new(),
-                                    }
-                                })
-                                .collect::<Vec<_>>(),
-                        )
+                                .enumerate()
+                                .map(|(rank, doc)| (doc, 1.0 / (rank + 1) as f64))
+                                .collect()),
+                        }
                     })
                     .try_fold(vec![], |mut acc, docs| async {
                         acc.extend(docs);
                         Ok(acc)
                     })
-                    .await
-                    .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+                    .await?;
 
                 let dynamic_tools = stream::iter(self.dynamic_tools.iter())
                     .then(|(num_sample, index)| async {
@@ -255,8 +317,11 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
                     .collect::<Vec<_>>()
                     .await;
 
-                completion_request
-                    .documents(dynamic_context)
+                dynamic_context
+                    .into_iter()
+                    .fold(completion_request, |request, (doc, relevance)| {
+                        request.document_with_relevance(doc, relevance)
+                    })
                     .tools([static_tools.clone(), dynamic_tools].concat())
             }
             None => {
@@ -302,19 +367,234 @@ impl<M: CompletionModel> Chat for Agent<M> {
         prompt: impl Into<Message> + Send,
         chat_history: Vec<Message>,
     ) -> Result<String, PromptError> {
+        if let Some(max_turns) = self.max_turns {
+            let mut chat_history = chat_history;
+            return self
+                .multi_turn_chat(prompt, &mut chat_history, max_turns)
+                .await;
+        }
+
+        let resp = self.respond(prompt, chat_history).await?;
+
+        // The text of the response followed by the outputs of the tools, in the order they
+        // were called, so that no tool output is dropped when the model also returned text
+        Ok(resp
+            .texts()
+            .into_iter()
+            .chain(resp.tool_results.into_iter().map(|(_, output)| output))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// Response of a single agent turn, containing every piece of assistant content returned by
+/// the model (text and tool calls) along with the output of each tool call.
+#[derive(Clone, Debug)]
+pub struct AgentResponse {
+    /// The assistant content returned by the model, in the order it was returned
+    pub choice: OneOrMany<AssistantContent>,
+    /// Each tool call in `choice` with its output, in the order the tools were called (i.e.:
+    /// the order of the tool calls in `choice`). The id of the tool call of a result is its
+    /// [ToolCall::id]. Results are not keyed by id since some providers (e.g.: Gemini, Ollama)
+    /// use the name of the tool as id, which is not unique when a tool is called several times.
+    pub tool_results: Vec<(ToolCall, String)>,
+}
+
+impl AgentResponse {
+    /// Get the text content of the response
+    pub fn texts(&self) -> Vec<String> {
+        self.choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.clone()),
+                AssistantContent::ToolCall(_) | AssistantContent::Reasoning(_) => None,
+            })
+            .collect()
+    }
+
+    /// Get the reasoning content of the response, if the model reasoned before answering
+    pub fn reasoning(&self) -> Vec<String> {
+        self.choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Reasoning(reasoning) if !reasoning.redacted => {
+                    Some(reasoning.reasoning.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the tool calls of the response
+    pub fn tool_calls(&self) -> Vec<ToolCall> {
+        self.choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::ToolCall(tool_call) => Some(tool_call.clone()),
+                AssistantContent::Text(_) | AssistantContent::Reasoning(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl<M: CompletionModel> Agent<M> {
+    /// Send a prompt to the agent and execute every tool call in the model's response.
+    ///
+    /// Unlike [Chat::chat], the full response is returned: the text and tool call content
+    /// in the order produced by the model, and the output of each tool call.
+    /// Tool calls are executed concurrently (see [AgentBuilder::tool_concurrency]).
+    pub async fn respond(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: Vec<Message>,
+    ) -> Result<AgentResponse, PromptError> {
         let resp = self.completion(prompt, chat_history).await?.send().await?;
 
-        // TODO: consider returning a `Message` instead of `String` for parallel responses / tool calls
-        match resp.choice.first() {
-            AssistantContent::Text(text) => Ok(text.text.clone()),
-            AssistantContent::ToolCall(tool_call) => Ok(self
-                .tools
-                .call(
-                    &tool_call.function.name,
-                    tool_call.function.arguments.to_string(),
-                )
-                .await?),
+        Ok(AgentResponse {
+            tool_results: self.call_tools(&resp.choice).await?,
+            choice: resp.choice,
+        })
+    }
+
+    /// Execute every tool call in `choice`, running at most `tool_concurrency` of them at once.
+    /// The outputs are returned in the order of the tool calls.
+    async fn call_tools(
+        &self,
+        choice: &OneOrMany<AssistantContent>,
+    ) -> Result<Vec<(ToolCall, String)>, ToolSetError> {
+        let tool_calls = choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::ToolCall(tool_call) => Some(tool_call.clone()),
+                AssistantContent::Text(_) | AssistantContent::Reasoning(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let concurrency = self.tool_concurrency.unwrap_or(tool_calls.len()).max(1);
+
+        stream::iter(tool_calls)
+            .map(|tool_call| async move {
+                let output = self
+                    .tools
+                    .call(
+                        &tool_call.function.name,
+                        tool_call.function.arguments.to_string(),
+                    )
+                    .await?;
+                Ok::<_, ToolSetError>((tool_call, output))
+            })
+            .buffered(concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// Send a prompt to the agent and keep executing the tool calls it makes until the model
+    /// answers with text, feeding each tool result back to the model as a new turn.
+    ///
+    /// The prompt, the assistant responses (including tool calls) and the tool results are
+    /// appended to `chat_history` as the conversation progresses, so the same history can be
+    /// reused for follow-up prompts.
+    ///
+    /// Returns [PromptError::MaxTurnsError] if the model is still calling tools after
+    /// `max_turns` completion requests.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use rig::{completion::ToolDefinition, tool::Tool};
+    /// # use serde::Deserialize;
+    /// use rig::providers::openai;
+    /// #
+    /// # #[derive(Deserialize)]
+    /// # struct OperationArgs { x: i32, y: i32 }
+    /// #
+    /// # #[derive(Debug, thiserror::Error)]
+    /// # #[error("Math error")]
+    /// # struct MathError;
+    /// #
+    /// # struct Add;
+    /// # impl Tool for Add {
+    /// #     const NAME: &'static str = "add";
+    /// #     type Error = MathError;
+    /// #     type Args = OperationArgs;
+    /// #     type Output = i32;
+    /// #     async fn definition(&self, _prompt: String) -> ToolDefinition { unimplemented!() }
+    /// #     async fn call(&self, args: Self::Args) -> Result<i32, MathError> { Ok(args.x + args.y) }
+    /// # }
+    /// #
+    /// # struct Subtract;
+    /// # impl Tool for Subtract {
+    /// #     const NAME: &'static str = "subtract";
+    /// #     type Error = MathError;
+    /// #     type Args = OperationArgs;
+    /// #     type Output = i32;
+    /// #     async fn definition(&self, _prompt: String) -> ToolDefinition { unimplemented!() }
+    /// #     async fn call(&self, args: Self::Args) -> Result<i32, MathError> { Ok(args.x - args.y) }
+    /// # }
+    /// #
+    /// # async fn run() {
+    /// let openai = openai::Client::from_env();
+    ///
+    /// let agent = openai.agent(openai::GPT_4O)
+    ///     .preamble("You are a calculator. Use the tools provided to perform arithmetic.")
+    ///     .tool(Add)
+    ///     .tool(Subtract)
+    ///     .build();
+    ///
+    /// let mut chat_history = vec![];
+    ///
+    /// let response = agent
+    ///     .multi_turn_chat("Calculate (3 + 5) - 9", &mut chat_history, 10)
+    ///     .await
+    ///     .expect("Failed to prompt the agent");
+    /// # }
+    /// ```
+    pub async fn multi_turn_chat(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: &mut Vec<Message>,
+        max_turns: usize,
+    ) -> Result<String, PromptError> {
+        let mut current_prompt: Message = prompt.into();
+
+        for _ in 0..max_turns {
+            let resp = self
+                .completion(current_prompt.clone(), chat_history.clone())
+                .await?
+                .send()
+                .await?;
+
+            chat_history.push(current_prompt);
+            chat_history.push(Message::Assistant {
+                content: resp.choice.clone(),
+            });
+
+            let resp = AgentResponse {
+                tool_results: self.call_tools(&resp.choice).await?,
+                choice: resp.choice,
+            };
+
+            let tool_results = resp
+                .tool_results
+                .iter()
+                .map(|(tool_call, output)| {
+                    UserContent::tool_result(
+                        tool_call.id.clone(),
+                        OneOrMany::one(ToolResultContent::text(output.clone())),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            match OneOrMany::many(tool_results) {
+                Ok(content) => current_prompt = Message::User { content },
+                // No tool calls: the model answered with text
+                Err(_) => return Ok(resp.texts().join("\n")),
+            }
         }
+
+        // Keep the pending tool results so the conversation can be resumed
+        chat_history.push(current_prompt);
+
+        Err(PromptError::MaxTurnsError(max_turns))
     }
 }
 
@@ -354,12 +634,26 @@ pub struct AgentBuilder<M: CompletionModel> {
     max_tokens: Option<u64>,
     /// List of vector store, with the sample number
     dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
+    /// Reranker applied to the dynamic context, with the number of candidates
+    reranker: Option<(usize, Box<dyn RerankerDyn>)>,
     /// Dynamic tools
     dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
     /// Temperature of the model
     temperature: Option<f64>,
     /// Actual tool implementations
     tools: ToolSet,
+    /// Maximum number of turns when running tool calls in a loop
+    max_turns: Option<usize>,
+    /// Maximum number of tool calls executed concurrently
+    tool_concurrency: Option<usize>,
+    /// JSON schema the responses of the model must conform to
+    output_schema: Option<serde_json::Value>,
+    /// Context window requests are trimmed to fit in
+    context_window: Option<u64>,
+    /// Tokenizer used to count the tokens of requests
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    /// Whether the reasoning content of the chat history is removed from requests
+    strip_reasoning: bool,
 }
 
 impl<M: CompletionModel> AgentBuilder<M> {
@@ -373,8 +667,15 @@ impl<M: CompletionModel> AgentBuilder<M> {
             max_tokens: None,
             additional_params: None,
             dynamic_context: vec![],
+            reranker: None,
             dynamic_tools: vec![],
             tools: ToolSet::default(),
+            max_turns: None,
+            tool_concurrency: None,
+            output_schema: None,
+            context_window: None,
+            tokenizer: None,
+            strip_reasoning: false,
         }
     }
 
@@ -424,6 +725,24 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Rerank the dynamic context with `reranker`. On each prompt, `num_candidates` documents
+    /// are fetched from each vector store of the dynamic context, and only the `sample` most
+    /// relevant ones according to the reranker are inserted in the request.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::{providers::{cohere, openai}, rerank::LlmReranker};
+    ///
+    /// let agent = openai.agent(openai::GPT_4O)
+    ///     .dynamic_context(3, index)
+    ///     .reranker(LlmReranker::new(openai.completion_model(openai::GPT_4O_MINI)), 20)
+    ///     .build();
+    /// ```
+    pub fn reranker(mut self, reranker: impl RerankerDyn + 'static, num_candidates: usize) -> Self {
+        self.reranker = Some((num_candidates, Box::new(reranker)));
+        self
+    }
+
     /// Add some dynamic tools to the agent. On each prompt, `sample` tools from the
     /// dynamic toolset will be inserted in the request.
     pub fn dynamic_tools(
@@ -455,6 +774,58 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Set the maximum number of turns the agent can take when calling tools. When set,
+    /// [Chat::chat] and [Prompt::prompt] feed tool results back to the model until it
+    /// answers with text instead of returning the raw tool output.
+    pub fn max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = Some(max_turns);
+        self
+    }
+
+    /// Set the maximum number of tool calls from a single response that are executed
+    /// concurrently. By default, all of them are executed at once.
+    pub fn tool_concurrency(mut self, tool_concurrency: usize) -> Self {
+        self.tool_concurrency = Some(tool_concurrency);
+        self
+    }
+
+    /// Set the JSON schema the responses of the model must conform to. Only used by models
+    /// that natively support structured output (see [CompletionModel::supports_structured_output]).
+    pub fn output_schema(mut self, output_schema: serde_json::Value) -> Self {
+        self.output_schema = Some(output_schema);
+        self
+    }
+
+    /// Trim the requests of the agent to fit in a context window of `tokens` tokens, including
+    /// the `max_tokens` reserved for the response, by dropping the oldest messages of the chat
+    /// history and then the least relevant dynamic context documents.
+    /// See [CompletionRequestBuilder::context_window].
+    pub fn context_window(mut self, tokens: u64) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// Trim the requests of the agent to fit in the context window of the model, if known
+    /// (see [CompletionModel::context_window] and [Self::context_window]).
+    pub fn fit_context_window(mut self) -> Self {
+        self.context_window = self.model.context_window();
+        self
+    }
+
+    /// Set the tokenizer used to count the tokens of requests when trimming them to fit in
+    /// the context window. By default, tokens are estimated from the number of characters.
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Some(Arc::new(tokenizer));
+        self
+    }
+
+    /// Remove the reasoning content of the chat history from the requests of the agent
+    /// (see [CompletionRequestBuilder::strip_reasoning]). By default, reasoning is kept.
+    pub fn strip_reasoning(mut self, strip: bool) -> Self {
+        self.strip_reasoning = strip;
+        self
+    }
+
     /// Build the agent
     pub fn build(self) -> Agent<M> {
         Agent {
@@ -466,12 +837,86 @@ impl<M: CompletionModel> AgentBuilder<M> {
             max_tokens: self.max_tokens,
             additional_params: self.additional_params,
             dynamic_context: self.dynamic_context,
+            reranker: self.reranker,
             dynamic_tools: self.dynamic_tools,
             tools: self.tools,
+            max_turns: self.max_turns,
+            tool_concurrency: self.tool_concurrency,
+            output_schema: self.output_schema,
+            context_window: self.context_window,
+            tokenizer: self.tokenizer,
+            strip_reasoning: self.strip_reasoning,
         }
     }
 }
 
+impl<M: CompletionModel> Agent<M> {
+    fn map_model<N: CompletionModel>(self, f: impl FnOnce(M) -> N) -> Agent<N> {
+        Agent {
+            model: f(self.model),
+            preamble: self.preamble,
+            static_context: self.static_context,
+            static_tools: self.static_tools,
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            additional_params: self.additional_params,
+            dynamic_context: self.dynamic_context,
+            reranker: self.reranker,
+            dynamic_tools: self.dynamic_tools,
+            tools: self.tools,
+            max_turns: self.max_turns,
+            tool_concurrency: self.tool_concurrency,
+            output_schema: self.output_schema,
+            context_window: self.context_window,
+            tokenizer: self.tokenizer,
+            strip_reasoning: self.strip_reasoning,
+        }
+    }
+}
+
+impl<M> Agent<M>
+where
+    M: CompletionModel + 'static,
+    M::Response: 'static,
+{
+    /// Convert the agent into an agent with a type-erased completion model, so that agents
+    /// using models of different providers can be held in the same collection.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use rig::{
+    ///     agent::Agent,
+    ///     completion::{CompletionModelDyn, Prompt},
+    ///     providers::{anthropic, openai},
+    /// };
+    ///
+    /// let mut agents: HashMap<&str, Agent<Box<dyn CompletionModelDyn>>> = HashMap::new();
+    /// agents.insert("openai", openai.agent(openai::GPT_4O).build().into_dyn());
+    /// agents.insert(
+    ///     "anthropic",
+    ///     anthropic.agent(anthropic::CLAUDE_3_5_SONNET).max_tokens(1024).build().into_dyn(),
+    /// );
+    ///
+    /// let response = agents["anthropic"].prompt("Hello!").await?;
+    /// ```
+    pub fn into_dyn(self) -> Agent<Box<dyn CompletionModelDyn>> {
+        self.map_model(|model| Box::new(model) as Box<dyn CompletionModelDyn>)
+    }
+}
+
+impl<M> Agent<M>
+where
+    M: StreamingCompletionModel + 'static,
+    M::Response: 'static,
+{
+    /// Convert the agent into an agent with a type-erased streaming completion model (see
+    /// [Agent::into_dyn]).
+    pub fn into_dyn_streaming(self) -> Agent<Box<dyn StreamingCompletionModelDyn>> {
+        self.map_model(|model| Box::new(model) as Box<dyn StreamingCompletionModelDyn>)
+    }
+}
+
 impl<M: StreamingCompletionModel> StreamingCompletion<M> for Agent<M> {
     async fn stream_completion(
         &self,
@@ -502,3 +947,323 @@ impl<M: StreamingCompletionModel> StreamingChat for Agent<M> {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::completion::{CompletionRequest, CompletionResponse, ToolDefinition};
+
+    /// Mock model that calls the `add` tool on its first turn and answers with text afterwards
+    #[derive(Clone, Default)]
+    struct MockModel {
+        turns: Arc<AtomicUsize>,
+    }
+
+    impl CompletionModel for MockModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let choice = match self.turns.fetch_add(1, Ordering::SeqCst) {
+                0 => OneOrMany::one(AssistantContent::tool_call(
+                    "call_1",
+                    "add",
+                    json!({"x": 2, "y": 3}),
+                )),
+                _ => match request.prompt {
+                    Message::User { content } => match content.first() {
+                        UserContent::ToolResult(result) => OneOrMany::one(AssistantContent::text(
+                            format!("The answer is {:?}", result.content.first()),
+                        )),
+                        _ => OneOrMany::one(AssistantContent::tool_call(
+                            "call_2",
+                            "add",
+                            json!({"x": 1, "y": 1}),
+                        )),
+                    },
+                    _ => unreachable!(),
+                },
+            };
+
+            Ok(CompletionResponse {
+                choice,
+                usage: None,
+                raw_response: (),
+            })
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    struct AddArgs {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Math error")]
+    struct MathError;
+
+    struct Add;
+
+    impl Tool for Add {
+        const NAME: &'static str = "add";
+
+        type Error = MathError;
+        type Args = AddArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: "add".to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: json!({}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    /// Mock model that answers with text and two parallel tool calls
+    #[derive(Clone)]
+    struct ParallelMockModel;
+
+    impl CompletionModel for ParallelMockModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::many(vec![
+                    AssistantContent::text("Let me add those for you."),
+                    AssistantContent::tool_call("call_1", "add", json!({"x": 2, "y": 3})),
+                    AssistantContent::tool_call("call_2", "add", json!({"x": 4, "y": 5})),
+                ])
+                .unwrap(),
+                usage: None,
+                raw_response: (),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_respond_parallel_tool_calls() {
+        let agent = AgentBuilder::new(ParallelMockModel)
+            .tool(Add)
+            .tool_concurrency(1)
+            .build();
+
+        let response = agent.respond("Add 2 + 3 and 4 + 5", vec![]).await.unwrap();
+
+        assert_eq!(response.choice.len(), 3);
+        assert_eq!(response.texts(), vec!["Let me add those for you."]);
+        assert_eq!(response.tool_calls().len(), 2);
+        assert_eq!(
+            response
+                .tool_results
+                .iter()
+                .map(|(tool_call, output)| (tool_call.id.as_str(), output.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("call_1", "5"), ("call_2", "9")]
+        );
+
+        // The outputs of the tools are returned along with the text
+        assert_eq!(
+            agent.chat("Add 2 + 3 and 4 + 5", vec![]).await.unwrap(),
+            "Let me add those for you.\n5\n9"
+        );
+    }
+
+    /// Mock model calling the same tool twice with the same id, like Gemini and Ollama do
+    #[derive(Clone)]
+    struct SameIdMockModel;
+
+    impl CompletionModel for SameIdMockModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let choice = match request.prompt {
+                Message::User { content } if content.len() == 2 => {
+                    OneOrMany::one(AssistantContent::text(format!("{:?}", content)))
+                }
+                _ => OneOrMany::many(vec![
+                    AssistantContent::tool_call("add", "add", json!({"x": 2, "y": 3})),
+                    AssistantContent::tool_call("add", "add", json!({"x": 4, "y": 5})),
+                ])
+                .unwrap(),
+            };
+
+            Ok(CompletionResponse {
+                choice,
+                usage: None,
+                raw_response: (),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_same_id_tool_calls() {
+        let agent = AgentBuilder::new(SameIdMockModel).tool(Add).build();
+
+        let response = agent.respond("Add 2 + 3 and 4 + 5", vec![]).await.unwrap();
+        assert_eq!(
+            response
+                .tool_results
+                .iter()
+                .map(|(_, output)| output.as_str())
+                .collect::<Vec<_>>(),
+            vec!["5", "9"]
+        );
+
+        assert_eq!(
+            agent.chat("Add 2 + 3 and 4 + 5", vec![]).await.unwrap(),
+            "5\n9"
+        );
+
+        // Both results are sent back to the model, in the order of the tool calls
+        let mut chat_history = vec![];
+        agent
+            .multi_turn_chat("Add 2 + 3 and 4 + 5", &mut chat_history, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            chat_history[2],
+            Message::User {
+                content: OneOrMany::many(vec![
+                    UserContent::tool_result("add", OneOrMany::one(ToolResultContent::text("5"))),
+                    UserContent::tool_result("add", OneOrMany::one(ToolResultContent::text("9"))),
+                ])
+                .unwrap(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dyn_agents() {
+        let agents: HashMap<&str, Agent<Box<dyn CompletionModelDyn>>> = HashMap::from([
+            (
+                "sequential",
+                AgentBuilder::new(MockModel::default())
+                    .tool(Add)
+                    .build()
+                    .into_dyn(),
+            ),
+            (
+                "parallel",
+                AgentBuilder::new(ParallelMockModel)
+                    .tool(Add)
+                    .build()
+                    .into_dyn(),
+            ),
+        ]);
+
+        let response = agents["sequential"]
+            .multi_turn_chat("What is 2 + 3?", &mut vec![], 5)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            format!("The answer is {:?}", ToolResultContent::text("5"))
+        );
+
+        let response = agents["parallel"]
+            .respond("Add 2 + 3 and 4 + 5", vec![])
+            .await
+            .unwrap();
+        assert_eq!(response.tool_results.len(), 2);
+
+        // The raw response can be downcast to the response type of the underlying model
+        let model: Box<dyn CompletionModelDyn> = Box::new(ParallelMockModel);
+        let response = model.completion_request("Hi").send().await.unwrap();
+        assert!(response.raw_response.downcast_ref::<()>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_multi_turn_chat() {
+        let agent = AgentBuilder::new(MockModel::default()).tool(Add).build();
+        let mut chat_history = vec![];
+
+        let response = agent
+            .multi_turn_chat("What is 2 + 3?", &mut chat_history, 5)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response,
+            format!("The answer is {:?}", ToolResultContent::text("5"))
+        );
+        // prompt, tool call, tool result, final answer
+        assert_eq!(chat_history.len(), 4);
+        assert_eq!(
+            chat_history[2],
+            Message::User {
+                content: OneOrMany::one(UserContent::tool_result(
+                    "call_1",
+                    OneOrMany::one(ToolResultContent::text("5"))
+                )),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multi_turn_chat_max_turns() {
+        let agent = AgentBuilder::new(MockModel::default()).tool(Add).build();
+        let mut chat_history = vec![];
+
+        let result = agent
+            .multi_turn_chat("What is 2 + 3?", &mut chat_history, 1)
+            .await;
+
+        assert!(matches!(result, Err(PromptError::MaxTurnsError(1))));
+        // prompt, tool call, pending tool result
+        assert_eq!(chat_history.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_context_reranker() {
+        use crate::pipeline::agent_ops::tests::{MockIndex2, MockReranker};
+
+        let agent = AgentBuilder::new(MockModel::default())
+            .dynamic_context(1, MockIndex2)
+            .build();
+        let request = agent.completion("baz", vec![]).await.unwrap().build();
+        assert_eq!(
+            request
+                .documents
+                .iter()
+                .map(|doc| doc.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["doc1"]
+        );
+
+        // The second candidate is fetched and ranked first by the reranker
+        let agent = AgentBuilder::new(MockModel::default())
+            .dynamic_context(1, MockIndex2)
+            .reranker(MockReranker, 2)
+            .build();
+        let request = agent.completion("baz", vec![]).await.unwrap().build();
+        assert_eq!(
+            request
+                .documents
+                .iter()
+                .map(|doc| doc.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["doc2"]
+        );
+    }
+}
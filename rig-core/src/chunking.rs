@@ -0,0 +1,680 @@
+//! This module provides text splitters, which cut long documents into chunks small enough to be
+//! embedded (embedding models have a limited input size) and specific enough to be retrieved.
+//!
+//! All splitters implement the [TextSplitter] trait and return chunks that are slices of the
+//! source text, along with their byte offsets in the source and the metadata of the source
+//! (e.g.: the path of the file or the page number), see [Chunk]:
+//! - [CharacterSplitter]: fixed-size chunks of characters, with overlap
+//! - [RecursiveSplitter]: splits on paragraphs, then lines, then sentences, then words, so that
+//!   chunks end at the most natural boundary possible
+//! - [SentenceSplitter]: groups whole sentences
+//! - [MarkdownSplitter]: splits Markdown documents by section, keeping track of the headings of
+//!   each chunk
+//!
+//! The size of the chunks of the last three splitters is measured with a [ChunkSizer], which
+//! counts characters by default. Use a token counter (e.g.: [ApproximateTokenSizer], or any
+//! `Fn(&str) -> usize` closure wrapping the tokenizer of the model) to bound the number of tokens
+//! of each chunk instead.
+//!
+//! Chunks implement [Embed], so they can be passed as is to an
+//! [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder). The loaders can also produce
+//! chunks directly, e.g.: with [FileLoader::chunk](crate::loaders::FileLoader::chunk).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     chunking::{RecursiveSplitter, TextSplitter},
+//!     embeddings::EmbeddingsBuilder,
+//!     loaders::FileLoader,
+//! };
+//!
+//! // Chunks of at most 1000 characters, overlapping by up to 200 characters
+//! let splitter = RecursiveSplitter::new(1000).with_overlap(200);
+//!
+//! let chunks = FileLoader::with_glob("docs/*.txt")?
+//!     .read_with_path()
+//!     .ignore_errors()
+//!     .chunk(splitter)
+//!     .into_iter()
+//!     .collect::<Vec<_>>();
+//!
+//! let embeddings = EmbeddingsBuilder::new(model)
+//!     .documents(chunks)?
+//!     .build()
+//!     .await?;
+//! ```
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
+/// A chunk of a source text, produced by a [TextSplitter].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk<M = ()> {
+    /// The text of the chunk, equal to `source[range]`
+    pub text: String,
+    /// Byte offsets of the chunk in the source text
+    pub range: Range<usize>,
+    /// Position of the chunk among the chunks of the source text
+    pub index: usize,
+    /// Headings of the section containing the chunk, from the top-level heading down.
+    /// Only set by the [MarkdownSplitter].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headings: Vec<String>,
+    /// Metadata of the source text (e.g.: its path)
+    pub metadata: M,
+}
+
+impl<M> Embed for Chunk<M> {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text.clone());
+        Ok(())
+    }
+}
+
+/// Trait for types that split a text into chunks.
+pub trait TextSplitter {
+    /// Byte ranges of the chunks of `text`, in order. Chunks may overlap, and whitespace
+    /// between chunks is not part of any chunk.
+    fn split_ranges(&self, text: &str) -> Vec<Range<usize>>;
+
+    /// Split `text` into chunks.
+    fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.split_ranges(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    /// Split `text` into [Chunk]s, each holding a copy of `metadata`.
+    fn chunks<M: Clone>(&self, text: &str, metadata: M) -> Vec<Chunk<M>> {
+        self.split_ranges(text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| Chunk {
+                text: text[range.clone()].to_string(),
+                range,
+                index,
+                headings: vec![],
+                metadata: metadata.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Trait for measuring the size of a chunk, in characters, tokens or any other unit.
+pub trait ChunkSizer {
+    fn size(&self, text: &str) -> usize;
+}
+
+/// Measures chunks by their number of characters (not bytes).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CharacterSizer;
+
+impl ChunkSizer for CharacterSizer {
+    fn size(&self, text: &str) -> usize {
+        text.chars().count()
+    }
+}
+
+/// Estimates the number of tokens of a chunk as one token per 4 characters, which is close
+/// to the average of most tokenizers on English text. Leave some margin below the input limit
+/// of the model, or use the actual tokenizer of the model when it is available.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApproximateTokenSizer;
+
+impl ChunkSizer for ApproximateTokenSizer {
+    fn size(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+}
+
+impl<F: Fn(&str) -> usize> ChunkSizer for F {
+    fn size(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// Splits a text into chunks of `chunk_size` characters, each chunk starting `overlap`
+/// characters before the end of the previous one. The simplest and fastest splitter, but
+/// chunks may end in the middle of a word.
+#[derive(Clone, Debug)]
+pub struct CharacterSplitter {
+    chunk_size: usize,
+    overlap: usize,
+}
+
+impl CharacterSplitter {
+    /// # Panics
+    /// If `chunk_size` is zero, or `overlap` is not smaller than `chunk_size`.
+    pub fn new(chunk_size: usize, overlap: usize) -> Self {
+        assert!(
+            overlap < chunk_size,
+            "The overlap must be smaller than the chunk size"
+        );
+        Self {
+            chunk_size,
+            overlap,
+        }
+    }
+}
+
+impl TextSplitter for CharacterSplitter {
+    fn split_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        let boundaries = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+        let num_chars = boundaries.len() - 1;
+
+        let mut ranges = vec![];
+        let mut start = 0;
+        while start < num_chars {
+            let end = (start + self.chunk_size).min(num_chars);
+            ranges.push(boundaries[start]..boundaries[end]);
+            if end == num_chars {
+                break;
+            }
+            start = end - self.overlap;
+        }
+        ranges
+    }
+}
+
+/// Default separators of the [RecursiveSplitter], from the most to the least natural boundary.
+pub const DEFAULT_SEPARATORS: [&str; 5] = ["\n\n", "\n", ". ", " ", ""];
+
+/// Splits a text on the first separator found in it (paragraphs by default), splits the parts
+/// still too large on the next separators (lines, sentences, words and finally characters),
+/// and then merges consecutive parts into chunks of at most `chunk_size`.
+#[derive(Clone, Debug)]
+pub struct RecursiveSplitter<S = CharacterSizer> {
+    chunk_size: usize,
+    overlap: usize,
+    separators: Vec<String>,
+    sizer: S,
+}
+
+impl RecursiveSplitter {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            overlap: 0,
+            separators: DEFAULT_SEPARATORS.map(String::from).to_vec(),
+            sizer: CharacterSizer,
+        }
+    }
+}
+
+impl<S: ChunkSizer> RecursiveSplitter<S> {
+    /// Start each chunk with the last parts of the previous chunk, up to `overlap` in size.
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Replace the default separators. An empty separator splits between characters, which
+    /// guarantees that no chunk exceeds the chunk size.
+    pub fn with_separators(mut self, separators: &[&str]) -> Self {
+        self.separators = separators.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Measure the size of chunks with the given sizer (e.g.: a token counter).
+    pub fn with_sizer<S2: ChunkSizer>(self, sizer: S2) -> RecursiveSplitter<S2> {
+        RecursiveSplitter {
+            chunk_size: self.chunk_size,
+            overlap: self.overlap,
+            separators: self.separators,
+            sizer,
+        }
+    }
+
+    /// Split `text[range]` into contiguous parts, each fitting in a chunk if possible.
+    fn split_parts(&self, text: &str, range: Range<usize>, parts: &mut Vec<Range<usize>>) {
+        split_parts(
+            text,
+            range,
+            &self.separators,
+            self.chunk_size,
+            &self.sizer,
+            parts,
+        );
+    }
+}
+
+impl<S: ChunkSizer> TextSplitter for RecursiveSplitter<S> {
+    fn split_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        let mut parts = vec![];
+        self.split_parts(text, 0..text.len(), &mut parts);
+        merge_parts(text, &parts, self.chunk_size, self.overlap, &self.sizer)
+    }
+}
+
+/// Groups whole sentences into chunks of at most `chunk_size`. A sentence ends with a `.`,
+/// `!` or `?` followed by whitespace, or with an empty line. Sentences larger than a chunk are
+/// split between words.
+#[derive(Clone, Debug)]
+pub struct SentenceSplitter<S = CharacterSizer> {
+    chunk_size: usize,
+    overlap: usize,
+    sizer: S,
+}
+
+impl SentenceSplitter {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            overlap: 0,
+            sizer: CharacterSizer,
+        }
+    }
+}
+
+impl<S: ChunkSizer> SentenceSplitter<S> {
+    /// Start each chunk with the last sentences of the previous chunk, up to `overlap` in size.
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Measure the size of chunks with the given sizer (e.g.: a token counter).
+    pub fn with_sizer<S2: ChunkSizer>(self, sizer: S2) -> SentenceSplitter<S2> {
+        SentenceSplitter {
+            chunk_size: self.chunk_size,
+            overlap: self.overlap,
+            sizer,
+        }
+    }
+}
+
+impl<S: ChunkSizer> TextSplitter for SentenceSplitter<S> {
+    fn split_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        let separators = [" ".to_string(), "".to_string()];
+
+        let mut parts = vec![];
+        for sentence in sentences(text) {
+            split_parts(
+                text,
+                sentence,
+                &separators,
+                self.chunk_size,
+                &self.sizer,
+                &mut parts,
+            );
+        }
+        merge_parts(text, &parts, self.chunk_size, self.overlap, &self.sizer)
+    }
+}
+
+/// Splits Markdown documents at their headings (outside of code blocks), then splits the
+/// sections larger than `chunk_size` with a [RecursiveSplitter]. Each [Chunk] holds the
+/// headings of its section, e.g.: `["Installation", "From source"]`.
+#[derive(Clone, Debug)]
+pub struct MarkdownSplitter<S = CharacterSizer> {
+    splitter: RecursiveSplitter<S>,
+}
+
+impl MarkdownSplitter {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            splitter: RecursiveSplitter::new(chunk_size),
+        }
+    }
+}
+
+impl<S: ChunkSizer> MarkdownSplitter<S> {
+    /// Start each chunk of a section with the last parts of the previous chunk of the same
+    /// section, up to `overlap` in size.
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.splitter = self.splitter.with_overlap(overlap);
+        self
+    }
+
+    /// Measure the size of chunks with the given sizer (e.g.: a token counter).
+    pub fn with_sizer<S2: ChunkSizer>(self, sizer: S2) -> MarkdownSplitter<S2> {
+        MarkdownSplitter {
+            splitter: self.splitter.with_sizer(sizer),
+        }
+    }
+
+    /// Byte ranges of the chunks of `text`, with the headings of their section.
+    fn split_sections(&self, text: &str) -> Vec<(Range<usize>, Vec<String>)> {
+        let mut chunks = vec![];
+        for (section, headings) in markdown_sections(text) {
+            let mut parts = vec![];
+            self.splitter.split_parts(text, section, &mut parts);
+            chunks.extend(
+                merge_parts(
+                    text,
+                    &parts,
+                    self.splitter.chunk_size,
+                    self.splitter.overlap,
+                    &self.splitter.sizer,
+                )
+                .into_iter()
+                .map(|range| (range, headings.clone())),
+            );
+        }
+        chunks
+    }
+}
+
+impl<S: ChunkSizer> TextSplitter for MarkdownSplitter<S> {
+    fn split_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        self.split_sections(text)
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    fn chunks<M: Clone>(&self, text: &str, metadata: M) -> Vec<Chunk<M>> {
+        self.split_sections(text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (range, headings))| Chunk {
+                text: text[range.clone()].to_string(),
+                range,
+                index,
+                headings,
+                metadata: metadata.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Split `text[range]` on the first of the `separators` found in it, then split the parts
+/// larger than `chunk_size` on the next separators, recursively.
+fn split_parts(
+    text: &str,
+    range: Range<usize>,
+    separators: &[String],
+    chunk_size: usize,
+    sizer: &impl ChunkSizer,
+    parts: &mut Vec<Range<usize>>,
+) {
+    if sizer.size(&text[range.clone()]) <= chunk_size {
+        parts.push(range);
+        return;
+    }
+
+    let Some(position) = separators
+        .iter()
+        .position(|separator| text[range.clone()].contains(separator.as_str()))
+    else {
+        // No separator left: the part will exceed the chunk size
+        parts.push(range);
+        return;
+    };
+
+    for part in split_inclusive(text, range, &separators[position]) {
+        split_parts(
+            text,
+            part,
+            &separators[position + 1..],
+            chunk_size,
+            sizer,
+            parts,
+        );
+    }
+}
+
+/// Split `text[range]` after each occurrence of `separator`, or between characters if the
+/// separator is empty.
+fn split_inclusive(text: &str, range: Range<usize>, separator: &str) -> Vec<Range<usize>> {
+    let slice = &text[range.clone()];
+    if separator.is_empty() {
+        return slice
+            .char_indices()
+            .map(|(i, c)| range.start + i..range.start + i + c.len_utf8())
+            .collect();
+    }
+
+    let mut parts = vec![];
+    let mut start = 0;
+    for (i, _) in slice.match_indices(separator) {
+        parts.push(range.start + start..range.start + i + separator.len());
+        start = i + separator.len();
+    }
+    if start < slice.len() {
+        parts.push(range.start + start..range.end);
+    }
+    parts
+}
+
+/// Merge consecutive contiguous parts into chunks of at most `chunk_size` (parts larger than
+/// a chunk make a chunk on their own), starting each chunk with the last parts of the previous
+/// chunk fitting in `overlap`. Whitespace is trimmed from both ends of the chunks.
+fn merge_parts(
+    text: &str,
+    parts: &[Range<usize>],
+    chunk_size: usize,
+    overlap: usize,
+    sizer: &impl ChunkSizer,
+) -> Vec<Range<usize>> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    while start < parts.len() {
+        let mut end = start + 1;
+        while end < parts.len()
+            && sizer.size(&text[parts[start].start..parts[end].end]) <= chunk_size
+        {
+            end += 1;
+        }
+
+        if let Some(range) = trim(text, parts[start].start..parts[end - 1].end) {
+            chunks.push(range);
+        }
+        if end == parts.len() {
+            break;
+        }
+
+        // The next chunk starts with the longest suffix of the chunk fitting in the overlap,
+        // but always after the start of this chunk
+        let mut next = end;
+        while next > start + 1
+            && sizer.size(&text[parts[next - 1].start..parts[end - 1].end]) <= overlap
+        {
+            next -= 1;
+        }
+        start = next;
+    }
+    chunks
+}
+
+/// Trim the whitespace around `text[range]`, returning `None` if nothing is left.
+fn trim(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start().len());
+    let end = range.end - (slice.len() - slice.trim_end().len());
+    (start < end).then_some(start..end)
+}
+
+/// Contiguous ranges of the sentences of `text`, including their trailing whitespace.
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut sentences = vec![];
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        let is_end = match c {
+            '.' | '!' | '?' => next.is_none_or(char::is_whitespace),
+            '\n' => next == Some('\n'),
+            _ => false,
+        };
+        if is_end {
+            // Include the whitespace following the end of the sentence
+            let mut end = i + c.len_utf8();
+            while let Some((j, c)) = chars.next_if(|(_, c)| c.is_whitespace()) {
+                end = j + c.len_utf8();
+            }
+            sentences.push(start..end);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        sentences.push(start..text.len());
+    }
+    sentences
+}
+
+/// Contiguous ranges of the sections of a Markdown document, each starting at a heading
+/// (except possibly the first one), with the headings of the section.
+fn markdown_sections(text: &str) -> Vec<(Range<usize>, Vec<String>)> {
+    let mut sections = vec![];
+    // Headings of the current section, with their level
+    let mut headings: Vec<(usize, String)> = vec![];
+    let mut start = 0;
+    let mut fence: Option<&str> = None;
+
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+            continue;
+        }
+
+        // ATX headings may be indented by up to 3 spaces
+        if line.len() - line.trim_start_matches(' ').len() > 3 {
+            continue;
+        }
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        let title = &trimmed[level..];
+        if !(1..=6).contains(&level) || !(title.is_empty() || title.starts_with([' ', '\t'])) {
+            continue;
+        }
+
+        if line_start > start {
+            sections.push((start..line_start, section_headings(&headings)));
+        }
+        start = line_start;
+        headings.retain(|(l, _)| *l < level);
+        headings.push((level, title.trim().trim_end_matches('#').trim().to_string()));
+    }
+    if start < text.len() {
+        sections.push((start..text.len(), section_headings(&headings)));
+    }
+    sections
+}
+
+fn section_headings(headings: &[(usize, String)]) -> Vec<String> {
+    headings.iter().map(|(_, title)| title.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_splitter() {
+        let splitter = CharacterSplitter::new(4, 1);
+        assert_eq!(splitter.split("abcdéfghij"), vec!["abcd", "défg", "ghij"]);
+        assert_eq!(splitter.split_ranges("abcdé"), vec![0..4, 3..6]);
+        assert!(splitter.split("").is_empty());
+    }
+
+    #[test]
+    fn test_recursive_splitter() {
+        let text = "The first paragraph.\n\nThe second paragraph is longer. It has two sentences.";
+
+        let splitter = RecursiveSplitter::new(40);
+        assert_eq!(
+            splitter.split(text),
+            vec![
+                "The first paragraph.",
+                "The second paragraph is longer.",
+                "It has two sentences."
+            ]
+        );
+
+        let chunks = RecursiveSplitter::new(12)
+            .with_overlap(6)
+            .chunks(text, "doc");
+        assert!(chunks.iter().all(|chunk| chunk.text.chars().count() <= 12));
+        assert!(chunks
+            .iter()
+            .all(|chunk| text[chunk.range.clone()] == chunk.text && chunk.metadata == "doc"));
+        assert_eq!(chunks[0].text, "The first");
+        assert_eq!(chunks[1].text, "first");
+        assert_eq!(chunks.last().unwrap().index, chunks.len() - 1);
+
+        // Chunk sizes can be measured in tokens instead of characters
+        let splitter =
+            RecursiveSplitter::new(3).with_sizer(|text: &str| text.split_whitespace().count());
+        assert_eq!(
+            splitter.split("one two three four five"),
+            vec!["one two three", "four five"]
+        );
+    }
+
+    #[test]
+    fn test_sentence_splitter() {
+        let text = "Hello there! How are you? I am fine.\n\nNew paragraph";
+
+        assert_eq!(
+            SentenceSplitter::new(30).split(text),
+            vec!["Hello there! How are you?", "I am fine.\n\nNew paragraph"]
+        );
+        assert_eq!(
+            SentenceSplitter::new(30).with_overlap(13).split(text),
+            vec![
+                "Hello there! How are you?",
+                "How are you? I am fine.",
+                "I am fine.\n\nNew paragraph"
+            ]
+        );
+        // Sentences larger than a chunk are split between words
+        assert_eq!(
+            SentenceSplitter::new(10).split("Hello there! How are you?"),
+            vec!["Hello", "there!", "How are", "you?"]
+        );
+    }
+
+    #[test]
+    fn test_markdown_splitter() {
+        let text = "Intro\n\
+            # Title\n\
+            Some text\n\
+            ## Section A #\n\
+            ```\n\
+            # not a heading\n\
+            ```\n\
+            ## Section B\n\
+            ### Sub\n\
+            Details\n";
+
+        let chunks = MarkdownSplitter::new(100).chunks(text, ());
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.text.as_str(), chunk.headings.join(" > ")))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Intro", "".to_string()),
+                ("# Title\nSome text", "Title".to_string()),
+                (
+                    "## Section A #\n```\n# not a heading\n```",
+                    "Title > Section A".to_string()
+                ),
+                ("## Section B", "Title > Section B".to_string()),
+                ("### Sub\nDetails", "Title > Section B > Sub".to_string()),
+            ]
+        );
+        assert!(chunks
+            .iter()
+            .all(|chunk| text[chunk.range.clone()] == chunk.text));
+    }
+}
@@ -0,0 +1,457 @@
+//! This module provides traits implemented by the clients of all providers, so that the
+//! provider of a model can be selected at runtime (e.g.: from a configuration file).
+//!
+//! - [ProviderClient]: Implemented by all provider clients. Creates a client from the
+//!   environment and gives access to the features of the provider.
+//! - [CompletionClient]: Implemented by the clients of providers of completion models.
+//! - [EmbeddingsClient]: Implemented by the clients of providers of embedding models.
+//!
+//! [CompletionClientDyn] and [EmbeddingsClientDyn] are the object safe versions of these traits,
+//! returning type-erased models: `Box<dyn CompletionModelDyn>` and `Box<dyn EmbeddingModelDyn>`.
+//! Type-erased models implement [CompletionModel] and [EmbeddingModel], so they can be used
+//! with agents, extractors and the embeddings builder.
+//!
+//! The [DynClientBuilder] creates the clients of providers by name.
+//!
+//! # Example
+//! ```rust
+//! use rig::{client::DynClientBuilder, completion::Prompt};
+//!
+//! // e.g.: read from a configuration file
+//! let (provider, model) = ("anthropic", "claude-3-7-sonnet-latest");
+//!
+//! let agent = DynClientBuilder::new()
+//!     .agent(provider, model)?
+//!     .preamble("You are a helpful assistant.")
+//!     .max_tokens(1024)
+//!     .build();
+//!
+//! let response = agent.prompt("Hello!").await?;
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::AgentBuilder,
+    completion::{CompletionModel, CompletionModelDyn},
+    embeddings::{Embed, EmbeddingModel, EmbeddingModelDyn, EmbeddingsBuilder},
+    extractor::ExtractorBuilder,
+    providers::{
+        anthropic, azure, cohere, deepseek, galadriel, gemini, groq, hyperbolic, moonshot, ollama,
+        openai, openai_compatible, perplexity, together, xai,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientBuilderError {
+    /// No provider is registered with the given name
+    #[error("Unknown provider: {0}")]
+    UnknownProvider(String),
+
+    /// The provider does not provide the requested kind of model
+    #[error("Provider {provider} does not support {feature}")]
+    UnsupportedFeature {
+        provider: String,
+        feature: &'static str,
+    },
+
+    /// An environment variable required by the provider is not set
+    #[error("Environment variable {0} not set")]
+    MissingEnv(String),
+
+    /// The configuration of the client is invalid (e.g.: an API key which is not a valid
+    /// header value)
+    #[error("Invalid client configuration: {0}")]
+    InvalidConfig(String),
+}
+
+/// Read a required environment variable of a provider.
+pub(crate) fn env_var(name: &str) -> Result<String, ClientBuilderError> {
+    std::env::var(name).map_err(|_| ClientBuilderError::MissingEnv(name.to_string()))
+}
+
+/// Trait implemented by the clients of all providers.
+pub trait ProviderClient: Send + Sync {
+    /// Create a client from the environment variables of the provider (e.g.: `OPENAI_API_KEY`).
+    /// Returns [ClientBuilderError::MissingEnv] if a required environment variable is not set.
+    fn try_from_env() -> Result<Self, ClientBuilderError>
+    where
+        Self: Sized;
+
+    /// Create a client from the environment variables of the provider.
+    /// Panics if a required environment variable is not set.
+    fn from_env() -> Self
+    where
+        Self: Sized,
+    {
+        Self::try_from_env().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// The client as a [CompletionClientDyn], if the provider has completion models.
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        None
+    }
+
+    /// The client as an [EmbeddingsClientDyn], if the provider has embedding models.
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        None
+    }
+}
+
+/// Trait implemented by the clients of providers of completion models.
+pub trait CompletionClient: ProviderClient + Clone {
+    /// The type of the completion models of the provider
+    type CompletionModel: CompletionModel;
+
+    /// Create a completion model with the given name.
+    fn completion_model(&self, model: &str) -> Self::CompletionModel;
+
+    /// Create an agent builder with the given completion model.
+    fn agent(&self, model: &str) -> AgentBuilder<Self::CompletionModel> {
+        AgentBuilder::new(self.completion_model(model))
+    }
+
+    /// Create an extractor builder with the given completion model.
+    fn extractor<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync>(
+        &self,
+        model: &str,
+    ) -> ExtractorBuilder<T, Self::CompletionModel> {
+        ExtractorBuilder::new(self.completion_model(model))
+    }
+}
+
+/// Trait implemented by the clients of providers of embedding models.
+pub trait EmbeddingsClient: ProviderClient + Clone {
+    /// The type of the embedding models of the provider
+    type EmbeddingModel: EmbeddingModel;
+
+    /// Create an embedding model with the given name. The number of dimensions is known for
+    /// the models listed by the provider module, and is `0` for other models.
+    fn embedding_model(&self, model: &str) -> Self::EmbeddingModel;
+
+    /// Create an embedding model with the given name and number of dimensions.
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> Self::EmbeddingModel;
+
+    /// Create an embeddings builder with the given embedding model.
+    fn embeddings<D: Embed>(&self, model: &str) -> EmbeddingsBuilder<Self::EmbeddingModel, D> {
+        EmbeddingsBuilder::new(self.embedding_model(model))
+    }
+}
+
+/// Object safe version of [CompletionClient], returning type-erased completion models.
+pub trait CompletionClientDyn: Send + Sync {
+    fn completion_model(&self, model: &str) -> Box<dyn CompletionModelDyn>;
+
+    fn agent(&self, model: &str) -> AgentBuilder<Box<dyn CompletionModelDyn>>;
+}
+
+impl<C> CompletionClientDyn for C
+where
+    C: CompletionClient,
+    C::CompletionModel: 'static,
+    <C::CompletionModel as CompletionModel>::Response: 'static,
+{
+    fn completion_model(&self, model: &str) -> Box<dyn CompletionModelDyn> {
+        Box::new(<Self as CompletionClient>::completion_model(self, model))
+    }
+
+    fn agent(&self, model: &str) -> AgentBuilder<Box<dyn CompletionModelDyn>> {
+        AgentBuilder::new(CompletionClientDyn::completion_model(self, model))
+    }
+}
+
+/// Object safe version of [EmbeddingsClient], returning type-erased embedding models.
+pub trait EmbeddingsClientDyn: Send + Sync {
+    fn embedding_model(&self, model: &str) -> Box<dyn EmbeddingModelDyn>;
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> Box<dyn EmbeddingModelDyn>;
+}
+
+impl<C> EmbeddingsClientDyn for C
+where
+    C: EmbeddingsClient,
+    C::EmbeddingModel: 'static,
+{
+    fn embedding_model(&self, model: &str) -> Box<dyn EmbeddingModelDyn> {
+        Box::new(<Self as EmbeddingsClient>::embedding_model(self, model))
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> Box<dyn EmbeddingModelDyn> {
+        Box::new(<Self as EmbeddingsClient>::embedding_model_with_ndims(
+            self, model, ndims,
+        ))
+    }
+}
+
+type ClientFactory =
+    Arc<dyn Fn() -> Result<Arc<dyn ProviderClient>, ClientBuilderError> + Send + Sync>;
+
+/// A registered provider: the factory of its client, and the client once created.
+#[derive(Clone)]
+struct Registration {
+    factory: ClientFactory,
+    client: Arc<Mutex<Option<Arc<dyn ProviderClient>>>>,
+}
+
+/// Registry creating the clients of providers by name.
+///
+/// [DynClientBuilder::new] registers the providers of this crate, whose clients are created
+/// from the environment (see [ProviderClient::try_from_env]): `anthropic`, `azure`, `cohere`,
+/// `deepseek`, `galadriel`, `gemini`, `groq`, `hyperbolic`, `moonshot`, `ollama`, `openai`,
+/// `openai_compatible`, `perplexity`, `together` and `xai`.
+///
+/// Other providers, or clients configured differently, can be registered with
+/// [DynClientBuilder::register_with].
+///
+/// The client of a provider is created the first time it is used, and shared by the models
+/// created afterwards. If the client can't be created (e.g.: missing environment variable),
+/// the error is returned and creation is retried on the next use.
+///
+/// # Example
+/// ```rust
+/// use rig::{
+///     client::DynClientBuilder,
+///     providers::{openai, openai_compatible},
+/// };
+///
+/// let clients = DynClientBuilder::new().register_with("llama.cpp", || {
+///     openai_compatible::ClientBuilder::new("http://localhost:8080/v1").build()
+/// });
+///
+/// let model = clients.completion("llama.cpp", "qwen2.5-7b-instruct")?;
+/// let embedding_model = clients.embeddings("openai", openai::TEXT_EMBEDDING_3_SMALL)?;
+/// ```
+#[derive(Clone)]
+pub struct DynClientBuilder {
+    providers: HashMap<String, Registration>,
+}
+
+impl Default for DynClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DynClientBuilder {
+    /// Create a registry of the providers of this crate.
+    pub fn new() -> Self {
+        Self::empty()
+            .register::<anthropic::Client>("anthropic")
+            .register::<azure::Client>("azure")
+            .register::<cohere::Client>("cohere")
+            .register::<deepseek::Client>("deepseek")
+            .register::<galadriel::Client>("galadriel")
+            .register::<gemini::Client>("gemini")
+            .register::<groq::Client>("groq")
+            .register::<hyperbolic::Client>("hyperbolic")
+            .register::<moonshot::Client>("moonshot")
+            .register::<ollama::Client>("ollama")
+            .register::<openai::Client>("openai")
+            .register::<openai_compatible::Client>("openai_compatible")
+            .register::<perplexity::Client>("perplexity")
+            .register::<together::Client>("together")
+            .register::<xai::Client>("xai")
+    }
+
+    /// Create a registry without any provider.
+    pub fn empty() -> Self {
+        Self {
+            providers: HashMap::new(),
+        }
+    }
+
+    /// Register a provider whose client is created from the environment.
+    pub fn register<C: ProviderClient + 'static>(self, provider: &str) -> Self {
+        self.insert(provider, Arc::new(|| Ok(Arc::new(C::try_from_env()?))))
+    }
+
+    /// Register a provider whose client is created by the given function. Replaces the
+    /// provider with the same name, if any.
+    pub fn register_with<C: ProviderClient + 'static>(
+        self,
+        provider: &str,
+        factory: impl Fn() -> C + Send + Sync + 'static,
+    ) -> Self {
+        self.insert(provider, Arc::new(move || Ok(Arc::new(factory()))))
+    }
+
+    fn insert(mut self, provider: &str, factory: ClientFactory) -> Self {
+        self.providers.insert(
+            provider.to_string(),
+            Registration {
+                factory,
+                client: Default::default(),
+            },
+        );
+        self
+    }
+
+    /// Get the client of the given provider, creating it on first use.
+    pub fn build(&self, provider: &str) -> Result<Arc<dyn ProviderClient>, ClientBuilderError> {
+        let registration = self
+            .providers
+            .get(provider)
+            .ok_or_else(|| ClientBuilderError::UnknownProvider(provider.to_string()))?;
+
+        let mut client = registration
+            .client
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match &*client {
+            Some(client) => Ok(client.clone()),
+            None => {
+                let created = (registration.factory)()?;
+                *client = Some(created.clone());
+                Ok(created)
+            }
+        }
+    }
+
+    /// Create a completion model of the given provider.
+    pub fn completion(
+        &self,
+        provider: &str,
+        model: &str,
+    ) -> Result<Box<dyn CompletionModelDyn>, ClientBuilderError> {
+        self.build(provider)?
+            .as_completion()
+            .map(|client| client.completion_model(model))
+            .ok_or_else(|| unsupported(provider, "completion models"))
+    }
+
+    /// Create an agent builder with a completion model of the given provider.
+    pub fn agent(
+        &self,
+        provider: &str,
+        model: &str,
+    ) -> Result<AgentBuilder<Box<dyn CompletionModelDyn>>, ClientBuilderError> {
+        Ok(AgentBuilder::new(self.completion(provider, model)?))
+    }
+
+    /// Create an embedding model of the given provider.
+    pub fn embeddings(
+        &self,
+        provider: &str,
+        model: &str,
+    ) -> Result<Box<dyn EmbeddingModelDyn>, ClientBuilderError> {
+        self.build(provider)?
+            .as_embeddings()
+            .map(|client| client.embedding_model(model))
+            .ok_or_else(|| unsupported(provider, "embedding models"))
+    }
+
+    /// Create an embedding model of the given provider, with the given number of dimensions.
+    pub fn embeddings_with_ndims(
+        &self,
+        provider: &str,
+        model: &str,
+        ndims: usize,
+    ) -> Result<Box<dyn EmbeddingModelDyn>, ClientBuilderError> {
+        self.build(provider)?
+            .as_embeddings()
+            .map(|client| client.embedding_model_with_ndims(model, ndims))
+            .ok_or_else(|| unsupported(provider, "embedding models"))
+    }
+}
+
+fn unsupported(provider: &str, feature: &'static str) -> ClientBuilderError {
+    ClientBuilderError::UnsupportedFeature {
+        provider: provider.to_string(),
+        feature,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn clients() -> DynClientBuilder {
+        DynClientBuilder::empty()
+            .register_with("openai", || openai::Client::new("test-key"))
+            .register_with("perplexity", || perplexity::Client::new("test-key"))
+    }
+
+    #[test]
+    fn test_dyn_client_builder() {
+        let clients = clients();
+
+        let model = clients.completion("openai", openai::GPT_4O).unwrap();
+        assert!(CompletionModel::supports_structured_output(&model));
+
+        let model = clients.completion("perplexity", perplexity::SONAR).unwrap();
+        assert!(!CompletionModel::supports_structured_output(&model));
+        assert!(clients.agent("perplexity", perplexity::SONAR).is_ok());
+
+        let embedding_model = clients
+            .embeddings("openai", openai::TEXT_EMBEDDING_3_SMALL)
+            .unwrap();
+        assert_eq!(EmbeddingModel::ndims(&embedding_model), 1536);
+        let embedding_model = clients
+            .embeddings_with_ndims("openai", "text-embedding-custom", 256)
+            .unwrap();
+        assert_eq!(EmbeddingModel::ndims(&embedding_model), 256);
+
+        assert!(matches!(
+            clients.embeddings("perplexity", perplexity::SONAR),
+            Err(ClientBuilderError::UnsupportedFeature { .. })
+        ));
+        assert!(matches!(
+            clients.completion("mistral", "mistral-large"),
+            Err(ClientBuilderError::UnknownProvider(provider)) if provider == "mistral"
+        ));
+    }
+
+    #[test]
+    fn test_dyn_client_builder_reuses_client() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let clients = DynClientBuilder::empty().register_with("openai", {
+            let created = created.clone();
+            move || {
+                created.fetch_add(1, Ordering::SeqCst);
+                openai::Client::new("test-key")
+            }
+        });
+
+        let client = clients.build("openai").unwrap();
+        assert!(clients.completion("openai", openai::GPT_4O).is_ok());
+        assert!(clients
+            .embeddings("openai", openai::TEXT_EMBEDDING_3_SMALL)
+            .is_ok());
+        assert!(Arc::ptr_eq(
+            &client,
+            &clients.clone().build("openai").unwrap()
+        ));
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+    }
+
+    struct EnvClient;
+
+    impl ProviderClient for EnvClient {
+        fn try_from_env() -> Result<Self, ClientBuilderError> {
+            env_var("RIG_TEST_UNSET_API_KEY")?;
+            Ok(Self)
+        }
+    }
+
+    #[test]
+    fn test_dyn_client_builder_missing_env() {
+        let clients = DynClientBuilder::empty().register::<EnvClient>("env");
+
+        assert!(matches!(
+            clients.build("env"),
+            Err(ClientBuilderError::MissingEnv(name)) if name == "RIG_TEST_UNSET_API_KEY"
+        ));
+        assert!(matches!(
+            clients.completion("env", "model"),
+            Err(ClientBuilderError::MissingEnv(_))
+        ));
+    }
+}
@@ -45,12 +45,14 @@ pub enum UserContent {
     Document(Document),
 }
 
-/// Describes responses from a provider which is either text or a tool call.
+/// Describes responses from a provider which is either text, a tool call or the reasoning of
+///  the model.
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(untagged)]
 pub enum AssistantContent {
     Text(Text),
     ToolCall(ToolCall),
+    Reasoning(Reasoning),
 }
 
 /// Tool result content containing information about a tool call and it's resulting content.
@@ -81,6 +83,46 @@ pub struct ToolFunction {
     pub arguments: serde_json::Value,
 }
 
+/// Reasoning (a.k.a. thinking) of a model before its answer, produced by reasoning models
+///  (e.g.: DeepSeek's `reasoning_content`, Anthropic's thinking blocks or the `<think>` tags of
+///  R1 models). Providers that do not accept reasoning back in the chat history drop it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Reasoning {
+    pub reasoning: String,
+    /// Signature of the reasoning, which some providers (e.g.: Anthropic) require to be sent
+    ///  back unchanged in multi-turn conversations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Whether the reasoning was redacted (i.e.: encrypted) by the provider, in which case
+    ///  `reasoning` contains the encrypted data.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool,
+}
+
+impl Reasoning {
+    pub fn new(reasoning: impl Into<String>) -> Self {
+        Self {
+            reasoning: reasoning.into(),
+            signature: None,
+            redacted: false,
+        }
+    }
+
+    pub fn with_signature(mut self, signature: impl Into<String>) -> Self {
+        self.signature = Some(signature.into());
+        self
+    }
+
+    /// Reasoning encrypted by the provider, to be sent back as is.
+    pub fn redacted(data: impl Into<String>) -> Self {
+        Self {
+            reasoning: data.into(),
+            signature: None,
+            redacted: true,
+        }
+    }
+}
+
 // ================================================================
 // Base content models
 // ================================================================
@@ -229,6 +271,23 @@ impl Message {
             content: OneOrMany::one(AssistantContent::text(text)),
         }
     }
+
+    /// Remove the reasoning content of an assistant message, e.g.: to save tokens when sending
+    ///  back the chat history. Returns `None` if the message only contained reasoning.
+    pub fn without_reasoning(self) -> Option<Self> {
+        match self {
+            Message::Assistant { content } => {
+                let content = content
+                    .into_iter()
+                    .filter(|content| !matches!(content, AssistantContent::Reasoning(_)))
+                    .collect::<Vec<_>>();
+                OneOrMany::many(content)
+                    .ok()
+                    .map(|content| Message::Assistant { content })
+            }
+            message => Some(message),
+        }
+    }
 }
 
 impl UserContent {
@@ -307,6 +366,11 @@ impl AssistantContent {
             },
         })
     }
+
+    /// Helper constructor to make creating assistant reasoning content easier.
+    pub fn reasoning(reasoning: impl Into<String>) -> Self {
+        AssistantContent::Reasoning(Reasoning::new(reasoning))
+    }
 }
 
 impl ToolResultContent {
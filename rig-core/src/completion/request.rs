@@ -62,16 +62,19 @@
 //!
 //! For more information on how to use the completion functionality, refer to the documentation of
 //! the individual traits, structs, and enums defined in this module.
-use std::collections::HashMap;
+use std::{any::Any, collections::HashMap, sync::Arc};
 
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::streaming::{StreamingCompletionModel, StreamingResult};
 use crate::OneOrMany;
 use crate::{
+    http_error::HttpStatusError,
     json_utils,
     message::{Message, UserContent},
+    tokenizer::{self, ApproximateTokenizer, Tokenizer},
     tool::ToolSetError,
 };
 
@@ -99,6 +102,19 @@ pub enum CompletionError {
     /// Error returned by the completion model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Unsuccessful HTTP response returned by the completion model provider
+    #[error("StatusError: {0}")]
+    StatusError(#[from] HttpStatusError),
+
+    /// The budget of a [CostTracker](crate::cost::CostTracker) was exceeded
+    #[error("BudgetExceeded: spent {spent} out of a budget of {budget}")]
+    BudgetExceeded { spent: f64, budget: f64 },
+
+    /// The request does not fit in the context window of the model, even after trimming the
+    /// chat history and documents (see [CompletionRequestBuilder::context_window])
+    #[error("ContextWindowExceeded: the request needs {tokens} tokens out of a limit of {limit}")]
+    ContextWindowExceeded { tokens: u64, limit: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -108,6 +124,10 @@ pub enum PromptError {
 
     #[error("ToolCallError: {0}")]
     ToolError(#[from] ToolSetError),
+
+    /// The model kept calling tools after the maximum number of turns was reached
+    #[error("MaxTurnsError: reached limit of {0} turns")]
+    MaxTurnsError(usize),
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -156,8 +176,8 @@ pub trait Prompt: Send + Sync {
     ///
     /// If the completion model's response is a message, then it is returned as a string.
     ///
-    /// If the completion model's response is a tool call, then the tool is called and
-    /// the result is returned as a string.
+    /// If the completion model's response contains tool calls, then the tools are called and
+    /// their results are returned as a string.
     ///
     /// If the tool does not exist, or the tool call fails, then an error is returned.
     fn prompt(
@@ -172,8 +192,8 @@ pub trait Chat: Send + Sync {
     ///
     /// If the completion model's response is a message, then it is returned as a string.
     ///
-    /// If the completion model's response is a tool call, then the tool is called and the result
-    /// is returned as a string.
+    /// If the completion model's response contains tool calls, then the tools are called and
+    /// their results are returned as a string.
     ///
     /// If the tool does not exist, or the tool call fails, then an error is returned.
     fn chat(
@@ -210,10 +230,63 @@ pub struct CompletionResponse<T> {
     /// The completion choice (represented by one or more assistant message content)
     /// returned by the completion model provider
     pub choice: OneOrMany<AssistantContent>,
+    /// The token usage of the request, if reported by the completion model provider
+    pub usage: Option<Usage>,
     /// The raw response returned by the completion model provider
     pub raw_response: T,
 }
 
+/// Token usage of a request, normalized across providers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Number of tokens in the prompt (including the preamble, history and documents)
+    pub input_tokens: u64,
+    /// Number of tokens generated by the model
+    pub output_tokens: u64,
+    /// Number of input tokens read from the provider's prompt cache (included in `input_tokens`)
+    #[serde(default)]
+    pub cached_input_tokens: u64,
+    /// Number of tokens spent on reasoning by the model (included in `output_tokens`)
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+}
+
+impl Usage {
+    /// Total number of tokens used by the request
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            input_tokens: self.input_tokens + other.input_tokens,
+            output_tokens: self.output_tokens + other.output_tokens,
+            cached_input_tokens: self.cached_input_tokens + other.cached_input_tokens,
+            reasoning_tokens: self.reasoning_tokens + other.reasoning_tokens,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::fmt::Display for Usage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Input tokens: {} (cached: {}) Output tokens: {} (reasoning: {})",
+            self.input_tokens, self.cached_input_tokens, self.output_tokens, self.reasoning_tokens
+        )
+    }
+}
+
 /// Trait defining a completion model that can be used to generate completion responses.
 /// This trait is meant to be implemented by the user to define a custom completion model,
 /// either from a third party provider (e.g.: OpenAI) or a local model.
@@ -228,13 +301,107 @@ pub trait CompletionModel: Clone + Send + Sync {
     ) -> impl std::future::Future<Output = Result<CompletionResponse<Self::Response>, CompletionError>>
            + Send;
 
+    /// Whether the model natively supports constraining its response to a JSON schema
+    /// (see [CompletionRequest::output_schema]). Defaults to `false`, in which case the
+    /// output schema of requests is ignored.
+    fn supports_structured_output(&self) -> bool {
+        false
+    }
+
+    /// Maximum number of tokens of a request (input and output tokens), if known.
+    /// Used by [CompletionRequestBuilder::fit_context_window]. Defaults to `None`.
+    fn context_window(&self) -> Option<u64> {
+        None
+    }
+
     /// Generates a completion request builder for the given `prompt`.
     fn completion_request(&self, prompt: impl Into<Message>) -> CompletionRequestBuilder<Self> {
         CompletionRequestBuilder::new(self.clone(), prompt)
     }
 }
 
+/// Raw response of a [CompletionModelDyn], which can be downcast to the response type of the
+/// underlying completion model (e.g.: `openai::CompletionResponse`).
+pub type DynResponse = Box<dyn Any + Send + Sync>;
+
+/// Wrapper trait to allow for dynamic dispatch of completion models, e.g.: to select the
+/// provider of a model at runtime (see [client](crate::client)).
+///
+/// `Box<dyn CompletionModelDyn>` implements [CompletionModel] and can be used wherever a
+/// completion model is expected, e.g.: to hold agents of different providers in the same
+/// collection (see [Agent::into_dyn](crate::agent::Agent::into_dyn)).
+pub trait CompletionModelDyn: Send + Sync {
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<DynResponse>, CompletionError>>;
+
+    fn supports_structured_output(&self) -> bool;
+
+    fn context_window(&self) -> Option<u64>;
+
+    fn box_clone(&self) -> Box<dyn CompletionModelDyn>;
+}
+
+impl<M> CompletionModelDyn for M
+where
+    M: CompletionModel + 'static,
+    M::Response: 'static,
+{
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<DynResponse>, CompletionError>> {
+        Box::pin(async move {
+            let response = <Self as CompletionModel>::completion(self, request).await?;
+            Ok(CompletionResponse {
+                choice: response.choice,
+                usage: response.usage,
+                raw_response: Box::new(response.raw_response) as DynResponse,
+            })
+        })
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        <Self as CompletionModel>::supports_structured_output(self)
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        <Self as CompletionModel>::context_window(self)
+    }
+
+    fn box_clone(&self) -> Box<dyn CompletionModelDyn> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn CompletionModelDyn> {
+    fn clone(&self) -> Self {
+        (**self).box_clone()
+    }
+}
+
+impl CompletionModel for Box<dyn CompletionModelDyn> {
+    type Response = DynResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<DynResponse>, CompletionError> {
+        (**self).completion(request).await
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        (**self).supports_structured_output()
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        (**self).context_window()
+    }
+}
+
 /// Struct representing a general completion request that can be sent to a completion model provider.
+#[derive(Clone)]
 pub struct CompletionRequest {
     /// The prompt to be sent to the completion model provider
     pub prompt: Message,
@@ -252,6 +419,9 @@ pub struct CompletionRequest {
     pub max_tokens: Option<u64>,
     /// Additional provider-specific parameters to be sent to the completion model provider
     pub additional_params: Option<serde_json::Value>,
+    /// JSON schema the response must conform to. Only used by models that natively support
+    /// structured output (see [CompletionModel::supports_structured_output]).
+    pub output_schema: Option<serde_json::Value>,
 }
 
 impl CompletionRequest {
@@ -324,11 +494,16 @@ pub struct CompletionRequestBuilder<M: CompletionModel> {
     prompt: Message,
     preamble: Option<String>,
     chat_history: Vec<Message>,
-    documents: Vec<Document>,
+    /// Documents with their relevance, if known
+    documents: Vec<(Document, Option<f64>)>,
     tools: Vec<ToolDefinition>,
     temperature: Option<f64>,
     max_tokens: Option<u64>,
     additional_params: Option<serde_json::Value>,
+    output_schema: Option<serde_json::Value>,
+    context_window: Option<u64>,
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    strip_reasoning: bool,
 }
 
 impl<M: CompletionModel> CompletionRequestBuilder<M> {
@@ -343,6 +518,10 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
             temperature: None,
             max_tokens: None,
             additional_params: None,
+            output_schema: None,
+            context_window: None,
+            tokenizer: None,
+            strip_reasoning: false,
         }
     }
 
@@ -367,7 +546,16 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 
     /// Adds a document to the completion request.
     pub fn document(mut self, document: Document) -> Self {
-        self.documents.push(document);
+        self.documents.push((document, None));
+        self
+    }
+
+    /// Adds a document to the completion request, along with its relevance to the prompt
+    /// (higher is more relevant). When trimming the request to fit in the context window,
+    /// the least relevant documents are dropped first, while documents without a relevance
+    /// are always kept.
+    pub fn document_with_relevance(mut self, document: Document, relevance: f64) -> Self {
+        self.documents.push((document, Some(relevance)));
         self
     }
 
@@ -444,22 +632,207 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
         self
     }
 
-    /// Builds the completion request.
-    pub fn build(self) -> CompletionRequest {
+    /// Sets the JSON schema the response must conform to.
+    /// Note: This is ignored by models that do not natively support structured output
+    /// (see [CompletionModel::supports_structured_output])
+    pub fn output_schema(mut self, output_schema: serde_json::Value) -> Self {
+        self.output_schema = Some(output_schema);
+        self
+    }
+
+    /// Sets the JSON schema the response must conform to.
+    /// Note: This is ignored by models that do not natively support structured output
+    /// (see [CompletionModel::supports_structured_output])
+    pub fn output_schema_opt(mut self, output_schema: Option<serde_json::Value>) -> Self {
+        self.output_schema = output_schema;
+        self
+    }
+
+    /// Trims the request to fit in a context window of `tokens` tokens, including the
+    /// `max_tokens` reserved for the response.
+    ///
+    /// The oldest messages of the chat history are dropped first, then the least relevant
+    /// documents (see [Self::document_with_relevance]). The preamble, prompt, tools and
+    /// documents without relevance are never dropped: if the request still does not fit,
+    /// [Self::send] fails with [CompletionError::ContextWindowExceeded].
+    pub fn context_window(mut self, tokens: u64) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// Trims the request to fit in the given context window, if any (see [Self::context_window]).
+    pub fn context_window_opt(mut self, tokens: Option<u64>) -> Self {
+        self.context_window = tokens;
+        self
+    }
+
+    /// Trims the request to fit in the context window of the model (see [Self::context_window]
+    /// and [CompletionModel::context_window]). Does nothing if the context window of the model
+    /// is unknown.
+    pub fn fit_context_window(mut self) -> Self {
+        self.context_window = self.model.context_window();
+        self
+    }
+
+    /// Sets the tokenizer used to count the tokens of the request when trimming it to fit in
+    /// the context window. Defaults to an [ApproximateTokenizer].
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Some(Arc::new(tokenizer));
+        self
+    }
+
+    /// Sets the tokenizer used to count the tokens of the request, if any (see [Self::tokenizer]).
+    pub fn tokenizer_opt(mut self, tokenizer: Option<Arc<dyn Tokenizer>>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Sets whether the reasoning content of the chat history is removed from the request.
+    /// By default, reasoning is kept so that providers which require it in multi-turn
+    /// conversations (e.g.: Anthropic's signed thinking blocks when using tools) receive it,
+    /// while providers which do not accept it drop it themselves. Stripping reasoning saves
+    /// tokens, and assistant messages only containing reasoning are removed altogether.
+    pub fn strip_reasoning(mut self, strip: bool) -> Self {
+        self.strip_reasoning = strip;
+        self
+    }
+
+    /// Remove the reasoning content of the chat history, if requested (see [Self::strip_reasoning]).
+    fn strip_reasoning_from_history(&mut self) {
+        if self.strip_reasoning {
+            self.chat_history = std::mem::take(&mut self.chat_history)
+                .into_iter()
+                .filter_map(Message::without_reasoning)
+                .collect();
+        }
+    }
+
+    /// Drop the oldest messages and the least relevant documents until the request fits in
+    /// the context window, if set.
+    fn trim_to_context_window(&mut self) -> Result<(), CompletionError> {
+        let Some(context_window) = self.context_window else {
+            return Ok(());
+        };
+        let tokenizer = self
+            .tokenizer
+            .clone()
+            .unwrap_or_else(|| Arc::new(ApproximateTokenizer::default()));
+        let tokenizer = tokenizer.as_ref();
+
+        let limit = context_window.saturating_sub(self.max_tokens.unwrap_or_default()) as usize;
+
+        let mut history = self
+            .chat_history
+            .iter()
+            .map(|message| tokenizer::message_tokens(tokenizer, message))
+            .collect::<Vec<_>>();
+        let documents = self
+            .documents
+            .iter()
+            .map(|(document, _)| tokenizer::document_tokens(tokenizer, document))
+            .collect::<Vec<_>>();
+        let mut total = self
+            .preamble
+            .as_ref()
+            .map(|preamble| tokenizer.count_tokens(preamble) + tokenizer::MESSAGE_OVERHEAD_TOKENS)
+            .unwrap_or_default()
+            + tokenizer::message_tokens(tokenizer, &self.prompt)
+            + self
+                .tools
+                .iter()
+                .map(|tool| tokenizer::tool_tokens(tokenizer, tool))
+                .sum::<usize>()
+            + history.iter().sum::<usize>()
+            + documents.iter().sum::<usize>();
+
+        if total <= limit {
+            return Ok(());
+        }
+
+        // Drop the oldest messages, making sure the history still starts with a user message
+        // which is not the result of a dropped tool call
+        let mut dropped_messages = 0;
+        while total > limit && dropped_messages < history.len() {
+            total -= history[dropped_messages];
+            dropped_messages += 1;
+            while dropped_messages < history.len()
+                && !starts_conversation(&self.chat_history[dropped_messages])
+            {
+                total -= history[dropped_messages];
+                dropped_messages += 1;
+            }
+        }
+        self.chat_history.drain(..dropped_messages);
+        history.drain(..dropped_messages);
+
+        // Drop the least relevant documents, the last ones first in case of ties
+        let mut by_relevance = self
+            .documents
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, relevance))| relevance.map(|relevance| (i, relevance)))
+            .collect::<Vec<_>>();
+        by_relevance.sort_by(|(i, a), (j, b)| a.total_cmp(b).then(j.cmp(i)));
+
+        let mut dropped_documents = vec![false; self.documents.len()];
+        for (i, _) in by_relevance {
+            if total <= limit {
+                break;
+            }
+            total -= documents[i];
+            dropped_documents[i] = true;
+        }
+        let mut dropped = dropped_documents.into_iter();
+        self.documents
+            .retain(|_| !dropped.next().unwrap_or_default());
+
+        tracing::debug!(target: "rig",
+            "Trimmed {} messages and {} documents to fit in the context window ({} tokens)",
+            dropped_messages,
+            documents.len() - self.documents.len(),
+            total
+        );
+
+        if total > limit {
+            return Err(CompletionError::ContextWindowExceeded {
+                tokens: (total as u64) + self.max_tokens.unwrap_or_default(),
+                limit: context_window,
+            });
+        }
+        // The request now fits, there is no need to count its tokens again
+        self.context_window = None;
+        Ok(())
+    }
+
+    /// Builds the completion request, trimming it to fit in the context window if set
+    /// (see [Self::context_window]).
+    pub fn build(mut self) -> CompletionRequest {
+        self.strip_reasoning_from_history();
+        if let Err(err) = self.trim_to_context_window() {
+            tracing::warn!(target: "rig", "{}", err);
+        }
+
         CompletionRequest {
             prompt: self.prompt,
             preamble: self.preamble,
             chat_history: self.chat_history,
-            documents: self.documents,
+            documents: self
+                .documents
+                .into_iter()
+                .map(|(document, _)| document)
+                .collect(),
             tools: self.tools,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
             additional_params: self.additional_params,
+            output_schema: self.output_schema,
         }
     }
 
     /// Sends the completion request to the completion model provider and returns the completion response.
-    pub async fn send(self) -> Result<CompletionResponse<M::Response>, CompletionError> {
+    pub async fn send(mut self) -> Result<CompletionResponse<M::Response>, CompletionError> {
+        self.strip_reasoning_from_history();
+        self.trim_to_context_window()?;
         let model = self.model.clone();
         model.completion(self.build()).await
     }
@@ -467,12 +840,25 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 
 impl<M: StreamingCompletionModel> CompletionRequestBuilder<M> {
     /// Stream the completion request
-    pub async fn stream(self) -> Result<StreamingResult, CompletionError> {
+    pub async fn stream(mut self) -> Result<StreamingResult, CompletionError> {
+        self.strip_reasoning_from_history();
+        self.trim_to_context_window()?;
         let model = self.model.clone();
         model.stream(self.build()).await
     }
 }
 
+/// Whether a chat history can start with the message: providers expect a user message, and
+/// reject tool results whose tool call is not part of the history.
+fn starts_conversation(message: &Message) -> bool {
+    match message {
+        Message::User { content } => !content
+            .iter()
+            .any(|content| matches!(content, UserContent::ToolResult(_))),
+        Message::Assistant { .. } => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::OneOrMany;
@@ -535,6 +921,7 @@ mod tests {
             temperature: None,
             max_tokens: None,
             additional_params: None,
+            output_schema: None,
         };
 
         let expected = Message::User {
@@ -554,4 +941,122 @@ mod tests {
 
         assert_eq!(request.prompt_with_context(), expected);
     }
+
+    #[derive(Clone)]
+    struct MockModel;
+
+    impl CompletionModel for MockModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text("Hello")),
+                usage: None,
+                raw_response: (),
+            })
+        }
+    }
+
+    #[test]
+    fn test_strip_reasoning() {
+        let history = vec![
+            Message::user("What is 2 + 2?"),
+            Message::Assistant {
+                content: OneOrMany::many(vec![
+                    AssistantContent::Reasoning(
+                        crate::message::Reasoning::new("Adding").with_signature("sig"),
+                    ),
+                    AssistantContent::text("4"),
+                ])
+                .unwrap(),
+            },
+            Message::Assistant {
+                content: OneOrMany::one(AssistantContent::reasoning("Nothing to add")),
+            },
+        ];
+
+        let request = MockModel
+            .completion_request("And 3 + 3?")
+            .messages(history.clone())
+            .build();
+        assert_eq!(request.chat_history, history);
+
+        let request = MockModel
+            .completion_request("And 3 + 3?")
+            .messages(history)
+            .strip_reasoning(true)
+            .build();
+        assert_eq!(
+            request.chat_history,
+            vec![Message::user("What is 2 + 2?"), Message::assistant("4")]
+        );
+    }
+
+    #[test]
+    fn test_context_window_trimming() {
+        let document = |id: &str| Document {
+            id: id.to_string(),
+            text: "text".to_string(),
+            additional_props: HashMap::new(),
+        };
+        // Each document counts as 5 words, each message as its words plus 4
+        let builder = || {
+            MockModel
+                .completion_request("question")
+                .messages(vec![
+                    Message::user("one two three"),
+                    Message::Assistant {
+                        content: OneOrMany::one(AssistantContent::tool_call(
+                            "call_1",
+                            "lookup",
+                            serde_json::json!({}),
+                        )),
+                    },
+                    Message::User {
+                        content: OneOrMany::one(UserContent::tool_result(
+                            "call_1",
+                            OneOrMany::one(crate::message::ToolResultContent::text("result")),
+                        )),
+                    },
+                    Message::user("four five"),
+                ])
+                .document(document("static"))
+                .document_with_relevance(document("low"), 0.5)
+                .document_with_relevance(document("high"), 1.0)
+                .max_tokens(4)
+                .tokenizer(|text: &str| text.split_whitespace().count())
+        };
+        let ids = |request: &CompletionRequest| {
+            request
+                .documents
+                .iter()
+                .map(|document| document.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // 44 tokens in total: the tool call and its result are dropped with the first message
+        let request = builder().context_window(30).build();
+        assert_eq!(request.chat_history, vec![Message::user("four five")]);
+        assert_eq!(ids(&request), vec!["static", "low", "high"]);
+
+        let request = builder().context_window(20).build();
+        assert!(request.chat_history.is_empty());
+        assert_eq!(ids(&request), vec!["static", "high"]);
+
+        let request = builder().build();
+        assert_eq!(request.chat_history.len(), 4);
+
+        // The prompt and the static document can't be dropped
+        let mut builder = builder().context_window(10);
+        assert!(matches!(
+            builder.trim_to_context_window(),
+            Err(CompletionError::ContextWindowExceeded {
+                tokens: 14,
+                limit: 10
+            })
+        ));
+    }
 }
@@ -0,0 +1,424 @@
+//! This module provides a cost and budget tracking layer for completion and embedding models.
+//!
+//! A [CostTracker] accumulates the token usage reported by the providers (see [Usage]) and
+//! the estimated cost of each model, using a pluggable [PriceTable]. Models are wrapped with
+//! [CostTracker::track], which returns a [CostTracked] model implementing the same traits as
+//! the wrapped model. The tracker can be shared by any number of models so that a single budget
+//! can be enforced over a whole application or batch job.
+//!
+//! Once the budget is exceeded, every subsequent request fails with
+//! [CompletionError::BudgetExceeded] (or [EmbeddingError::BudgetExceeded]) without being sent.
+//! Note that requests already in flight when the budget is crossed are still completed
+//! (and accounted for).
+//!
+//! # Example
+//! ```rust
+//! use std::collections::HashMap;
+//! use rig::{cost::{CostTracker, ModelPrice}, providers::openai};
+//!
+//! let openai = openai::Client::new("YOUR_API_KEY");
+//!
+//! let prices = HashMap::from([(
+//!     openai::GPT_4O.to_string(),
+//!     ModelPrice::per_million(2.5, 10.0).with_cached_input(1.25),
+//! )]);
+//! let tracker = CostTracker::new(prices).with_budget(5.0);
+//!
+//! let gpt4o = tracker.track(openai::GPT_4O, openai.completion_model(openai::GPT_4O));
+//!
+//! // Use `gpt4o` as any other completion model, then:
+//! println!("Spent so far: ${:.4}", tracker.total_cost());
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::StreamExt;
+
+use crate::{
+    completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Usage},
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel, EmbeddingsResponse},
+    streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult},
+};
+
+/// Price of a model, in dollars (or any other currency) per million tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelPrice {
+    /// Price per million input tokens
+    pub input: f64,
+    /// Price per million output tokens (including reasoning tokens)
+    pub output: f64,
+    /// Price per million cached input tokens. If `None`, cached tokens are billed as input tokens.
+    pub cached_input: Option<f64>,
+}
+
+impl ModelPrice {
+    /// Create a price from the price per million input and output tokens
+    pub fn per_million(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            cached_input: None,
+        }
+    }
+
+    /// Set the price per million cached input tokens
+    pub fn with_cached_input(mut self, cached_input: f64) -> Self {
+        self.cached_input = Some(cached_input);
+        self
+    }
+
+    /// Estimate the cost of the given token usage
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let (uncached_input_tokens, cached_input_price) = match self.cached_input {
+            Some(price) => (
+                usage.input_tokens.saturating_sub(usage.cached_input_tokens),
+                usage.cached_input_tokens as f64 * price,
+            ),
+            None => (usage.input_tokens, 0.0),
+        };
+
+        (uncached_input_tokens as f64 * self.input
+            + cached_input_price
+            + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Trait for looking up the price of a model by name.
+/// Implemented for maps of model names to [ModelPrice], but it can be implemented to fetch
+/// prices from any other source.
+pub trait PriceTable: Send + Sync {
+    /// Returns the price of the model, or `None` if the price of the model is unknown
+    fn price(&self, model: &str) -> Option<ModelPrice>;
+}
+
+impl PriceTable for HashMap<String, ModelPrice> {
+    fn price(&self, model: &str) -> Option<ModelPrice> {
+        self.get(model).copied()
+    }
+}
+
+impl PriceTable for std::collections::BTreeMap<String, ModelPrice> {
+    fn price(&self, model: &str) -> Option<ModelPrice> {
+        self.get(model).copied()
+    }
+}
+
+/// Usage and estimated cost accumulated for a single model
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelCost {
+    /// Number of requests made to the model
+    pub requests: u64,
+    /// Accumulated token usage of the model
+    pub usage: Usage,
+    /// Accumulated estimated cost of the model (zero if the price of the model is unknown)
+    pub cost: f64,
+}
+
+#[derive(Default)]
+struct CostState {
+    models: HashMap<String, ModelCost>,
+    total_cost: f64,
+}
+
+/// Accumulates the token usage and estimated cost of the models it tracks and optionally
+/// enforces a budget over them. Cloning the tracker is cheap and the clones share their state.
+#[derive(Clone)]
+pub struct CostTracker {
+    prices: Arc<dyn PriceTable>,
+    budget: Option<f64>,
+    state: Arc<Mutex<CostState>>,
+}
+
+impl CostTracker {
+    /// Create a new tracker using the given price table
+    pub fn new(prices: impl PriceTable + 'static) -> Self {
+        Self {
+            prices: Arc::new(prices),
+            budget: None,
+            state: Arc::default(),
+        }
+    }
+
+    /// Set a hard budget. Requests made once the budget is exceeded fail without being sent.
+    pub fn with_budget(mut self, budget: f64) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Wrap the completion or embedding model `model` so that its usage is tracked under the
+    /// name `name`, which is also used to look up its price
+    pub fn track<M>(&self, name: &str, model: M) -> CostTracked<M> {
+        CostTracked {
+            model,
+            name: name.to_string(),
+            tracker: self.clone(),
+        }
+    }
+
+    /// The budget of the tracker (if any)
+    pub fn budget(&self) -> Option<f64> {
+        self.budget
+    }
+
+    /// Total estimated cost of all the tracked models
+    pub fn total_cost(&self) -> f64 {
+        self.state().total_cost
+    }
+
+    /// Total token usage of all the tracked models
+    pub fn total_usage(&self) -> Usage {
+        self.state()
+            .models
+            .values()
+            .fold(Usage::default(), |total, model| total + model.usage)
+    }
+
+    /// Usage and estimated cost of each tracked model, keyed by model name
+    pub fn costs(&self) -> HashMap<String, ModelCost> {
+        self.state().models.clone()
+    }
+
+    /// Remaining budget (if any). Can be negative once the budget is exceeded.
+    pub fn remaining_budget(&self) -> Option<f64> {
+        self.budget.map(|budget| budget - self.total_cost())
+    }
+
+    /// Record the usage of a request made to the model `name`, returning its estimated cost
+    pub fn record(&self, name: &str, usage: &Usage) -> f64 {
+        let cost = match self.prices.price(name) {
+            Some(price) => price.cost(usage),
+            None => {
+                tracing::debug!(target: "rig", "No price found for model {}, cost not tracked", name);
+                0.0
+            }
+        };
+
+        let mut state = self.state();
+        let model = state.models.entry(name.to_string()).or_default();
+        model.requests += 1;
+        model.usage += *usage;
+        model.cost += cost;
+        state.total_cost += cost;
+
+        cost
+    }
+
+    /// Reset the accumulated usage and costs (e.g.: at the start of a new billing period)
+    pub fn reset(&self) {
+        *self.state() = CostState::default();
+    }
+
+    fn check_budget(&self) -> Result<(), (f64, f64)> {
+        match self.budget {
+            Some(budget) => {
+                let spent = self.total_cost();
+                if spent >= budget {
+                    Err((spent, budget))
+                } else {
+                    Ok(())
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, CostState> {
+        // The state is always left consistent, so a poisoned lock can be recovered
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A completion or embedding model whose usage is tracked by a [CostTracker].
+/// Created with [CostTracker::track].
+#[derive(Clone)]
+pub struct CostTracked<M> {
+    model: M,
+    name: String,
+    tracker: CostTracker,
+}
+
+impl<M> CostTracked<M> {
+    /// The wrapped model
+    pub fn inner(&self) -> &M {
+        &self.model
+    }
+
+    /// The tracker of the model
+    pub fn tracker(&self) -> &CostTracker {
+        &self.tracker
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for CostTracked<M> {
+    type Response = M::Response;
+
+    fn supports_structured_output(&self) -> bool {
+        self.model.supports_structured_output()
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        self.model.context_window()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        self.tracker
+            .check_budget()
+            .map_err(|(spent, budget)| CompletionError::BudgetExceeded { spent, budget })?;
+
+        let response = self.model.completion(request).await?;
+        if let Some(usage) = &response.usage {
+            self.tracker.record(&self.name, usage);
+        }
+
+        Ok(response)
+    }
+}
+
+impl<M: StreamingCompletionModel + Sync> StreamingCompletionModel for CostTracked<M> {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        self.tracker
+            .check_budget()
+            .map_err(|(spent, budget)| CompletionError::BudgetExceeded { spent, budget })?;
+
+        let tracker = self.tracker.clone();
+        let name = self.name.clone();
+        let stream = self.model.stream(request).await?;
+
+        // The usage is recorded from the usage chunk only, since the final chunk repeats it
+        Ok(Box::pin(stream.inspect(move |chunk| {
+            if let Ok(StreamingChoice::Usage(usage)) = chunk {
+                tracker.record(&name, usage);
+            }
+        })))
+    }
+}
+
+impl<M: EmbeddingModel> EmbeddingModel for CostTracked<M> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.embed_texts_with_usage(texts)
+            .await
+            .map(|response| response.embeddings)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<EmbeddingsResponse, EmbeddingError> {
+        self.tracker
+            .check_budget()
+            .map_err(|(spent, budget)| EmbeddingError::BudgetExceeded { spent, budget })?;
+
+        let response = self.model.embed_texts_with_usage(texts).await?;
+        if let Some(usage) = &response.usage {
+            self.tracker.record(&self.name, usage);
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        completion::{AssistantContent, Prompt, PromptError},
+        OneOrMany,
+    };
+
+    #[derive(Clone)]
+    struct MockModel;
+
+    impl CompletionModel for MockModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text("Hello")),
+                usage: Some(Usage {
+                    input_tokens: 600_000,
+                    output_tokens: 100_000,
+                    cached_input_tokens: 400_000,
+                    reasoning_tokens: 0,
+                }),
+                raw_response: (),
+            })
+        }
+    }
+
+    #[test]
+    fn test_model_price_cost() {
+        let usage = Usage {
+            input_tokens: 2_000_000,
+            output_tokens: 1_000_000,
+            cached_input_tokens: 1_000_000,
+            reasoning_tokens: 500_000,
+        };
+
+        assert_eq!(ModelPrice::per_million(1.0, 4.0).cost(&usage), 6.0);
+        assert_eq!(
+            ModelPrice::per_million(1.0, 4.0)
+                .with_cached_input(0.5)
+                .cost(&usage),
+            5.5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cost_tracking_and_budget() {
+        let prices = HashMap::from([("mock".to_string(), ModelPrice::per_million(2.0, 10.0))]);
+        let tracker = CostTracker::new(prices).with_budget(3.0);
+
+        let agent = crate::agent::AgentBuilder::new(tracker.track("mock", MockModel)).build();
+        let unpriced = crate::agent::AgentBuilder::new(tracker.track("other", MockModel)).build();
+
+        // Each request costs 0.6 * 2.0 + 0.1 * 10.0 = 2.2
+        agent.prompt("Hi").await.unwrap();
+        assert_eq!(tracker.total_cost(), 2.2);
+
+        // Unknown prices only track usage
+        unpriced.prompt("Hi").await.unwrap();
+        assert_eq!(tracker.total_cost(), 2.2);
+        assert_eq!(tracker.total_usage().input_tokens, 1_200_000);
+
+        agent.prompt("Hi").await.unwrap();
+        assert!(tracker.remaining_budget().unwrap() < 0.0);
+
+        match agent.prompt("Hi").await {
+            Err(PromptError::CompletionError(CompletionError::BudgetExceeded {
+                spent,
+                budget,
+            })) => {
+                assert_eq!(spent, 4.4);
+                assert_eq!(budget, 3.0);
+            }
+            result => panic!("Expected a budget exceeded error, got {:?}", result),
+        }
+
+        let costs = tracker.costs();
+        assert_eq!(costs["mock"].requests, 2);
+        assert_eq!(costs["other"].requests, 1);
+        assert_eq!(costs["other"].cost, 0.0);
+
+        tracker.reset();
+        assert!(agent.prompt("Hi").await.is_ok());
+    }
+}
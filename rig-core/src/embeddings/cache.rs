@@ -0,0 +1,512 @@
+//! This module provides a caching layer for embedding models.
+//!
+//! Embedding the same text twice with the same model always yields the same vector, so
+//! re-ingesting a mostly unchanged corpus or repeating queries should not cost anything.
+//! [CachedEmbeddingModel] wraps an [EmbeddingModel] and looks up the embedding of each text
+//! in an [EmbeddingCache] before sending the texts that were not found to the wrapped model.
+//!
+//! Entries are identified by a [CacheKey], made of an identifier of the model and a hash of
+//! the embedded text. Rig provides the following caches:
+//! - [LruEmbeddingCache], an in-memory cache holding a bounded number of embeddings
+//! - [DirectoryEmbeddingCache], which stores each embedding in a file of a directory
+//! - `SqliteEmbeddingCache`, in the `rig-sqlite` companion crate
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     embeddings::{cache::{CachedEmbeddingModel, DirectoryEmbeddingCache}, EmbeddingsBuilder},
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let model = CachedEmbeddingModel::new(
+//!     openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL),
+//!     openai::TEXT_EMBEDDING_3_SMALL,
+//!     DirectoryEmbeddingCache::new("embeddings_cache")?,
+//! );
+//!
+//! // Only the documents that changed since the last run are sent to OpenAI
+//! let embeddings = EmbeddingsBuilder::new(model.clone())
+//!     .documents(documents)?
+//!     .build()
+//!     .await?;
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{Embedding, EmbeddingError, EmbeddingModel, EmbeddingsResponse};
+
+/// Key of an embedding in an [EmbeddingCache].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheKey {
+    /// Identifier of the model which generated the embedding
+    pub model: String,
+    /// Hex encoded SHA-256 hash of the embedded text, stable across runs and platforms
+    pub text_hash: String,
+}
+
+impl CacheKey {
+    pub fn new(model: &str, text: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            text_hash: Sha256::digest(text.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+}
+
+/// Trait for the storage backends of a [CachedEmbeddingModel].
+///
+/// Both methods work on batches of entries so that backends can serve a whole request to the
+/// embedding model in a single round trip.
+pub trait EmbeddingCache: Send + Sync {
+    /// Get the embeddings of the given keys, in the same order. Missing keys map to `None`.
+    fn get(
+        &self,
+        keys: &[CacheKey],
+    ) -> impl Future<Output = Result<Vec<Option<Vec<f64>>>, EmbeddingError>> + Send;
+
+    /// Insert (or replace) embeddings in the cache.
+    fn insert(
+        &self,
+        entries: Vec<(CacheKey, Vec<f64>)>,
+    ) -> impl Future<Output = Result<(), EmbeddingError>> + Send;
+}
+
+/// Embedding model wrapper which only embeds the texts missing from an [EmbeddingCache].
+///
+/// The `model_id` given to [CachedEmbeddingModel::new] is part of the cache keys, so that a
+/// cache can be shared by several models. It should change whenever the embeddings would
+/// (e.g.: a different model, or a different number of dimensions).
+pub struct CachedEmbeddingModel<M: EmbeddingModel, C: EmbeddingCache> {
+    model: M,
+    model_id: String,
+    cache: Arc<C>,
+}
+
+impl<M: EmbeddingModel, C: EmbeddingCache> Clone for CachedEmbeddingModel<M, C> {
+    fn clone(&self) -> Self {
+        Self {
+            model: self.model.clone(),
+            model_id: self.model_id.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<M: EmbeddingModel, C: EmbeddingCache> CachedEmbeddingModel<M, C> {
+    pub fn new(model: M, model_id: &str, cache: C) -> Self {
+        Self {
+            model,
+            model_id: model_id.to_string(),
+            cache: Arc::new(cache),
+        }
+    }
+
+    /// The wrapped embedding model
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// The cache of the model
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+}
+
+impl<M: EmbeddingModel, C: EmbeddingCache> EmbeddingModel for CachedEmbeddingModel<M, C> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.embed_texts_with_usage(texts)
+            .await
+            .map(|response| response.embeddings)
+    }
+
+    /// Only the usage of the texts missing from the cache is reported. When every text is
+    /// found in the cache, the usage is zero.
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<EmbeddingsResponse, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        let keys = texts
+            .iter()
+            .map(|text| CacheKey::new(&self.model_id, text))
+            .collect::<Vec<_>>();
+
+        let mut vecs = self.cache.get(&keys).await?;
+
+        // Identical texts of the same batch are only embedded once
+        let mut misses = HashMap::<&CacheKey, Vec<usize>>::new();
+        let mut missing_texts = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            if vecs[i].is_none() {
+                let positions = misses.entry(key).or_default();
+                if positions.is_empty() {
+                    missing_texts.push((key, texts[i].clone()));
+                }
+                positions.push(i);
+            }
+        }
+
+        tracing::debug!(target: "rig",
+            "Embedding cache: {} hits, {} misses",
+            texts.len() - misses.values().map(Vec::len).sum::<usize>(),
+            misses.len()
+        );
+
+        let usage = if missing_texts.is_empty() {
+            Some(Default::default())
+        } else {
+            let response = self
+                .model
+                .embed_texts_with_usage(missing_texts.iter().map(|(_, text)| text.clone()))
+                .await?;
+
+            if response.embeddings.len() != missing_texts.len() {
+                return Err(EmbeddingError::ResponseError(format!(
+                    "Expected {} embeddings, got {}",
+                    missing_texts.len(),
+                    response.embeddings.len()
+                )));
+            }
+
+            let mut entries = Vec::with_capacity(missing_texts.len());
+            for ((key, _), embedding) in missing_texts.iter().zip(response.embeddings) {
+                for &i in &misses[key] {
+                    vecs[i] = Some(embedding.vec.clone());
+                }
+                entries.push(((*key).clone(), embedding.vec));
+            }
+            self.cache.insert(entries).await?;
+
+            response.usage
+        };
+
+        Ok(EmbeddingsResponse {
+            embeddings: texts
+                .into_iter()
+                .zip(vecs)
+                .map(|(document, vec)| Embedding {
+                    document,
+                    vec: vec.unwrap_or_default(),
+                })
+                .collect(),
+            usage,
+        })
+    }
+}
+
+/// In-memory [EmbeddingCache] holding at most `capacity` embeddings. Once full, the least
+/// recently used embeddings are evicted first.
+pub struct LruEmbeddingCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Default)]
+struct LruState {
+    /// Embeddings by key, with the time of their last use
+    entries: HashMap<CacheKey, (Vec<f64>, u64)>,
+    /// Keys by time of last use
+    recency: BTreeMap<u64, CacheKey>,
+    clock: u64,
+}
+
+impl LruState {
+    fn touch(&mut self, key: &CacheKey) -> Option<Vec<f64>> {
+        self.clock += 1;
+        let (vec, last_used) = self.entries.get_mut(key)?;
+        self.recency.remove(last_used);
+        *last_used = self.clock;
+        self.recency.insert(self.clock, key.clone());
+        Some(vec.clone())
+    }
+}
+
+impl LruEmbeddingCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Number of embeddings in the cache
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, LruState> {
+        // The state is always consistent between two statements, so a poisoned lock is safe
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl EmbeddingCache for LruEmbeddingCache {
+    async fn get(&self, keys: &[CacheKey]) -> Result<Vec<Option<Vec<f64>>>, EmbeddingError> {
+        let mut state = self.state();
+        Ok(keys.iter().map(|key| state.touch(key)).collect())
+    }
+
+    async fn insert(&self, entries: Vec<(CacheKey, Vec<f64>)>) -> Result<(), EmbeddingError> {
+        let mut state = self.state();
+        for (key, vec) in entries {
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some((_, last_used)) = state.entries.insert(key.clone(), (vec, clock)) {
+                state.recency.remove(&last_used);
+            }
+            state.recency.insert(clock, key);
+
+            while state.entries.len() > self.capacity {
+                let Some((_, oldest)) = state.recency.pop_first() else {
+                    break;
+                };
+                state.entries.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// [EmbeddingCache] storing each embedding in a file of a directory, so that it persists
+/// across runs. Files are grouped in a subdirectory per model, and named after the hash of
+/// the embedded text. Embeddings are stored as little-endian `f64` values.
+#[derive(Clone, Debug)]
+pub struct DirectoryEmbeddingCache {
+    path: PathBuf,
+}
+
+impl DirectoryEmbeddingCache {
+    /// Use the given directory as cache, creating it if needed.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, EmbeddingError> {
+        let path = path.as_ref().to_path_buf();
+        std::fs::create_dir_all(&path).map_err(|err| EmbeddingError::CacheError(err.into()))?;
+        Ok(Self { path })
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        // Model ids often contain slashes (e.g.: "BAAI/bge-small-en-v1.5")
+        let model = key
+            .model
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        self.path.join(model).join(&key.text_hash)
+    }
+
+    /// Path of a new temporary file next to the given entry. Names are unique across the
+    /// writers of the process and across processes sharing the directory.
+    fn temp_path(path: &Path) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    async fn write(&self, key: &CacheKey, vec: &[f64]) -> std::io::Result<()> {
+        let path = self.entry_path(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write to a temporary file first so that readers never see a partial embedding
+        let tmp = Self::temp_path(&path);
+        let bytes = vec
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        let result = match tokio::fs::write(&tmp, bytes).await {
+            Ok(()) => tokio::fs::rename(&tmp, path).await,
+            Err(err) => Err(err),
+        };
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&tmp).await;
+        }
+        result
+    }
+}
+
+impl EmbeddingCache for DirectoryEmbeddingCache {
+    async fn get(&self, keys: &[CacheKey]) -> Result<Vec<Option<Vec<f64>>>, EmbeddingError> {
+        let mut vecs = Vec::with_capacity(keys.len());
+        for key in keys {
+            vecs.push(match tokio::fs::read(self.entry_path(key)).await {
+                Ok(bytes) => Some(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+                        .collect(),
+                ),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(EmbeddingError::CacheError(err.into())),
+            });
+        }
+        Ok(vecs)
+    }
+
+    async fn insert(&self, entries: Vec<(CacheKey, Vec<f64>)>) -> Result<(), EmbeddingError> {
+        for (key, vec) in &entries {
+            self.write(key, vec)
+                .await
+                .map_err(|err| EmbeddingError::CacheError(err.into()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct CountingModel {
+        embedded: Arc<AtomicUsize>,
+    }
+
+    impl EmbeddingModel for CountingModel {
+        const MAX_DOCUMENTS: usize = 16;
+
+        fn ndims(&self) -> usize {
+            1
+        }
+
+        async fn embed_texts(
+            &self,
+            texts: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            Ok(texts
+                .into_iter()
+                .map(|text| {
+                    self.embedded.fetch_add(1, Ordering::SeqCst);
+                    Embedding {
+                        vec: vec![text.len() as f64],
+                        document: text,
+                    }
+                })
+                .collect())
+        }
+    }
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(
+            CacheKey::new("model", "").text_hash,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(CacheKey::new("model", "a"), CacheKey::new("model", "b"));
+        assert_ne!(CacheKey::new("model", "a"), CacheKey::new("other", "a"));
+    }
+
+    #[tokio::test]
+    async fn test_lru_cache() {
+        let inner = CountingModel::default();
+        let model = CachedEmbeddingModel::new(inner.clone(), "counting", LruEmbeddingCache::new(2));
+
+        let embeddings = model.embed_texts(texts(&["a", "bb", "a"])).await.unwrap();
+        assert_eq!(
+            embeddings.iter().map(|e| e.vec[0]).collect::<Vec<_>>(),
+            vec![1.0, 2.0, 1.0]
+        );
+        assert_eq!(inner.embedded.load(Ordering::SeqCst), 2);
+
+        let response = model
+            .embed_texts_with_usage(texts(&["bb", "a"]))
+            .await
+            .unwrap();
+        assert_eq!(response.embeddings[0].document, "bb");
+        assert_eq!(response.usage, Some(Default::default()));
+        assert_eq!(inner.embedded.load(Ordering::SeqCst), 2);
+
+        // "bb" is the least recently used embedding
+        model.embed_text("ccc").await.unwrap();
+        assert_eq!(model.cache().len(), 2);
+        model.embed_texts(texts(&["a", "ccc"])).await.unwrap();
+        assert_eq!(inner.embedded.load(Ordering::SeqCst), 3);
+        model.embed_text("bb").await.unwrap();
+        assert_eq!(inner.embedded.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_directory_cache() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let inner = CountingModel::default();
+
+        let cache = DirectoryEmbeddingCache::new(dir.path()).unwrap();
+        let model = CachedEmbeddingModel::new(inner.clone(), "org/model", cache);
+        model.embed_texts(texts(&["a", "bb"])).await.unwrap();
+        assert!(dir.path().join("org_model").is_dir());
+
+        // A new cache over the same directory finds the embeddings of the previous one
+        let cache = DirectoryEmbeddingCache::new(dir.path()).unwrap();
+        let model = CachedEmbeddingModel::new(inner.clone(), "org/model", cache);
+        let embeddings = model.embed_texts(texts(&["bb", "a"])).await.unwrap();
+        assert_eq!(
+            embeddings.iter().map(|e| e.vec[0]).collect::<Vec<_>>(),
+            vec![2.0, 1.0]
+        );
+        assert_eq!(inner.embedded.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_directory_cache_concurrent_writers() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let cache = DirectoryEmbeddingCache::new(dir.path()).unwrap();
+        let key = CacheKey::new("model", "text");
+
+        let writers = (0..8).map(|i| {
+            let cache = cache.clone();
+            let key = key.clone();
+            tokio::spawn(async move { cache.insert(vec![(key, vec![i as f64; 64])]).await })
+        });
+        for result in futures::future::join_all(writers).await {
+            result.unwrap().unwrap();
+        }
+
+        let vec = cache.get(&[key]).await.unwrap().remove(0).unwrap();
+        assert_eq!(vec.len(), 64);
+        assert!(vec.iter().all(|value| *value == vec[0]));
+
+        let files = std::fs::read_dir(dir.path().join("model"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1, "Unexpected files: {:?}", files);
+    }
+}
@@ -19,6 +19,57 @@ pub trait VectorDistance {
     fn chebyshev_distance(&self, other: &Self) -> f64;
 }
 
+/// Metric used to compare embeddings in vector searches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Cosine similarity, higher is better
+    #[default]
+    Cosine,
+    /// Dot product, higher is better. Equivalent to [DistanceMetric::Cosine] for normalized embeddings.
+    DotProduct,
+    /// Angular distance, smaller is better
+    Angular,
+    /// Euclidean distance, smaller is better
+    Euclidean,
+    /// Manhattan distance, smaller is better
+    Manhattan,
+    /// Chebyshev distance, smaller is better
+    Chebyshev,
+}
+
+impl DistanceMetric {
+    /// Compare two embeddings with the metric.
+    /// If `normalized` is true, the embeddings are assumed to have unit length.
+    pub fn compute(
+        &self,
+        a: &crate::embeddings::Embedding,
+        b: &crate::embeddings::Embedding,
+        normalized: bool,
+    ) -> f64 {
+        match self {
+            Self::Cosine => a.cosine_similarity(b, normalized),
+            Self::DotProduct => a.dot_product(b),
+            Self::Angular => a.angular_distance(b, normalized),
+            Self::Euclidean => a.euclidean_distance(b),
+            Self::Manhattan => a.manhattan_distance(b),
+            Self::Chebyshev => a.chebyshev_distance(b),
+        }
+    }
+
+    /// Whether higher values mean closer embeddings, i.e.: the metric is a similarity
+    /// rather than a distance.
+    pub fn higher_is_better(&self) -> bool {
+        matches!(self, Self::Cosine | Self::DotProduct)
+    }
+
+    /// Whether the metric only depends on the direction of the embeddings, in which case
+    /// embeddings can be normalized beforehand.
+    pub fn is_angular(&self) -> bool {
+        matches!(self, Self::Cosine | Self::Angular)
+    }
+}
+
 #[cfg(not(feature = "rayon"))]
 impl VectorDistance for crate::embeddings::Embedding {
     fn dot_product(&self, other: &Self) -> f64 {
@@ -134,7 +185,7 @@ mod rayon {
 
 #[cfg(test)]
 mod tests {
-    use super::VectorDistance;
+    use super::{DistanceMetric, VectorDistance};
     use crate::embeddings::Embedding;
 
     fn embeddings() -> (Embedding, Embedding) {
@@ -151,6 +202,26 @@ mod tests {
         (embedding_1, embedding_2)
     }
 
+    #[test]
+    fn test_distance_metric() {
+        let (embedding_1, embedding_2) = embeddings();
+
+        assert_eq!(
+            DistanceMetric::Cosine.compute(&embedding_1, &embedding_2, false),
+            embedding_1.cosine_similarity(&embedding_2, false)
+        );
+        assert_eq!(
+            DistanceMetric::Euclidean.compute(&embedding_1, &embedding_2, false),
+            5.0
+        );
+        assert!(DistanceMetric::DotProduct.higher_is_better());
+        assert!(!DistanceMetric::Manhattan.higher_is_better());
+        assert_eq!(
+            serde_json::to_value(DistanceMetric::DotProduct).unwrap(),
+            serde_json::json!("dot_product")
+        );
+    }
+
     #[test]
     fn test_dot_product() {
         let (embedding_1, embedding_2) = embeddings();
@@ -6,6 +6,7 @@
 //! Finally, the module defines the [EmbeddingError] enum, which represents various errors that
 //! can occur during embedding generation or processing.
 
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +30,18 @@ pub enum EmbeddingError {
     /// Error returned by the embedding model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Unsuccessful HTTP response returned by the embedding model provider
+    #[error("StatusError: {0}")]
+    StatusError(#[from] crate::http_error::HttpStatusError),
+
+    /// Error reading or writing an [EmbeddingCache](super::cache::EmbeddingCache)
+    #[error("CacheError: {0}")]
+    CacheError(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    /// The budget of a [CostTracker](crate::cost::CostTracker) was exceeded
+    #[error("BudgetExceeded: spent {spent} out of a budget of {budget}")]
+    BudgetExceeded { spent: f64, budget: f64 },
 }
 
 /// Trait for embedding models that can generate embeddings for documents.
@@ -45,6 +58,20 @@ pub trait EmbeddingModel: Clone + Sync + Send {
         texts: impl IntoIterator<Item = String> + Send,
     ) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send;
 
+    /// Embed multiple text documents in a single request, also returning the token usage
+    /// reported by the provider. Models whose provider does not report usage return `None`.
+    fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> impl std::future::Future<Output = Result<EmbeddingsResponse, EmbeddingError>> + Send {
+        async {
+            Ok(EmbeddingsResponse {
+                embeddings: self.embed_texts(texts).await?,
+                usage: None,
+            })
+        }
+    }
+
     /// Embed a single text document.
     fn embed_text(
         &self,
@@ -60,6 +87,103 @@ pub trait EmbeddingModel: Clone + Sync + Send {
     }
 }
 
+/// Wrapper trait to allow for dynamic dispatch of embedding models, e.g.: to select the
+/// provider of a model at runtime (see [client](crate::client)).
+///
+/// `Box<dyn EmbeddingModelDyn>` implements [EmbeddingModel] and can be used wherever an
+/// embedding model is expected (e.g.: with the [EmbeddingsBuilder](super::EmbeddingsBuilder)).
+pub trait EmbeddingModelDyn: Send + Sync {
+    fn max_documents(&self) -> usize;
+
+    fn ndims(&self) -> usize;
+
+    fn embed_texts_with_usage(
+        &self,
+        texts: Vec<String>,
+    ) -> BoxFuture<'_, Result<EmbeddingsResponse, EmbeddingError>>;
+
+    fn box_clone(&self) -> Box<dyn EmbeddingModelDyn>;
+}
+
+impl<M: EmbeddingModel + 'static> EmbeddingModelDyn for M {
+    fn max_documents(&self) -> usize {
+        M::MAX_DOCUMENTS
+    }
+
+    fn ndims(&self) -> usize {
+        <Self as EmbeddingModel>::ndims(self)
+    }
+
+    fn embed_texts_with_usage(
+        &self,
+        texts: Vec<String>,
+    ) -> BoxFuture<'_, Result<EmbeddingsResponse, EmbeddingError>> {
+        Box::pin(<Self as EmbeddingModel>::embed_texts_with_usage(
+            self, texts,
+        ))
+    }
+
+    fn box_clone(&self) -> Box<dyn EmbeddingModelDyn> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn EmbeddingModelDyn> {
+    fn clone(&self) -> Self {
+        (**self).box_clone()
+    }
+}
+
+impl EmbeddingModel for Box<dyn EmbeddingModelDyn> {
+    /// The texts are split again in batches of the maximum number of documents of the
+    /// underlying model, which are embedded sequentially.
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        (**self).ndims()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(EmbeddingModel::embed_texts_with_usage(self, texts)
+            .await?
+            .embeddings)
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<EmbeddingsResponse, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        let mut response = EmbeddingsResponse {
+            embeddings: Vec::with_capacity(texts.len()),
+            usage: None,
+        };
+
+        for batch in texts.chunks((**self).max_documents().max(1)) {
+            let batch_response = (**self).embed_texts_with_usage(batch.to_vec()).await?;
+            response.embeddings.extend(batch_response.embeddings);
+            response.usage = match (response.usage, batch_response.usage) {
+                (Some(usage), Some(batch_usage)) => Some(usage + batch_usage),
+                (usage, batch_usage) => usage.or(batch_usage),
+            };
+        }
+
+        Ok(response)
+    }
+}
+
+/// Embeddings generated by a single request along with the token usage of that request.
+#[derive(Clone, Debug)]
+pub struct EmbeddingsResponse {
+    /// The embeddings, in the same order as the input texts
+    pub embeddings: Vec<Embedding>,
+    /// Token usage reported by the provider (if any)
+    pub usage: Option<crate::completion::Usage>,
+}
+
 /// Struct that holds a single document and its embedding.
 #[derive(Clone, Default, Deserialize, Serialize, Debug)]
 pub struct Embedding {
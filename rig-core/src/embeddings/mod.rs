@@ -4,6 +4,7 @@
 //! and document similarity.
 
 pub mod builder;
+pub mod cache;
 pub mod embed;
 pub mod embedding;
 pub mod tool;
@@ -11,5 +12,7 @@ pub mod tool;
 pub mod distance;
 pub use builder::EmbeddingsBuilder;
 pub use embed::{to_texts, Embed, EmbedError, TextEmbedder};
-pub use embedding::{Embedding, EmbeddingError, EmbeddingModel};
+pub use embedding::{
+    Embedding, EmbeddingError, EmbeddingModel, EmbeddingModelDyn, EmbeddingsResponse,
+};
 pub use tool::ToolSchema;
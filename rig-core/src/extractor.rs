@@ -3,6 +3,11 @@
 //! Note: The target structure must implement the `serde::Deserialize`, `serde::Serialize`,
 //! and `schemars::JsonSchema` traits. Those can be easily derived using the `derive` macro.
 //!
+//! When the completion model natively supports structured output (e.g.: OpenAI, Gemini and
+//! Ollama, see [CompletionModel::supports_structured_output]), the model is asked to answer
+//! with JSON conforming to the schema of the target structure. Otherwise, the model is asked
+//! to call a `submit` tool whose arguments are the extracted data.
+//!
 //! # Example
 //! ```
 //! use rig::providers::openai;
@@ -86,15 +91,27 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
     ExtractorBuilder<T, M>
 {
     pub fn new(model: M) -> Self {
-        Self {
-            agent_builder: AgentBuilder::new(model)
+        let agent_builder = if model.supports_structured_output() {
+            AgentBuilder::new(model)
+                .preamble("\
+                    You are an AI assistant whose purpose is to extract structured data from the provided text.\n\
+                    Answer with the data extracted from the provided text as a JSON object following the provided schema.\n\
+                    Be sure to fill out every field, even with default values!!!.
+                ")
+                .output_schema(json!(schema_for!(T)))
+        } else {
+            AgentBuilder::new(model)
                 .preamble("\
                     You are an AI assistant whose purpose is to extract structured data from the provided text.\n\
                     You will have access to a `submit` function that defines the structure of the data to extract from the provided text.\n\
                     Use the `submit` function to submit the structured data.\n\
                     Be sure to fill out every field and ALWAYS CALL THE `submit` function, event with default values!!!.
                 ")
-                .tool(SubmitTool::<T> {_t: PhantomData}),
+                .tool(SubmitTool::<T> {_t: PhantomData})
+        };
+
+        Self {
+            agent_builder,
             _t: PhantomData,
         }
     }
@@ -150,3 +167,83 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync> Tool for
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        completion::{AssistantContent, CompletionError, CompletionRequest, CompletionResponse},
+        OneOrMany,
+    };
+
+    #[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq)]
+    struct Person {
+        name: String,
+        age: u8,
+    }
+
+    /// Mock model answering with the extracted data, either as text (when it supports
+    /// structured output) or as a call to the `submit` tool
+    #[derive(Clone)]
+    struct MockModel {
+        structured_output: bool,
+        requests: Arc<Mutex<Vec<CompletionRequest>>>,
+    }
+
+    impl CompletionModel for MockModel {
+        type Response = ();
+
+        fn supports_structured_output(&self) -> bool {
+            self.structured_output
+        }
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            self.requests.lock().unwrap().push(request);
+
+            let data = json!({ "name": "John Doe", "age": 30 });
+            let choice = if self.structured_output {
+                AssistantContent::text(data.to_string())
+            } else {
+                AssistantContent::tool_call("call_1", "submit", data)
+            };
+
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(choice),
+                usage: None,
+                raw_response: (),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extraction() {
+        for structured_output in [true, false] {
+            let model = MockModel {
+                structured_output,
+                requests: Arc::default(),
+            };
+            let extractor = ExtractorBuilder::<Person, _>::new(model.clone()).build();
+
+            let person = extractor
+                .extract("John Doe is a 30 year old doctor.")
+                .await
+                .unwrap();
+            assert_eq!(
+                person,
+                Person {
+                    name: "John Doe".to_string(),
+                    age: 30
+                }
+            );
+
+            let requests = model.requests.lock().unwrap();
+            assert_eq!(requests[0].output_schema.is_some(), structured_output);
+            assert_eq!(requests[0].tools.is_empty(), structured_output);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! This module defines the [HttpStatusError] struct, which represents an unsuccessful HTTP
+//! response (i.e.: a non-2xx status) returned by a model provider.
+//!
+//! It is wrapped by the `StatusError` variant of the completion, embedding and transcription
+//! errors so that callers can branch on the kind of failure (see [ProviderErrorKind]), e.g.: to
+//! tell rate limits, context length overflows, authentication failures and content filter
+//! refusals apart without parsing strings.
+//!
+//! The error body is parsed the same way for all providers, since they all follow one of a few
+//! formats, e.g.:
+//! - OpenAI (and compatible providers): `{"error": {"message": "...", "type": "...", "code": "..."}}`
+//! - Anthropic: `{"type": "error", "error": {"type": "...", "message": "..."}}`
+//! - Gemini: `{"error": {"code": 429, "message": "...", "status": "RESOURCE_EXHAUSTED"}}`
+//! - Cohere: `{"message": "..."}`
+//! - Ollama: `{"error": "..."}`
+
+use std::time::Duration;
+
+use reqwest::{header::HeaderMap, StatusCode};
+use serde_json::Value;
+
+/// Kind of failure of an unsuccessful HTTP response, classified from its status and the error
+/// type and message returned by the provider
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProviderErrorKind {
+    /// The API key is missing or invalid
+    Authentication,
+    /// The API key is not allowed to use the resource (e.g.: the model)
+    PermissionDenied,
+    /// The resource (e.g.: the model) does not exist
+    NotFound,
+    /// Too many requests were sent in a given amount of time
+    RateLimited,
+    /// The account ran out of credits or reached its spending limit
+    QuotaExceeded,
+    /// The request (e.g.: the prompt and chat history) does not fit in the context window
+    ContextLengthExceeded,
+    /// The request or the response was blocked by the provider's content filter
+    ContentFiltered,
+    /// The request is malformed or has invalid parameters
+    InvalidRequest,
+    /// The request timed out
+    Timeout,
+    /// The provider is temporarily overloaded
+    Overloaded,
+    /// The provider failed to process the request
+    ServerError,
+    /// Any other failure
+    Other,
+}
+
+impl ProviderErrorKind {
+    /// Whether requests failing with this kind of error may succeed if retried
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ProviderErrorKind::RateLimited
+                | ProviderErrorKind::Timeout
+                | ProviderErrorKind::Overloaded
+                | ProviderErrorKind::ServerError
+        )
+    }
+}
+
+/// Unsuccessful HTTP response returned by a model provider
+#[derive(Clone, Debug)]
+pub struct HttpStatusError {
+    /// The status code of the response
+    pub status: StatusCode,
+    /// The kind of failure
+    pub kind: ProviderErrorKind,
+    /// The error type (or code) returned by the provider, e.g.: `rate_limit_error`
+    pub error_type: Option<String>,
+    /// The error message returned by the provider
+    pub message: Option<String>,
+    /// The id of the request, if returned by the provider (useful when contacting its support)
+    pub request_id: Option<String>,
+    /// Whether the request may succeed if retried
+    pub retryable: bool,
+    /// How long to wait before retrying, as requested by the provider
+    /// (i.e.: with the `Retry-After` or `retry-after-ms` headers)
+    pub retry_after: Option<Duration>,
+    /// The raw body of the response
+    pub body: String,
+}
+
+impl HttpStatusError {
+    /// Build the error from the status and body of an unsuccessful response
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        let body = body.into();
+        let (error_type, message) = parse_body(&body);
+        let kind = classify(status, error_type.as_deref(), message.as_deref());
+
+        Self {
+            status,
+            kind,
+            error_type,
+            message,
+            request_id: None,
+            retryable: kind.is_retryable(),
+            retry_after: None,
+            body,
+        }
+    }
+
+    /// Build the error from an unsuccessful response, consuming its body
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        let request_id = request_id(response.headers());
+        let body = response.text().await.unwrap_or_default();
+
+        Self {
+            request_id,
+            retry_after,
+            ..Self::new(status, body)
+        }
+    }
+
+    /// Whether the request may succeed if retried, i.e.: the request timed out, was rate limited
+    /// or the provider failed to process it (e.g.: the provider is overloaded).
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.error_type, &self.message) {
+            (Some(error_type), Some(message)) => {
+                write!(f, "{} ({}): {}", self.status, error_type, message)
+            }
+            (None, Some(message)) => write!(f, "{}: {}", self.status, message),
+            _ => write!(f, "{}: {}", self.status, self.body),
+        }
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    match status {
+        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::NOT_IMPLEMENTED | StatusCode::HTTP_VERSION_NOT_SUPPORTED => false,
+        status => status.is_server_error(),
+    }
+}
+
+/// Extract the error type and message from the body of an error response
+fn parse_body(body: &str) -> (Option<String>, Option<String>) {
+    let Ok(value) = serde_json::from_str::<Value>(body) else {
+        let body = body.trim();
+        return (None, (!body.is_empty()).then(|| body.to_string()));
+    };
+
+    let string = |value: &Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| value.get(key).and_then(Value::as_str))
+            .map(str::to_string)
+    };
+
+    match value.get("error") {
+        Some(Value::String(message)) => (string(&value, &["type", "code"]), Some(message.clone())),
+        Some(error @ Value::Object(_)) => (
+            string(error, &["type", "code", "status"]),
+            string(error, &["message"]),
+        ),
+        _ => (
+            string(&value, &["type", "code"]).filter(|error_type| error_type != "error"),
+            string(&value, &["message", "detail"]),
+        ),
+    }
+}
+
+fn classify(
+    status: StatusCode,
+    error_type: Option<&str>,
+    message: Option<&str>,
+) -> ProviderErrorKind {
+    let error_type = error_type.unwrap_or_default().to_lowercase();
+    let message = message.unwrap_or_default().to_lowercase();
+    let mentions = |patterns: &[&str]| {
+        patterns
+            .iter()
+            .any(|pattern| error_type.contains(pattern) || message.contains(pattern))
+    };
+
+    if mentions(&["insufficient_quota", "billing", "credit balance"]) {
+        ProviderErrorKind::QuotaExceeded
+    } else if mentions(&[
+        "context_length",
+        "context length",
+        "context window",
+        "maximum context",
+        "prompt is too long",
+        "too many tokens",
+    ]) {
+        ProviderErrorKind::ContextLengthExceeded
+    } else if mentions(&[
+        "content_filter",
+        "content_policy",
+        "content management policy",
+    ]) {
+        ProviderErrorKind::ContentFiltered
+    } else if mentions(&["overloaded"]) {
+        ProviderErrorKind::Overloaded
+    } else {
+        match status {
+            StatusCode::UNAUTHORIZED => ProviderErrorKind::Authentication,
+            StatusCode::FORBIDDEN => ProviderErrorKind::PermissionDenied,
+            StatusCode::NOT_FOUND => ProviderErrorKind::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => ProviderErrorKind::RateLimited,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => ProviderErrorKind::Timeout,
+            StatusCode::SERVICE_UNAVAILABLE => ProviderErrorKind::Overloaded,
+            status if is_retryable_status(status) => ProviderErrorKind::ServerError,
+            status if status.is_client_error() => ProviderErrorKind::InvalidRequest,
+            _ => ProviderErrorKind::Other,
+        }
+    }
+}
+
+/// Parse the delay requested by the provider. Only delays in seconds are supported for the
+/// `Retry-After` header, i.e.: HTTP dates are ignored.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value >= 0.0)
+    };
+
+    header("retry-after-ms")
+        .map(|millis| Duration::from_secs_f64(millis / 1000.0))
+        .or_else(|| header("retry-after").map(Duration::from_secs_f64))
+}
+
+fn request_id(headers: &HeaderMap) -> Option<String> {
+    [
+        "x-request-id",
+        "request-id",
+        "x-goog-request-id",
+        "x-amzn-requestid",
+    ]
+    .iter()
+    .find_map(|name| headers.get(*name))
+    .and_then(|value| value.to_str().ok())
+    .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert("retry-after", HeaderValue::from_static("2"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(1500)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_retryable_status() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::from_u16(529).unwrap()));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::NOT_IMPLEMENTED));
+    }
+
+    #[test]
+    fn test_provider_error_bodies() {
+        let cases = [
+            // OpenAI
+            (
+                429,
+                r#"{"error": {"message": "Rate limit reached for gpt-4o", "type": "requests", "param": null, "code": "rate_limit_exceeded"}}"#,
+                ProviderErrorKind::RateLimited,
+                Some("requests"),
+            ),
+            (
+                429,
+                r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "param": null, "code": "insufficient_quota"}}"#,
+                ProviderErrorKind::QuotaExceeded,
+                Some("insufficient_quota"),
+            ),
+            (
+                400,
+                r#"{"error": {"message": "This model's maximum context length is 128000 tokens.", "type": "invalid_request_error", "param": "messages", "code": "context_length_exceeded"}}"#,
+                ProviderErrorKind::ContextLengthExceeded,
+                Some("invalid_request_error"),
+            ),
+            (
+                400,
+                r#"{"error": {"message": "The response was filtered due to the prompt triggering Azure OpenAI's content management policy.", "type": null, "param": "prompt", "code": "content_filter"}}"#,
+                ProviderErrorKind::ContentFiltered,
+                Some("content_filter"),
+            ),
+            // Anthropic
+            (
+                529,
+                r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#,
+                ProviderErrorKind::Overloaded,
+                Some("overloaded_error"),
+            ),
+            (
+                401,
+                r#"{"type": "error", "error": {"type": "authentication_error", "message": "invalid x-api-key"}}"#,
+                ProviderErrorKind::Authentication,
+                Some("authentication_error"),
+            ),
+            // Gemini
+            (
+                429,
+                r#"{"error": {"code": 429, "message": "Resource has been exhausted (e.g. check quota).", "status": "RESOURCE_EXHAUSTED"}}"#,
+                ProviderErrorKind::RateLimited,
+                Some("RESOURCE_EXHAUSTED"),
+            ),
+            // Cohere
+            (
+                400,
+                r#"{"message": "invalid request: model 'foo' not found"}"#,
+                ProviderErrorKind::InvalidRequest,
+                None,
+            ),
+            // Ollama
+            (
+                404,
+                r#"{"error": "model \"llama9\" not found, try pulling it first"}"#,
+                ProviderErrorKind::NotFound,
+                None,
+            ),
+            // Non-JSON body
+            (502, "Bad Gateway", ProviderErrorKind::ServerError, None),
+        ];
+
+        for (status, body, kind, error_type) in cases {
+            let error = HttpStatusError::new(StatusCode::from_u16(status).unwrap(), body);
+            assert_eq!(error.kind, kind, "{}", body);
+            assert_eq!(error.error_type.as_deref(), error_type, "{}", body);
+            assert!(error.message.is_some(), "{}", body);
+            assert_eq!(error.retryable, kind.is_retryable());
+            assert_eq!(error.body, body);
+        }
+    }
+}
@@ -81,16 +81,25 @@
 //! implement the [VectorStoreIndex](crate::vector_store::VectorStoreIndex) trait.
 
 pub mod agent;
+pub mod chunking;
 pub mod cli_chatbot;
+pub mod client;
 pub mod completion;
+pub mod cost;
 pub mod embeddings;
 pub mod extractor;
+pub mod http_error;
 pub(crate) mod json_utils;
 pub mod loaders;
 pub mod one_or_many;
 pub mod pipeline;
 pub mod providers;
+pub mod rerank;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod retry;
+pub mod routing;
 pub mod streaming;
+pub mod tokenizer;
 pub mod tool;
 pub mod transcription;
 pub mod vector_store;
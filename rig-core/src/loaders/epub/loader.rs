@@ -1,3 +1,4 @@
+use crate::chunking::{Chunk, TextSplitter};
 use crate::loaders::file::FileLoaderError;
 use epub::doc::EpubDoc;
 
@@ -312,6 +313,43 @@ impl<'a, P: TextProcessor> EpubFileLoader<'a, ByChapter, P> {
     }
 }
 
+impl<'a, P> EpubFileLoader<'a, (PathBuf, Vec<(usize, String)>), P> {
+    /// Splits the chapters of the documents into chunks with the given [TextSplitter]. The path
+    ///  of the document and the chapter number are kept as metadata of each chunk.
+    ///
+    /// # Example
+    /// Split the chapters of the epubs in directory "tests/data/*.epub" into chunks.
+    ///
+    /// ```rust
+    /// let chunks = EpubFileLoader::<_, StripXmlProcessor>::with_glob("tests/data/*.epub")?
+    ///     .load_with_path()
+    ///     .ignore_errors()
+    ///     .by_chapter()
+    ///     .ignore_errors()
+    ///     .chunk(RecursiveSplitter::new(1000));
+    /// for chunk in chunks {
+    ///     let (path, chapter) = &chunk.metadata;
+    ///     println!("{:?} chapter {}: {}", path, chapter, chunk.text)
+    /// }
+    /// ```
+    pub fn chunk(
+        self,
+        splitter: impl TextSplitter + 'a,
+    ) -> EpubFileLoader<'a, Chunk<(PathBuf, usize)>, P> {
+        EpubFileLoader {
+            iterator: Box::new(self.iterator.flat_map(move |(path, chapters)| {
+                chapters
+                    .into_iter()
+                    .flat_map(|(chapter, content)| {
+                        splitter.chunks(&content, (path.clone(), chapter))
+                    })
+                    .collect::<Vec<_>>()
+            })),
+            _processor: PhantomData,
+        }
+    }
+}
+
 impl<'a, P, T: 'a> EpubFileLoader<'a, Result<T, EpubLoaderError>, P> {
     /// Ignores errors in the iterator, returning only successful results. This can be used on any
     ///  [EpubFileLoader] state of iterator whose items are results.
@@ -3,6 +3,8 @@ use std::{fs, path::PathBuf};
 use glob::glob;
 use thiserror::Error;
 
+use crate::chunking::{Chunk, TextSplitter};
+
 #[derive(Error, Debug)]
 pub enum FileLoaderError {
     #[error("Invalid glob pattern: {0}")]
@@ -162,6 +164,32 @@ impl<'a, T: 'a> FileLoader<'a, Result<T, FileLoaderError>> {
     }
 }
 
+impl<'a> FileLoader<'a, (PathBuf, String)> {
+    /// Splits the contents of the files into chunks with the given [TextSplitter]. The path of
+    ///  the file is kept as metadata of each chunk.
+    ///
+    /// # Example
+    /// Split the files in directory "files/*.md" by section.
+    ///
+    /// ```rust
+    /// let chunks = FileLoader::with_glob("files/*.md")?
+    ///     .read_with_path()
+    ///     .ignore_errors()
+    ///     .chunk(MarkdownSplitter::new(1000));
+    /// for chunk in chunks {
+    ///     println!("{:?} {:?}: {}", chunk.metadata, chunk.headings, chunk.text)
+    /// }
+    /// ```
+    pub fn chunk(self, splitter: impl TextSplitter + 'a) -> FileLoader<'a, Chunk<PathBuf>> {
+        FileLoader {
+            iterator: Box::new(
+                self.iterator
+                    .flat_map(move |(path, content)| splitter.chunks(&content, path)),
+            ),
+        }
+    }
+}
+
 impl FileLoader<'_, Result<PathBuf, FileLoaderError>> {
     /// Creates a new [FileLoader] using a glob pattern to match files.
     ///
@@ -15,6 +15,9 @@
 //! and keeping track of the chapter numbers along with their contents.
 //!
 //! Note: The [EpubFileLoader] requires the `epub` feature to be enabled in the `Cargo.toml` file.
+//!
+//! Once read, the files, pages or chapters can be split into smaller chunks with the `chunk`
+//! method of each loader, using any [TextSplitter](crate::chunking::TextSplitter).
 
 pub mod file;
 
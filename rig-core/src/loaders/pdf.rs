@@ -5,6 +5,7 @@ use lopdf::{Document, Error as LopdfError};
 use thiserror::Error;
 
 use super::file::FileLoaderError;
+use crate::chunking::{Chunk, TextSplitter};
 
 #[derive(Error, Debug)]
 pub enum PdfLoaderError {
@@ -315,6 +316,42 @@ impl<'a> PdfFileLoader<'a, ByPage> {
     }
 }
 
+impl<'a> PdfFileLoader<'a, (PathBuf, Vec<(usize, String)>)> {
+    /// Splits the pages of the documents into chunks with the given [TextSplitter]. The path of
+    ///  the document and the page number are kept as metadata of each chunk.
+    ///
+    /// # Example
+    /// Split the pages of the pdfs in directory "tests/data/*.pdf" into chunks.
+    ///
+    /// ```rust
+    /// let chunks = PdfFileLoader::with_glob("tests/data/*.pdf")?
+    ///     .load_with_path()
+    ///     .ignore_errors()
+    ///     .by_page()
+    ///     .ignore_errors()
+    ///     .chunk(RecursiveSplitter::new(1000));
+    /// for chunk in chunks {
+    ///     let (path, page_no) = &chunk.metadata;
+    ///     println!("{:?} page {}: {}", path, page_no, chunk.text)
+    /// }
+    /// ```
+    pub fn chunk(
+        self,
+        splitter: impl TextSplitter + 'a,
+    ) -> PdfFileLoader<'a, Chunk<(PathBuf, usize)>> {
+        PdfFileLoader {
+            iterator: Box::new(self.iterator.flat_map(move |(path, pages)| {
+                pages
+                    .into_iter()
+                    .flat_map(|(page_no, content)| {
+                        splitter.chunks(&content, (path.clone(), page_no))
+                    })
+                    .collect::<Vec<_>>()
+            })),
+        }
+    }
+}
+
 impl<'a, T: 'a> PdfFileLoader<'a, Result<T, PdfLoaderError>> {
     /// Ignores errors in the iterator, returning only successful results. This can be used on any
     ///  [PdfFileLoader] state of iterator whose items are results.
@@ -1,6 +1,7 @@
 use crate::{
     completion::{self, CompletionModel},
     extractor::{ExtractionError, Extractor},
+    rerank::{self, RerankError},
     vector_store,
 };
 
@@ -63,6 +64,92 @@ where
     Lookup::new(index, n)
 }
 
+pub struct Rerank<I, R, In, T> {
+    index: I,
+    reranker: R,
+    num_candidates: usize,
+    n: usize,
+    _in: std::marker::PhantomData<In>,
+    _t: std::marker::PhantomData<T>,
+}
+
+impl<I, R, In, T> Rerank<I, R, In, T>
+where
+    I: vector_store::VectorStoreIndex,
+    R: rerank::Reranker,
+{
+    pub(crate) fn new(index: I, reranker: R, num_candidates: usize, n: usize) -> Self {
+        Self {
+            index,
+            reranker,
+            num_candidates,
+            n,
+            _in: std::marker::PhantomData,
+            _t: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I, R, In, T> Op for Rerank<I, R, In, T>
+where
+    I: vector_store::VectorStoreIndex,
+    R: rerank::Reranker,
+    In: Into<String> + Send + Sync,
+    T: Send + Sync + for<'a> serde::Deserialize<'a>,
+{
+    type Input = In;
+    type Output = Result<Vec<(f64, String, T)>, RerankError>;
+
+    async fn call(&self, input: Self::Input) -> Self::Output {
+        let query: String = input.into();
+
+        let mut candidates = self
+            .index
+            .top_n::<serde_json::Value>(&query, self.num_candidates.max(self.n))
+            .await?
+            .into_iter()
+            .map(|(_, id, doc)| Some((id, doc)))
+            .collect::<Vec<_>>();
+
+        let texts = candidates
+            .iter()
+            .flatten()
+            .map(|(_, doc)| serde_json::to_string_pretty(doc).unwrap_or_else(|_| doc.to_string()))
+            .collect();
+
+        self.reranker
+            .rerank(&query, texts, self.n)
+            .await?
+            .into_iter()
+            .filter_map(|result| {
+                let (id, doc) = candidates.get_mut(result.index)?.take()?;
+                Some((result.relevance_score, id, doc))
+            })
+            .map(|(score, id, doc)| Ok((score, id, serde_json::from_value(doc)?)))
+            .collect()
+    }
+}
+
+/// Create a new rerank operation.
+///
+/// The op will perform semantic search on the provided index to fetch `num_candidates`
+/// documents, rerank them against the input with `reranker` and return the `n` most relevant
+/// ones, scored by the reranker.
+pub fn rerank<I, R, In, T>(
+    index: I,
+    reranker: R,
+    num_candidates: usize,
+    n: usize,
+) -> Rerank<I, R, In, T>
+where
+    I: vector_store::VectorStoreIndex,
+    R: rerank::Reranker,
+    In: Into<String> + Send + Sync,
+    T: Send + Sync + for<'a> serde::Deserialize<'a>,
+{
+    Rerank::new(index, reranker, num_candidates, n)
+}
+
 pub struct Prompt<P, In> {
     prompt: P,
     _in: std::marker::PhantomData<In>,
@@ -154,6 +241,7 @@ pub mod tests {
     use super::*;
     use crate::message;
     use completion::{Prompt, PromptError};
+    use rerank::{RerankResult, Reranker};
     use vector_store::{VectorStoreError, VectorStoreIndex};
 
     pub struct MockModel;
@@ -220,6 +308,79 @@ pub mod tests {
         );
     }
 
+    /// Mock index returning two documents, ordered by their id
+    pub struct MockIndex2;
+
+    impl VectorStoreIndex for MockIndex2 {
+        async fn top_n<T: for<'a> serde::Deserialize<'a> + std::marker::Send>(
+            &self,
+            _query: &str,
+            n: usize,
+        ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+            Ok([(0.9, "doc1", "bar"), (0.8, "doc2", "baz")]
+                .into_iter()
+                .take(n)
+                .map(|(score, id, foo)| {
+                    let doc = serde_json::from_value(serde_json::json!({ "foo": foo })).unwrap();
+                    (score, id.to_string(), doc)
+                })
+                .collect())
+        }
+
+        async fn top_n_ids(
+            &self,
+            _query: &str,
+            n: usize,
+        ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+            Ok([(0.9, "doc1"), (0.8, "doc2")]
+                .into_iter()
+                .take(n)
+                .map(|(score, id)| (score, id.to_string()))
+                .collect())
+        }
+    }
+
+    /// Mock reranker scoring documents by the number of times they contain the query
+    pub struct MockReranker;
+
+    impl Reranker for MockReranker {
+        async fn rerank(
+            &self,
+            query: &str,
+            documents: Vec<String>,
+            top_n: usize,
+        ) -> Result<Vec<RerankResult>, RerankError> {
+            let mut results = documents
+                .iter()
+                .enumerate()
+                .map(|(index, document)| RerankResult {
+                    index,
+                    relevance_score: document.matches(query).count() as f64,
+                })
+                .collect::<Vec<_>>();
+            results.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+            results.truncate(top_n);
+            Ok(results)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rerank() {
+        let rerank = rerank::<_, _, String, Foo>(MockIndex2, MockReranker, 10, 1);
+
+        let result = rerank.call("baz".to_string()).await.unwrap();
+        assert_eq!(
+            result,
+            vec![(
+                1.0,
+                "doc2".to_string(),
+                Foo {
+                    foo: "baz".to_string()
+                }
+            )]
+        );
+    }
+
     #[tokio::test]
     async fn test_prompt() {
         let model = MockModel;
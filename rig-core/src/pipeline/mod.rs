@@ -100,7 +100,7 @@ use std::future::Future;
 pub use op::{map, passthrough, then, Op};
 pub use try_op::TryOp;
 
-use crate::{completion, extractor::Extractor, vector_store};
+use crate::{completion, extractor::Extractor, rerank, vector_store};
 
 pub struct PipelineBuilder<E> {
     _error: std::marker::PhantomData<E>,
@@ -216,6 +216,39 @@ impl<E> PipelineBuilder<E> {
         agent_ops::Lookup::new(index, n)
     }
 
+    /// Add a rerank operation to the current pipeline/op. Like the lookup operation, it expects
+    /// the current pipeline to output a query, but it fetches `num_candidates` documents from
+    /// the `index` and only returns the `n` most relevant ones according to the `reranker`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::{pipeline::{self, Op}, providers::cohere};
+    ///
+    /// let reranker = cohere_client.rerank_model(cohere::RERANK_V3_5);
+    ///
+    /// let pipeline = pipeline::new()
+    ///     .rerank(index, reranker, 20, 2)
+    ///     .map_ok(|docs: Vec<(f64, String, String)>| docs.into_iter().map(|(_, _, doc)| doc).collect::<Vec<_>>());
+    ///
+    /// let result = pipeline.call("What is a flurbo?".to_string()).await?;
+    /// ```
+    pub fn rerank<I, R, Input, Output>(
+        self,
+        index: I,
+        reranker: R,
+        num_candidates: usize,
+        n: usize,
+    ) -> agent_ops::Rerank<I, R, Input, Output>
+    where
+        I: vector_store::VectorStoreIndex,
+        R: rerank::Reranker,
+        Output: Send + Sync + for<'a> serde::Deserialize<'a>,
+        Input: Into<String> + Send + Sync,
+        Self: Sized,
+    {
+        agent_ops::Rerank::new(index, reranker, num_candidates, n)
+    }
+
     /// Add a prompt operation to the current pipeline/op. The prompt operation expects the
     /// current pipeline to output a string. The prompt operation will use the string to prompt
     /// the given `agent`, which must implements the [Prompt](completion::Prompt) trait and return
@@ -286,6 +319,9 @@ pub enum ChainError {
 
     #[error("Failed to lookup documents: {0}")]
     LookupError(#[from] vector_store::VectorStoreError),
+
+    #[error("Failed to rerank documents: {0}")]
+    RerankError(#[from] rerank::RerankError),
 }
 
 pub fn new() -> PipelineBuilder<ChainError> {
@@ -1,5 +1,8 @@
 //! Anthropic client api implementation
 
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, ProviderClient,
+};
 use crate::{agent::AgentBuilder, extractor::ExtractorBuilder};
 
 use schemars::JsonSchema;
@@ -154,3 +157,21 @@ impl Client {
         ExtractorBuilder::new(self.completion_model(model))
     }
 }
+
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(ClientBuilder::new(&env_var("ANTHROPIC_API_KEY")?).build())
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
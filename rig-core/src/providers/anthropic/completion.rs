@@ -4,6 +4,7 @@ use std::{convert::Infallible, str::FromStr};
 
 use crate::{
     completion::{self, CompletionError},
+    http_error::HttpStatusError,
     json_utils,
     message::{self, MessageError},
     one_or_many::string_or_one_or_many,
@@ -50,12 +51,29 @@ pub struct CompletionResponse {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Usage {
+    /// Not sent in the usage of `message_delta` stream events
+    #[serde(default)]
     pub input_tokens: u64,
     pub cache_read_input_tokens: Option<u64>,
     pub cache_creation_input_tokens: Option<u64>,
     pub output_tokens: u64,
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        // Anthropic does not count cached tokens as input tokens
+        let cache_read_input_tokens = usage.cache_read_input_tokens.unwrap_or_default();
+        completion::Usage {
+            input_tokens: usage.input_tokens
+                + cache_read_input_tokens
+                + usage.cache_creation_input_tokens.unwrap_or_default(),
+            output_tokens: usage.output_tokens,
+            cached_input_tokens: cache_read_input_tokens,
+            reasoning_tokens: 0,
+        }
+    }
+}
+
 impl std::fmt::Display for Usage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -101,6 +119,9 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                     Content::ToolUse { id, name, input } => {
                         completion::AssistantContent::tool_call(id, name, input.clone())
                     }
+                    Content::Thinking { .. } | Content::RedactedThinking { .. } => {
+                        message::AssistantContent::try_from(content.clone())?
+                    }
                     _ => {
                         return Err(CompletionError::ResponseError(
                             "Response did not contain a message or tool call".into(),
@@ -118,6 +139,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 
         Ok(completion::CompletionResponse {
             choice,
+            usage: Some((&response.usage).into()),
             raw_response: response,
         })
     }
@@ -161,6 +183,14 @@ pub enum Content {
     Document {
         source: DocumentSource,
     },
+    Thinking {
+        thinking: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+    RedactedThinking {
+        data: String,
+    },
 }
 
 impl FromStr for Content {
@@ -288,9 +318,11 @@ impl From<ImageFormat> for message::ImageMediaType {
     }
 }
 
-impl From<message::AssistantContent> for Content {
-    fn from(text: message::AssistantContent) -> Self {
-        match text {
+impl TryFrom<message::AssistantContent> for Content {
+    type Error = MessageError;
+
+    fn try_from(content: message::AssistantContent) -> Result<Self, Self::Error> {
+        Ok(match content {
             message::AssistantContent::Text(message::Text { text }) => Content::Text { text },
             message::AssistantContent::ToolCall(message::ToolCall { id, function }) => {
                 Content::ToolUse {
@@ -299,7 +331,25 @@ impl From<message::AssistantContent> for Content {
                     input: function.arguments,
                 }
             }
-        }
+            message::AssistantContent::Reasoning(message::Reasoning {
+                reasoning,
+                redacted: true,
+                ..
+            }) => Content::RedactedThinking { data: reasoning },
+            message::AssistantContent::Reasoning(message::Reasoning {
+                reasoning,
+                signature: Some(signature),
+                ..
+            }) => Content::Thinking {
+                thinking: reasoning,
+                signature: Some(signature),
+            },
+            message::AssistantContent::Reasoning(_) => {
+                return Err(MessageError::ConversionError(
+                    "Anthropic only accepts signed reasoning".to_owned(),
+                ))
+            }
+        })
     }
 }
 
@@ -381,7 +431,19 @@ impl TryFrom<message::Message> for Message {
             },
 
             message::Message::Assistant { content } => Message {
-                content: content.map(|content| content.into()),
+                // Reasoning without signature (e.g.: produced by another provider) is rejected
+                // by Anthropic, so it is dropped
+                content: OneOrMany::many(
+                    content
+                        .into_iter()
+                        .filter_map(|content| content.try_into().ok())
+                        .collect::<Vec<_>>(),
+                )
+                .map_err(|_| {
+                    MessageError::ConversionError(
+                        "Assistant message only contains unsigned reasoning".to_owned(),
+                    )
+                })?,
                 role: Role::Assistant,
             },
         })
@@ -397,6 +459,17 @@ impl TryFrom<Content> for message::AssistantContent {
             Content::ToolUse { id, name, input } => {
                 message::AssistantContent::tool_call(id, name, input)
             }
+            Content::Thinking {
+                thinking,
+                signature,
+            } => message::AssistantContent::Reasoning(message::Reasoning {
+                reasoning: thinking,
+                signature,
+                redacted: false,
+            }),
+            Content::RedactedThinking { data } => {
+                message::AssistantContent::Reasoning(message::Reasoning::redacted(data))
+            }
             _ => {
                 return Err(MessageError::ConversionError(
                     format!("Unsupported content type for Assistant role: {:?}", content)
@@ -462,7 +535,10 @@ impl TryFrom<Message> for message::Message {
                 })?,
             },
             Role::Assistant => match message.content.first() {
-                Content::Text { .. } | Content::ToolUse { .. } => message::Message::Assistant {
+                Content::Text { .. }
+                | Content::ToolUse { .. }
+                | Content::Thinking { .. }
+                | Content::RedactedThinking { .. } => message::Message::Assistant {
                     content: message.content.try_map(|content| content.try_into())?,
                 },
 
@@ -530,6 +606,16 @@ pub enum ToolChoice {
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
 
+    fn context_window(&self) -> Option<u64> {
+        match self.model.as_str() {
+            model if model.starts_with("claude-3") || model.starts_with("claude-2.1") => {
+                Some(200_000)
+            }
+            model if model.starts_with("claude-2") => Some(100_000),
+            _ => None,
+        }
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -621,7 +707,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -950,4 +1036,78 @@ mod tests {
         assert_eq!(assistant_message, original_assistant_message);
         assert_eq!(tool_message, original_tool_message);
     }
+
+    #[test]
+    fn test_usage_conversion() {
+        let usage: Usage = serde_json::from_value(serde_json::json!({
+            "input_tokens": 20,
+            "cache_creation_input_tokens": 100,
+            "cache_read_input_tokens": 1000,
+            "output_tokens": 50
+        }))
+        .unwrap();
+
+        assert_eq!(
+            completion::Usage::from(&usage),
+            completion::Usage {
+                input_tokens: 1120,
+                output_tokens: 50,
+                cached_input_tokens: 1000,
+                reasoning_tokens: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_thinking_blocks() {
+        let message: Message = serde_json::from_value(json!({
+            "role": "assistant",
+            "content": [
+                { "type": "thinking", "thinking": "Let me check the weather", "signature": "sig_1" },
+                { "type": "redacted_thinking", "data": "encrypted" },
+                { "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {} }
+            ]
+        }))
+        .unwrap();
+
+        let converted: message::Message = message.clone().try_into().unwrap();
+        match &converted {
+            message::Message::Assistant { content } => {
+                assert_eq!(
+                    content.first(),
+                    message::AssistantContent::Reasoning(
+                        message::Reasoning::new("Let me check the weather").with_signature("sig_1")
+                    )
+                );
+                assert_eq!(
+                    content.iter().nth(1),
+                    Some(&message::AssistantContent::Reasoning(
+                        message::Reasoning::redacted("encrypted")
+                    ))
+                );
+            }
+            _ => panic!("Expected assistant message"),
+        }
+
+        // Signed and redacted thinking blocks are sent back unchanged
+        let original: Message = converted.try_into().unwrap();
+        assert_eq!(original, message);
+
+        // Unsigned reasoning (e.g.: from another provider) is dropped
+        let message: Message = message::Message::Assistant {
+            content: OneOrMany::many(vec![
+                message::AssistantContent::reasoning("Thinking"),
+                message::AssistantContent::text("Hello"),
+            ])
+            .unwrap(),
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(
+            message.content,
+            OneOrMany::one(Content::Text {
+                text: "Hello".to_string()
+            })
+        );
+    }
 }
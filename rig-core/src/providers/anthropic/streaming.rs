@@ -4,10 +4,13 @@ use serde::Deserialize;
 use serde_json::json;
 
 use super::completion::{CompletionModel, Content, Message, ToolChoice, ToolDefinition, Usage};
-use crate::completion::{CompletionError, CompletionRequest};
+use crate::completion::{self, CompletionError, CompletionRequest};
+use crate::http_error::HttpStatusError;
 use crate::json_utils::merge_inplace;
-use crate::message::MessageError;
-use crate::streaming::{StreamingChoice, StreamingCompletionModel, StreamingResult};
+use crate::message::{MessageError, Reasoning};
+use crate::streaming::{
+    with_final_response, StreamingChoice, StreamingCompletionModel, StreamingResult,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -50,6 +53,8 @@ pub struct MessageStart {
 pub enum ContentDelta {
     TextDelta { text: String },
     InputJsonDelta { partial_json: String },
+    ThinkingDelta { thinking: String },
+    SignatureDelta { signature: String },
 }
 
 #[derive(Debug, Deserialize)]
@@ -139,11 +144,13 @@ impl StreamingCompletionModel for CompletionModel {
             .await?;
 
         if !response.status().is_success() {
-            return Err(CompletionError::ProviderError(response.text().await?));
+            return Err(HttpStatusError::from_response(response).await.into());
         }
 
-        Ok(Box::pin(stream! {
+        Ok(with_final_response(Box::pin(stream! {
             let mut current_tool_call: Option<ToolCallState> = None;
+            // Input tokens are only reported when the message starts
+            let mut message_usage = completion::Usage::default();
             let mut stream = response.bytes_stream();
 
             while let Some(chunk_result) = stream.next().await {
@@ -177,10 +184,29 @@ impl StreamingCompletionModel for CompletionModel {
                                         ContentDelta::InputJsonDelta { partial_json } => {
                                             if let Some(ref mut tool_call) = current_tool_call {
                                                 tool_call.input_json.push_str(&partial_json);
+                                                yield Ok(StreamingChoice::ToolCallDelta(
+                                                    tool_call.id.clone(),
+                                                    partial_json,
+                                                ));
                                             }
                                         }
+                                        ContentDelta::ThinkingDelta { thinking } => {
+                                            yield Ok(StreamingChoice::Reasoning(Reasoning::new(thinking)));
+                                        }
+                                        // Sent once the thinking block is complete
+                                        ContentDelta::SignatureDelta { signature } => {
+                                            yield Ok(StreamingChoice::Reasoning(
+                                                Reasoning::new("").with_signature(signature),
+                                            ));
+                                        }
                                     }
                                 }
+                                StreamingEvent::ContentBlockStart {
+                                    content_block: Content::RedactedThinking { data },
+                                    ..
+                                } => {
+                                    yield Ok(StreamingChoice::Reasoning(Reasoning::redacted(data)));
+                                }
                                 StreamingEvent::ContentBlockStart {
                                     content_block: Content::ToolUse { id, name, .. },
                                     ..
@@ -212,12 +238,24 @@ impl StreamingCompletionModel for CompletionModel {
                                         }
                                     }
                                 },
+                                StreamingEvent::MessageStart { message } => {
+                                    message_usage = completion::Usage::from(&message.usage);
+                                }
+                                StreamingEvent::MessageDelta { delta, usage } => {
+                                    if let Some(stop_reason) = delta.stop_reason {
+                                        yield Ok(StreamingChoice::FinishReason(stop_reason));
+                                    }
+                                    yield Ok(StreamingChoice::Usage(completion::Usage {
+                                        output_tokens: usage.output_tokens,
+                                        ..message_usage
+                                    }));
+                                }
                                 _ => {}
                             }
                         }
                     }
                 }
             }
-        }))
+        })))
     }
 }
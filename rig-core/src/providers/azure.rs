@@ -8,13 +8,19 @@
 //!
 //! let gpt4o = client.completion_model(azure::GPT_4O);
 //! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, EmbeddingsClient,
+    EmbeddingsClientDyn, ProviderClient,
+};
 use crate::{
     agent::AgentBuilder,
     completion::{self, CompletionError, CompletionRequest},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
+    http_error::HttpStatusError,
     json_utils,
     providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
     Embed,
 };
 use schemars::JsonSchema;
@@ -82,6 +88,14 @@ impl Client {
         self.http_client.post(url)
     }
 
+    /// Whether the API version supports `stream_options`, to get the usage of streamed
+    /// requests (since `2024-07-01-preview`). Older versions reject the option.
+    fn supports_stream_usage(&self) -> bool {
+        self.api_version
+            .get(..10)
+            .is_some_and(|date| date >= "2024-07-01")
+    }
+
     /// Create an embedding model with the given name.
     /// Note: default embedding dimension of 0 will be used if model is not known.
     /// If this is the case, it's better to use function `embedding_model_with_ndims`
@@ -181,6 +195,44 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(
+            &env_var("AZURE_API_KEY")?,
+            &env_var("AZURE_API_VERSION")?,
+            &env_var("AZURE_ENDPOINT")?,
+        ))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    fn embedding_model(&self, model: &str) -> EmbeddingModel {
+        self.embedding_model(model)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        self.embedding_model_with_ndims(model, ndims)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -249,6 +301,16 @@ impl std::fmt::Display for Usage {
     }
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel {
     client: Client,
@@ -266,8 +328,18 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        self.embed_texts_with_usage(documents)
+            .await
+            .map(|response| response.embeddings)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String>,
+    ) -> Result<embeddings::EmbeddingsResponse, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
         let response = self
@@ -293,7 +365,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         ));
                     }
 
-                    Ok(response
+                    let embeddings = response
                         .data
                         .into_iter()
                         .zip(documents.into_iter())
@@ -301,12 +373,17 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                             document,
                             vec: embedding.embedding,
                         })
-                        .collect())
+                        .collect();
+
+                    Ok(embeddings::EmbeddingsResponse {
+                        embeddings,
+                        usage: Some((&response.usage).into()),
+                    })
                 }
                 ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -365,16 +442,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = openai::CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Add preamble to chat history (if available)
         let mut full_history: Vec<openai::Message> = match &completion_request.preamble {
             Some(preamble) => vec![openai::Message::system(preamble)],
@@ -414,16 +486,28 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = openai::CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post_chat_completion(&self.model)
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -442,8 +526,29 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
+        }
+    }
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+        if self.client.supports_stream_usage() {
+            json_utils::merge_inplace(
+                &mut request,
+                json!({ "stream_options": { "include_usage": true } }),
+            );
         }
+
+        openai::send_compatible_streaming_request(
+            self.client.post_chat_completion(&self.model).json(&request),
+        )
+        .await
     }
 }
 
@@ -486,6 +591,7 @@ mod azure_tests {
                 temperature: Some(0.0),
                 tools: vec![],
                 additional_params: None,
+                output_schema: None,
             })
             .await
             .unwrap();
@@ -493,3 +599,18 @@ mod azure_tests {
         tracing::info!("Azure completion: {:?}", completion);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports_stream_usage() {
+        let client = |api_version| Client::new("key", api_version, "https://example.com");
+
+        assert!(client("2024-10-21").supports_stream_usage());
+        assert!(client("2024-10-01-preview").supports_stream_usage());
+        assert!(!client("2024-06-01").supports_stream_usage());
+        assert!(!client("2024-02-15-preview").supports_stream_usage());
+    }
+}
@@ -8,6 +8,10 @@
 //!
 //! let command_r = client.completion_model(cohere::COMMAND_R);
 //! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, EmbeddingsClient,
+    EmbeddingsClientDyn, ProviderClient,
+};
 use std::collections::HashMap;
 
 use crate::{
@@ -15,9 +19,17 @@ use crate::{
     completion::{self, CompletionError},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, message, Embed, OneOrMany,
+    http_error::HttpStatusError,
+    json_utils, message,
+    rerank::{self, RerankError},
+    streaming::{
+        self, with_final_response, StreamingChoice, StreamingCompletionModel, StreamingResult,
+    },
+    Embed, OneOrMany,
 };
 
+use async_stream::stream;
+use futures::StreamExt;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -100,6 +112,10 @@ impl Client {
         EmbeddingsBuilder::new(self.embedding_model(model, input_type))
     }
 
+    pub fn rerank_model(&self, model: &str) -> RerankModel {
+        RerankModel::new(self.clone(), model)
+    }
+
     pub fn completion_model(&self, model: &str) -> CompletionModel {
         CompletionModel::new(self.clone(), model)
     }
@@ -116,6 +132,40 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(&env_var("COHERE_API_KEY")?))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    fn embedding_model(&self, model: &str) -> EmbeddingModel {
+        self.embedding_model(model, "search_document")
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        self.embedding_model_with_ndims(model, "search_document", ndims)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -157,7 +207,7 @@ pub struct EmbeddingResponse {
     pub meta: Option<Meta>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct Meta {
     pub api_version: ApiVersion,
     pub billed_units: BilledUnits,
@@ -165,7 +215,7 @@ pub struct Meta {
     pub warnings: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct ApiVersion {
     pub version: String,
     #[serde(default)]
@@ -186,6 +236,16 @@ pub struct BilledUnits {
     pub classifications: u32,
 }
 
+impl From<&BilledUnits> for completion::Usage {
+    fn from(billed_units: &BilledUnits) -> Self {
+        completion::Usage {
+            input_tokens: billed_units.input_tokens as u64,
+            output_tokens: billed_units.output_tokens as u64,
+            ..Default::default()
+        }
+    }
+}
+
 impl std::fmt::Display for BilledUnits {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -214,8 +274,18 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        self.embed_texts_with_usage(documents)
+            .await
+            .map(|response| response.embeddings)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String>,
+    ) -> Result<embeddings::EmbeddingsResponse, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
         let response = self
@@ -232,7 +302,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         if response.status().is_success() {
             match response.json::<ApiResponse<EmbeddingResponse>>().await? {
                 ApiResponse::Ok(response) => {
-                    match response.meta {
+                    match &response.meta {
                         Some(meta) => tracing::info!(target: "rig",
                             "Cohere embeddings billed units: {}",
                             meta.billed_units,
@@ -253,20 +323,26 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         ));
                     }
 
-                    Ok(response
-                        .embeddings
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding,
-                        })
-                        .collect())
+                    Ok(embeddings::EmbeddingsResponse {
+                        usage: response
+                            .meta
+                            .as_ref()
+                            .map(|meta| (&meta.billed_units).into()),
+                        embeddings: response
+                            .embeddings
+                            .into_iter()
+                            .zip(documents.into_iter())
+                            .map(|(embedding, document)| embeddings::Embedding {
+                                document,
+                                vec: embedding,
+                            })
+                            .collect(),
+                    })
                 }
                 ApiResponse::Err(error) => Err(EmbeddingError::ProviderError(error.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -282,6 +358,105 @@ impl EmbeddingModel {
     }
 }
 
+// ================================================================
+// Cohere Rerank API
+// ================================================================
+/// `rerank-v3.5` rerank model
+pub const RERANK_V3_5: &str = "rerank-v3.5";
+/// `rerank-english-v3.0` rerank model
+pub const RERANK_ENGLISH_V3: &str = "rerank-english-v3.0";
+/// `rerank-multilingual-v3.0` rerank model
+pub const RERANK_MULTILINGUAL_V3: &str = "rerank-multilingual-v3.0";
+
+#[derive(Debug, Deserialize)]
+pub struct RerankResponse {
+    pub id: String,
+    pub results: Vec<RerankResult>,
+    #[serde(default)]
+    pub meta: Option<Meta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RerankResult {
+    pub index: usize,
+    pub relevance_score: f64,
+}
+
+#[derive(Clone)]
+pub struct RerankModel {
+    client: Client,
+    pub model: String,
+}
+
+impl RerankModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+}
+
+impl rerank::Reranker for RerankModel {
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> Result<Vec<rerank::RerankResult>, RerankError> {
+        if documents.is_empty() || top_n == 0 {
+            return Ok(vec![]);
+        }
+
+        let response = self
+            .client
+            .post("/v2/rerank")
+            .json(&json!({
+                "model": self.model,
+                "query": query,
+                "documents": documents,
+                "top_n": top_n.min(documents.len()),
+            }))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<RerankResponse>>().await? {
+                ApiResponse::Ok(response) => {
+                    if let Some(meta) = &response.meta {
+                        tracing::info!(target: "rig",
+                            "Cohere rerank billed units: {}",
+                            meta.billed_units,
+                        );
+                    }
+
+                    response
+                        .results
+                        .into_iter()
+                        .map(|result| {
+                            if result.index < documents.len() {
+                                Ok(rerank::RerankResult {
+                                    index: result.index,
+                                    relevance_score: result.relevance_score,
+                                })
+                            } else {
+                                Err(RerankError::ResponseError(format!(
+                                    "Document index {} out of range",
+                                    result.index
+                                )))
+                            }
+                        })
+                        .collect()
+                }
+                ApiResponse::Err(error) => Err(RerankError::ProviderError(error.message)),
+            }
+        } else {
+            Err(HttpStatusError::from_response(response).await.into())
+        }
+    }
+}
+
 // ================================================================
 // Cohere Completion API
 // ================================================================
@@ -317,6 +492,8 @@ pub struct CompletionResponse {
     pub tool_calls: Vec<ToolCall>,
     #[serde(default)]
     pub chat_history: Vec<ChatHistory>,
+    #[serde(default)]
+    pub meta: Option<Meta>,
 }
 
 impl From<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
@@ -342,6 +519,10 @@ impl From<CompletionResponse> for completion::CompletionResponse<CompletionRespo
 
         completion::CompletionResponse {
             choice: OneOrMany::many(model_response).expect("There is atleast one content"),
+            usage: response
+                .meta
+                .as_ref()
+                .map(|meta| (&meta.billed_units).into()),
             raw_response: response,
         }
     }
@@ -355,6 +536,17 @@ pub struct Citation {
     pub document_ids: Vec<String>,
 }
 
+impl From<Citation> for streaming::Citation {
+    fn from(citation: Citation) -> Self {
+        Self {
+            start: citation.start as usize,
+            end: citation.end as usize,
+            text: citation.text,
+            document_ids: citation.document_ids,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Document {
     pub id: String,
@@ -552,16 +744,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         let chat_history = completion_request
             .chat_history
             .into_iter()
@@ -598,18 +785,33 @@ impl completion::CompletionModel for CompletionModel {
             "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
         });
 
-        let response = self
-            .client
-            .post("/v1/chat")
-            .json(
-                &if let Some(ref params) = completion_request.additional_params {
-                    json_utils::merge(request.clone(), params.clone())
-                } else {
-                    request.clone()
-                },
-            )
-            .send()
-            .await?;
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    fn context_window(&self) -> Option<u64> {
+        match self.model.as_str() {
+            model if model.starts_with("command-r") => Some(128_000),
+            model if model.starts_with("command") => Some(4_096),
+            _ => None,
+        }
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
+        let response = self.client.post("/v1/chat").json(&request).send().await?;
 
         if response.status().is_success() {
             match response.json::<ApiResponse<CompletionResponse>>().await? {
@@ -617,7 +819,247 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
+        }
+    }
+}
+
+// ================================================================
+// Cohere Streaming API
+// ================================================================
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event_type", rename_all = "kebab-case")]
+pub enum StreamingEvent {
+    StreamStart {
+        generation_id: String,
+    },
+    SearchQueriesGeneration {
+        search_queries: Vec<SearchQuery>,
+    },
+    SearchResults {
+        #[serde(default)]
+        search_results: Vec<SearchResult>,
+        #[serde(default)]
+        documents: Vec<Document>,
+    },
+    TextGeneration {
+        text: String,
+    },
+    CitationGeneration {
+        citations: Vec<Citation>,
+    },
+    ToolCallsChunk {
+        tool_call_delta: ToolCallDelta,
+    },
+    ToolCallsGeneration {
+        #[serde(default)]
+        text: Option<String>,
+        tool_calls: Vec<ToolCall>,
+    },
+    StreamEnd {
+        finish_reason: String,
+        #[serde(default)]
+        response: Option<Box<CompletionResponse>>,
+    },
+}
+
+/// A chunk of a tool call being generated. The name is only sent in the first chunk.
+#[derive(Debug, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Default)]
+struct StreamingState {
+    tool_call_names: HashMap<usize, String>,
+}
+
+impl StreamingState {
+    /// Convert a stream event into the streaming choices it carries. Text is forwarded as it is
+    /// generated while tool calls are only forwarded once Cohere has emitted them whole.
+    fn process_event(&mut self, event: StreamingEvent) -> Vec<StreamingChoice> {
+        match event {
+            StreamingEvent::TextGeneration { text } => vec![StreamingChoice::Message(text)],
+            StreamingEvent::ToolCallsChunk { tool_call_delta } => {
+                if let Some(name) = tool_call_delta.name {
+                    self.tool_call_names.insert(tool_call_delta.index, name);
+                }
+                // Cohere tool calls have no id, so the tool name is used instead
+                match (
+                    self.tool_call_names.get(&tool_call_delta.index),
+                    tool_call_delta.parameters,
+                ) {
+                    (Some(name), Some(parameters)) if !parameters.is_empty() => {
+                        vec![StreamingChoice::ToolCallDelta(name.clone(), parameters)]
+                    }
+                    _ => vec![],
+                }
+            }
+            StreamingEvent::ToolCallsGeneration { tool_calls, .. } => tool_calls
+                .into_iter()
+                .map(|tool_call| {
+                    StreamingChoice::ToolCall(
+                        tool_call.name.clone(),
+                        tool_call.name,
+                        tool_call.parameters,
+                    )
+                })
+                .collect(),
+            StreamingEvent::CitationGeneration { citations } => citations
+                .into_iter()
+                .map(|citation| StreamingChoice::Citation(citation.into()))
+                .collect(),
+            StreamingEvent::StreamEnd {
+                finish_reason,
+                response,
+            } => {
+                let mut choices = vec![StreamingChoice::FinishReason(finish_reason)];
+                if let Some(meta) = response.and_then(|response| response.meta) {
+                    choices.push(StreamingChoice::Usage((&meta.billed_units).into()));
+                }
+                choices
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+
+        let response = self.client.post("/v1/chat").json(&request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(HttpStatusError::from_response(response).await.into());
+        }
+
+        Ok(with_final_response(Box::pin(stream! {
+            let mut state = StreamingState::default();
+            let mut stream = response.bytes_stream();
+            // Cohere streams newline-delimited JSON events which may be split across chunks
+            let mut buffer: Vec<u8> = vec![];
+
+            'outer: while let Some(chunk_result) = stream.next().await {
+                let chunk = match chunk_result {
+                    Ok(c) => c,
+                    Err(e) => {
+                        yield Err(CompletionError::from(e));
+                        break;
+                    }
+                };
+
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                    let line = match std::str::from_utf8(&line) {
+                        Ok(line) => line.trim(),
+                        Err(e) => {
+                            yield Err(CompletionError::ResponseError(e.to_string()));
+                            break 'outer;
+                        }
+                    };
+
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    match serde_json::from_str::<StreamingEvent>(line) {
+                        Ok(StreamingEvent::StreamEnd { finish_reason, .. })
+                            if finish_reason == "ERROR" || finish_reason == "ERROR_TOXIC" =>
+                        {
+                            yield Err(CompletionError::ProviderError(format!(
+                                "Cohere stream ended with {}",
+                                finish_reason
+                            )));
+                            break 'outer;
+                        }
+                        Ok(event) => {
+                            let is_end = matches!(event, StreamingEvent::StreamEnd { .. });
+                            for choice in state.process_event(event) {
+                                yield Ok(choice);
+                            }
+                            if is_end {
+                                break 'outer;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::debug!(target: "rig", "Couldn't parse Cohere stream event: {}", e);
+                        }
+                    }
+                }
+            }
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streaming_events() {
+        let events = [
+            r#"{"is_finished":false,"event_type":"stream-start","generation_id":"abc"}"#,
+            r#"{"is_finished":false,"event_type":"text-generation","text":"Hello"}"#,
+            r#"{"is_finished":false,"event_type":"citation-generation","citations":[{"start":0,"end":5,"text":"Hello","document_ids":["doc_0"]}]}"#,
+            r#"{"is_finished":false,"event_type":"tool-calls-chunk","tool_call_delta":{"index":0,"name":"add"}}"#,
+            r#"{"is_finished":false,"event_type":"tool-calls-chunk","tool_call_delta":{"index":0,"parameters":"{\"x\":1,\"y\":2}"}}"#,
+            r#"{"is_finished":false,"event_type":"tool-calls-generation","text":"I will add the numbers.","tool_calls":[{"name":"add","parameters":{"x":1,"y":2}}]}"#,
+        ];
+
+        let mut state = StreamingState::default();
+        let choices = events
+            .iter()
+            .flat_map(|event| state.process_event(serde_json::from_str(event).unwrap()))
+            .collect::<Vec<_>>();
+
+        match &choices[..] {
+            [StreamingChoice::Message(text), StreamingChoice::Citation(citation), StreamingChoice::ToolCallDelta(delta_id, delta), StreamingChoice::ToolCall(name, id, args)] =>
+            {
+                assert_eq!(text, "Hello");
+                assert_eq!(
+                    citation,
+                    &streaming::Citation {
+                        start: 0,
+                        end: 5,
+                        text: "Hello".to_string(),
+                        document_ids: vec!["doc_0".to_string()],
+                    }
+                );
+                assert_eq!(
+                    (delta_id.as_str(), delta.as_str()),
+                    ("add", r#"{"x":1,"y":2}"#)
+                );
+                assert_eq!((name.as_str(), id.as_str()), ("add", "add"));
+                assert_eq!(args, &json!({"x": 1, "y": 2}));
+            }
+            _ => panic!("Unexpected streaming choices: {:?}", choices),
+        }
+
+        let end = state.process_event(
+            serde_json::from_str(
+                r#"{"is_finished":true,"event_type":"stream-end","finish_reason":"COMPLETE","response":{"text":"Hello","generation_id":"abc","finish_reason":"COMPLETE","meta":{"api_version":{"version":"1"},"billed_units":{"input_tokens":10,"output_tokens":5}}}}"#,
+            )
+            .unwrap(),
+        );
+        match &end[..] {
+            [StreamingChoice::FinishReason(reason), StreamingChoice::Usage(usage)] => {
+                assert_eq!(reason, "COMPLETE");
+                assert_eq!((usage.input_tokens, usage.output_tokens), (10, 5));
+            }
+            _ => panic!("Unexpected streaming choices: {:?}", end),
         }
     }
 }
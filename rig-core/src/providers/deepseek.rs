@@ -8,10 +8,17 @@
 //!
 //! let deepseek_chat = client.completion_model(deepseek::DEEPSEEK_CHAT);
 //! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, ProviderClient,
+};
 use crate::{
     completion::{self, CompletionError, CompletionModel, CompletionRequest},
     extractor::ExtractorBuilder,
-    json_utils, message, OneOrMany,
+    http_error::HttpStatusError,
+    json_utils, message,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
+    OneOrMany,
 };
 use reqwest::Client as HttpClient;
 use schemars::JsonSchema;
@@ -89,6 +96,24 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(&env_var("DEEPSEEK_API_KEY")?))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = DeepSeekCompletionModel;
+
+    fn completion_model(&self, model: &str) -> DeepSeekCompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -112,7 +137,40 @@ impl From<ApiErrorResponse> for CompletionError {
 pub struct CompletionResponse {
     // We'll match the JSON:
     pub choices: Vec<Choice>,
-    // you may want usage or other fields
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    #[serde(default)]
+    pub prompt_cache_hit_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+}
+
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+            cached_input_tokens: usage.prompt_cache_hit_tokens,
+            reasoning_tokens: usage
+                .completion_tokens_details
+                .as_ref()
+                .map(|details| details.reasoning_tokens)
+                .unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -142,6 +200,10 @@ pub enum Message {
         name: Option<String>,
         #[serde(default, deserialize_with = "json_utils::null_or_vec")]
         tool_calls: Vec<ToolCall>,
+        /// Chain of thought of `deepseek-reasoner`. The API rejects it in requests, so it is
+        /// never sent back.
+        #[serde(default, skip_serializing)]
+        reasoning_content: Option<String>,
     },
     #[serde(rename = "Tool")]
     ToolResult {
@@ -246,6 +308,7 @@ impl TryFrom<message::Message> for Vec<Message> {
                         content: "".to_string(),
                         name: None,
                         tool_calls,
+                        reasoning_content: None,
                     });
                 }
 
@@ -257,6 +320,7 @@ impl TryFrom<message::Message> for Vec<Message> {
                             content: text.text,
                             name: None,
                             tool_calls: vec![],
+                            reasoning_content: None,
                         }),
                         _ => None,
                     })
@@ -319,15 +383,20 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
             Message::Assistant {
                 content,
                 tool_calls,
+                reasoning_content,
                 ..
             } => {
-                let mut content = if content.trim().is_empty() {
-                    vec![]
-                } else {
-                    vec![completion::AssistantContent::text(content)]
-                };
+                let mut choice = reasoning_content
+                    .iter()
+                    .filter(|reasoning| !reasoning.is_empty())
+                    .map(completion::AssistantContent::reasoning)
+                    .collect::<Vec<_>>();
 
-                content.extend(
+                if !content.trim().is_empty() {
+                    choice.push(completion::AssistantContent::text(content));
+                }
+
+                choice.extend(
                     tool_calls
                         .iter()
                         .map(|call| {
@@ -339,7 +408,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                         })
                         .collect::<Vec<_>>(),
                 );
-                Ok(content)
+                Ok(choice)
             }
             _ => Err(CompletionError::ResponseError(
                 "Response did not contain a valid message or tool call".into(),
@@ -354,6 +423,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 
         Ok(completion::CompletionResponse {
             choice,
+            usage: response.usage.as_ref().map(completion::Usage::from),
             raw_response: response,
         })
     }
@@ -366,17 +436,11 @@ pub struct DeepSeekCompletionModel {
     pub model: String,
 }
 
-impl CompletionModel for DeepSeekCompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+impl DeepSeekCompletionModel {
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<
-        completion::CompletionResponse<CompletionResponse>,
-        crate::completion::CompletionError,
-    > {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Add preamble to chat history (if available)
         let mut full_history: Vec<Message> = match &completion_request.preamble {
             Some(preamble) => vec![Message::system(preamble)],
@@ -416,16 +480,38 @@ impl CompletionModel for DeepSeekCompletionModel {
             })
         };
 
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl CompletionModel for DeepSeekCompletionModel {
+    type Response = CompletionResponse;
+
+    fn context_window(&self) -> Option<u64> {
+        match self.model.as_str() {
+            DEEPSEEK_CHAT | DEEPSEEK_REASONER => Some(65_536),
+            _ => None,
+        }
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<
+        completion::CompletionResponse<CompletionResponse>,
+        crate::completion::CompletionError,
+    > {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -438,11 +524,29 @@ impl CompletionModel for DeepSeekCompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
 
+impl StreamingCompletionModel for DeepSeekCompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        json_utils::merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        openai::send_compatible_streaming_request(
+            self.client.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
+
 // ================================================================
 // DeepSeek Completion API
 // ================================================================
@@ -589,9 +693,47 @@ mod tests {
                     index: 0,
                     r#type: ToolType::Function,
                 }],
+                reasoning_content: None,
             },
         };
 
         assert_eq!(choice, expected_choice);
     }
+
+    #[test]
+    fn test_reasoning_content() {
+        let response: CompletionResponse = serde_json::from_value(json!({
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "9.11 is smaller.",
+                    "reasoning_content": "Comparing the decimals, 0.11 < 0.9."
+                },
+                "logprobs": null,
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap();
+
+        let response: completion::CompletionResponse<_> = response.try_into().unwrap();
+        assert_eq!(
+            response.choice.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                completion::AssistantContent::reasoning("Comparing the decimals, 0.11 < 0.9."),
+                completion::AssistantContent::text("9.11 is smaller."),
+            ]
+        );
+
+        // The reasoning is not sent back to DeepSeek
+        let messages: Vec<Message> = message::Message::Assistant {
+            content: response.choice,
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(!serde_json::to_string(&messages)
+            .unwrap()
+            .contains("reasoning"));
+    }
 }
@@ -10,11 +10,17 @@
 //!
 //! let gpt4o = client.completion_model(galadriel::GPT_4O);
 //! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, ProviderClient,
+};
 use crate::{
     agent::AgentBuilder,
     completion::{self, CompletionError, CompletionRequest},
     extractor::ExtractorBuilder,
-    json_utils, message, OneOrMany,
+    http_error::HttpStatusError,
+    json_utils, message,
+    streaming::{StreamingCompletionModel, StreamingResult},
+    OneOrMany,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -130,6 +136,28 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        let fine_tune_api_key = std::env::var("GALADRIEL_FINE_TUNE_API_KEY").ok();
+        Ok(Self::new(
+            &env_var("GALADRIEL_API_KEY")?,
+            fine_tune_api_key.as_deref(),
+        ))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -148,6 +176,16 @@ pub struct Usage {
     pub total_tokens: usize,
 }
 
+impl From<Usage> for completion::Usage {
+    fn from(usage: Usage) -> Self {
+        completion::Usage {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            ..Default::default()
+        }
+    }
+}
+
 impl std::fmt::Display for Usage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -251,6 +289,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 
         Ok(completion::CompletionResponse {
             choice,
+            usage: response.usage.clone().map(completion::Usage::from),
             raw_response: response,
         })
     }
@@ -350,6 +389,8 @@ impl TryFrom<message::Message> for Message {
                         message::AssistantContent::ToolCall(tool_call) => {
                             tool_calls.push(tool_call.clone().into());
                         }
+                        // Reasoning is not sent back to the model
+                        message::AssistantContent::Reasoning(_) => {}
                     }
                 }
 
@@ -398,16 +439,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Add preamble to chat history (if available)
         let mut full_history: Vec<Message> = match &completion_request.preamble {
             Some(preamble) => vec![Message {
@@ -448,16 +484,28 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -476,7 +524,25 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        json_utils::merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        openai::send_compatible_streaming_request(
+            self.client.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
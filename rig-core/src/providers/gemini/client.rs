@@ -1,3 +1,7 @@
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, EmbeddingsClient,
+    EmbeddingsClientDyn, ProviderClient,
+};
 use crate::{
     agent::AgentBuilder,
     embeddings::{self},
@@ -147,6 +151,40 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(&env_var("GEMINI_API_KEY")?))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    fn embedding_model(&self, model: &str) -> EmbeddingModel {
+        self.embedding_model(model)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        self.embedding_model_with_ndims(model, ndims)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ApiErrorResponse {
     pub message: String,
@@ -23,6 +23,7 @@ use std::convert::TryFrom;
 
 use crate::{
     completion::{self, CompletionError, CompletionRequest},
+    http_error::HttpStatusError,
     OneOrMany,
 };
 
@@ -34,7 +35,7 @@ use super::Client;
 
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(crate) client: Client,
     pub model: String,
 }
 
@@ -50,57 +51,28 @@ impl CompletionModel {
 impl completion::CompletionModel for CompletionModel {
     type Response = GenerateContentResponse;
 
+    fn supports_structured_output(&self) -> bool {
+        // Response schemas are not supported by Gemini 1.0 models (`gemini-pro` is Gemini 1.0)
+        !(self.model.starts_with("gemini-1.0") || self.model.starts_with("gemini-pro"))
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        match self.model.as_str() {
+            model if model.starts_with("gemini-1.5-pro") => Some(2_097_152),
+            model if model.starts_with("gemini-1.5-flash") || model.starts_with("gemini-2.0") => {
+                Some(1_048_576)
+            }
+            model if model.starts_with("gemini-1.0-pro") => Some(32_760),
+            _ => None,
+        }
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
-        mut completion_request: CompletionRequest,
+        completion_request: CompletionRequest,
     ) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
-        let mut full_history = Vec::new();
-        full_history.append(&mut completion_request.chat_history);
-
-        full_history.push(completion_request.prompt_with_context());
-
-        // Handle Gemini specific parameters
-        let additional_params = completion_request
-            .additional_params
-            .unwrap_or_else(|| Value::Object(Map::new()));
-        let mut generation_config = serde_json::from_value::<GenerationConfig>(additional_params)?;
-
-        // Set temperature from completion_request or additional_params
-        if let Some(temp) = completion_request.temperature {
-            generation_config.temperature = Some(temp);
-        }
-
-        // Set max_tokens from completion_request or additional_params
-        if let Some(max_tokens) = completion_request.max_tokens {
-            generation_config.max_output_tokens = Some(max_tokens);
-        }
-
-        let system_instruction = completion_request.preamble.clone().map(|preamble| Content {
-            parts: OneOrMany::one(preamble.into()),
-            role: Some(Role::Model),
-        });
-
-        let request = GenerateContentRequest {
-            contents: full_history
-                .into_iter()
-                .map(|msg| {
-                    msg.try_into()
-                        .map_err(|e| CompletionError::RequestError(Box::new(e)))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-            generation_config: Some(generation_config),
-            safety_settings: None,
-            tools: Some(
-                completion_request
-                    .tools
-                    .into_iter()
-                    .map(Tool::try_from)
-                    .collect::<Result<Vec<_>, _>>()?,
-            ),
-            tool_config: None,
-            system_instruction,
-        };
+        let request = create_request_body(completion_request)?;
 
         tracing::debug!(
             "Sending completion request to Gemini API {}",
@@ -130,11 +102,195 @@ impl completion::CompletionModel for CompletionModel {
 
             Ok(completion::CompletionResponse::try_from(response))
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from(
+                HttpStatusError::from_response(response).await,
+            ))
         }?
     }
 }
 
+pub(crate) fn create_request_body(
+    mut completion_request: CompletionRequest,
+) -> Result<GenerateContentRequest, CompletionError> {
+    // Reasoning of the model is not sent back to Gemini
+    let mut full_history = completion_request
+        .chat_history
+        .drain(..)
+        .filter_map(completion::Message::without_reasoning)
+        .collect::<Vec<_>>();
+
+    full_history.push(completion_request.prompt_with_context());
+
+    // Handle Gemini specific parameters
+    let additional_params = completion_request
+        .additional_params
+        .unwrap_or_else(|| Value::Object(Map::new()));
+    let mut generation_config = serde_json::from_value::<GenerationConfig>(additional_params)?;
+
+    // Set temperature from completion_request or additional_params
+    if let Some(temp) = completion_request.temperature {
+        generation_config.temperature = Some(temp);
+    }
+
+    // Set max_tokens from completion_request or additional_params
+    if let Some(max_tokens) = completion_request.max_tokens {
+        generation_config.max_output_tokens = Some(max_tokens);
+    }
+
+    // Constrain the response to the output schema (if any)
+    if let Some(schema) = completion_request.output_schema {
+        generation_config.response_mime_type = Some("application/json".to_string());
+        generation_config.response_schema = Some(simplify_schema(schema).try_into()?);
+    }
+
+    let system_instruction = completion_request.preamble.clone().map(|preamble| Content {
+        parts: OneOrMany::one(preamble.into()),
+        role: Some(Role::Model),
+    });
+
+    let request = GenerateContentRequest {
+        contents: full_history
+            .into_iter()
+            .map(|msg| {
+                msg.try_into()
+                    .map_err(|e| CompletionError::RequestError(Box::new(e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        generation_config: Some(generation_config),
+        safety_settings: None,
+        tools: Some(
+            completion_request
+                .tools
+                .into_iter()
+                .map(Tool::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        tool_config: None,
+        system_instruction,
+    };
+
+    Ok(request)
+}
+
+/// Gemini only supports a subset of the OpenAPI schema, so references to definitions
+/// (e.g.: nested structs) are inlined and nullable types (e.g.: `Option` fields) are
+/// converted to their non-null type marked as `nullable`.
+fn simplify_schema(mut schema: Value) -> Value {
+    let definitions = schema
+        .as_object_mut()
+        .and_then(|schema| {
+            schema
+                .remove("definitions")
+                .or_else(|| schema.remove("$defs"))
+        })
+        .and_then(|definitions| match definitions {
+            Value::Object(definitions) => Some(definitions),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    simplify_schema_inner(schema, &definitions, 0)
+}
+
+fn simplify_schema_inner(schema: Value, definitions: &Map<String, Value>, depth: usize) -> Value {
+    // Recursive types cannot be represented, so stop inlining references at some point
+    const MAX_DEPTH: usize = 32;
+
+    let Value::Object(mut schema) = schema else {
+        return schema;
+    };
+
+    let mut nullable = false;
+
+    // Inline references (wrapped in a single `allOf` when schemars adds a description)
+    let reference = match schema.remove("allOf") {
+        Some(Value::Array(mut variants)) if variants.len() == 1 => variants.pop(),
+        Some(all_of) => {
+            schema.insert("allOf".to_string(), all_of);
+            None
+        }
+        None => None,
+    }
+    .or_else(|| {
+        schema
+            .remove("$ref")
+            .map(|reference| serde_json::json!({ "$ref": reference }))
+    });
+
+    // Unwrap nullable variants, e.g.: `anyOf: [{ "$ref": "..." }, { "type": "null" }]`
+    let reference = reference.or_else(|| match schema.remove("anyOf") {
+        Some(Value::Array(variants)) => {
+            let (nulls, mut variants): (Vec<_>, Vec<_>) = variants
+                .into_iter()
+                .partition(|variant| variant.get("type") == Some(&Value::from("null")));
+            nullable = !nulls.is_empty();
+            if variants.len() == 1 {
+                variants.pop()
+            } else {
+                schema.insert("anyOf".to_string(), Value::Array(variants));
+                None
+            }
+        }
+        _ => None,
+    });
+
+    if let Some(reference) = reference {
+        let resolved = match reference
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.rsplit('/').next())
+            .and_then(|name| definitions.get(name))
+        {
+            Some(definition) if depth < MAX_DEPTH => definition.clone(),
+            Some(_) => serde_json::json!({ "type": "object" }),
+            None => reference,
+        };
+
+        if let Value::Object(resolved) = simplify_schema_inner(resolved, definitions, depth + 1) {
+            // Keys set next to the reference (e.g.: `description`) take precedence
+            for (key, value) in resolved {
+                schema.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    // Unwrap nullable types, e.g.: `type: ["string", "null"]`
+    if let Some(Value::Array(types)) = schema.get("type") {
+        let types = types
+            .iter()
+            .filter(|r#type| r#type.as_str() != Some("null"))
+            .cloned()
+            .collect::<Vec<_>>();
+        if let [r#type] = &types[..] {
+            nullable = true;
+            schema.insert("type".to_string(), r#type.clone());
+        }
+    }
+
+    if nullable {
+        schema.insert("nullable".to_string(), Value::Bool(true));
+    }
+
+    schema
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match (key.as_str(), value) {
+                ("properties", Value::Object(properties)) => Value::Object(
+                    properties
+                        .into_iter()
+                        .map(|(name, property)| {
+                            (name, simplify_schema_inner(property, definitions, depth))
+                        })
+                        .collect(),
+                ),
+                ("items", items) => simplify_schema_inner(items, definitions, depth),
+                (_, value) => value,
+            };
+            (key, value)
+        })
+        .collect()
+}
+
 impl TryFrom<completion::ToolDefinition> for Tool {
     type Error = CompletionError;
 
@@ -187,6 +343,10 @@ impl TryFrom<GenerateContentResponse> for completion::CompletionResponse<Generat
 
         Ok(completion::CompletionResponse {
             choice,
+            usage: response
+                .usage_metadata
+                .as_ref()
+                .map(completion::Usage::from),
             raw_response: response,
         })
     }
@@ -202,7 +362,7 @@ pub mod gemini_api_types {
     use serde_json::Value;
 
     use crate::{
-        completion::CompletionError,
+        completion::{self, CompletionError},
         message::{self, MimeType as _},
         one_or_many::string_or_one_or_many,
         providers::gemini::gemini_api_types::{CodeExecutionResult, ExecutableCode},
@@ -274,7 +434,7 @@ pub mod gemini_api_types {
                 },
                 message::Message::Assistant { content } => Content {
                     role: Some(Role::Model),
-                    parts: content.map(|content| content.into()),
+                    parts: content.try_map(|content| content.try_into())?,
                 },
             })
         }
@@ -479,11 +639,18 @@ pub mod gemini_api_types {
         }
     }
 
-    impl From<message::AssistantContent> for Part {
-        fn from(content: message::AssistantContent) -> Self {
+    impl TryFrom<message::AssistantContent> for Part {
+        type Error = message::MessageError;
+
+        fn try_from(content: message::AssistantContent) -> Result<Self, Self::Error> {
             match content {
-                message::AssistantContent::Text(message::Text { text }) => text.into(),
-                message::AssistantContent::ToolCall(tool_call) => tool_call.into(),
+                message::AssistantContent::Text(message::Text { text }) => Ok(text.into()),
+                message::AssistantContent::ToolCall(tool_call) => Ok(tool_call.into()),
+                message::AssistantContent::Reasoning(_) => {
+                    Err(message::MessageError::ConversionError(
+                        "Reasoning is not supported by Gemini".to_string(),
+                    ))
+                }
             }
         }
     }
@@ -602,10 +769,26 @@ pub mod gemini_api_types {
         pub prompt_token_count: i32,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub cached_content_token_count: Option<i32>,
+        #[serde(default)]
         pub candidates_token_count: i32,
+        #[serde(default)]
+        pub thoughts_token_count: Option<i32>,
         pub total_token_count: i32,
     }
 
+    impl From<&UsageMetadata> for completion::Usage {
+        fn from(usage: &UsageMetadata) -> Self {
+            let reasoning_tokens = usage.thoughts_token_count.unwrap_or_default() as u64;
+            completion::Usage {
+                input_tokens: usage.prompt_token_count as u64,
+                // Thinking tokens are counted separately from the candidates by Gemini
+                output_tokens: usage.candidates_token_count as u64 + reasoning_tokens,
+                cached_input_tokens: usage.cached_content_token_count.unwrap_or_default() as u64,
+                reasoning_tokens,
+            }
+        }
+    }
+
     impl std::fmt::Display for UsageMetadata {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(
@@ -648,7 +831,7 @@ pub mod gemini_api_types {
         ProhibitedContent,
     }
 
-    #[derive(Debug, Deserialize)]
+    #[derive(Debug, Deserialize, Serialize)]
     #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
     pub enum FinishReason {
         /// Default value. This value is unused.
@@ -1137,4 +1320,63 @@ mod tests {
             panic!("Expected function call part");
         }
     }
+
+    #[test]
+    fn test_simplify_schema() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Address {
+            city: String,
+        }
+
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Person {
+            name: Option<String>,
+            /// Where the person lives
+            address: Address,
+            previous_address: Option<Address>,
+        }
+
+        let schema = simplify_schema(json!(schemars::schema_for!(Person)));
+
+        assert!(schema.get("definitions").is_none());
+        assert_eq!(
+            schema["properties"]["name"],
+            json!({ "type": "string", "nullable": true })
+        );
+        assert_eq!(
+            schema["properties"]["address"],
+            json!({
+                "description": "Where the person lives",
+                "type": "object",
+                "properties": { "city": { "type": "string" } },
+                "required": ["city"],
+            })
+        );
+        assert_eq!(schema["properties"]["previous_address"]["nullable"], true);
+        assert_eq!(
+            schema["properties"]["previous_address"]["properties"]["city"],
+            json!({ "type": "string" })
+        );
+
+        let schema: gemini_api_types::Schema = schema.try_into().unwrap();
+        assert_eq!(schema.r#type, "object");
+    }
+
+    #[test]
+    fn test_supports_structured_output() {
+        use crate::completion::CompletionModel as _;
+
+        let client = Client::new("test-key");
+        assert!(client
+            .completion_model(GEMINI_1_5_FLASH)
+            .supports_structured_output());
+        assert!(!client
+            .completion_model(GEMINI_1_0_PRO)
+            .supports_structured_output());
+        assert!(!client
+            .completion_model("gemini-pro")
+            .supports_structured_output());
+    }
 }
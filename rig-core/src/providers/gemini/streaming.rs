@@ -0,0 +1,160 @@
+use async_stream::stream;
+use futures::StreamExt;
+
+use super::completion::{
+    create_request_body,
+    gemini_api_types::{GenerateContentResponse, Part},
+    CompletionModel,
+};
+use crate::completion::{self, CompletionError, CompletionRequest};
+use crate::http_error::HttpStatusError;
+use crate::streaming::{
+    with_final_response, StreamingChoice, StreamingCompletionModel, StreamingResult,
+};
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let request = create_request_body(completion_request)?;
+
+        let response = self
+            .client
+            .post(&format!(
+                "/v1beta/models/{}:streamGenerateContent",
+                self.model
+            ))
+            .query(&[("alt", "sse")])
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(HttpStatusError::from_response(response).await.into());
+        }
+
+        Ok(with_final_response(Box::pin(stream! {
+            let mut stream = response.bytes_stream();
+            // Usage metadata is cumulative, so only the last one received is reported
+            let mut usage = None;
+            // Events can be split across chunks, so only complete lines are parsed
+            let mut buffer: Vec<u8> = vec![];
+
+            'outer: while let Some(chunk_result) = stream.next().await {
+                let chunk = match chunk_result {
+                    Ok(c) => c,
+                    Err(e) => {
+                        yield Err(CompletionError::from(e));
+                        break;
+                    }
+                };
+
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                    let line = match std::str::from_utf8(&line) {
+                        Ok(line) => line,
+                        Err(e) => {
+                            yield Err(CompletionError::ResponseError(e.to_string()));
+                            break 'outer;
+                        }
+                    };
+
+                    let Some(data) = line.trim().strip_prefix("data:") else {
+                        continue;
+                    };
+
+                    match serde_json::from_str::<GenerateContentResponse>(data.trim()) {
+                        Ok(response) => {
+                            if let Some(usage_metadata) = &response.usage_metadata {
+                                usage = Some(completion::Usage::from(usage_metadata));
+                            }
+
+                            for choice in streaming_choices(response) {
+                                yield Ok(choice);
+                            }
+                        }
+                        Err(e) => {
+                            tracing::debug!(target: "rig", "Couldn't parse Gemini stream chunk: {}", e);
+                        }
+                    }
+                }
+            }
+
+            if let Some(usage) = usage {
+                yield Ok(StreamingChoice::Usage(usage));
+            }
+        })))
+    }
+}
+
+/// Convert the first candidate of a streamed response into streaming choices.
+/// Gemini sends function calls whole, so they are forwarded as soon as they arrive.
+fn streaming_choices(response: GenerateContentResponse) -> Vec<StreamingChoice> {
+    let Some(candidate) = response.candidates.into_iter().next() else {
+        return vec![];
+    };
+
+    let mut choices = candidate
+        .content
+        .parts
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Text(text) if !text.is_empty() => Some(StreamingChoice::Message(text)),
+            Part::FunctionCall(function_call) => Some(StreamingChoice::ToolCall(
+                function_call.name.clone(),
+                function_call.name,
+                function_call.args,
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(finish_reason) = candidate
+        .finish_reason
+        .and_then(|reason| serde_json::to_value(reason).ok())
+        .and_then(|reason| reason.as_str().map(str::to_owned))
+    {
+        choices.push(StreamingChoice::FinishReason(finish_reason));
+    }
+
+    choices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_streaming_choices() {
+        let response: GenerateContentResponse = serde_json::from_value(json!({
+            "candidates": [{
+                "content": {
+                    "parts": [
+                        { "text": "Let me check." },
+                        { "functionCall": { "name": "get_weather", "args": { "city": "Paris" } } }
+                    ],
+                    "role": "model"
+                },
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "modelVersion": "gemini-2.0-flash"
+        }))
+        .unwrap();
+
+        match &streaming_choices(response)[..] {
+            [StreamingChoice::Message(text), StreamingChoice::ToolCall(name, id, args), StreamingChoice::FinishReason(reason)] =>
+            {
+                assert_eq!(reason, "STOP");
+                assert_eq!(text, "Let me check.");
+                assert_eq!((name.as_str(), id.as_str()), ("get_weather", "get_weather"));
+                assert_eq!(args, &json!({ "city": "Paris" }));
+            }
+            choices => panic!("Unexpected streaming choices: {:?}", choices),
+        }
+    }
+}
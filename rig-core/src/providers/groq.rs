@@ -8,13 +8,18 @@
 //!
 //! let gpt4o = client.completion_model(groq::GPT_4O);
 //! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, ProviderClient,
+};
 use crate::{
     agent::AgentBuilder,
     completion::{self, CompletionError, CompletionRequest},
     extractor::ExtractorBuilder,
+    http_error::HttpStatusError,
     json_utils,
     message::{self, MessageError},
-    providers::openai::ToolDefinition,
+    providers::openai::{self, ToolDefinition},
+    streaming::{StreamingCompletionModel, StreamingResult},
     OneOrMany,
 };
 use schemars::JsonSchema;
@@ -114,6 +119,24 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(&env_var("GROQ_API_KEY")?))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -200,6 +223,8 @@ impl TryFrom<message::Message> for Message {
                                 "Tool calls do not exist on this message".into(),
                             ))
                         }
+                        // Reasoning is not sent back to the model
+                        message::AssistantContent::Reasoning(_) => {}
                     }
                 }
 
@@ -256,16 +281,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Add preamble to chat history (if available)
         let mut full_history: Vec<Message> = match &completion_request.preamble {
             Some(preamble) => vec![Message {
@@ -305,16 +325,28 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -330,7 +362,23 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        // Groq sends the usage in the `x_groq` field of the last chunk
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+
+        openai::send_compatible_streaming_request(
+            self.client.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
@@ -11,12 +11,18 @@
 //! - DeepSeek
 //! - Azure OpenAI
 //!
+//! Other APIs compatible with the OpenAI chat completions API (e.g.: vLLM, LM Studio,
+//! llama.cpp server or OpenRouter) can be used with the [openai_compatible] client.
+//!
 //! Each provider has its own module, which contains a `Client` implementation that can
 //! be used to initialize completion and embedding models and execute requests to those models.
 //!
 //! The clients also contain methods to easily create higher level AI constructs such as
 //! agents and RAG systems, reducing the need for boilerplate.
 //!
+//! The clients implement the traits of the [client](crate::client) module, which allow the
+//! provider of a model to be selected at runtime.
+//!
 //! # Example
 //! ```
 //! use rig::{providers::openai, agent::AgentBuilder};
@@ -55,6 +61,7 @@ pub mod hyperbolic;
 pub mod moonshot;
 pub mod ollama;
 pub mod openai;
+pub mod openai_compatible;
 pub mod perplexity;
 pub mod together;
 pub mod xai;
@@ -38,15 +38,24 @@
 //! let agent = client.agent("llama3.2");
 //! let extractor = client.extractor::<serde_json::Value>("llama3.2");
 //! ```
+use crate::client::{
+    ClientBuilderError, CompletionClient, CompletionClientDyn, EmbeddingsClient,
+    EmbeddingsClientDyn, ProviderClient,
+};
 use crate::{
     agent::AgentBuilder,
     completion::{self, CompletionError, CompletionRequest},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
-    json_utils, message,
+    http_error::HttpStatusError,
+    json_utils::{self, merge_inplace},
+    message,
     message::{ImageDetail, Text},
+    streaming::{with_final_response, StreamingChoice, StreamingCompletionModel, StreamingResult},
     Embed, OneOrMany,
 };
+use async_stream::stream;
+use futures::StreamExt;
 use reqwest;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -74,6 +83,14 @@ impl Client {
     pub fn new() -> Self {
         Self::from_url(OLLAMA_API_BASE_URL)
     }
+    /// Create a new Ollama client from the `OLLAMA_API_BASE_URL` environment variable, or
+    /// for the local server if the environment variable is not set.
+    pub fn from_env() -> Self {
+        match std::env::var("OLLAMA_API_BASE_URL") {
+            Ok(base_url) => Self::from_url(&base_url),
+            Err(_) => Self::new(),
+        }
+    }
     pub fn from_url(base_url: &str) -> Self {
         Self {
             base_url: base_url.to_owned(),
@@ -109,6 +126,40 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::from_env())
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    fn embedding_model(&self, model: &str) -> EmbeddingModel {
+        self.embedding_model(model)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        self.embedding_model_with_ndims(model, ndims)
+    }
+}
+
 // ---------- API Error and Response Structures ----------
 
 #[derive(Debug, Deserialize)]
@@ -182,20 +233,23 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        self.embed_texts_with_usage(documents)
+            .await
+            .map(|response| response.embeddings)
+    }
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String>,
+    ) -> Result<embeddings::EmbeddingsResponse, EmbeddingError> {
         let docs: Vec<String> = documents.into_iter().collect();
         let payload = json!({
             "model": self.model,
             "input": docs,
         });
-        let response = self
-            .client
-            .post("api/embed")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
+        let response = self.client.post("api/embed").json(&payload).send().await?;
         if response.status().is_success() {
             let api_resp: EmbeddingResponse = response
                 .json()
@@ -206,14 +260,23 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                     "Number of returned embeddings does not match input".into(),
                 ));
             }
-            Ok(api_resp
-                .embeddings
-                .into_iter()
-                .zip(docs.into_iter())
-                .map(|(vec, document)| embeddings::Embedding { document, vec })
-                .collect())
+            let usage = api_resp
+                .prompt_eval_count
+                .map(|input_tokens| completion::Usage {
+                    input_tokens,
+                    ..Default::default()
+                });
+            Ok(embeddings::EmbeddingsResponse {
+                embeddings: api_resp
+                    .embeddings
+                    .into_iter()
+                    .zip(docs.into_iter())
+                    .map(|(vec, document)| embeddings::Embedding { document, vec })
+                    .collect(),
+                usage,
+            })
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -245,6 +308,17 @@ pub struct CompletionResponse {
     #[serde(default)]
     pub eval_duration: Option<u64>,
 }
+impl CompletionResponse {
+    /// Token usage of the request. Only reported once the response is done.
+    fn usage(&self) -> Option<completion::Usage> {
+        self.done.then(|| completion::Usage {
+            input_tokens: self.prompt_eval_count.unwrap_or_default(),
+            output_tokens: self.eval_count.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+}
+
 impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
     type Error = CompletionError;
     fn try_from(resp: CompletionResponse) -> Result<Self, Self::Error> {
@@ -292,6 +366,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 };
                 Ok(completion::CompletionResponse {
                     choice,
+                    usage: raw_response.usage(),
                     raw_response,
                 })
             }
@@ -317,18 +392,11 @@ impl CompletionModel {
             model: model.to_owned(),
         }
     }
-}
-
-// ---------- CompletionModel Implementation ----------
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+    ) -> Result<Value, CompletionError> {
         // Convert internal prompt into a provider Message
         let prompt: Message = completion_request.prompt_with_context().try_into()?;
         let options = if let Some(extra) = completion_request.additional_params {
@@ -354,7 +422,6 @@ impl completion::CompletionModel for CompletionModel {
             "model": self.model,
             "messages": full_history,
             "options": options,
-            "stream": false,
         });
         if !completion_request.tools.is_empty() {
             request_payload["tools"] = json!(completion_request
@@ -363,6 +430,31 @@ impl completion::CompletionModel for CompletionModel {
                 .map(|tool| tool.into())
                 .collect::<Vec<ToolDefinition>>());
         }
+        // Constrain the response to the output schema (if any)
+        if let Some(schema) = completion_request.output_schema {
+            request_payload["format"] = schema;
+        }
+
+        Ok(request_payload)
+    }
+}
+
+// ---------- CompletionModel Implementation ----------
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+        let mut request_payload = self.create_completion_request(completion_request)?;
+        merge_inplace(&mut request_payload, json!({ "stream": false }));
 
         tracing::debug!(target: "rig", "Chat mode payload: {}", request_payload);
         let response = self
@@ -370,25 +462,128 @@ impl completion::CompletionModel for CompletionModel {
             .post("api/chat")
             .json(&request_payload)
             .send()
-            .await
-            .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+            .await?;
         if response.status().is_success() {
-            let text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+            let text = response.text().await?;
             tracing::debug!(target: "rig", "Ollama chat response: {}", text);
             let chat_resp: CompletionResponse = serde_json::from_str(&text)
                 .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
             let conv: completion::CompletionResponse<CompletionResponse> = chat_resp.try_into()?;
             Ok(conv)
         } else {
-            let err_text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
-            Err(CompletionError::ProviderError(err_text))
+            Err(HttpStatusError::from_response(response).await.into())
+        }
+    }
+}
+
+// ---------- StreamingCompletionModel Implementation ----------
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        let mut request_payload = self.create_completion_request(request)?;
+        merge_inplace(&mut request_payload, json!({ "stream": true }));
+
+        let response = self
+            .client
+            .post("api/chat")
+            .json(&request_payload)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(HttpStatusError::from_response(response).await.into());
+        }
+
+        Ok(with_final_response(Box::pin(stream! {
+            let mut stream = response.bytes_stream();
+            // Ollama streams newline-delimited JSON objects which may be split across chunks
+            let mut buffer: Vec<u8> = vec![];
+
+            'outer: while let Some(chunk_result) = stream.next().await {
+                let chunk = match chunk_result {
+                    Ok(c) => c,
+                    Err(e) => {
+                        yield Err(CompletionError::from(e));
+                        break;
+                    }
+                };
+
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                    let line = match std::str::from_utf8(&line) {
+                        Ok(line) => line.trim(),
+                        Err(e) => {
+                            yield Err(CompletionError::ResponseError(e.to_string()));
+                            break 'outer;
+                        }
+                    };
+
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    let chunk = match serde_json::from_str::<ApiResponse<CompletionResponse>>(line) {
+                        Ok(ApiResponse::Ok(chunk)) => chunk,
+                        Ok(ApiResponse::Err(err)) => {
+                            yield Err(CompletionError::ProviderError(err.message));
+                            break 'outer;
+                        }
+                        Err(e) => {
+                            tracing::debug!(target: "rig", "Couldn't parse Ollama stream chunk: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let done = chunk.done;
+                    for choice in chunk.into_streaming_choices() {
+                        yield Ok(choice);
+                    }
+
+                    if done {
+                        break 'outer;
+                    }
+                }
+            }
+        })))
+    }
+}
+
+impl CompletionResponse {
+    /// Split a streamed chat chunk into its text delta and the tool calls it carries.
+    /// Ollama sends tool calls whole, so they can be forwarded as soon as they arrive.
+    /// The last chunk (i.e.: `done`) also carries the finish reason and token counts.
+    fn into_streaming_choices(self) -> Vec<StreamingChoice> {
+        let mut choices = vec![];
+        let usage = self.usage();
+
+        if let Message::Assistant {
+            content,
+            tool_calls,
+            ..
+        } = self.message
+        {
+            if !content.is_empty() {
+                choices.push(StreamingChoice::Message(content));
+            }
+            choices.extend(tool_calls.into_iter().map(|tool_call| {
+                StreamingChoice::ToolCall(
+                    tool_call.function.name.clone(),
+                    tool_call.function.name,
+                    tool_call.function.arguments,
+                )
+            }));
+        }
+
+        if let Some(usage) = usage {
+            if let Some(done_reason) = self.done_reason {
+                choices.push(StreamingChoice::FinishReason(done_reason));
+            }
+            choices.push(StreamingChoice::Usage(usage));
         }
+
+        choices
     }
 }
 
@@ -518,6 +713,8 @@ impl TryFrom<crate::message::Message> for Message {
                                 },
                             });
                         }
+                        // Reasoning is not sent back to the model
+                        crate::message::AssistantContent::Reasoning(_) => {}
                     }
                 }
                 let content_str = texts.join(" ");
@@ -797,4 +994,55 @@ mod tests {
         let params = &ollama_tool.function.parameters;
         assert_eq!(params["properties"]["location"]["type"], "string");
     }
+
+    // Test conversion of streamed /api/chat chunks into streaming choices.
+    #[test]
+    fn test_streaming_chunk_conversion() {
+        let text_chunk: CompletionResponse = serde_json::from_value(json!({
+            "model": "llama3.2",
+            "created_at": "2023-08-04T08:52:19.385406455-07:00",
+            "message": {
+                "role": "assistant",
+                "content": "The"
+            },
+            "done": false
+        }))
+        .expect("Invalid JSON structure");
+        match &text_chunk.into_streaming_choices()[..] {
+            [StreamingChoice::Message(text)] => assert_eq!(text, "The"),
+            choices => panic!("Expected a single text chunk, got {:?}", choices),
+        }
+
+        let tool_chunk: CompletionResponse = serde_json::from_value(json!({
+            "model": "llama3.2",
+            "created_at": "2023-08-04T08:52:19.385406455-07:00",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    {
+                        "function": {
+                            "name": "get_current_weather",
+                            "arguments": { "location": "Paris" }
+                        }
+                    }
+                ]
+            },
+            "done": true,
+            "done_reason": "stop",
+            "eval_count": 12u64
+        }))
+        .expect("Invalid JSON structure");
+        match &tool_chunk.into_streaming_choices()[..] {
+            [StreamingChoice::ToolCall(name, id, args), StreamingChoice::FinishReason(reason), StreamingChoice::Usage(usage)] =>
+            {
+                assert_eq!(reason, "stop");
+                assert_eq!((usage.input_tokens, usage.output_tokens), (0, 12));
+                assert_eq!(name, "get_current_weather");
+                assert_eq!(id, "get_current_weather");
+                assert_eq!(args, &json!({ "location": "Paris" }));
+            }
+            choices => panic!("Expected a single tool call, got {:?}", choices),
+        }
+    }
 }
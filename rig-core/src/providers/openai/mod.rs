@@ -8,6 +8,10 @@
 //!
 //! let gpt4o = client.completion_model(openai::GPT_4O);
 //! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, EmbeddingsClient,
+    EmbeddingsClientDyn, ProviderClient,
+};
 use std::{convert::Infallible, str::FromStr};
 
 use crate::{
@@ -15,6 +19,7 @@ use crate::{
     completion::{self, CompletionError, CompletionRequest},
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     extractor::ExtractorBuilder,
+    http_error::HttpStatusError,
     json_utils,
     message::{self, AudioMediaType, ImageDetail},
     one_or_many::string_or_one_or_many,
@@ -26,6 +31,10 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+pub mod streaming;
+
+pub(crate) use streaming::send_compatible_streaming_request;
+
 // ================================================================
 // Main OpenAI Client
 // ================================================================
@@ -70,7 +79,16 @@ impl Client {
         Self::new(&api_key)
     }
 
-    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+    /// Create a client sending its requests to `base_url` with the given HTTP client, which
+    /// holds the authentication and other default headers.
+    pub(crate) fn from_http_client(base_url: &str, http_client: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            http_client,
+        }
+    }
+
+    pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
         self.http_client.post(url)
     }
@@ -189,6 +207,40 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(&env_var("OPENAI_API_KEY")?))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    fn embedding_model(&self, model: &str) -> EmbeddingModel {
+        self.embedding_model(model)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> EmbeddingModel {
+        self.embedding_model_with_ndims(model, ndims)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -245,6 +297,39 @@ pub struct EmbeddingData {
 pub struct Usage {
     pub prompt_tokens: usize,
     pub total_tokens: usize,
+    #[serde(default)]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    #[serde(default)]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: usize,
+}
+
+impl From<Usage> for completion::Usage {
+    fn from(usage: Usage) -> Self {
+        completion::Usage {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            cached_input_tokens: usage
+                .prompt_tokens_details
+                .map(|details| details.cached_tokens as u64)
+                .unwrap_or_default(),
+            reasoning_tokens: usage
+                .completion_tokens_details
+                .map(|details| details.reasoning_tokens as u64)
+                .unwrap_or_default(),
+        }
+    }
 }
 
 impl std::fmt::Display for Usage {
@@ -274,8 +359,18 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
-        documents: impl IntoIterator<Item = String>,
+        documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        self.embed_texts_with_usage(documents)
+            .await
+            .map(|response| response.embeddings)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_usage(
+        &self,
+        documents: impl IntoIterator<Item = String>,
+    ) -> Result<embeddings::EmbeddingsResponse, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
         let response = self
@@ -302,7 +397,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         ));
                     }
 
-                    Ok(response
+                    let embeddings = response
                         .data
                         .into_iter()
                         .zip(documents.into_iter())
@@ -310,12 +405,17 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                             document,
                             vec: embedding.embedding,
                         })
-                        .collect())
+                        .collect();
+
+                    Ok(embeddings::EmbeddingsResponse {
+                        embeddings,
+                        usage: Some(response.usage.into()),
+                    })
                 }
                 ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -388,9 +488,14 @@ pub const GPT_35_TURBO_INSTRUCT: &str = "gpt-3.5-turbo-instruct";
 
 #[derive(Debug, Deserialize)]
 pub struct CompletionResponse {
+    // Metadata fields are not returned by every OpenAI-compatible API
+    #[serde(default)]
     pub id: String,
+    #[serde(default)]
     pub object: String,
+    #[serde(default)]
     pub created: u64,
+    #[serde(default)]
     pub model: String,
     pub system_fingerprint: Option<String>,
     pub choices: Vec<Choice>,
@@ -415,17 +520,10 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
             Message::Assistant {
                 content,
                 tool_calls,
+                reasoning_content,
                 ..
             } => {
-                let mut content = content
-                    .iter()
-                    .map(|c| match c {
-                        AssistantContent::Text { text } => completion::AssistantContent::text(text),
-                        AssistantContent::Refusal { refusal } => {
-                            completion::AssistantContent::text(refusal)
-                        }
-                    })
-                    .collect::<Vec<_>>();
+                let mut content = assistant_content(content, reasoning_content.as_deref());
 
                 content.extend(
                     tool_calls
@@ -454,6 +552,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 
         Ok(completion::CompletionResponse {
             choice,
+            usage: response.usage.clone().map(completion::Usage::from),
             raw_response: response,
         })
     }
@@ -497,6 +596,10 @@ pub enum Message {
             skip_serializing_if = "Vec::is_empty"
         )]
         tool_calls: Vec<ToolCall>,
+        /// Reasoning of the model, returned by some OpenAI-compatible APIs (e.g.: DeepSeek,
+        /// Groq). It is never sent back, as most of them reject it in requests.
+        #[serde(default, alias = "reasoning", skip_serializing)]
+        reasoning_content: Option<String>,
     },
     #[serde(rename = "tool")]
     ToolResult {
@@ -540,6 +643,56 @@ pub enum AssistantContent {
     Refusal { refusal: String },
 }
 
+/// Convert the content of an assistant message, along with the reasoning of the model. The
+/// reasoning is either returned separately (`reasoning_content`) or at the start of the text
+/// between `<think>` tags, as done by R1 models (e.g.: on Groq or Hyperbolic).
+pub(crate) fn assistant_content(
+    content: &[AssistantContent],
+    reasoning_content: Option<&str>,
+) -> Vec<message::AssistantContent> {
+    let mut reasoning = reasoning_content
+        .filter(|reasoning| !reasoning.is_empty())
+        .map(message::AssistantContent::reasoning)
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut texts = vec![];
+
+    for content in content {
+        match content {
+            AssistantContent::Text { text } => match split_think_tags(text) {
+                (Some(thinking), text) => {
+                    reasoning.push(message::AssistantContent::reasoning(thinking));
+                    if !text.is_empty() {
+                        texts.push(message::AssistantContent::text(text));
+                    }
+                }
+                (None, text) => texts.push(message::AssistantContent::text(text)),
+            },
+            // TODO: Currently, refusals are converted into text, but should be
+            //  investigated for generalization.
+            AssistantContent::Refusal { refusal } => {
+                texts.push(message::AssistantContent::text(refusal))
+            }
+        }
+    }
+
+    reasoning.extend(texts);
+    reasoning
+}
+
+/// Split a text starting with `<think>...</think>` into the reasoning and the answer. The
+/// reasoning runs until the end of the text if the response was cut off while thinking.
+pub(crate) fn split_think_tags(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text.trim_start().strip_prefix("<think>") else {
+        return (None, text);
+    };
+
+    match rest.split_once("</think>") {
+        Some((thinking, answer)) => (Some(thinking.trim()), answer.trim_start()),
+        None => (Some(rest.trim()), ""),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum UserContent {
@@ -709,13 +862,18 @@ impl TryFrom<message::Message> for Vec<Message> {
                         match content {
                             message::AssistantContent::Text(text) => texts.push(text),
                             message::AssistantContent::ToolCall(tool_call) => tools.push(tool_call),
+                            // Reasoning is not sent back to the model
+                            message::AssistantContent::Reasoning(_) => {}
                         }
                         (texts, tools)
                     },
                 );
 
-                // `OneOrMany` ensures at least one `AssistantContent::Text` or `ToolCall` exists,
-                //  so either `content` or `tool_calls` will have some content.
+                // Messages which only contained reasoning have nothing left to send
+                if text_content.is_empty() && tool_calls.is_empty() {
+                    return Ok(vec![]);
+                }
+
                 Ok(vec![Message::Assistant {
                     content: text_content
                         .into_iter()
@@ -728,6 +886,7 @@ impl TryFrom<message::Message> for Vec<Message> {
                         .into_iter()
                         .map(|tool_call| tool_call.into())
                         .collect::<Vec<_>>(),
+                    reasoning_content: None,
                 }])
             }
         }
@@ -770,20 +929,10 @@ impl TryFrom<Message> for message::Message {
             Message::Assistant {
                 content,
                 tool_calls,
+                reasoning_content,
                 ..
             } => {
-                let mut content = content
-                    .into_iter()
-                    .map(|content| match content {
-                        AssistantContent::Text { text } => message::AssistantContent::text(text),
-
-                        // TODO: Currently, refusals are converted into text, but should be
-                        //  investigated for generalization.
-                        AssistantContent::Refusal { refusal } => {
-                            message::AssistantContent::text(refusal)
-                        }
-                    })
-                    .collect::<Vec<_>>();
+                let mut content = assistant_content(&content, reasoning_content.as_deref());
 
                 content.extend(
                     tool_calls
@@ -905,16 +1054,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Add preamble to chat history (if available)
         let mut full_history: Vec<Message> = match &completion_request.preamble {
             Some(preamble) => vec![Message::system(preamble)],
@@ -966,16 +1110,128 @@ impl completion::CompletionModel for CompletionModel {
             request
         };
 
+        // Constrain the response to the output schema (if any)
+        let request = if let Some(schema) = completion_request.output_schema {
+            json_utils::merge(
+                request,
+                json!({
+                    "response_format": json_schema_response_format(schema),
+                }),
+            )
+        } else {
+            request
+        };
+
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
+}
+
+/// Build the `response_format` parameter constraining the response to the JSON schema `schema`.
+/// Strict mode is not enabled since it requires every property of the schema to be required.
+fn json_schema_response_format(schema: serde_json::Value) -> serde_json::Value {
+    let name = schema
+        .get("title")
+        .and_then(|title| title.as_str())
+        .map(|title| {
+            title
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                .take(64)
+                .collect::<String>()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "output".to_string());
+
+    json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": name,
+            "schema": schema,
+            "strict": false,
+        },
+    })
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    fn supports_structured_output(&self) -> bool {
+        // JSON schema response formats are supported since `gpt-4o-2024-08-06` and `o1`, other
+        // models reject them (extraction then falls back to the `submit` tool)
+        const UNSUPPORTED: [&str; 3] = [GPT_4O_2024_05_13, O1_PREVIEW, O1_MINI];
+        const SUPPORTED: [&str; 7] = ["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "o1", "o3", "o4"];
+
+        !UNSUPPORTED
+            .iter()
+            .any(|prefix| self.model.starts_with(prefix))
+            && SUPPORTED
+                .iter()
+                .any(|prefix| self.model.starts_with(prefix))
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        // Model names, which also match their snapshots (i.e.: `<model>-YYYY-MM-DD`). Names
+        // are not matched as prefixes, since newer models extend the names of older ones
+        // (e.g.: `gpt-4.1` and `gpt-4`) but not their context window.
+        const CONTEXT_WINDOWS: [(&str, u64); 19] = [
+            ("o1", 200_000),
+            ("o1-preview", 128_000),
+            ("o1-mini", 128_000),
+            ("o3-mini", 200_000),
+            ("gpt-4o", 128_000),
+            ("gpt-4o-mini", 128_000),
+            ("gpt-4-turbo", 128_000),
+            ("gpt-4-turbo-preview", 128_000),
+            ("gpt-4-0125-preview", 128_000),
+            ("gpt-4-1106-preview", 128_000),
+            ("gpt-4-vision-preview", 128_000),
+            ("gpt-4-1106-vision-preview", 128_000),
+            ("gpt-4", 8_192),
+            ("gpt-4-0613", 8_192),
+            ("gpt-4-0314", 8_192),
+            ("gpt-4-32k", 32_768),
+            ("gpt-4-32k-0613", 32_768),
+            ("gpt-3.5-turbo", 16_385),
+            ("gpt-3.5-turbo-instruct", 4_096),
+        ];
+
+        let is_snapshot_of = |name: &str| {
+            self.model
+                .strip_prefix(name)
+                .and_then(|suffix| suffix.strip_prefix('-'))
+                .is_some_and(|date| {
+                    date.len() == 10
+                        && date.chars().enumerate().all(|(i, c)| {
+                            if i == 4 || i == 7 {
+                                c == '-'
+                            } else {
+                                c.is_ascii_digit()
+                            }
+                        })
+                })
+        };
+
+        CONTEXT_WINDOWS
+            .iter()
+            .find(|(name, _)| self.model == *name || is_snapshot_of(name))
+            .map(|(_, tokens)| *tokens)
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(params) = completion_request.additional_params {
-                    json_utils::merge(request, params)
-                } else {
-                    request
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -994,7 +1250,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -1093,7 +1349,7 @@ impl transcription::TranscriptionModel for TranscriptionModel {
                 )),
             }
         } else {
-            Err(TranscriptionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -1357,6 +1613,7 @@ mod tests {
             audio: None,
             name: None,
             tool_calls: vec![],
+            reasoning_content: None,
         };
 
         let converted_user_message: message::Message = user_message.clone().try_into().unwrap();
@@ -1387,4 +1644,110 @@ mod tests {
         assert_eq!(original_user_message[0], user_message);
         assert_eq!(original_assistant_message[0], assistant_message);
     }
+
+    #[test]
+    fn test_usage_conversion() {
+        let usage: Usage = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 1200,
+            "completion_tokens": 300,
+            "total_tokens": 1500,
+            "prompt_tokens_details": { "cached_tokens": 1024 },
+            "completion_tokens_details": { "reasoning_tokens": 256 }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            completion::Usage::from(usage),
+            completion::Usage {
+                input_tokens: 1200,
+                output_tokens: 300,
+                cached_input_tokens: 1024,
+                reasoning_tokens: 256,
+            }
+        );
+    }
+
+    #[test]
+    fn test_think_tags() {
+        assert_eq!(
+            split_think_tags("<think>\nOkay, the user greets me.\n</think>\n\nHello!"),
+            (Some("Okay, the user greets me."), "Hello!")
+        );
+        assert_eq!(split_think_tags("<think>Cut off"), (Some("Cut off"), ""));
+        assert_eq!(split_think_tags("Hello <think>"), (None, "Hello <think>"));
+
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "role": "assistant",
+            "content": "<think>Greeting</think>Hello!"
+        }))
+        .unwrap();
+        let message: message::Message = message.try_into().unwrap();
+        assert_eq!(
+            message,
+            message::Message::Assistant {
+                content: OneOrMany::many(vec![
+                    message::AssistantContent::reasoning("Greeting"),
+                    message::AssistantContent::text("Hello!"),
+                ])
+                .unwrap()
+            }
+        );
+
+        // Reasoning is not sent back
+        let messages: Vec<Message> = message.try_into().unwrap();
+        assert_eq!(
+            messages,
+            vec![Message::Assistant {
+                content: vec!["Hello!".to_string().into()],
+                refusal: None,
+                audio: None,
+                name: None,
+                tool_calls: vec![],
+                reasoning_content: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_supports_structured_output() {
+        use crate::completion::CompletionModel as _;
+
+        let client = Client::new("test-key");
+        for model in [GPT_4O, GPT_4O_MINI, O1, O3_MINI, "gpt-4.1-mini"] {
+            assert!(client.completion_model(model).supports_structured_output());
+        }
+        for model in [
+            GPT_4O_2024_05_13,
+            O1_PREVIEW,
+            O1_MINI,
+            GPT_4_TURBO,
+            GPT_4,
+            GPT_35_TURBO,
+        ] {
+            assert!(!client.completion_model(model).supports_structured_output());
+        }
+    }
+
+    #[test]
+    fn test_context_window() {
+        use crate::completion::CompletionModel as _;
+
+        let client = Client::new("test-key");
+        let context_window = |model| client.completion_model(model).context_window();
+
+        assert_eq!(context_window(GPT_4O), Some(128_000));
+        assert_eq!(context_window("gpt-4o-2024-08-06"), Some(128_000));
+        assert_eq!(context_window(GPT_4O_MINI), Some(128_000));
+        assert_eq!(context_window(GPT_4_TURBO_2024_04_09), Some(128_000));
+        assert_eq!(context_window(O1_PREVIEW_2024_09_12), Some(128_000));
+        assert_eq!(context_window(O3_MINI_2025_01_31), Some(200_000));
+        assert_eq!(context_window(GPT_4), Some(8_192));
+        assert_eq!(context_window(GPT_4_0613), Some(8_192));
+        assert_eq!(context_window(GPT_4_32K_0613), Some(32_768));
+
+        // Unknown models
+        assert_eq!(context_window("gpt-4.1"), None);
+        assert_eq!(context_window("gpt-4.5-preview"), None);
+        assert_eq!(context_window("gpt-4o-audio-preview"), None);
+    }
 }
@@ -0,0 +1,530 @@
+use std::collections::BTreeMap;
+
+use async_stream::stream;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{CompletionModel, Usage};
+use crate::completion::{CompletionError, CompletionRequest};
+use crate::http_error::HttpStatusError;
+use crate::json_utils::{self, merge_inplace};
+use crate::message::Reasoning;
+use crate::streaming::{
+    with_final_response, StreamingChoice, StreamingCompletionModel, StreamingResult,
+};
+
+// ================================================================
+// OpenAI Streaming API
+// ================================================================
+#[derive(Debug, Deserialize)]
+pub struct StreamingCompletionChunk {
+    #[serde(default)]
+    pub choices: Vec<StreamingChoiceChunk>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    /// Groq metadata, whose last chunk has the usage of the request
+    #[serde(default)]
+    pub x_groq: Option<GroqMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GroqMetadata {
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamingChoiceChunk {
+    pub delta: StreamingDelta,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    /// Usage sent with the finished choice by some OpenAI-compatible APIs (e.g.: Moonshot)
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamingDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Reasoning of the model, sent by some OpenAI-compatible APIs (e.g.: DeepSeek, Groq)
+    #[serde(default, alias = "reasoning")]
+    pub reasoning_content: Option<String>,
+    #[serde(default, deserialize_with = "json_utils::null_or_vec")]
+    pub tool_calls: Vec<StreamingToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamingToolCall {
+    #[serde(default)]
+    pub index: usize,
+    #[serde(default)]
+    pub id: Option<String>,
+    pub function: StreamingFunction,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamingFunction {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[derive(Default)]
+struct ToolCallState {
+    name: String,
+    id: String,
+    arguments: String,
+}
+
+const THINK_START: &str = "<think>";
+const THINK_END: &str = "</think>";
+
+/// Position of the stream relative to the `<think>` tags R1 models start their response with
+#[derive(Default)]
+enum ThinkState {
+    /// Nothing but whitespace or the start of a `<think>` tag was received yet
+    #[default]
+    Start,
+    /// Inside the `<think>` tags
+    Thinking,
+    /// After the `</think>` tag, until the answer starts
+    AfterThinking,
+    /// Answering, i.e.: text is forwarded as is
+    Answering,
+}
+
+/// Accumulates the chunks of an OpenAI-compatible SSE stream. Text deltas are forwarded as
+/// they arrive while tool call deltas are assembled (by index) until the choice is finished.
+/// Text between the `<think>` tags the response starts with is forwarded as reasoning.
+#[derive(Default)]
+struct StreamingState {
+    tool_calls: BTreeMap<usize, ToolCallState>,
+    think: ThinkState,
+    /// Text held back until it is known whether it is part of a `<think>` tag
+    pending: String,
+}
+
+impl StreamingState {
+    fn process_chunk(
+        &mut self,
+        chunk: StreamingCompletionChunk,
+    ) -> Vec<Result<StreamingChoice, CompletionError>> {
+        let mut items = vec![];
+
+        let mut usage = chunk
+            .usage
+            .or_else(|| chunk.x_groq.and_then(|metadata| metadata.usage));
+
+        for choice in chunk.choices {
+            usage = usage.or(choice.usage);
+
+            if let Some(reasoning) = choice.delta.reasoning_content {
+                if !reasoning.is_empty() {
+                    items.push(Ok(StreamingChoice::Reasoning(Reasoning::new(reasoning))));
+                }
+            }
+
+            if let Some(content) = choice.delta.content {
+                items.extend(self.process_content(content).into_iter().map(Ok));
+            }
+
+            for tool_call in choice.delta.tool_calls {
+                let state = self.tool_calls.entry(tool_call.index).or_default();
+                if let Some(id) = tool_call.id {
+                    state.id = id;
+                }
+                if let Some(name) = tool_call.function.name {
+                    state.name = name;
+                }
+                if let Some(arguments) = tool_call.function.arguments {
+                    if !arguments.is_empty() {
+                        state.arguments.push_str(&arguments);
+                        items.push(Ok(StreamingChoice::ToolCallDelta(
+                            state.id.clone(),
+                            arguments,
+                        )));
+                    }
+                }
+            }
+
+            if let Some(finish_reason) = choice.finish_reason {
+                items.extend(self.finish());
+                items.push(Ok(StreamingChoice::FinishReason(finish_reason)));
+            }
+        }
+
+        // Usage is sent in a last chunk without choices (if requested with `stream_options`)
+        if let Some(usage) = usage {
+            items.push(Ok(StreamingChoice::Usage(usage.into())));
+        }
+
+        items
+    }
+
+    /// Split the text deltas into reasoning (between the `<think>` tags the response starts
+    /// with) and text, holding back the text which could be part of a tag.
+    fn process_content(&mut self, content: String) -> Vec<StreamingChoice> {
+        let mut items = vec![];
+        self.pending.push_str(&content);
+
+        loop {
+            match self.think {
+                ThinkState::Start => {
+                    let text = self.pending.trim_start();
+                    if let Some(rest) = text.strip_prefix(THINK_START) {
+                        self.pending = rest.to_string();
+                        self.think = ThinkState::Thinking;
+                    } else if THINK_START.starts_with(text) {
+                        break;
+                    } else {
+                        self.think = ThinkState::Answering;
+                    }
+                }
+                ThinkState::Thinking => match self.pending.split_once(THINK_END) {
+                    Some((reasoning, text)) => {
+                        if !reasoning.is_empty() {
+                            items.push(StreamingChoice::Reasoning(Reasoning::new(reasoning)));
+                        }
+                        self.pending = text.to_string();
+                        self.think = ThinkState::AfterThinking;
+                    }
+                    None => {
+                        // Hold back the end of the reasoning if it could be the start of `</think>`
+                        let held = (1..THINK_END.len())
+                            .rev()
+                            .find(|len| self.pending.ends_with(&THINK_END[..*len]))
+                            .unwrap_or_default();
+                        let reasoning = self
+                            .pending
+                            .drain(..self.pending.len() - held)
+                            .collect::<String>();
+                        if !reasoning.is_empty() {
+                            items.push(StreamingChoice::Reasoning(Reasoning::new(reasoning)));
+                        }
+                        break;
+                    }
+                },
+                ThinkState::AfterThinking => {
+                    self.pending = self.pending.trim_start().to_string();
+                    if self.pending.is_empty() {
+                        break;
+                    }
+                    self.think = ThinkState::Answering;
+                }
+                ThinkState::Answering => {
+                    if !self.pending.is_empty() {
+                        items.push(StreamingChoice::Message(std::mem::take(&mut self.pending)));
+                    }
+                    break;
+                }
+            }
+        }
+
+        items
+    }
+
+    /// Flush the text held back and the tool calls assembled so far, in the order they
+    /// were started
+    fn finish(&mut self) -> Vec<Result<StreamingChoice, CompletionError>> {
+        let pending = std::mem::take(&mut self.pending);
+        let pending = match self.think {
+            _ if pending.is_empty() => None,
+            ThinkState::Thinking => Some(StreamingChoice::Reasoning(Reasoning::new(pending))),
+            _ => Some(StreamingChoice::Message(pending)),
+        };
+
+        pending
+            .map(Ok)
+            .into_iter()
+            .chain(
+                std::mem::take(&mut self.tool_calls)
+                    .into_values()
+                    .map(|tool_call| {
+                        let arguments = if tool_call.arguments.is_empty() {
+                            "{}"
+                        } else {
+                            &tool_call.arguments
+                        };
+
+                        Ok(StreamingChoice::ToolCall(
+                            tool_call.name,
+                            tool_call.id,
+                            serde_json::from_str(arguments)?,
+                        ))
+                    }),
+            )
+            .collect()
+    }
+}
+
+/// Send a streaming chat completion request to an OpenAI-compatible endpoint and parse the
+/// server-sent events of the response. The request body must have `"stream": true` set.
+pub(crate) async fn send_compatible_streaming_request(
+    request_builder: reqwest::RequestBuilder,
+) -> Result<StreamingResult, CompletionError> {
+    let response = request_builder.send().await?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response).await.into());
+    }
+
+    Ok(with_final_response(Box::pin(stream! {
+        let mut state = StreamingState::default();
+        let mut stream = response.bytes_stream();
+        // Events can be split across chunks, so only complete lines are parsed
+        let mut buffer: Vec<u8> = vec![];
+
+        'outer: while let Some(chunk_result) = stream.next().await {
+            let chunk = match chunk_result {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(CompletionError::from(e));
+                    break;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                let line = match String::from_utf8(line) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        yield Err(CompletionError::ResponseError(e.to_string()));
+                        break 'outer;
+                    }
+                };
+
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+
+                if data == "[DONE]" {
+                    break 'outer;
+                }
+
+                match serde_json::from_str::<StreamingCompletionChunk>(data) {
+                    Ok(chunk) => {
+                        for item in state.process_chunk(chunk) {
+                            yield item;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::debug!(target: "rig", "Couldn't parse SSE payload as a chunk: {}", e);
+                    }
+                }
+            }
+        }
+
+        for item in state.finish() {
+            yield item;
+        }
+    })))
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        send_compatible_streaming_request(self.client.post("/chat/completions").json(&request))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(data: serde_json::Value) -> StreamingCompletionChunk {
+        serde_json::from_value(data).unwrap()
+    }
+
+    #[test]
+    fn test_text_deltas() {
+        let mut state = StreamingState::default();
+
+        let items = state.process_chunk(chunk(json!({
+            "choices": [{ "index": 0, "delta": { "role": "assistant", "content": "Hello" }, "finish_reason": null }]
+        })));
+
+        assert!(matches!(&items[..], [Ok(StreamingChoice::Message(text))] if text == "Hello"));
+    }
+
+    #[test]
+    fn test_tool_call_argument_assembly() {
+        let mut state = StreamingState::default();
+
+        let chunks = [
+            json!({
+                "choices": [{ "index": 0, "delta": { "tool_calls": [
+                    { "index": 0, "id": "call_1", "type": "function", "function": { "name": "add", "arguments": "" } },
+                ] } }]
+            }),
+            json!({
+                "choices": [{ "index": 0, "delta": { "tool_calls": [
+                    { "index": 0, "function": { "arguments": "{\"x\": 2," } },
+                    { "index": 1, "id": "call_2", "type": "function", "function": { "name": "subtract", "arguments": "{}" } },
+                ] } }]
+            }),
+            json!({
+                "choices": [{ "index": 0, "delta": { "tool_calls": [
+                    { "index": 0, "function": { "arguments": " \"y\": 5}" } },
+                ] } }]
+            }),
+        ];
+
+        let deltas = chunks
+            .into_iter()
+            .flat_map(|data| state.process_chunk(chunk(data)))
+            .map(|item| match item {
+                Ok(StreamingChoice::ToolCallDelta(id, delta)) => (id, delta),
+                _ => panic!("Expected a tool call delta, got {:?}", item),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deltas,
+            vec![
+                ("call_1".to_string(), "{\"x\": 2,".to_string()),
+                ("call_2".to_string(), "{}".to_string()),
+                ("call_1".to_string(), " \"y\": 5}".to_string()),
+            ]
+        );
+
+        let items = state.process_chunk(chunk(json!({
+            "choices": [{ "index": 0, "delta": {}, "finish_reason": "tool_calls" }]
+        })));
+
+        match &items[..] {
+            [Ok(StreamingChoice::ToolCall(name1, id1, args1)), Ok(StreamingChoice::ToolCall(name2, id2, args2)), Ok(StreamingChoice::FinishReason(reason))] =>
+            {
+                assert_eq!(reason, "tool_calls");
+                assert_eq!((name1.as_str(), id1.as_str()), ("add", "call_1"));
+                assert_eq!(args1, &json!({"x": 2, "y": 5}));
+                assert_eq!((name2.as_str(), id2.as_str()), ("subtract", "call_2"));
+                assert_eq!(args2, &json!({}));
+            }
+            _ => panic!("Expected two tool calls, got {:?}", items),
+        }
+
+        assert!(state.finish().is_empty());
+    }
+
+    #[test]
+    fn test_reasoning_deltas() {
+        let mut state = StreamingState::default();
+
+        let items = state.process_chunk(chunk(json!({
+            "choices": [{ "index": 0, "delta": { "content": null, "reasoning_content": "Hmm" } }]
+        })));
+
+        assert!(
+            matches!(&items[..], [Ok(StreamingChoice::Reasoning(reasoning))] if reasoning.reasoning == "Hmm")
+        );
+    }
+
+    #[test]
+    fn test_think_tags() {
+        let mut state = StreamingState::default();
+
+        let mut items = [
+            "\n<th",
+            "ink>\nOkay, ",
+            "the user</th",
+            "ink>",
+            "\n\n",
+            "Hello",
+            "</think>",
+        ]
+        .into_iter()
+        .flat_map(|content| {
+            state.process_chunk(chunk(json!({
+                "choices": [{ "index": 0, "delta": { "content": content } }]
+            })))
+        })
+        .collect::<Vec<_>>();
+        items.extend(state.finish());
+
+        let items = items
+            .into_iter()
+            .map(|item| match item {
+                Ok(StreamingChoice::Reasoning(reasoning)) => {
+                    format!("reasoning: {}", reasoning.reasoning)
+                }
+                Ok(StreamingChoice::Message(text)) => format!("text: {}", text),
+                _ => panic!("Expected reasoning or text, got {:?}", item),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            items,
+            vec![
+                "reasoning: \nOkay, ",
+                "reasoning: the user",
+                "text: Hello",
+                "text: </think>",
+            ]
+        );
+
+        // Responses without `<think>` tags are forwarded as is
+        let mut state = StreamingState::default();
+        let items = state.process_chunk(chunk(json!({
+            "choices": [{ "index": 0, "delta": { "content": "<b>Hi</b>" } }]
+        })));
+        assert!(matches!(&items[..], [Ok(StreamingChoice::Message(text))] if text == "<b>Hi</b>"));
+    }
+
+    #[test]
+    fn test_usage_chunk() {
+        let mut state = StreamingState::default();
+
+        let items = state.process_chunk(chunk(json!({
+            "choices": [],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 30, "total_tokens": 42 }
+        })));
+
+        match &items[..] {
+            [Ok(StreamingChoice::Usage(usage))] => {
+                assert_eq!((usage.input_tokens, usage.output_tokens), (12, 30));
+            }
+            _ => panic!("Expected usage, got {:?}", items),
+        }
+
+        // Groq
+        let items = state.process_chunk(chunk(json!({
+            "choices": [],
+            "x_groq": {
+                "id": "req_1",
+                "usage": { "prompt_tokens": 5, "completion_tokens": 7, "total_tokens": 12 }
+            }
+        })));
+        assert!(
+            matches!(&items[..], [Ok(StreamingChoice::Usage(usage))] if usage.output_tokens == 7)
+        );
+
+        // Moonshot
+        let items = state.process_chunk(chunk(json!({
+            "choices": [{
+                "index": 0,
+                "delta": {},
+                "finish_reason": "stop",
+                "usage": { "prompt_tokens": 5, "completion_tokens": 9, "total_tokens": 14 }
+            }]
+        })));
+        assert!(matches!(
+            &items[..],
+            [Ok(StreamingChoice::FinishReason(_)), Ok(StreamingChoice::Usage(usage))]
+                if usage.output_tokens == 9
+        ));
+    }
+}
@@ -0,0 +1,610 @@
+//! Client for any API compatible with the OpenAI chat completions API, such as self-hosted
+//! servers (vLLM, LM Studio, llama.cpp server), routers (OpenRouter) or internal gateways.
+//!
+//! Requests and responses use the types of the [openai] provider, so tools, images, structured
+//! output and streaming work as with OpenAI. Since not every server supports every feature,
+//! the [Capabilities] of the models can be restricted.
+//!
+//! # Example
+//! ```
+//! use rig::providers::openai_compatible::{self, Capabilities};
+//!
+//! // A local llama.cpp server, without authentication nor tool support
+//! let llama_cpp = openai_compatible::ClientBuilder::new("http://localhost:8080/v1")
+//!     .capabilities(Capabilities {
+//!         tools: false,
+//!         ..Default::default()
+//!     })
+//!     .build();
+//!
+//! let agent = llama_cpp.agent("qwen2.5-7b-instruct")
+//!     .preamble("You are a helpful assistant.")
+//!     .build();
+//!
+//! // OpenRouter, with its (optional) app attribution headers
+//! let openrouter = openai_compatible::ClientBuilder::new("https://openrouter.ai/api/v1")
+//!     .api_key("your-openrouter-api-key")
+//!     .header("HTTP-Referer", "https://example.com")
+//!     .header("X-Title", "My app")
+//!     .build();
+//!
+//! let claude = openrouter
+//!     .completion_model("anthropic/claude-3.5-sonnet")
+//!     .with_context_window(200_000);
+//! ```
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, EmbeddingsClient,
+    EmbeddingsClientDyn, ProviderClient,
+};
+use crate::{
+    agent::AgentBuilder,
+    completion::{self, CompletionError, CompletionRequest},
+    embeddings::EmbeddingsBuilder,
+    extractor::ExtractorBuilder,
+    http_error::HttpStatusError,
+    json_utils,
+    message::{Message, UserContent},
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
+    Embed,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+// ================================================================
+// Main OpenAI-compatible Client
+// ================================================================
+
+/// How the API key is sent to the API
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuthStyle<'a> {
+    /// `Authorization: Bearer <api key>`, as expected by most APIs
+    #[default]
+    Bearer,
+    /// The API key as is, in the given header (e.g.: `api-key` or `x-api-key`)
+    Header(&'a str),
+}
+
+/// Features supported by the models of an API. Requests using a feature which is not
+/// supported fail with a [CompletionError::RequestError] before being sent, instead of
+/// failing with an error from the API (or being silently ignored by it).
+///
+/// Defaults to the features of the OpenAI API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Tool calling
+    pub tools: bool,
+    /// Image content in user messages
+    pub images: bool,
+    /// Constraining the response to a JSON schema (`response_format`). Without it, the output
+    /// schema of requests is ignored and extractors fall back to tool calls.
+    pub structured_output: bool,
+    /// Token usage reported at the end of streamed responses (`stream_options`)
+    pub stream_usage: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            images: true,
+            structured_output: true,
+            stream_usage: true,
+        }
+    }
+}
+
+/// Create a client for an OpenAI-compatible API using the builder
+///
+/// # Example
+/// ```
+/// use rig::providers::openai_compatible::{AuthStyle, ClientBuilder};
+///
+/// // An internal gateway expecting the API key in an `api-key` header
+/// let client = ClientBuilder::new("https://llm-gateway.internal/v1")
+///     .api_key("your-gateway-api-key")
+///     .auth_style(AuthStyle::Header("api-key"))
+///     .header("x-team", "search")
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct ClientBuilder<'a> {
+    base_url: &'a str,
+    api_key: Option<&'a str>,
+    auth_style: AuthStyle<'a>,
+    headers: Vec<(&'a str, &'a str)>,
+    capabilities: Capabilities,
+}
+
+impl<'a> ClientBuilder<'a> {
+    /// Create a builder for the API at `base_url`, i.e.: the URL the `/chat/completions`
+    /// path is appended to (e.g.: `http://localhost:8000/v1`).
+    pub fn new(base_url: &'a str) -> Self {
+        Self {
+            base_url,
+            api_key: None,
+            auth_style: AuthStyle::default(),
+            headers: vec![],
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    /// Set the API key. Requests are not authenticated if no API key is set, as is common
+    /// for local servers.
+    pub fn api_key(mut self, api_key: &'a str) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Set how the API key is sent to the API. Defaults to [AuthStyle::Bearer].
+    pub fn auth_style(mut self, auth_style: AuthStyle<'a>) -> Self {
+        self.auth_style = auth_style;
+        self
+    }
+
+    /// Add a header sent with every request
+    pub fn header(mut self, name: &'a str, value: &'a str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Set the features supported by the models of the API (see [Capabilities]). Can be
+    /// overridden for specific models with [CompletionModel::with_capabilities].
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Build the client.
+    ///
+    /// Panics if the client cannot be built, see [ClientBuilder::try_build].
+    pub fn build(self) -> Client {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Build the client. Fails with [ClientBuilderError::InvalidConfig]:
+    /// - If the API key or a header cannot be parsed as a header value.
+    /// - If the reqwest client cannot be built (if the TLS backend cannot be initialized).
+    pub fn try_build(self) -> Result<Client, ClientBuilderError> {
+        fn header_name(name: &str) -> Result<reqwest::header::HeaderName, ClientBuilderError> {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                ClientBuilderError::InvalidConfig(format!("Invalid header name {name:?}"))
+            })
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+
+        if let Some(api_key) = self.api_key {
+            let (name, value) = match self.auth_style {
+                AuthStyle::Bearer => ("Authorization", format!("Bearer {}", api_key)),
+                AuthStyle::Header(name) => (name, api_key.to_string()),
+            };
+            // The API key is not included in the error, so that it doesn't end up in logs
+            let value = value.parse().map_err(|_| {
+                ClientBuilderError::InvalidConfig("API key is not a valid header value".into())
+            })?;
+            headers.insert(header_name(name)?, value);
+        }
+
+        for (name, value) in self.headers {
+            let value = value.parse().map_err(|_| {
+                ClientBuilderError::InvalidConfig(format!(
+                    "Value of header {name:?} is not a valid header value"
+                ))
+            })?;
+            headers.insert(header_name(name)?, value);
+        }
+
+        let http_client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|err| ClientBuilderError::InvalidConfig(err.to_string()))?;
+
+        Ok(Client {
+            inner: openai::Client::from_http_client(self.base_url, http_client),
+            capabilities: self.capabilities,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    inner: openai::Client,
+    capabilities: Capabilities,
+}
+
+impl Client {
+    /// Create a new client for the API at `base_url`, authenticated with the given API key
+    /// as a bearer token. Use the [ClientBuilder] for other settings.
+    pub fn new(base_url: &str, api_key: &str) -> Self {
+        ClientBuilder::new(base_url).api_key(api_key).build()
+    }
+
+    /// Create a new client from the `OPENAI_COMPATIBLE_BASE_URL` and (optional)
+    /// `OPENAI_COMPATIBLE_API_KEY` environment variables.
+    /// Panics if the base URL environment variable is not set or if the API key is invalid.
+    pub fn from_env() -> Self {
+        <Self as ProviderClient>::from_env()
+    }
+
+    /// Create a completion model with the given name.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::openai_compatible::Client;
+    ///
+    /// // Initialize a client for a vLLM server
+    /// let vllm = Client::new("http://localhost:8000/v1", "your-vllm-api-key");
+    ///
+    /// let qwen = vllm.completion_model("Qwen/Qwen2.5-7B-Instruct");
+    /// ```
+    pub fn completion_model(&self, model: &str) -> CompletionModel {
+        CompletionModel::new(self.clone(), model)
+    }
+
+    /// Create an embedding model with the given name and the number of dimensions of the
+    /// embeddings it generates.
+    pub fn embedding_model(&self, model: &str, ndims: usize) -> openai::EmbeddingModel {
+        openai::EmbeddingModel::new(self.inner.clone(), model, ndims)
+    }
+
+    /// Create an embedding builder with the given embedding model.
+    pub fn embeddings<D: Embed>(
+        &self,
+        model: &str,
+        ndims: usize,
+    ) -> EmbeddingsBuilder<openai::EmbeddingModel, D> {
+        EmbeddingsBuilder::new(self.embedding_model(model, ndims))
+    }
+
+    /// Create an agent builder with the given completion model.
+    pub fn agent(&self, model: &str) -> AgentBuilder<CompletionModel> {
+        AgentBuilder::new(self.completion_model(model))
+    }
+
+    /// Create an extractor builder with the given completion model.
+    pub fn extractor<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync>(
+        &self,
+        model: &str,
+    ) -> ExtractorBuilder<T, CompletionModel> {
+        ExtractorBuilder::new(self.completion_model(model))
+    }
+}
+
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        let base_url = env_var("OPENAI_COMPATIBLE_BASE_URL")?;
+        let api_key = std::env::var("OPENAI_COMPATIBLE_API_KEY").ok();
+
+        let builder = ClientBuilder::new(&base_url);
+        match &api_key {
+            Some(api_key) => builder.api_key(api_key).try_build(),
+            None => builder.try_build(),
+        }
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+
+    fn as_embeddings(&self) -> Option<&dyn EmbeddingsClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = openai::EmbeddingModel;
+
+    fn embedding_model(&self, model: &str) -> openai::EmbeddingModel {
+        self.inner.embedding_model(model)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> openai::EmbeddingModel {
+        self.embedding_model(model, ndims)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+/// Errors are either returned in the format of the OpenAI API or as a bare error object
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ApiErrorResponse {
+    Wrapped { error: ApiError },
+    Bare(ApiError),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ApiResponse<T> {
+    Ok(T),
+    Err(ApiErrorResponse),
+}
+
+impl From<ApiErrorResponse> for CompletionError {
+    fn from(err: ApiErrorResponse) -> Self {
+        match err {
+            ApiErrorResponse::Wrapped { error } | ApiErrorResponse::Bare(error) => {
+                CompletionError::ProviderError(error.message)
+            }
+        }
+    }
+}
+
+// ================================================================
+// OpenAI-compatible Completion API
+// ================================================================
+#[derive(Clone)]
+pub struct CompletionModel {
+    client: Client,
+    /// Name of the model (e.g.: Qwen/Qwen2.5-7B-Instruct)
+    pub model: String,
+    capabilities: Capabilities,
+    context_window: Option<u64>,
+}
+
+impl CompletionModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            capabilities: client.capabilities,
+            client,
+            model: model.to_string(),
+            context_window: None,
+        }
+    }
+
+    /// Set the features supported by the model, instead of the ones of the client
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Set the context window of the model, in tokens (see
+    /// [CompletionModel::context_window](completion::CompletionModel::context_window)).
+    /// Unknown by default.
+    pub fn with_context_window(mut self, tokens: u64) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    pub(crate) fn create_completion_request(
+        &self,
+        mut completion_request: CompletionRequest,
+    ) -> Result<serde_json::Value, CompletionError> {
+        if !self.capabilities.tools && !completion_request.tools.is_empty() {
+            return Err(CompletionError::RequestError(
+                format!("Model {} does not support tools", self.model).into(),
+            ));
+        }
+
+        let has_images = |message: &Message| match message {
+            Message::User { content } => content
+                .iter()
+                .any(|content| matches!(content, UserContent::Image(_))),
+            Message::Assistant { .. } => false,
+        };
+        if !self.capabilities.images
+            && (has_images(&completion_request.prompt)
+                || completion_request.chat_history.iter().any(has_images))
+        {
+            return Err(CompletionError::RequestError(
+                format!("Model {} does not support images", self.model).into(),
+            ));
+        }
+
+        if !self.capabilities.structured_output {
+            completion_request.output_schema = None;
+        }
+
+        // Messages, tools and structured output are sent as to the OpenAI API
+        openai::CompletionModel::new(self.client.inner.clone(), &self.model)
+            .create_completion_request(completion_request)
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = openai::CompletionResponse;
+
+    fn supports_structured_output(&self) -> bool {
+        self.capabilities.structured_output
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        self.context_window
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+
+        let response = self
+            .client
+            .inner
+            .post("/chat/completions")
+            .json(&request)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            let t = response.text().await?;
+            tracing::debug!(target: "rig", "OpenAI-compatible completion response: {}", t);
+
+            match serde_json::from_str::<ApiResponse<openai::CompletionResponse>>(&t)? {
+                ApiResponse::Ok(response) => {
+                    tracing::info!(target: "rig",
+                        "OpenAI-compatible completion token usage: {:?}",
+                        response.usage.clone().map(|usage| format!("{usage}")).unwrap_or("N/A".to_string())
+                    );
+                    response.try_into()
+                }
+                ApiResponse::Err(err) => Err(err.into()),
+            }
+        } else {
+            Err(HttpStatusError::from_response(response).await.into())
+        }
+    }
+}
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+        if self.capabilities.stream_usage {
+            json_utils::merge_inplace(
+                &mut request,
+                json!({ "stream_options": { "include_usage": true } }),
+            );
+        }
+
+        openai::send_compatible_streaming_request(
+            self.client.inner.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::CompletionModel as _;
+
+    fn request(tools: bool, image: bool) -> CompletionRequest {
+        let prompt = if image {
+            Message::User {
+                content: crate::OneOrMany::one(UserContent::image("aGVsbG8=", None, None, None)),
+            }
+        } else {
+            Message::user("Hello")
+        };
+
+        CompletionRequest {
+            prompt,
+            preamble: None,
+            chat_history: vec![],
+            documents: vec![],
+            tools: if tools {
+                vec![completion::ToolDefinition {
+                    name: "add".to_string(),
+                    description: "Add two numbers".to_string(),
+                    parameters: json!({}),
+                }]
+            } else {
+                vec![]
+            },
+            temperature: None,
+            max_tokens: None,
+            additional_params: None,
+            output_schema: Some(json!({ "type": "object" })),
+        }
+    }
+
+    #[test]
+    fn test_try_build() {
+        assert!(ClientBuilder::new("http://localhost:8080/v1")
+            .api_key("key")
+            .header("x-team", "search")
+            .try_build()
+            .is_ok());
+
+        let err = ClientBuilder::new("http://localhost:8080/v1")
+            .api_key("secret\nkey")
+            .try_build()
+            .err()
+            .unwrap();
+        assert!(matches!(&err, ClientBuilderError::InvalidConfig(_)));
+        assert!(!err.to_string().contains("secret"));
+
+        assert!(matches!(
+            ClientBuilder::new("http://localhost:8080/v1")
+                .header("x team", "search")
+                .try_build(),
+            Err(ClientBuilderError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let client = ClientBuilder::new("http://localhost:8080/v1").build();
+
+        let model = client.completion_model("local-model");
+        assert!(model.supports_structured_output());
+        let body = model
+            .create_completion_request(request(true, true))
+            .unwrap();
+        assert_eq!(body["tools"][0]["function"]["name"], "add");
+        assert!(body.get("response_format").is_some());
+
+        let model = model
+            .with_capabilities(Capabilities {
+                tools: false,
+                images: false,
+                structured_output: false,
+                stream_usage: false,
+            })
+            .with_context_window(8192);
+        assert!(!model.supports_structured_output());
+        assert_eq!(model.context_window(), Some(8192));
+        assert!(matches!(
+            model.create_completion_request(request(true, false)),
+            Err(CompletionError::RequestError(_))
+        ));
+        assert!(matches!(
+            model.create_completion_request(request(false, true)),
+            Err(CompletionError::RequestError(_))
+        ));
+        let body = model
+            .create_completion_request(request(false, false))
+            .unwrap();
+        assert!(body.get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_deserialize_minimal_response() {
+        // e.g.: llama.cpp server responses have no `system_fingerprint` and some gateways
+        // strip the metadata of the response
+        let response = serde_json::from_value::<ApiResponse<openai::CompletionResponse>>(json!({
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "Hello!" },
+                "logprobs": null,
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap();
+        let ApiResponse::Ok(response) = response else {
+            panic!("Expected a completion response");
+        };
+        let response: completion::CompletionResponse<_> = response.try_into().unwrap();
+        assert_eq!(
+            response.choice.first(),
+            completion::AssistantContent::text("Hello!")
+        );
+
+        let error = serde_json::from_value::<ApiResponse<openai::CompletionResponse>>(json!({
+            "error": { "message": "model not found", "type": "invalid_request_error" }
+        }))
+        .unwrap();
+        assert!(matches!(
+            error,
+            ApiResponse::Err(ApiErrorResponse::Wrapped { error }) if error.message == "model not found"
+        ));
+    }
+}
@@ -9,11 +9,18 @@
 //! let llama_3_1_sonar_small_online = client.completion_model(perplexity::LLAMA_3_1_SONAR_SMALL_ONLINE);
 //! ```
 
+use crate::client::{
+    env_var, ClientBuilderError, CompletionClient, CompletionClientDyn, ProviderClient,
+};
 use crate::{
     agent::AgentBuilder,
     completion::{self, message, CompletionError, MessageError},
     extractor::ExtractorBuilder,
-    json_utils, OneOrMany,
+    http_error::HttpStatusError,
+    json_utils,
+    providers::openai,
+    streaming::{StreamingCompletionModel, StreamingResult},
+    OneOrMany,
 };
 
 use schemars::JsonSchema;
@@ -83,6 +90,24 @@ impl Client {
     }
 }
 
+impl ProviderClient for Client {
+    fn try_from_env() -> Result<Self, ClientBuilderError> {
+        Ok(Self::new(&env_var("PERPLEXITY_API_KEY")?))
+    }
+
+    fn as_completion(&self) -> Option<&dyn CompletionClientDyn> {
+        Some(self)
+    }
+}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> CompletionModel {
+        self.completion_model(model)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     message: String,
@@ -149,6 +174,16 @@ pub struct Usage {
     pub total_tokens: u32,
 }
 
+impl From<&Usage> for completion::Usage {
+    fn from(usage: &Usage) -> Self {
+        completion::Usage {
+            input_tokens: usage.prompt_tokens as u64,
+            output_tokens: usage.completion_tokens as u64,
+            ..Default::default()
+        }
+    }
+}
+
 impl std::fmt::Display for Usage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -173,6 +208,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                 content,
             } => Ok(completion::CompletionResponse {
                 choice: OneOrMany::one(content.clone().into()),
+                usage: Some((&response.usage).into()),
                 raw_response: response,
             }),
             _ => Err(CompletionError::ResponseError(
@@ -195,6 +231,53 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
+
+    pub(crate) fn create_completion_request(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<serde_json::Value, CompletionError> {
+        // Add context documents to current prompt
+        let prompt_with_context = completion_request.prompt_with_context();
+
+        // Add preamble to messages (if available)
+        let mut messages: Vec<Message> = if let Some(preamble) = completion_request.preamble {
+            vec![Message {
+                role: Role::System,
+                content: preamble,
+            }]
+        } else {
+            vec![]
+        };
+
+        // Add chat history to messages
+        for message in completion_request.chat_history {
+            messages.push(
+                message
+                    .try_into()
+                    .map_err(|e: MessageError| CompletionError::RequestError(e.into()))?,
+            );
+        }
+
+        // Add user prompt to messages
+        messages.push(
+            prompt_with_context
+                .try_into()
+                .map_err(|e: MessageError| CompletionError::RequestError(e.into()))?,
+        );
+
+        // Compose request
+        let request = json!({
+            "model": self.model,
+            "messages": messages,
+            "temperature": completion_request.temperature,
+        });
+
+        Ok(if let Some(params) = completion_request.additional_params {
+            json_utils::merge(request, params)
+        } else {
+            request
+        })
+    }
 }
 
 impl TryFrom<message::Message> for Message {
@@ -265,52 +348,12 @@ impl completion::CompletionModel for CompletionModel {
         &self,
         completion_request: completion::CompletionRequest,
     ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
-        // Add context documents to current prompt
-        let prompt_with_context = completion_request.prompt_with_context();
-
-        // Add preamble to messages (if available)
-        let mut messages: Vec<Message> = if let Some(preamble) = completion_request.preamble {
-            vec![Message {
-                role: Role::System,
-                content: preamble,
-            }]
-        } else {
-            vec![]
-        };
-
-        // Add chat history to messages
-        for message in completion_request.chat_history {
-            messages.push(
-                message
-                    .try_into()
-                    .map_err(|e: MessageError| CompletionError::RequestError(e.into()))?,
-            );
-        }
-
-        // Add user prompt to messages
-        messages.push(
-            prompt_with_context
-                .try_into()
-                .map_err(|e: MessageError| CompletionError::RequestError(e.into()))?,
-        );
-
-        // Compose request
-        let request = json!({
-            "model": self.model,
-            "messages": messages,
-            "temperature": completion_request.temperature,
-        });
+        let request = self.create_completion_request(completion_request)?;
 
         let response = self
             .client
             .post("/chat/completions")
-            .json(
-                &if let Some(ref params) = completion_request.additional_params {
-                    json_utils::merge(request.clone(), params.clone())
-                } else {
-                    request.clone()
-                },
-            )
+            .json(&request)
             .send()
             .await?;
 
@@ -326,10 +369,27 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        // Perplexity sends the usage in every chunk
+        json_utils::merge_inplace(&mut request, json!({ "stream": true }));
+
+        openai::send_compatible_streaming_request(
+            self.client.post("/chat/completions").json(&request),
+        )
+        .await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
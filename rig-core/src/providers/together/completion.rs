@@ -5,6 +5,7 @@
 
 use crate::{
     completion::{self, CompletionError},
+    http_error::HttpStatusError,
     json_utils,
     providers::openai,
 };
@@ -128,7 +129,7 @@ pub const WIZARDLM_13B_V1_2: &str = "WizardLM/WizardLM-13B-V1.2";
 
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(crate) client: Client,
     pub model: String,
 }
 
@@ -139,16 +140,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
 
-impl completion::CompletionModel for CompletionModel {
-    type Response = openai::CompletionResponse;
-
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         let mut full_history: Vec<openai::Message> = match &completion_request.preamble {
             Some(preamble) => vec![openai::Message::system(preamble)],
             None => vec![],
@@ -171,7 +167,7 @@ impl completion::CompletionModel for CompletionModel {
         full_history.extend(chat_history);
         full_history.extend(prompt);
 
-        let mut request = if completion_request.tools.is_empty() {
+        let request = if completion_request.tools.is_empty() {
             json!({
                 "model": self.model,
                 "messages": full_history,
@@ -187,11 +183,23 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
-        request = if let Some(params) = completion_request.additional_params {
+        Ok(if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
         } else {
             request
-        };
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = openai::CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
 
         let response = self
             .client
@@ -215,7 +223,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(err) => Err(CompletionError::ProviderError(err.error)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
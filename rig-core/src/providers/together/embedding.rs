@@ -7,6 +7,7 @@ use serde::Deserialize;
 use serde_json::json;
 
 use crate::embeddings::{self, EmbeddingError};
+use crate::http_error::HttpStatusError;
 
 use super::{
     client::together_ai_api_types::{ApiErrorResponse, ApiResponse},
@@ -115,7 +116,7 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                 ApiResponse::Error(err) => Err(EmbeddingError::ProviderError(err.message())),
             }
         } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
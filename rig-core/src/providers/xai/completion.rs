@@ -5,6 +5,7 @@
 
 use crate::{
     completion::{self, CompletionError},
+    http_error::HttpStatusError,
     json_utils,
     providers::openai::Message,
 };
@@ -23,7 +24,7 @@ pub const GROK_BETA: &str = "grok-beta";
 
 #[derive(Clone)]
 pub struct CompletionModel {
-    client: Client,
+    pub(crate) client: Client,
     pub model: String,
 }
 
@@ -34,16 +35,11 @@ impl CompletionModel {
             model: model.to_string(),
         }
     }
-}
-
-impl completion::CompletionModel for CompletionModel {
-    type Response = CompletionResponse;
 
-    #[cfg_attr(feature = "worker", worker::send)]
-    async fn completion(
+    pub(crate) fn create_completion_request(
         &self,
         completion_request: completion::CompletionRequest,
-    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+    ) -> Result<serde_json::Value, CompletionError> {
         // Add preamble to chat history (if available)
         let mut full_history: Vec<Message> = match &completion_request.preamble {
             Some(preamble) => {
@@ -73,7 +69,7 @@ impl completion::CompletionModel for CompletionModel {
         full_history.extend(chat_history);
         full_history.extend(prompt);
 
-        let mut request = if completion_request.tools.is_empty() {
+        let request = if completion_request.tools.is_empty() {
             json!({
                 "model": self.model,
                 "messages": full_history,
@@ -89,11 +85,23 @@ impl completion::CompletionModel for CompletionModel {
             })
         };
 
-        request = if let Some(params) = completion_request.additional_params {
+        Ok(if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
         } else {
             request
-        };
+        })
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = CompletionResponse;
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn completion(
+        &self,
+        completion_request: completion::CompletionRequest,
+    ) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
 
         let response = self
             .client
@@ -108,7 +116,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message())),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(HttpStatusError::from_response(response).await.into())
         }
     }
 }
@@ -172,6 +180,7 @@ pub mod xai_api_types {
 
             Ok(completion::CompletionResponse {
                 choice,
+                usage: Some((&response.usage).into()),
                 raw_response: response,
             })
         }
@@ -222,4 +231,14 @@ pub mod xai_api_types {
         pub prompt_tokens: i32,
         pub total_tokens: i32,
     }
+
+    impl From<&Usage> for completion::Usage {
+        fn from(usage: &Usage) -> Self {
+            completion::Usage {
+                input_tokens: usage.prompt_tokens as u64,
+                output_tokens: usage.completion_tokens as u64,
+                ..Default::default()
+            }
+        }
+    }
 }
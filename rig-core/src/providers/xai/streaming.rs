@@ -0,0 +1,23 @@
+use serde_json::json;
+
+use super::completion::CompletionModel;
+use crate::completion::{CompletionError, CompletionRequest};
+use crate::json_utils::merge_inplace;
+use crate::providers::openai::send_compatible_streaming_request;
+use crate::streaming::{StreamingCompletionModel, StreamingResult};
+
+impl StreamingCompletionModel for CompletionModel {
+    async fn stream(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<StreamingResult, CompletionError> {
+        let mut request = self.create_completion_request(completion_request)?;
+        merge_inplace(
+            &mut request,
+            json!({ "stream": true, "stream_options": { "include_usage": true } }),
+        );
+
+        send_compatible_streaming_request(self.client.post("/v1/chat/completions").json(&request))
+            .await
+    }
+}
@@ -0,0 +1,260 @@
+//! This module provides functionality for reranking documents by their relevance to a query.
+//!
+//! Vector searches rank documents by the similarity of their embeddings with the embedding
+//! of the query, which is cheap but coarse. A [Reranker] scores each (query, document) pair
+//! directly (e.g.: with a cross-encoder or an LLM), which is more accurate but too expensive
+//! to run on a whole corpus. Rerankers are therefore applied to a larger set of candidates
+//! returned by a vector search, from which only the most relevant documents are kept.
+//!
+//! Rig provides the following rerankers:
+//! - [Cohere's rerank models](crate::providers::cohere::RerankModel)
+//! - Local cross-encoders with the `rig-fastembed` companion crate
+//! - [LlmReranker], which prompts any completion model and can be used as a fallback
+//!
+//! # Example
+//! ```rust
+//! use rig::providers::{cohere, openai};
+//!
+//! let cohere = cohere::Client::from_env();
+//! let openai = openai::Client::from_env();
+//!
+//! // Fetch 20 candidates from the index and only keep the 3 most relevant documents
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .dynamic_context(3, index)
+//!     .reranker(cohere.rerank_model(cohere::RERANK_V3_5), 20)
+//!     .build();
+//! ```
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    completion::{CompletionError, CompletionModel},
+    http_error::HttpStatusError,
+    message::AssistantContent,
+    vector_store::VectorStoreError,
+};
+
+#[derive(Debug, Error)]
+pub enum RerankError {
+    /// Http error (e.g.: connection error, timeout, etc.)
+    #[error("HttpError: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    /// Json error (e.g.: serialization, deserialization)
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// Error parsing the rerank response
+    #[error("ResponseError: {0}")]
+    ResponseError(String),
+
+    /// Error returned by the rerank model provider
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
+
+    /// Unsuccessful HTTP response returned by the rerank model provider
+    #[error("StatusError: {0}")]
+    StatusError(#[from] HttpStatusError),
+
+    /// Error returned by the completion model of an [LlmReranker]
+    #[error("CompletionError: {0}")]
+    CompletionError(#[from] CompletionError),
+
+    /// Error fetching the candidate documents
+    #[error("VectorStoreError: {0}")]
+    VectorStoreError(#[from] VectorStoreError),
+}
+
+/// Relevance of one of the documents passed to [Reranker::rerank].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RerankResult {
+    /// Index of the document in the list of documents that was reranked
+    pub index: usize,
+    /// Relevance score of the document. Higher is more relevant, but the scale depends on
+    /// the reranker.
+    pub relevance_score: f64,
+}
+
+/// Trait for models that score documents by their relevance to a query.
+pub trait Reranker: Send + Sync {
+    /// Score the `documents` against the `query` and return the `top_n` most relevant ones,
+    /// ordered from most to least relevant.
+    fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<RerankResult>, RerankError>> + Send;
+}
+
+/// Dyn-compatible version of [Reranker], used by [Agent](crate::agent::Agent) to store
+/// rerankers of different types.
+pub trait RerankerDyn: Send + Sync {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> BoxFuture<'a, Result<Vec<RerankResult>, RerankError>>;
+}
+
+impl<R: Reranker> RerankerDyn for R {
+    fn rerank<'a>(
+        &'a self,
+        query: &'a str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> BoxFuture<'a, Result<Vec<RerankResult>, RerankError>> {
+        Box::pin(Reranker::rerank(self, query, documents, top_n))
+    }
+}
+
+const LLM_RERANKER_PREAMBLE: &str = "\
+You are a search relevance expert. You will be given a query and a numbered list of documents. \
+Rate how relevant each document is to the query on a scale from 0 (irrelevant) to 10 (answers the query). \
+Answer only with a JSON array containing one object per document, in the form \
+{\"index\": <document number>, \"score\": <relevance score>}.";
+
+/// Reranker which asks a completion model to rate the relevance of each document.
+///
+/// Slower and more expensive than a dedicated rerank model, but works with any provider.
+/// Scores are normalized between 0 and 1, and documents the model did not rate are dropped.
+///
+/// # Example
+/// ```rust
+/// use rig::{providers::openai, rerank::{LlmReranker, Reranker}};
+///
+/// let openai = openai::Client::from_env();
+/// let reranker = LlmReranker::new(openai.completion_model(openai::GPT_4O_MINI));
+///
+/// let results = reranker
+///     .rerank("What is a flurbo?", vec!["A flurbo is a green alien".to_string()], 1)
+///     .await?;
+/// ```
+#[derive(Clone)]
+pub struct LlmReranker<M: CompletionModel> {
+    model: M,
+    preamble: String,
+}
+
+impl<M: CompletionModel> LlmReranker<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            preamble: LLM_RERANKER_PREAMBLE.to_string(),
+        }
+    }
+
+    /// Replace the default system prompt. The model must still answer with a JSON array of
+    /// `{"index": <document number>, "score": <relevance score>}` objects.
+    pub fn preamble(mut self, preamble: &str) -> Self {
+        self.preamble = preamble.to_string();
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct LlmScore {
+    index: usize,
+    score: f64,
+}
+
+impl<M: CompletionModel> Reranker for LlmReranker<M> {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> Result<Vec<RerankResult>, RerankError> {
+        if documents.is_empty() || top_n == 0 {
+            return Ok(vec![]);
+        }
+
+        let prompt = documents.iter().enumerate().fold(
+            format!("Query: {query}\n\nDocuments:\n"),
+            |prompt, (i, document)| format!("{prompt}\n[{i}]\n{document}\n"),
+        );
+
+        let response = self
+            .model
+            .completion_request(prompt.as_str())
+            .preamble(self.preamble.clone())
+            .temperature(0.0)
+            .send()
+            .await?;
+
+        let text = response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        parse_llm_scores(&text, documents.len(), top_n)
+    }
+}
+
+/// Parse the JSON array of scores answered by the model of an [LlmReranker], ignoring
+/// any text around it (e.g.: markdown code fences).
+fn parse_llm_scores(
+    text: &str,
+    num_documents: usize,
+    top_n: usize,
+) -> Result<Vec<RerankResult>, RerankError> {
+    let json = text
+        .find('[')
+        .zip(text.rfind(']'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &text[start..=end])
+        .ok_or_else(|| {
+            RerankError::ResponseError(format!("Expected a JSON array of scores, got: {text}"))
+        })?;
+
+    let mut scores = serde_json::from_str::<Vec<LlmScore>>(json)?;
+    scores.retain(|score| score.index < num_documents);
+    // Ties (and duplicates) are resolved in favor of the best ranked candidate
+    scores.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.index.cmp(&b.index)));
+
+    let mut seen = vec![false; num_documents];
+    Ok(scores
+        .into_iter()
+        .filter(|score| !std::mem::replace(&mut seen[score.index], true))
+        .take(top_n)
+        .map(|score| RerankResult {
+            index: score.index,
+            relevance_score: score.score.clamp(0.0, 10.0) / 10.0,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_llm_scores, RerankResult};
+
+    #[test]
+    fn test_parse_llm_scores() {
+        let text = "```json\n[{\"index\": 0, \"score\": 2}, {\"index\": 2, \"score\": 9}, \
+            {\"index\": 5, \"score\": 10}, {\"index\": 1, \"score\": 9}, {\"index\": 2, \"score\": 1}]\n```";
+
+        assert_eq!(
+            parse_llm_scores(text, 3, 2).unwrap(),
+            vec![
+                RerankResult {
+                    index: 1,
+                    relevance_score: 0.9
+                },
+                RerankResult {
+                    index: 2,
+                    relevance_score: 0.9
+                },
+            ]
+        );
+        assert_eq!(parse_llm_scores(text, 3, 10).unwrap().len(), 3);
+        assert!(parse_llm_scores("I cannot answer", 3, 2).is_err());
+    }
+}
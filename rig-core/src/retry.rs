@@ -0,0 +1,455 @@
+//! This module provides a retry middleware for completion, embedding, transcription and rerank
+//! models.
+//!
+//! A [RetryPolicy] describes how many times a failed request is retried and how long to wait
+//! between attempts (exponential backoff with jitter). Wrapping a model with [RetryPolicy::wrap]
+//! returns a [WithRetry] model implementing the same traits as the wrapped model.
+//!
+//! Only errors that may succeed if retried are retried (see [RetryableError]), e.g.: timeouts,
+//! connection errors, rate limits (i.e.: `429`) and server errors (i.e.: `5xx`). When the
+//! provider specifies how long to wait (i.e.: with a `Retry-After` header), that delay is used
+//! instead of the backoff.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{embeddings::EmbeddingsBuilder, providers::openai, retry::RetryPolicy};
+//!
+//! let openai = openai::Client::new("YOUR_API_KEY");
+//!
+//! let model = RetryPolicy::default()
+//!     .max_retries(5)
+//!     .initial_backoff(Duration::from_secs(1))
+//!     .wrap(openai.embedding_model(openai::TEXT_EMBEDDING_ADA_002));
+//!
+//! // Throttled batches are retried instead of failing the whole build
+//! let embeddings = EmbeddingsBuilder::new(model)
+//!     .documents(vec!["Hello, world!".to_string()])?
+//!     .build()
+//!     .await?;
+//! ```
+
+use std::{
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use crate::{
+    completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
+    embeddings::{Embedding, EmbeddingError, EmbeddingModel, EmbeddingsResponse},
+    http_error::is_retryable_status,
+    rerank::{RerankError, RerankResult, Reranker},
+    streaming::{StreamingCompletionModel, StreamingResult},
+    transcription::{
+        TranscriptionError, TranscriptionModel, TranscriptionRequest, TranscriptionResponse,
+    },
+};
+
+/// Trait for errors that can tell whether the failed request may succeed if retried
+pub trait RetryableError: std::error::Error {
+    /// Whether the failed request may succeed if retried
+    fn is_retryable(&self) -> bool;
+
+    /// How long to wait before retrying, if specified by the provider
+    fn retry_after(&self) -> Option<Duration> {
+        None
+    }
+}
+
+fn is_retryable_http_error(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.status().is_some_and(is_retryable_status)
+}
+
+impl RetryableError for CompletionError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            CompletionError::HttpError(err) => is_retryable_http_error(err),
+            CompletionError::StatusError(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            CompletionError::StatusError(err) => err.retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl RetryableError for EmbeddingError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            EmbeddingError::HttpError(err) => is_retryable_http_error(err),
+            EmbeddingError::StatusError(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            EmbeddingError::StatusError(err) => err.retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl RetryableError for TranscriptionError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            TranscriptionError::HttpError(err) => is_retryable_http_error(err),
+            TranscriptionError::StatusError(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            TranscriptionError::StatusError(err) => err.retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl RetryableError for RerankError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RerankError::HttpError(err) => is_retryable_http_error(err),
+            RerankError::StatusError(err) => err.is_retryable(),
+            RerankError::CompletionError(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RerankError::StatusError(err) => err.retry_after,
+            RerankError::CompletionError(err) => err.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+/// Policy describing how failed requests are retried.
+///
+/// The delay before the `n`-th retry is `initial_backoff * multiplier^(n - 1)`, capped at
+/// `max_backoff`, of which up to a `jitter` fraction is randomly removed so that concurrent
+/// requests do not retry in lockstep.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Set the maximum number of retries (i.e.: not counting the first attempt). Defaults to 3.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the delay before the first retry. Defaults to 500ms.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the maximum delay between two attempts. Defaults to 30s.
+    /// Note: delays requested by the provider are not capped.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the factor by which the delay is multiplied after each retry. Defaults to 2.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Set the fraction of the delay (between 0 and 1) that can be randomly removed.
+    /// Defaults to 0.5, use 0 to disable jitter.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Wrap the completion, embedding, transcription or rerank model `model` so that its failed
+    /// requests are retried according to this policy
+    pub fn wrap<M>(self, model: M) -> WithRetry<M> {
+        WithRetry {
+            model,
+            policy: self,
+        }
+    }
+
+    /// Delay before the retry following the failed attempt `attempt` (starting at 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powi(attempt.min(i32::MAX as u32) as i32);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+
+        Duration::from_secs_f64(backoff * (1.0 - self.jitter * random_fraction()))
+    }
+
+    /// Run `operation` until it succeeds, fails with an error that is not retryable or the
+    /// maximum number of retries is reached
+    pub async fn retry<T, E, F, Fut>(&self, mut operation: F) -> Result<T, E>
+    where
+        E: RetryableError,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(err) if attempt < self.max_retries && err.is_retryable() => {
+                    let delay = err.retry_after().unwrap_or_else(|| self.backoff(attempt));
+                    attempt += 1;
+                    tracing::warn!(target: "rig",
+                        "Request failed ({}), retrying in {:?} ({}/{})",
+                        err, delay, attempt, self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Random number in `[0, 1)`. Randomly seeded hashers are good enough for jitter.
+fn random_fraction() -> f64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A completion, embedding, transcription or rerank model whose failed requests are retried according
+/// to a [RetryPolicy]. Created with [RetryPolicy::wrap].
+#[derive(Clone)]
+pub struct WithRetry<M> {
+    model: M,
+    policy: RetryPolicy,
+}
+
+impl<M> WithRetry<M> {
+    /// The wrapped model
+    pub fn inner(&self) -> &M {
+        &self.model
+    }
+
+    /// The retry policy of the model
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for WithRetry<M> {
+    type Response = M::Response;
+
+    fn supports_structured_output(&self) -> bool {
+        self.model.supports_structured_output()
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        self.model.context_window()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        self.policy
+            .retry(|| self.model.completion(request.clone()))
+            .await
+    }
+}
+
+/// Only establishing the stream is retried, i.e.: errors occurring once the stream has started
+/// are forwarded as is.
+impl<M: StreamingCompletionModel + Sync> StreamingCompletionModel for WithRetry<M> {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        self.policy
+            .retry(|| self.model.stream(request.clone()))
+            .await
+    }
+}
+
+impl<M: EmbeddingModel> EmbeddingModel for WithRetry<M> {
+    const MAX_DOCUMENTS: usize = M::MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.model.ndims()
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        self.policy
+            .retry(|| self.model.embed_texts(texts.clone()))
+            .await
+    }
+
+    async fn embed_texts_with_usage(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<EmbeddingsResponse, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        self.policy
+            .retry(|| self.model.embed_texts_with_usage(texts.clone()))
+            .await
+    }
+}
+
+impl<M: TranscriptionModel> TranscriptionModel for WithRetry<M> {
+    type Response = M::Response;
+
+    async fn transcription(
+        &self,
+        request: TranscriptionRequest,
+    ) -> Result<TranscriptionResponse<Self::Response>, TranscriptionError> {
+        self.policy
+            .retry(|| self.model.transcription(request.clone()))
+            .await
+    }
+}
+
+impl<R: Reranker> Reranker for WithRetry<R> {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> Result<Vec<RerankResult>, RerankError> {
+        self.policy
+            .retry(|| self.model.rerank(query, documents.clone(), top_n))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::{completion::AssistantContent, http_error::HttpStatusError, OneOrMany};
+
+    /// Mock model failing with the given statuses before succeeding
+    #[derive(Clone)]
+    struct FlakyModel {
+        statuses: Vec<StatusCode>,
+        calls: Arc<AtomicU32>,
+    }
+
+    impl CompletionModel for FlakyModel {
+        type Response = ();
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) as usize;
+            match self.statuses.get(call) {
+                Some(status) => Err(HttpStatusError::new(*status, "error").into()),
+                None => Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text("Hello")),
+                    usage: None,
+                    raw_response: (),
+                }),
+            }
+        }
+    }
+
+    fn flaky(statuses: Vec<StatusCode>) -> (WithRetry<FlakyModel>, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let model = RetryPolicy::default()
+            .max_retries(2)
+            .initial_backoff(Duration::from_millis(1))
+            .wrap(FlakyModel {
+                statuses,
+                calls: calls.clone(),
+            });
+        (model, calls)
+    }
+
+    fn request(model: &impl CompletionModel) -> CompletionRequest {
+        model.completion_request("Hi").build()
+    }
+
+    #[tokio::test]
+    async fn test_retries_retryable_errors() {
+        let (model, calls) = flaky(vec![
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ]);
+
+        assert!(model.completion(request(&model)).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let (model, calls) = flaky(vec![StatusCode::TOO_MANY_REQUESTS; 3]);
+
+        assert!(matches!(
+            model.completion(request(&model)).await,
+            Err(CompletionError::StatusError(HttpStatusError { status, .. })) if status == StatusCode::TOO_MANY_REQUESTS
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_fatal_errors() {
+        let (model, calls) = flaky(vec![StatusCode::UNAUTHORIZED]);
+
+        assert!(model.completion(request(&model)).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default()
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(5))
+            .jitter(0.0);
+
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(10), Duration::from_secs(5));
+
+        for attempt in 0..5 {
+            let expected = policy.backoff(attempt);
+            let backoff = policy.jitter(0.5).backoff(attempt);
+            assert!(backoff <= expected && backoff >= expected / 2);
+        }
+    }
+}
@@ -0,0 +1,429 @@
+//! This module provides completion models combining the completion models of one or several
+//! providers:
+//! - [Fallback]: Sends the request to an ordered list of models, falling through to the next
+//!   model when a model fails with one of the errors of the fallback rules (see [FallbackOn]),
+//!   e.g.: when its provider is down or the request does not fit in its context window.
+//! - [Router]: Sends the request to a model selected from the properties of the request (see
+//!   [RequestProperties]), e.g.: its number of tokens, or whether it has images or tools.
+//!
+//! Both implement [CompletionModel], and can be combined with each other and used with agents
+//! and extractors. The raw response is the type-erased raw response of the model which
+//! answered the request (see [DynResponse]).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     agent::AgentBuilder,
+//!     providers::{anthropic, ollama, openai},
+//!     routing::{Fallback, Router},
+//! };
+//!
+//! let openai = openai::Client::from_env();
+//! let anthropic = anthropic::Client::from_env();
+//!
+//! // Use Anthropic when OpenAI is down, and a local model when both are
+//! let model = Fallback::new(openai.completion_model(openai::GPT_4O))
+//!     .then(anthropic.completion_model(anthropic::CLAUDE_3_5_SONNET))
+//!     .then(ollama::Client::new().completion_model("llama3.2"));
+//!
+//! // Send small requests without images to a cheaper model
+//! let model = Router::new(model)
+//!     .route(
+//!         |request| request.tokens < 4000 && !request.has_images,
+//!         openai.completion_model(openai::GPT_4O_MINI),
+//!     );
+//!
+//! let agent = AgentBuilder::new(model)
+//!     .preamble("You are a helpful assistant.")
+//!     .max_tokens(1024)
+//!     .build();
+//! ```
+
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    completion::{
+        CompletionError, CompletionModel, CompletionModelDyn, CompletionRequest,
+        CompletionResponse, DynResponse,
+    },
+    http_error::ProviderErrorKind,
+    message::{Message, ToolResultContent, UserContent},
+    tokenizer::{self, ApproximateTokenizer, Tokenizer},
+};
+
+/// Errors of a model for which the request is sent to the next model of a [Fallback]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FallbackOn {
+    /// Unsuccessful response of the given kind. Requests exceeding the context window of the
+    /// model before being sent (see [CompletionError::ContextWindowExceeded]) are of the
+    /// `ContextLengthExceeded` kind, and timed out requests are of the `Timeout` kind.
+    Status(ProviderErrorKind),
+    /// The provider could not be reached (e.g.: connection refused, DNS failure)
+    Connection,
+    /// The request could not be built for the model (e.g.: the model does not support tools)
+    Request,
+    /// The response of the model could not be parsed
+    Response,
+    /// The provider returned an error in a successful response
+    Provider,
+    /// Any error
+    Any,
+}
+
+impl FallbackOn {
+    /// Whether the error matches this rule
+    pub fn matches(&self, error: &CompletionError) -> bool {
+        match (self, error) {
+            (FallbackOn::Any, _) => true,
+            (FallbackOn::Status(kind), CompletionError::StatusError(err)) => err.kind == *kind,
+            (
+                FallbackOn::Status(ProviderErrorKind::ContextLengthExceeded),
+                CompletionError::ContextWindowExceeded { .. },
+            ) => true,
+            (FallbackOn::Status(ProviderErrorKind::Timeout), CompletionError::HttpError(err)) => {
+                err.is_timeout()
+            }
+            (FallbackOn::Connection, CompletionError::HttpError(err)) => err.is_connect(),
+            (FallbackOn::Request, CompletionError::RequestError(_)) => true,
+            (
+                FallbackOn::Response,
+                CompletionError::ResponseError(_) | CompletionError::JsonError(_),
+            ) => true,
+            (FallbackOn::Provider, CompletionError::ProviderError(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// The default fallback rules: errors specific to the provider or the model, for which
+    /// another model may succeed. Invalid requests, content filter refusals and budget errors
+    /// are returned without trying the next models.
+    pub fn defaults() -> HashSet<FallbackOn> {
+        HashSet::from([
+            FallbackOn::Status(ProviderErrorKind::Authentication),
+            FallbackOn::Status(ProviderErrorKind::PermissionDenied),
+            FallbackOn::Status(ProviderErrorKind::NotFound),
+            FallbackOn::Status(ProviderErrorKind::RateLimited),
+            FallbackOn::Status(ProviderErrorKind::QuotaExceeded),
+            FallbackOn::Status(ProviderErrorKind::ContextLengthExceeded),
+            FallbackOn::Status(ProviderErrorKind::Timeout),
+            FallbackOn::Status(ProviderErrorKind::Overloaded),
+            FallbackOn::Status(ProviderErrorKind::ServerError),
+            FallbackOn::Connection,
+            FallbackOn::Request,
+        ])
+    }
+}
+
+/// Completion model sending the request to an ordered list of models, until a model answers
+/// or fails with an error which does not match the fallback rules (see [FallbackOn::defaults]).
+/// The error of the last model is returned if all models fail.
+#[derive(Clone)]
+pub struct Fallback {
+    models: Vec<Box<dyn CompletionModelDyn>>,
+    rules: HashSet<FallbackOn>,
+}
+
+impl Fallback {
+    /// Create a fallback chain starting with the given model.
+    pub fn new(model: impl CompletionModelDyn + 'static) -> Self {
+        Self {
+            models: vec![Box::new(model)],
+            rules: FallbackOn::defaults(),
+        }
+    }
+
+    /// Add a model to the end of the chain.
+    pub fn then(mut self, model: impl CompletionModelDyn + 'static) -> Self {
+        self.models.push(Box::new(model));
+        self
+    }
+
+    /// Also fall through to the next model on errors matching the rule.
+    pub fn fallback_on(mut self, rule: FallbackOn) -> Self {
+        self.rules.insert(rule);
+        self
+    }
+
+    /// Replace the fallback rules.
+    pub fn fallback_rules(mut self, rules: impl IntoIterator<Item = FallbackOn>) -> Self {
+        self.rules = rules.into_iter().collect();
+        self
+    }
+
+    fn should_fallback(&self, error: &CompletionError) -> bool {
+        self.rules.iter().any(|rule| rule.matches(error))
+    }
+}
+
+impl CompletionModel for Fallback {
+    type Response = DynResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<DynResponse>, CompletionError> {
+        let (last, models) = self
+            .models
+            .split_last()
+            .expect("Fallback chain should have at least one model");
+
+        for (i, model) in models.iter().enumerate() {
+            match (**model).completion(request.clone()).await {
+                Err(err) if self.should_fallback(&err) => {
+                    tracing::warn!(target: "rig",
+                        "Model {} of the fallback chain failed, trying the next model: {}",
+                        i, err
+                    );
+                }
+                result => return result,
+            }
+        }
+
+        (**last).completion(request).await
+    }
+
+    /// Only if all the models of the chain support structured output
+    fn supports_structured_output(&self) -> bool {
+        self.models
+            .iter()
+            .all(|model| (**model).supports_structured_output())
+    }
+
+    /// The largest context window of the models, so that requests too large for the first
+    /// models fall through to the models with a larger context window
+    fn context_window(&self) -> Option<u64> {
+        self.models
+            .iter()
+            .filter_map(|model| (**model).context_window())
+            .max()
+    }
+}
+
+/// Properties of a completion request, used by the routes of a [Router]
+#[derive(Clone, Copy, Debug)]
+pub struct RequestProperties {
+    /// Number of input tokens of the request (see [tokenizer::request_tokens])
+    pub tokens: usize,
+    /// Whether the prompt or chat history has images (including in tool results)
+    pub has_images: bool,
+    /// Whether the request has tool definitions
+    pub has_tools: bool,
+    /// Whether the request has an output schema
+    pub has_output_schema: bool,
+}
+
+impl RequestProperties {
+    /// Compute the properties of the request, counting tokens with the tokenizer.
+    pub fn new(tokenizer: &dyn Tokenizer, request: &CompletionRequest) -> Self {
+        Self {
+            tokens: tokenizer::request_tokens(tokenizer, request),
+            has_images: std::iter::once(&request.prompt)
+                .chain(&request.chat_history)
+                .any(has_images),
+            has_tools: !request.tools.is_empty(),
+            has_output_schema: request.output_schema.is_some(),
+        }
+    }
+}
+
+fn has_images(message: &Message) -> bool {
+    match message {
+        Message::User { content } => content.iter().any(|content| match content {
+            UserContent::Image(_) => true,
+            UserContent::ToolResult(result) => result
+                .content
+                .iter()
+                .any(|content| matches!(content, ToolResultContent::Image(_))),
+            _ => false,
+        }),
+        Message::Assistant { .. } => false,
+    }
+}
+
+type RouteCondition = Arc<dyn Fn(&RequestProperties) -> bool + Send + Sync>;
+
+/// Completion model sending the request to the model of the first route whose condition
+/// matches the properties of the request, or to the default model if none does.
+#[derive(Clone)]
+pub struct Router {
+    routes: Vec<(RouteCondition, Box<dyn CompletionModelDyn>)>,
+    default: Box<dyn CompletionModelDyn>,
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl Router {
+    /// Create a router sending all requests to the default model.
+    pub fn new(default: impl CompletionModelDyn + 'static) -> Self {
+        Self {
+            routes: vec![],
+            default: Box::new(default),
+            tokenizer: Arc::new(ApproximateTokenizer::default()),
+        }
+    }
+
+    /// Add a route sending the requests matching the condition to the model. Routes are
+    /// matched in the order they are added.
+    pub fn route(
+        mut self,
+        condition: impl Fn(&RequestProperties) -> bool + Send + Sync + 'static,
+        model: impl CompletionModelDyn + 'static,
+    ) -> Self {
+        self.routes.push((Arc::new(condition), Box::new(model)));
+        self
+    }
+
+    /// Set the tokenizer used to count the tokens of requests. Defaults to the
+    /// [ApproximateTokenizer].
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Arc::new(tokenizer);
+        self
+    }
+
+    fn models(&self) -> impl Iterator<Item = &Box<dyn CompletionModelDyn>> {
+        self.routes
+            .iter()
+            .map(|(_, model)| model)
+            .chain(std::iter::once(&self.default))
+    }
+}
+
+impl CompletionModel for Router {
+    type Response = DynResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<DynResponse>, CompletionError> {
+        let properties = RequestProperties::new(self.tokenizer.as_ref(), &request);
+        let model = self
+            .routes
+            .iter()
+            .find(|(condition, _)| condition(&properties))
+            .map(|(_, model)| model)
+            .unwrap_or(&self.default);
+
+        (**model).completion(request).await
+    }
+
+    /// Only if all the models of the router support structured output
+    fn supports_structured_output(&self) -> bool {
+        self.models()
+            .all(|model| (**model).supports_structured_output())
+    }
+
+    /// The largest context window of the models, so that large requests can be routed to
+    /// the models with a larger context window
+    fn context_window(&self) -> Option<u64> {
+        self.models()
+            .filter_map(|model| (**model).context_window())
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::{http_error::HttpStatusError, message::AssistantContent, OneOrMany};
+
+    /// Mock model answering with its name, or failing with the given status
+    #[derive(Clone)]
+    struct MockModel {
+        name: &'static str,
+        status: Option<StatusCode>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl MockModel {
+        fn new(name: &'static str, status: Option<StatusCode>) -> Self {
+            Self {
+                name,
+                status,
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl CompletionModel for MockModel {
+        type Response = &'static str;
+
+        async fn completion(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse<&'static str>, CompletionError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.status {
+                Some(status) => Err(HttpStatusError::new(status, "").into()),
+                None => Ok(CompletionResponse {
+                    choice: OneOrMany::one(AssistantContent::text(self.name)),
+                    usage: None,
+                    raw_response: self.name,
+                }),
+            }
+        }
+    }
+
+    fn answer(response: CompletionResponse<DynResponse>) -> &'static str {
+        response.raw_response.downcast_ref::<&str>().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let down = MockModel::new("down", Some(StatusCode::SERVICE_UNAVAILABLE));
+        let invalid = MockModel::new("invalid", Some(StatusCode::BAD_REQUEST));
+        let up = MockModel::new("up", None);
+
+        let model = Fallback::new(down.clone()).then(up.clone());
+        let response = model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(answer(response), "up");
+
+        // Invalid requests are not sent to the next model, unless the rules say so
+        let model = Fallback::new(invalid.clone()).then(up.clone());
+        assert!(matches!(
+            model.completion_request("Hi").send().await,
+            Err(CompletionError::StatusError(err)) if err.status == StatusCode::BAD_REQUEST
+        ));
+        let model = model.fallback_on(FallbackOn::Status(ProviderErrorKind::InvalidRequest));
+        let response = model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(answer(response), "up");
+
+        // The error of the last model is returned
+        let model = Fallback::new(down.clone()).then(down.clone());
+        assert!(model.completion_request("Hi").send().await.is_err());
+        assert_eq!(down.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(up.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        let small = MockModel::new("small", None);
+        let large = MockModel::new("large", None);
+        let vision = MockModel::new("vision", None);
+
+        let model = Router::new(small)
+            .route(|request| request.has_images, vision)
+            .route(|request| request.tokens > 10, large);
+
+        let response = model.completion_request("Hi").send().await.unwrap();
+        assert_eq!(answer(response), "small");
+
+        let response = model
+            .completion_request("Tell me a long story about a flurbo")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(answer(response), "large");
+
+        let prompt = Message::User {
+            content: OneOrMany::one(UserContent::image("aGVsbG8=", None, None, None)),
+        };
+        let response = model.completion_request(prompt).send().await.unwrap();
+        assert_eq!(answer(response), "vision");
+    }
+}
@@ -11,9 +11,12 @@
 
 use crate::agent::Agent;
 use crate::completion::{
-    CompletionError, CompletionModel, CompletionRequest, CompletionRequestBuilder, Message,
+    AssistantContent, CompletionError, CompletionModel, CompletionModelDyn, CompletionRequest,
+    CompletionRequestBuilder, CompletionResponse, DynResponse, Message, Usage,
 };
-use futures::{Stream, StreamExt};
+use crate::message::Reasoning;
+use crate::OneOrMany;
+use futures::{future::LocalBoxFuture, Stream, StreamExt};
 use std::boxed::Box;
 use std::fmt::{Display, Formatter};
 use std::future::Future;
@@ -25,21 +28,78 @@ pub enum StreamingChoice {
     /// A text chunk from a message response
     Message(String),
 
+    /// A reasoning chunk, i.e.: a chunk of the reasoning text of the model. Providers that sign
+    /// their reasoning send the signature in a last chunk (with an empty text) once the reasoning
+    /// block is complete, while redacted reasoning is sent in a single chunk.
+    Reasoning(Reasoning),
+
     /// A tool call response chunk
     ToolCall(String, String, serde_json::Value),
+
+    /// A partial tool call, i.e.: the id of the tool call and a chunk of its (JSON) arguments.
+    /// The complete tool call is still sent as a [StreamingChoice::ToolCall] once assembled.
+    ToolCallDelta(String, String),
+
+    /// Token usage of the request, as reported by the provider
+    Usage(Usage),
+
+    /// The reason why the model stopped generating (as reported by the provider)
+    FinishReason(String),
+
+    /// A citation of the documents of the request supporting a span of the generated text
+    Citation(Citation),
+
+    /// Terminal chunk of the stream containing the aggregated response
+    Final(Box<CompletionResponse<StreamingResponse>>),
 }
 
 impl Display for StreamingChoice {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             StreamingChoice::Message(text) => write!(f, "{}", text),
+            StreamingChoice::Reasoning(reasoning) => write!(f, "{}", reasoning.reasoning),
             StreamingChoice::ToolCall(name, id, params) => {
                 write!(f, "Tool call: {} {} {:?}", name, id, params)
             }
+            StreamingChoice::ToolCallDelta(id, delta) => {
+                write!(f, "Tool call delta: {} {}", id, delta)
+            }
+            StreamingChoice::Usage(usage) => write!(f, "Usage: {}", usage),
+            StreamingChoice::FinishReason(reason) => write!(f, "Finish reason: {}", reason),
+            StreamingChoice::Citation(citation) => write!(
+                f,
+                "Citation: {}..{} {:?}",
+                citation.start, citation.end, citation.document_ids
+            ),
+            StreamingChoice::Final(response) => {
+                write!(f, "Final response: {:?}", response.choice)
+            }
         }
     }
 }
 
+/// Raw response of a streamed completion, i.e.: the metadata collected over the whole stream
+#[derive(Clone, Debug, Default)]
+pub struct StreamingResponse {
+    /// The reason why the model stopped generating (if reported by the provider)
+    pub finish_reason: Option<String>,
+    /// The citations sent over the stream, in order
+    pub citations: Vec<Citation>,
+}
+
+/// A citation of documents supporting a span of the generated text
+#[derive(Clone, Debug, PartialEq)]
+pub struct Citation {
+    /// Start of the cited span, in characters of the generated text
+    pub start: usize,
+    /// End (exclusive) of the cited span, in characters of the generated text
+    pub end: usize,
+    /// The cited span of the generated text
+    pub text: String,
+    /// Ids of the documents supporting the span
+    pub document_ids: Vec<String>,
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub type StreamingResult =
     Pin<Box<dyn Stream<Item = Result<StreamingChoice, CompletionError>> + Send>>;
@@ -47,6 +107,74 @@ pub type StreamingResult =
 #[cfg(target_arch = "wasm32")]
 pub type StreamingResult = Pin<Box<dyn Stream<Item = Result<StreamingChoice, CompletionError>>>>;
 
+/// Wrap a provider stream so that it ends with a [StreamingChoice::Final] chunk aggregating
+/// the reasoning, text, tool calls, usage, finish reason and citations sent over the stream.
+/// No final chunk is sent if the stream fails or if the model did not send any content.
+pub(crate) fn with_final_response(mut stream: StreamingResult) -> StreamingResult {
+    Box::pin(async_stream::stream! {
+        let mut reasoning: Vec<Reasoning> = vec![];
+        // Whether the last reasoning block is complete (i.e.: signed or redacted)
+        let mut reasoning_complete = true;
+        let mut text = String::new();
+        let mut tool_calls = vec![];
+        let mut usage = None;
+        let mut metadata = StreamingResponse::default();
+
+        while let Some(chunk) = stream.next().await {
+            match &chunk {
+                Ok(StreamingChoice::Message(delta)) => text.push_str(delta),
+                Ok(StreamingChoice::Reasoning(delta)) if delta.redacted => {
+                    reasoning.push(delta.clone());
+                    reasoning_complete = true;
+                }
+                Ok(StreamingChoice::Reasoning(delta)) => {
+                    if reasoning_complete {
+                        reasoning.push(Reasoning::new(""));
+                    }
+                    if let Some(block) = reasoning.last_mut() {
+                        block.reasoning.push_str(&delta.reasoning);
+                        block.signature.clone_from(&delta.signature);
+                    }
+                    reasoning_complete = delta.signature.is_some();
+                }
+                Ok(StreamingChoice::ToolCall(name, id, params)) => {
+                    tool_calls.push(AssistantContent::tool_call(id, name, params.clone()))
+                }
+                Ok(StreamingChoice::Usage(chunk_usage)) => usage = Some(*chunk_usage),
+                Ok(StreamingChoice::FinishReason(reason)) => {
+                    metadata.finish_reason = Some(reason.clone())
+                }
+                Ok(StreamingChoice::Citation(citation)) => {
+                    metadata.citations.push(citation.clone())
+                }
+                Ok(StreamingChoice::ToolCallDelta(..)) | Ok(StreamingChoice::Final(_)) => {}
+                Err(_) => {
+                    yield chunk;
+                    return;
+                }
+            }
+            yield chunk;
+        }
+
+        let mut content = reasoning
+            .into_iter()
+            .map(AssistantContent::Reasoning)
+            .collect::<Vec<_>>();
+        if !text.is_empty() {
+            content.push(AssistantContent::text(text));
+        }
+        content.extend(tool_calls);
+
+        if let Ok(choice) = OneOrMany::many(content) {
+            yield Ok(StreamingChoice::Final(Box::new(CompletionResponse {
+                choice,
+                usage,
+                raw_response: metadata,
+            })));
+        }
+    })
+}
+
 /// Trait for high-level streaming prompt interface
 pub trait StreamingPrompt: Send + Sync {
     /// Stream a simple prompt to the model
@@ -85,6 +213,68 @@ pub trait StreamingCompletionModel: CompletionModel {
     ) -> impl Future<Output = Result<StreamingResult, CompletionError>>;
 }
 
+/// Wrapper trait to allow for dynamic dispatch of streaming completion models.
+///
+/// `Box<dyn StreamingCompletionModelDyn>` implements [CompletionModel] and
+/// [StreamingCompletionModel], e.g.: to hold streaming agents of different providers in the
+/// same collection (see [Agent::into_dyn_streaming]).
+pub trait StreamingCompletionModelDyn: CompletionModelDyn {
+    fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> LocalBoxFuture<'_, Result<StreamingResult, CompletionError>>;
+
+    fn box_clone_streaming(&self) -> Box<dyn StreamingCompletionModelDyn>;
+}
+
+impl<M> StreamingCompletionModelDyn for M
+where
+    M: StreamingCompletionModel + 'static,
+    M::Response: 'static,
+{
+    fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> LocalBoxFuture<'_, Result<StreamingResult, CompletionError>> {
+        Box::pin(<Self as StreamingCompletionModel>::stream(self, request))
+    }
+
+    fn box_clone_streaming(&self) -> Box<dyn StreamingCompletionModelDyn> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn StreamingCompletionModelDyn> {
+    fn clone(&self) -> Self {
+        (**self).box_clone_streaming()
+    }
+}
+
+impl CompletionModel for Box<dyn StreamingCompletionModelDyn> {
+    type Response = DynResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<DynResponse>, CompletionError> {
+        CompletionModelDyn::completion(&**self, request).await
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        CompletionModelDyn::supports_structured_output(&**self)
+    }
+
+    fn context_window(&self) -> Option<u64> {
+        CompletionModelDyn::context_window(&**self)
+    }
+}
+
+impl StreamingCompletionModel for Box<dyn StreamingCompletionModelDyn> {
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingResult, CompletionError> {
+        StreamingCompletionModelDyn::stream(&**self, request).await
+    }
+}
+
 /// helper function to stream a completion request to stdout
 pub async fn stream_to_stdout<M: StreamingCompletionModel>(
     agent: Agent<M>,
@@ -105,6 +295,7 @@ pub async fn stream_to_stdout<M: StreamingCompletionModel>(
                     .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
                 println!("\nResult: {}", res);
             }
+            Ok(_) => {}
             Err(e) => {
                 eprintln!("Error: {}", e);
                 break;
@@ -115,3 +306,112 @@ pub async fn stream_to_stdout<M: StreamingCompletionModel>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_final_response_aggregation() {
+        let chunks: Vec<Result<StreamingChoice, CompletionError>> = vec![
+            Ok(StreamingChoice::Message("Hello ".to_string())),
+            Ok(StreamingChoice::Message("world".to_string())),
+            Ok(StreamingChoice::ToolCallDelta(
+                "call_1".to_string(),
+                "{}".to_string(),
+            )),
+            Ok(StreamingChoice::ToolCall(
+                "add".to_string(),
+                "call_1".to_string(),
+                json!({}),
+            )),
+            Ok(StreamingChoice::Citation(Citation {
+                start: 0,
+                end: 5,
+                text: "Hello".to_string(),
+                document_ids: vec!["doc_0".to_string()],
+            })),
+            Ok(StreamingChoice::FinishReason("tool_calls".to_string())),
+            Ok(StreamingChoice::Usage(Usage {
+                input_tokens: 10,
+                output_tokens: 5,
+                ..Default::default()
+            })),
+        ];
+
+        let chunks = with_final_response(Box::pin(futures::stream::iter(chunks)))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(chunks.len(), 8);
+
+        let Some(Ok(StreamingChoice::Final(response))) = chunks.last() else {
+            panic!("Expected a final response, got {:?}", chunks.last());
+        };
+        let choice = response.choice.iter().collect::<Vec<_>>();
+        assert!(matches!(choice[0], AssistantContent::Text(text) if text.text == "Hello world"));
+        assert!(
+            matches!(choice[1], AssistantContent::ToolCall(call) if call.id == "call_1" && call.function.name == "add")
+        );
+        assert_eq!(
+            response.raw_response.finish_reason.as_deref(),
+            Some("tool_calls")
+        );
+        assert_eq!(response.usage.map(|usage| usage.total_tokens()), Some(15));
+        assert_eq!(
+            response
+                .raw_response
+                .citations
+                .iter()
+                .map(|citation| citation.document_ids.clone())
+                .collect::<Vec<_>>(),
+            vec![vec!["doc_0".to_string()]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_aggregation() {
+        let chunks: Vec<Result<StreamingChoice, CompletionError>> = vec![
+            Ok(StreamingChoice::Reasoning(Reasoning::new("Let me "))),
+            Ok(StreamingChoice::Reasoning(Reasoning::new("think"))),
+            Ok(StreamingChoice::Reasoning(
+                Reasoning::new("").with_signature("sig_1"),
+            )),
+            Ok(StreamingChoice::Reasoning(Reasoning::redacted("encrypted"))),
+            Ok(StreamingChoice::Reasoning(Reasoning::new("Done"))),
+            Ok(StreamingChoice::Message("Hello".to_string())),
+        ];
+
+        let chunks = with_final_response(Box::pin(futures::stream::iter(chunks)))
+            .collect::<Vec<_>>()
+            .await;
+
+        let Some(Ok(StreamingChoice::Final(response))) = chunks.last() else {
+            panic!("Expected a final response, got {:?}", chunks.last());
+        };
+        assert_eq!(
+            response.choice.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                AssistantContent::Reasoning(Reasoning::new("Let me think").with_signature("sig_1")),
+                AssistantContent::Reasoning(Reasoning::redacted("encrypted")),
+                AssistantContent::reasoning("Done"),
+                AssistantContent::text("Hello"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_final_response_on_error() {
+        let chunks: Vec<Result<StreamingChoice, CompletionError>> = vec![
+            Ok(StreamingChoice::Message("Hello".to_string())),
+            Err(CompletionError::ResponseError("boom".to_string())),
+            Ok(StreamingChoice::Message("ignored".to_string())),
+        ];
+
+        let chunks = with_final_response(Box::pin(futures::stream::iter(chunks)))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[1].is_err());
+    }
+}
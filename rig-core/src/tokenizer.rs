@@ -0,0 +1,206 @@
+//! This module provides token counting for completion requests, used to keep requests within
+//! the context window of the models.
+//!
+//! A [Tokenizer] counts the tokens of a text. Exact counts require the tokenizer of the model
+//! (e.g.: `tiktoken` for OpenAI models), which can be plugged in as a closure. By default, the
+//! [ApproximateTokenizer] is used, which estimates counts from the number of characters.
+//!
+//! The context window of a model is given by
+//! [CompletionModel::context_window](crate::completion::CompletionModel::context_window), when
+//! known by its provider. The `context_window` and `fit_context_window` methods of the
+//! [CompletionRequestBuilder](crate::completion::CompletionRequestBuilder) and of the
+//! [AgentBuilder](crate::agent::AgentBuilder) then trim the oldest messages of the chat history
+//! and the least relevant documents until the request fits, instead of letting the provider
+//! reject the request.
+//!
+//! # Example
+//! ```rust
+//! use rig::{completion::Prompt, providers::openai};
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     .dynamic_context(10, index)
+//!     .max_tokens(1024)
+//!     // Trim the request to the 128k tokens of gpt-4o, minus the 1024 tokens of the response
+//!     .fit_context_window()
+//!     // Count tokens with the actual tokenizer of the model
+//!     .tokenizer(|text: &str| bpe.encode_ordinary(text).len())
+//!     .build();
+//! ```
+
+use crate::{
+    completion::{CompletionRequest, Document, ToolDefinition},
+    message::{AssistantContent, ContentFormat, Message, ToolResultContent, UserContent},
+};
+
+/// Number of tokens added to each message by the chat templates of the providers (role,
+/// delimiters, etc.).
+pub const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Estimated number of tokens of binary content (images, audio, base64 documents), whose
+/// actual cost depends on the provider and the size of the content.
+pub const MEDIA_TOKENS: usize = 1000;
+
+/// Trait for counting the tokens of a text.
+pub trait Tokenizer: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+impl<F: Fn(&str) -> usize + Send + Sync> Tokenizer for F {
+    fn count_tokens(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// Estimates the number of tokens of a text from its number of characters. The default of
+/// 4 characters per token is close to the average of most tokenizers on English text, but
+/// code and other languages usually need more tokens.
+#[derive(Clone, Copy, Debug)]
+pub struct ApproximateTokenizer {
+    chars_per_token: f64,
+}
+
+impl Default for ApproximateTokenizer {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl ApproximateTokenizer {
+    pub fn new(chars_per_token: f64) -> Self {
+        Self { chars_per_token }
+    }
+}
+
+impl Tokenizer for ApproximateTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// Number of tokens of a message, including the overhead of the message itself.
+pub fn message_tokens(tokenizer: &dyn Tokenizer, message: &Message) -> usize {
+    let content_tokens = match message {
+        Message::User { content } => content
+            .iter()
+            .map(|content| match content {
+                UserContent::Text(text) => tokenizer.count_tokens(&text.text),
+                UserContent::ToolResult(result) => result
+                    .content
+                    .iter()
+                    .map(|content| match content {
+                        ToolResultContent::Text(text) => tokenizer.count_tokens(&text.text),
+                        ToolResultContent::Image(_) => MEDIA_TOKENS,
+                    })
+                    .sum(),
+                UserContent::Document(document)
+                    if matches!(document.format, Some(ContentFormat::String)) =>
+                {
+                    tokenizer.count_tokens(&document.data)
+                }
+                UserContent::Image(_) | UserContent::Audio(_) | UserContent::Document(_) => {
+                    MEDIA_TOKENS
+                }
+            })
+            .sum::<usize>(),
+        Message::Assistant { content } => content
+            .iter()
+            .map(|content| match content {
+                AssistantContent::Text(text) => tokenizer.count_tokens(&text.text),
+                AssistantContent::ToolCall(tool_call) => {
+                    tokenizer.count_tokens(&tool_call.function.name)
+                        + tokenizer.count_tokens(&tool_call.function.arguments.to_string())
+                }
+                AssistantContent::Reasoning(reasoning) => {
+                    tokenizer.count_tokens(&reasoning.reasoning)
+                }
+            })
+            .sum::<usize>(),
+    };
+
+    content_tokens + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Number of tokens of a document, as attached to the prompt.
+pub fn document_tokens(tokenizer: &dyn Tokenizer, document: &Document) -> usize {
+    tokenizer.count_tokens(&document.to_string())
+}
+
+/// Number of tokens of a tool definition, as sent to the provider.
+pub fn tool_tokens(tokenizer: &dyn Tokenizer, tool: &ToolDefinition) -> usize {
+    tokenizer.count_tokens(&tool.name)
+        + tokenizer.count_tokens(&tool.description)
+        + tokenizer.count_tokens(&tool.parameters.to_string())
+}
+
+/// Number of input tokens of a completion request: preamble, chat history, prompt, documents
+/// and tool definitions. The tokens reserved for the response (`max_tokens`) are not included.
+pub fn request_tokens(tokenizer: &dyn Tokenizer, request: &CompletionRequest) -> usize {
+    let preamble = request
+        .preamble
+        .as_ref()
+        .map(|preamble| tokenizer.count_tokens(preamble) + MESSAGE_OVERHEAD_TOKENS)
+        .unwrap_or_default();
+
+    preamble
+        + message_tokens(tokenizer, &request.prompt)
+        + request
+            .chat_history
+            .iter()
+            .map(|message| message_tokens(tokenizer, message))
+            .sum::<usize>()
+        + request
+            .documents
+            .iter()
+            .map(|document| document_tokens(tokenizer, document))
+            .sum::<usize>()
+        + request
+            .tools
+            .iter()
+            .map(|tool| tool_tokens(tokenizer, tool))
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OneOrMany;
+
+    #[test]
+    fn test_request_tokens() {
+        let tokenizer = ApproximateTokenizer::default();
+        assert_eq!(tokenizer.count_tokens("abcdé"), 2);
+
+        let words = |text: &str| text.split_whitespace().count();
+        let request = CompletionRequest {
+            prompt: "What is a flurbo?".into(),
+            preamble: Some("You are a dictionary".to_string()),
+            chat_history: vec![Message::Assistant {
+                content: OneOrMany::many(vec![
+                    AssistantContent::text("Let me check"),
+                    AssistantContent::tool_call("call_1", "define", serde_json::json!({})),
+                ])
+                .unwrap(),
+            }],
+            documents: vec![Document {
+                id: "doc0".to_string(),
+                text: "A green alien".to_string(),
+                additional_props: Default::default(),
+            }],
+            tools: vec![],
+            temperature: None,
+            max_tokens: None,
+            additional_params: None,
+            output_schema: None,
+        };
+
+        // Preamble 4 + prompt 4 + history 5 (text, tool name and arguments) + document 7
+        // (with its `<file id: doc0>` tags), plus the overhead of 3 messages
+        assert_eq!(
+            request_tokens(&words, &request),
+            4 + 4 + 5 + 7 + 3 * MESSAGE_OVERHEAD_TOKENS
+        );
+    }
+}
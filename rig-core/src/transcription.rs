@@ -6,7 +6,7 @@ use std::{fs, path::Path};
 
 use thiserror::Error;
 
-use crate::json_utils;
+use crate::{http_error::HttpStatusError, json_utils};
 
 // Errors
 #[derive(Debug, Error)]
@@ -30,6 +30,10 @@ pub enum TranscriptionError {
     /// Error returned by the transcription model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Unsuccessful HTTP response returned by the transcription model provider
+    #[error("StatusError: {0}")]
+    StatusError(#[from] HttpStatusError),
 }
 
 /// Trait defining a low-level LLM transcription interface
@@ -79,6 +83,7 @@ pub trait TranscriptionModel: Clone + Send + Sync {
 }
 
 /// Struct representing a general transcription request that can be sent to a transcription model provider.
+#[derive(Clone)]
 pub struct TranscriptionRequest {
     /// The file data to be sent to the transcription model provider
     pub data: Vec<u8>,
@@ -0,0 +1,227 @@
+//! In-memory [BM25](https://en.wikipedia.org/wiki/Okapi_BM25) keyword index.
+//!
+//! Unlike embeddings, keyword scoring matches exact terms, which makes it a good complement
+//! to vector search for identifiers, error codes or product references.
+//! See [HybridIndex](super::hybrid::HybridIndex) to combine both.
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::bm25::Bm25Index;
+//!
+//! let mut index = Bm25Index::new();
+//! index.insert("doc0", "Error E1042: connection refused");
+//! index.insert("doc1", "The connection was closed by the server");
+//!
+//! let results = index.search("E1042", 10);
+//! assert_eq!(results[0].1, "doc0");
+//! ```
+use std::collections::HashMap;
+
+/// Default term frequency saturation parameter.
+pub const DEFAULT_K1: f64 = 1.2;
+/// Default document length normalization parameter.
+pub const DEFAULT_B: f64 = 0.75;
+
+/// BM25 keyword index over the text of documents, identified by their ids.
+#[derive(Clone, Debug)]
+pub struct Bm25Index {
+    k1: f64,
+    b: f64,
+    /// Frequency of each term in each document, by term then document id.
+    postings: HashMap<String, HashMap<String, usize>>,
+    /// Length (in terms) and distinct terms of each document, by document id.
+    documents: HashMap<String, (usize, Vec<String>)>,
+    total_length: usize,
+}
+
+impl Default for Bm25Index {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bm25Index {
+    /// Create an empty index with the default parameters.
+    pub fn new() -> Self {
+        Self::with_parameters(DEFAULT_K1, DEFAULT_B)
+    }
+
+    /// Create an empty index with the given `k1` (term frequency saturation) and
+    /// `b` (document length normalization) parameters.
+    pub fn with_parameters(k1: f64, b: f64) -> Self {
+        Self {
+            k1,
+            b,
+            postings: HashMap::new(),
+            documents: HashMap::new(),
+            total_length: 0,
+        }
+    }
+
+    /// Index the text of a document, replacing the previous text of the document if any.
+    pub fn insert(&mut self, id: impl Into<String>, text: &str) {
+        let id = id.into();
+        self.remove(&id);
+
+        let terms = tokenize(text);
+        let mut frequencies = HashMap::<String, usize>::new();
+        for term in &terms {
+            *frequencies.entry(term.clone()).or_default() += 1;
+        }
+
+        for (term, frequency) in &frequencies {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(id.clone(), *frequency);
+        }
+        self.total_length += terms.len();
+        self.documents
+            .insert(id, (terms.len(), frequencies.into_keys().collect()));
+    }
+
+    /// Remove a document from the index. Unknown ids are ignored.
+    pub fn remove(&mut self, id: &str) {
+        let Some((length, terms)) = self.documents.remove(id) else {
+            return;
+        };
+
+        for term in terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.remove(id);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        self.total_length -= length;
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Get the ids of the `n` documents with the best scores for the query, with their scores.
+    /// Only documents containing at least one term of the query are returned.
+    pub fn search(&self, query: &str, n: usize) -> Vec<(f64, String)> {
+        self.search_by(query, n, |_| true)
+    }
+
+    /// Same as [Self::search], but only documents whose ids satisfy the predicate are returned.
+    pub fn search_by(
+        &self,
+        query: &str,
+        n: usize,
+        predicate: impl Fn(&str) -> bool,
+    ) -> Vec<(f64, String)> {
+        if self.documents.is_empty() {
+            return vec![];
+        }
+
+        let count = self.documents.len() as f64;
+        let average_length = (self.total_length as f64 / count).max(1.0);
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scores = HashMap::<&str, f64>::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+
+            let frequency = postings.len() as f64;
+            let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+
+            for (id, &term_frequency) in postings {
+                let length = self.documents[id].0 as f64;
+                let term_frequency = term_frequency as f64;
+                let score = idf * term_frequency * (self.k1 + 1.0)
+                    / (term_frequency
+                        + self.k1 * (1.0 - self.b + self.b * length / average_length));
+                *scores.entry(id).or_default() += score;
+            }
+        }
+
+        let mut results = scores
+            .into_iter()
+            .filter(|(id, _)| predicate(id))
+            .map(|(id, score)| (score, id.to_string()))
+            .collect::<Vec<_>>();
+        // Ties are broken by id to keep the results deterministic
+        results.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        results.truncate(n);
+        results
+    }
+}
+
+/// Split a text into lowercase terms made of alphanumeric characters and underscores.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Bm25Index};
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Error E1042: SKU_77-b, café!"),
+            vec!["error", "e1042", "sku_77", "b", "café"]
+        );
+    }
+
+    #[test]
+    fn test_bm25_search() {
+        let mut index = Bm25Index::new();
+        index.insert("doc0", "The quick brown fox");
+        index.insert("doc1", "The lazy dog sleeps. The dog dreams.");
+        index.insert("doc2", "Error E1042 in the dog house");
+
+        let results = index.search("dog", 10);
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["doc1", "doc2"]
+        );
+        assert!(results[0].0 > results[1].0);
+
+        assert_eq!(index.search("e1042", 10)[0].1, "doc2");
+        assert!(index.search("cat", 10).is_empty());
+        assert_eq!(index.search("the dog", 1).len(), 1);
+
+        let results = index.search_by("dog", 10, |id| id != "doc1");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "doc2");
+    }
+
+    #[test]
+    fn test_bm25_insert_remove() {
+        let mut index = Bm25Index::new();
+        index.insert("doc0", "alpha beta");
+        index.insert("doc1", "beta gamma");
+        assert_eq!(index.len(), 2);
+
+        // Replacing a document removes its previous terms
+        index.insert("doc0", "delta");
+        assert!(index.search("alpha", 10).is_empty());
+        assert_eq!(index.search("delta", 10)[0].1, "doc0");
+
+        index.remove("doc1");
+        index.remove("unknown");
+        assert_eq!(index.len(), 1);
+        assert!(index.search("beta", 10).is_empty());
+        assert_eq!(index.total_length, 1);
+    }
+}
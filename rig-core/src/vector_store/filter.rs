@@ -0,0 +1,325 @@
+//! Backend-neutral metadata filters for vector searches.
+//!
+//! A [Filter] is passed per query to [VectorStoreIndex::top_n_with_filter] and
+//! [VectorStoreIndex::top_n_ids_with_filter] and translated by each vector store into its
+//! native filter language. Field names refer to fields of the stored documents, with `.`
+//! separating nested fields (e.g.: `"metadata.tenant"`).
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::Filter;
+//!
+//! let filter = Filter::eq("tenant", "acme")
+//!     .and(Filter::is_in("category", ["news", "blog"]))
+//!     .and(!Filter::between("year", 1990, 1999));
+//! ```
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{VectorStoreError, VectorStoreIndex};
+
+/// A filter expression over the fields of the documents stored in a vector store.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// The field is equal to the value.
+    Eq(String, Value),
+    /// The field is not equal to the value.
+    Ne(String, Value),
+    /// The field is equal to one of the values.
+    In(String, Vec<Value>),
+    /// The field is greater than the value.
+    Gt(String, Value),
+    /// The field is greater than or equal to the value.
+    Gte(String, Value),
+    /// The field is less than the value.
+    Lt(String, Value),
+    /// The field is less than or equal to the value.
+    Lte(String, Value),
+    /// All the filters match. An empty list matches every document.
+    And(Vec<Filter>),
+    /// At least one of the filters matches. An empty list matches no document.
+    Or(Vec<Filter>),
+    /// The filter does not match.
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Eq(field.into(), value.into())
+    }
+
+    pub fn ne(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Ne(field.into(), value.into())
+    }
+
+    pub fn is_in(
+        field: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<Value>>,
+    ) -> Self {
+        Self::In(field.into(), values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn gt(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Gt(field.into(), value.into())
+    }
+
+    pub fn gte(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Gte(field.into(), value.into())
+    }
+
+    pub fn lt(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Lt(field.into(), value.into())
+    }
+
+    pub fn lte(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self::Lte(field.into(), value.into())
+    }
+
+    /// The field is within the inclusive range `[min, max]`.
+    pub fn between(field: impl Into<String>, min: impl Into<Value>, max: impl Into<Value>) -> Self {
+        let field = field.into();
+        Self::And(vec![Self::gte(field.clone(), min), Self::lte(field, max)])
+    }
+
+    /// Combine two filters, both of which must match.
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Self::And(mut filters) => {
+                filters.push(other);
+                Self::And(filters)
+            }
+            filter => Self::And(vec![filter, other]),
+        }
+    }
+
+    /// Combine two filters, at least one of which must match.
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Self::Or(mut filters) => {
+                filters.push(other);
+                Self::Or(filters)
+            }
+            filter => Self::Or(vec![filter, other]),
+        }
+    }
+
+    /// Check whether a document, serialized as json, matches the filter.
+    /// Used by stores that filter documents in-process, like
+    /// [InMemoryVectorStore](super::in_memory_store::InMemoryVectorStore).
+    pub fn matches(&self, document: &Value) -> bool {
+        match self {
+            Self::Eq(field, value) => lookup(document, field).is_some_and(|v| json_eq(v, value)),
+            Self::Ne(field, value) => !lookup(document, field).is_some_and(|v| json_eq(v, value)),
+            Self::In(field, values) => lookup(document, field)
+                .is_some_and(|v| values.iter().any(|value| json_eq(v, value))),
+            Self::Gt(field, value) => compare(document, field, value) == Some(Ordering::Greater),
+            Self::Gte(field, value) => matches!(
+                compare(document, field, value),
+                Some(Ordering::Greater | Ordering::Equal)
+            ),
+            Self::Lt(field, value) => compare(document, field, value) == Some(Ordering::Less),
+            Self::Lte(field, value) => matches!(
+                compare(document, field, value),
+                Some(Ordering::Less | Ordering::Equal)
+            ),
+            Self::And(filters) => filters.iter().all(|filter| filter.matches(document)),
+            Self::Or(filters) => filters.iter().any(|filter| filter.matches(document)),
+            Self::Not(filter) => !filter.matches(document),
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Not(filter) => *filter,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+}
+
+/// Get the value of a (possibly nested) field of a json document.
+fn lookup<'a>(document: &'a Value, field: &str) -> Option<&'a Value> {
+    field
+        .split('.')
+        .try_fold(document, |value, key| value.as_object()?.get(key))
+}
+
+/// Json equality where numbers are compared by value (i.e.: `1` equals `1.0`).
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (a, b) => a == b,
+    }
+}
+
+/// Compare a field of a document with a value. Only numbers and strings are ordered.
+fn compare(document: &Value, field: &str, value: &Value) -> Option<Ordering> {
+    match (lookup(document, field)?, value) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// A vector store index which applies a fixed [Filter] to every query.
+///
+/// Useful to share a single index between agents which should only see part of the
+/// documents (e.g.: one agent per tenant), since the filter is applied per query.
+/// # Example
+/// ```rust
+/// use std::sync::Arc;
+/// use rig::vector_store::{Filter, FilteredIndex};
+///
+/// // `index` is any type implementing `VectorStoreIndex`
+/// let index = Arc::new(index);
+///
+/// let agent = openai.agent("gpt-4o")
+///     .dynamic_context(2, FilteredIndex::new(index.clone(), Filter::eq("tenant", "acme")))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct FilteredIndex<I> {
+    index: I,
+    filter: Filter,
+}
+
+impl<I: VectorStoreIndex> FilteredIndex<I> {
+    pub fn new(index: I, filter: Filter) -> Self {
+        Self { index, filter }
+    }
+
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+}
+
+impl<I: VectorStoreIndex> VectorStoreIndex for FilteredIndex<I> {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.index
+            .top_n_with_filter(query, n, self.filter.clone())
+            .await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.index
+            .top_n_ids_with_filter(query, n, self.filter.clone())
+            .await
+    }
+
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.index
+            .top_n_with_filter(query, n, self.filter.clone().and(filter))
+            .await
+    }
+
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.index
+            .top_n_ids_with_filter(query, n, self.filter.clone().and(filter))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Filter;
+
+    #[test]
+    fn test_filter_matches() {
+        let document = json!({
+            "tenant": "acme",
+            "year": 1994,
+            "score": 0.5,
+            "metadata": { "category": "news", "draft": false },
+        });
+
+        assert!(Filter::eq("tenant", "acme").matches(&document));
+        assert!(Filter::eq("year", 1994.0).matches(&document));
+        assert!(Filter::eq("metadata.draft", false).matches(&document));
+        assert!(!Filter::eq("tenant", "globex").matches(&document));
+        assert!(!Filter::eq("missing", "acme").matches(&document));
+
+        assert!(Filter::ne("tenant", "globex").matches(&document));
+        assert!(Filter::ne("missing", "globex").matches(&document));
+
+        assert!(Filter::is_in("metadata.category", ["news", "blog"]).matches(&document));
+        assert!(!Filter::is_in("metadata.category", ["blog"]).matches(&document));
+        assert!(!Filter::is_in("metadata.category", Vec::<String>::new()).matches(&document));
+
+        assert!(Filter::gt("year", 1990).matches(&document));
+        assert!(Filter::gte("year", 1994).matches(&document));
+        assert!(!Filter::lt("year", 1994).matches(&document));
+        assert!(Filter::lte("score", 0.5).matches(&document));
+        assert!(Filter::between("year", 1990, 1999).matches(&document));
+        assert!(Filter::gt("tenant", "abc").matches(&document));
+        // Values of different types are not ordered
+        assert!(!Filter::gt("tenant", 1).matches(&document));
+        assert!(!Filter::lt("tenant", 1).matches(&document));
+
+        assert!(Filter::eq("tenant", "acme")
+            .and(Filter::lt("year", 2000))
+            .matches(&document));
+        assert!(!Filter::eq("tenant", "acme")
+            .and(Filter::gt("year", 2000))
+            .matches(&document));
+        assert!(Filter::eq("tenant", "globex")
+            .or(Filter::lt("year", 2000))
+            .matches(&document));
+        assert!((!Filter::eq("tenant", "globex")).matches(&document));
+
+        assert!(Filter::And(vec![]).matches(&document));
+        assert!(!Filter::Or(vec![]).matches(&document));
+    }
+
+    #[test]
+    fn test_filter_combinators() {
+        let filter = Filter::eq("a", 1)
+            .and(Filter::eq("b", 2))
+            .and(Filter::eq("c", 3));
+        assert_eq!(
+            filter,
+            Filter::And(vec![
+                Filter::eq("a", 1),
+                Filter::eq("b", 2),
+                Filter::eq("c", 3)
+            ])
+        );
+
+        assert_eq!(!!Filter::eq("a", 1), Filter::eq("a", 1));
+
+        assert_eq!(
+            serde_json::to_value(Filter::is_in("a", [1, 2]).or(!Filter::gt("b", "x"))).unwrap(),
+            serde_json::json!({
+                "or": [
+                    { "in": ["a", [1, 2]] },
+                    { "not": { "gt": ["b", "x"] } }
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,565 @@
+//! Approximate nearest neighbour index based on
+//! [HNSW graphs](https://arxiv.org/abs/1603.09320) (Hierarchical Navigable Small World).
+//!
+//! Instead of comparing the query with every embedding, the search greedily walks a layered
+//! proximity graph, which makes queries sublinear in the number of embeddings at the cost of
+//! occasionally missing some of the true nearest neighbours. The trade-off is controlled by
+//! [HnswParams]: higher values of `m`, `ef_construction` and `ef_search` improve recall but
+//! make insertions and queries slower.
+//!
+//! Embeddings are compared with cosine similarity by default, see [HnswIndex::with_metric]
+//! to use another [DistanceMetric]. See [InMemoryVectorStore::with_hnsw](super::in_memory_store::InMemoryVectorStore::with_hnsw)
+//! to use the index in an in-memory vector store.
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::hnsw::{HnswIndex, HnswParams};
+//!
+//! let mut index = HnswIndex::new(HnswParams::default().m(32).ef_search(100));
+//! index.insert("doc0", [[1.0, 0.0].as_slice()]);
+//! index.insert("doc1", [[0.0, 1.0].as_slice(), [0.7, 0.7].as_slice()]);
+//!
+//! // Ids of the documents with the closest embeddings, with the index of that embedding
+//! let results = index.search(&[0.6, 0.8], 1);
+//! assert_eq!(results, vec![("doc1", 1)]);
+//! ```
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use ordered_float::OrderedFloat;
+
+use crate::embeddings::distance::DistanceMetric;
+
+/// Default maximum number of neighbours of each node (twice as many on the bottom layer).
+pub const DEFAULT_M: usize = 16;
+/// Default size of the candidate list when inserting embeddings.
+pub const DEFAULT_EF_CONSTRUCTION: usize = 200;
+/// Default size of the candidate list when searching.
+pub const DEFAULT_EF_SEARCH: usize = 64;
+
+/// Parameters of a [HnswIndex].
+#[derive(Clone, Copy, Debug)]
+pub struct HnswParams {
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    seed: u64,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            ef_search: DEFAULT_EF_SEARCH,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl HnswParams {
+    /// Set the maximum number of neighbours of each node (at least 2). The bottom layer,
+    /// which contains every node, allows twice as many neighbours.
+    pub fn m(mut self, m: usize) -> Self {
+        self.m = m.max(2);
+        self
+    }
+
+    /// Set the number of candidates considered when inserting an embedding.
+    pub fn ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction.max(1);
+        self
+    }
+
+    /// Set the number of candidates considered when searching. Searches for more than
+    /// `ef_search` documents use `n` candidates instead.
+    pub fn ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search.max(1);
+        self
+    }
+
+    /// Set the seed of the random number generator drawing the layers of the nodes,
+    /// which makes the graph deterministic for a given insertion order.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Node of the graph, i.e.: one embedding of a document.
+#[derive(Clone, Debug)]
+struct Node {
+    id: String,
+    /// Index of the embedding among the embeddings of the document
+    embedding: usize,
+    /// Embedding vector, normalized for angular metrics
+    vector: Vec<f64>,
+    /// Neighbours of the node on each layer it belongs to, from the bottom layer up
+    neighbors: Vec<Vec<usize>>,
+    deleted: bool,
+}
+
+/// HNSW index over the embeddings of documents, identified by their ids.
+///
+/// Removed embeddings are only marked as deleted, since they are still needed to navigate
+/// the graph. The graph is rebuilt once more than half of its nodes are deleted.
+#[derive(Clone, Debug)]
+pub struct HnswIndex {
+    params: HnswParams,
+    metric: DistanceMetric,
+    nodes: Vec<Node>,
+    /// Nodes of each document, by document id.
+    ids: HashMap<String, Vec<usize>>,
+    entry_point: Option<usize>,
+    deleted: usize,
+    rng: u64,
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new(HnswParams::default())
+    }
+}
+
+impl HnswIndex {
+    /// Create an empty index comparing embeddings with cosine similarity.
+    pub fn new(params: HnswParams) -> Self {
+        Self::with_metric(params, DistanceMetric::Cosine)
+    }
+
+    /// Create an empty index comparing embeddings with the given metric.
+    pub fn with_metric(params: HnswParams, metric: DistanceMetric) -> Self {
+        Self {
+            params,
+            metric,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry_point: None,
+            deleted: 0,
+            rng: params.seed,
+        }
+    }
+
+    pub fn params(&self) -> &HnswParams {
+        &self.params
+    }
+
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Number of embeddings in the index.
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.deleted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index the embeddings of a document, replacing the previous embeddings of the
+    /// document if any.
+    pub fn insert<'a>(
+        &mut self,
+        id: impl Into<String>,
+        vectors: impl IntoIterator<Item = &'a [f64]>,
+    ) {
+        let id = id.into();
+        self.remove(&id);
+
+        for (embedding, vector) in vectors.into_iter().enumerate() {
+            let node = self.nodes.len();
+            let level = self.random_level();
+            self.nodes.push(Node {
+                id: id.clone(),
+                embedding,
+                vector: self.prepare(vector),
+                neighbors: vec![vec![]; level + 1],
+                deleted: false,
+            });
+            self.ids.entry(id.clone()).or_default().push(node);
+            self.link(node);
+        }
+    }
+
+    /// Remove the embeddings of a document from the index. Unknown ids are ignored.
+    pub fn remove(&mut self, id: &str) {
+        let Some(nodes) = self.ids.remove(id) else {
+            return;
+        };
+
+        for node in nodes {
+            self.nodes[node].deleted = true;
+            self.deleted += 1;
+        }
+
+        if self.deleted > self.nodes.len() / 2 {
+            self.rebuild();
+        }
+    }
+
+    /// Get the ids of the (approximately) `n` documents with the closest embeddings to the
+    /// query, best first, along with the index of their closest embedding.
+    pub fn search(&self, query: &[f64], n: usize) -> Vec<(&str, usize)> {
+        let Some(entry_point) = self.entry_point else {
+            return vec![];
+        };
+        if n == 0 {
+            return vec![];
+        }
+
+        let query = self.prepare(query);
+        let top_layer = self.nodes[entry_point].neighbors.len() - 1;
+
+        let mut entry_point = entry_point;
+        for layer in (1..=top_layer).rev() {
+            entry_point = self.search_layer(&query, &[entry_point], 1, layer)[0].1;
+        }
+
+        // Documents may have several embeddings, and deleted nodes are skipped, so the
+        // candidate list is enlarged until it contains `n` distinct documents
+        let mut ef = self.params.ef_search.max(n);
+        loop {
+            let candidates = self.search_layer(&query, &[entry_point], ef, 0);
+
+            let mut seen = HashSet::new();
+            let results = candidates
+                .iter()
+                .map(|(_, node)| &self.nodes[*node])
+                .filter(|node| !node.deleted && seen.insert(node.id.as_str()))
+                .take(n)
+                .map(|node| (node.id.as_str(), node.embedding))
+                .collect::<Vec<_>>();
+
+            if results.len() == n || candidates.len() < ef || ef >= self.nodes.len() {
+                return results;
+            }
+            ef *= 2;
+        }
+    }
+
+    /// Copy a vector, normalizing it if the metric only depends on its direction.
+    fn prepare(&self, vector: &[f64]) -> Vec<f64> {
+        if self.metric.is_angular() {
+            normalize(vector)
+        } else {
+            vector.to_vec()
+        }
+    }
+
+    /// Distance between two prepared vectors, smaller is better.
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        let differences = || a.iter().zip(b).map(|(x, y)| (x - y).abs());
+        match self.metric {
+            // Angular distance grows with the cosine distance, which is cheaper to compute
+            DistanceMetric::Cosine | DistanceMetric::Angular => 1.0 - dot_product(a, b),
+            DistanceMetric::DotProduct => -dot_product(a, b),
+            // Squaring preserves the order of distances
+            DistanceMetric::Euclidean => differences().map(|x| x * x).sum(),
+            DistanceMetric::Manhattan => differences().sum(),
+            DistanceMetric::Chebyshev => differences().fold(0.0, f64::max),
+        }
+    }
+
+    /// Maximum number of neighbours of a node on the given layer.
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.params.m * 2
+        } else {
+            self.params.m
+        }
+    }
+
+    /// Draw the top layer of a new node from an exponentially decaying distribution.
+    fn random_level(&mut self) -> usize {
+        // splitmix64
+        self.rng = self.rng.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+
+        // Uniform in (0, 1]
+        let uniform = ((z >> 11) + 1) as f64 / (1u64 << 53) as f64;
+        (-uniform.ln() / (self.params.m as f64).ln()).floor() as usize
+    }
+
+    /// Connect a new node to its nearest neighbours on each of its layers.
+    fn link(&mut self, node: usize) {
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(node);
+            return;
+        };
+
+        let query = self.nodes[node].vector.clone();
+        let level = self.nodes[node].neighbors.len() - 1;
+        let top_layer = self.nodes[entry_point].neighbors.len() - 1;
+
+        let mut entry_points = vec![entry_point];
+        for layer in (level + 1..=top_layer).rev() {
+            entry_points = vec![self.search_layer(&query, &entry_points, 1, layer)[0].1];
+        }
+
+        for layer in (0..=level.min(top_layer)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry_points, self.params.ef_construction, layer);
+            let neighbors = self.select_neighbors(&candidates, self.params.m);
+
+            for &neighbor in &neighbors {
+                self.nodes[neighbor].neighbors[layer].push(node);
+                if self.nodes[neighbor].neighbors[layer].len() > self.max_neighbors(layer) {
+                    self.shrink(neighbor, layer);
+                }
+            }
+            self.nodes[node].neighbors[layer] = neighbors;
+
+            entry_points = candidates.into_iter().map(|(_, node)| node).collect();
+        }
+
+        if level > top_layer {
+            self.entry_point = Some(node);
+        }
+    }
+
+    /// Prune the neighbours of a node which has too many on the given layer.
+    fn shrink(&mut self, node: usize, layer: usize) {
+        let vector = &self.nodes[node].vector;
+        let mut candidates = self.nodes[node].neighbors[layer]
+            .iter()
+            .map(|&neighbor| {
+                (
+                    self.distance(vector, &self.nodes[neighbor].vector),
+                    neighbor,
+                )
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        self.nodes[node].neighbors[layer] =
+            self.select_neighbors(&candidates, self.max_neighbors(layer));
+    }
+
+    /// Select up to `m` neighbours among candidates sorted by distance, preferring candidates
+    /// closer to the node than to the already selected neighbours so that the graph stays
+    /// connected across clusters.
+    fn select_neighbors(&self, candidates: &[(f64, usize)], m: usize) -> Vec<usize> {
+        let mut selected = Vec::with_capacity(m);
+        let mut pruned = vec![];
+
+        for &(candidate_distance, candidate) in candidates {
+            if selected.len() == m {
+                break;
+            }
+
+            let vector = &self.nodes[candidate].vector;
+            if selected.iter().all(|&other: &usize| {
+                self.distance(vector, &self.nodes[other].vector) > candidate_distance
+            }) {
+                selected.push(candidate);
+            } else {
+                pruned.push(candidate);
+            }
+        }
+
+        let missing = m - selected.len();
+        selected.extend(pruned.into_iter().take(missing));
+        selected
+    }
+
+    /// Get the `ef` nodes closest to the query on a layer, sorted by distance,
+    /// starting from the given entry points.
+    fn search_layer(
+        &self,
+        query: &[f64],
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<(f64, usize)> {
+        let mut visited = entry_points.iter().copied().collect::<HashSet<_>>();
+        // Closest candidates to expand first
+        let mut candidates = BinaryHeap::new();
+        // Furthest results to evict first
+        let mut results = BinaryHeap::new();
+
+        for &node in entry_points {
+            let distance = OrderedFloat(self.distance(query, &self.nodes[node].vector));
+            candidates.push(Reverse((distance, node)));
+            results.push((distance, node));
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(Reverse((candidate_distance, candidate))) = candidates.pop() {
+            if results
+                .peek()
+                .is_some_and(|&(furthest, _)| results.len() >= ef && candidate_distance > furthest)
+            {
+                break;
+            }
+
+            for &neighbor in &self.nodes[candidate].neighbors[layer] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+
+                let distance = OrderedFloat(self.distance(query, &self.nodes[neighbor].vector));
+                if results.len() < ef
+                    || results
+                        .peek()
+                        .is_some_and(|&(furthest, _)| distance < furthest)
+                {
+                    candidates.push(Reverse((distance, neighbor)));
+                    results.push((distance, neighbor));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(distance, node)| (distance.0, node))
+            .collect()
+    }
+
+    /// Rebuild the graph from the nodes which are not deleted.
+    fn rebuild(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.ids.clear();
+        self.entry_point = None;
+        self.deleted = 0;
+
+        for node in nodes.into_iter().filter(|node| !node.deleted) {
+            let index = self.nodes.len();
+            let level = self.random_level();
+            self.ids.entry(node.id.clone()).or_default().push(index);
+            self.nodes.push(Node {
+                neighbors: vec![vec![]; level + 1],
+                ..node
+            });
+            self.link(index);
+        }
+    }
+}
+
+fn dot_product(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Scale a vector to unit length. Zero vectors are left as is.
+pub(crate) fn normalize(vector: &[f64]) -> Vec<f64> {
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm == 0.0 {
+        vector.to_vec()
+    } else {
+        vector.iter().map(|x| x / norm).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dot_product, normalize, HnswIndex, HnswParams};
+    use crate::embeddings::distance::DistanceMetric;
+
+    /// Deterministic pseudo-random vectors in `[-1, 1]^dims`
+    fn random_vectors(count: usize, dims: usize, seed: u64) -> Vec<Vec<f64>> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                (0..dims)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        (state >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn exact_search(vectors: &[Vec<f64>], query: &[f64], n: usize) -> Vec<String> {
+        let query = normalize(query);
+        let mut distances = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, vector)| (-dot_product(&normalize(vector), &query), format!("doc{i}")))
+            .collect::<Vec<_>>();
+        distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+        distances.into_iter().take(n).map(|(_, id)| id).collect()
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let vectors = random_vectors(2000, 16, 42);
+        let queries = random_vectors(50, 16, 7);
+
+        let mut index =
+            HnswIndex::new(HnswParams::default().m(8).ef_construction(64).ef_search(32));
+        for (i, vector) in vectors.iter().enumerate() {
+            index.insert(format!("doc{i}"), [vector.as_slice()]);
+        }
+        assert_eq!(index.len(), 2000);
+
+        let found = queries
+            .iter()
+            .map(|query| {
+                let expected = exact_search(&vectors, query, 10);
+                index
+                    .search(query, 10)
+                    .into_iter()
+                    .filter(|(id, _)| expected.iter().any(|expected| expected == id))
+                    .count()
+            })
+            .sum::<usize>();
+
+        let recall = found as f64 / (queries.len() * 10) as f64;
+        assert!(recall > 0.9, "recall@10 is {recall}");
+    }
+
+    #[test]
+    fn test_hnsw_metric() {
+        let mut index = HnswIndex::with_metric(HnswParams::default(), DistanceMetric::Euclidean);
+        index.insert("doc0", [[1.0, 0.0].as_slice()]);
+        index.insert("doc1", [[10.0, 0.0].as_slice()]);
+        index.insert("doc2", [[0.0, 1.0].as_slice()]);
+
+        // Vectors are not normalized for metrics depending on their length
+        assert_eq!(
+            index.search(&[9.0, 0.0], 3),
+            vec![("doc1", 0), ("doc0", 0), ("doc2", 0)]
+        );
+    }
+
+    #[test]
+    fn test_hnsw_insert_remove() {
+        let mut index = HnswIndex::default();
+        index.insert("doc0", [[1.0, 0.0].as_slice()]);
+        index.insert("doc1", [[0.0, 1.0].as_slice(), [-1.0, 0.0].as_slice()]);
+        index.insert("doc2", [[0.7, 0.7].as_slice()]);
+        assert_eq!(index.len(), 4);
+
+        // Documents are returned once, with their closest embedding
+        assert_eq!(
+            index.search(&[-1.0, 0.1], 10),
+            vec![("doc1", 1), ("doc2", 0), ("doc0", 0)]
+        );
+
+        // Replacing a document removes its previous embeddings
+        index.insert("doc1", [[1.0, 0.1].as_slice()]);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.search(&[1.0, 0.0], 2), vec![("doc0", 0), ("doc1", 0)]);
+
+        index.remove("unknown");
+        index.remove("doc0");
+        assert_eq!(index.len(), 2);
+        // Deleted nodes are dropped once they outnumber the others
+        assert_eq!(index.nodes.len(), 2);
+        assert_eq!(index.search(&[1.0, 0.0], 2), vec![("doc1", 0), ("doc2", 0)]);
+    }
+}
@@ -0,0 +1,402 @@
+//! Hybrid search, combining keyword and vector search results.
+//!
+//! Vector search finds semantically similar documents, but tends to miss exact terms such as
+//! identifiers, error codes or product references, which keyword search handles well.
+//! A [HybridIndex] runs both searches on an index implementing [VectorStoreIndex] and
+//! [KeywordSearchIndex] and merges their results with a [Fusion] strategy.
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::{Fusion, HybridIndex};
+//!
+//! // `index` is any type implementing both `VectorStoreIndex` and `KeywordSearchIndex`,
+//! // e.g.: an `InMemoryVectorIndex`
+//! let index = HybridIndex::new(index).fusion(Fusion::weighted(0.7));
+//!
+//! let agent = openai.agent("gpt-4o")
+//!     .dynamic_context(3, index)
+//!     .build();
+//! ```
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::{Filter, VectorStoreError, VectorStoreIndex};
+
+/// Trait for indexes supporting keyword (i.e.: full-text) search, natively or with a
+/// [Bm25Index](super::bm25::Bm25Index).
+///
+/// Like the ones of [VectorStoreIndex], the results are `(score, id, document)` tuples
+/// sorted from best to worst. Whether higher or lower scores are better depends on the index.
+pub trait KeywordSearchIndex: Send + Sync {
+    /// Get the top n documents matching the keywords of the query.
+    fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send;
+
+    /// Same as `keyword_top_n` but returns the document ids only.
+    fn keyword_top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send;
+
+    /// Same as `keyword_top_n` but only documents matching the [Filter] are considered.
+    /// The default implementation returns [VectorStoreError::UnsupportedFilter].
+    fn keyword_top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        _query: &str,
+        _n: usize,
+        _filter: Filter,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send
+    {
+        std::future::ready(Err(VectorStoreError::UnsupportedFilter(
+            "This keyword index does not support filters".to_string(),
+        )))
+    }
+
+    /// Same as `keyword_top_n_ids` but only documents matching the [Filter] are considered.
+    /// The default implementation returns [VectorStoreError::UnsupportedFilter].
+    fn keyword_top_n_ids_with_filter(
+        &self,
+        _query: &str,
+        _n: usize,
+        _filter: Filter,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send
+    {
+        std::future::ready(Err(VectorStoreError::UnsupportedFilter(
+            "This keyword index does not support filters".to_string(),
+        )))
+    }
+}
+
+impl<I: KeywordSearchIndex> KeywordSearchIndex for std::sync::Arc<I> {
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        (**self).keyword_top_n(query, n).await
+    }
+
+    async fn keyword_top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        (**self).keyword_top_n_ids(query, n).await
+    }
+
+    async fn keyword_top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        (**self).keyword_top_n_with_filter(query, n, filter).await
+    }
+
+    async fn keyword_top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        (**self)
+            .keyword_top_n_ids_with_filter(query, n, filter)
+            .await
+    }
+}
+
+/// Strategy used to merge the rankings of the vector and keyword searches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fusion {
+    /// [Reciprocal rank fusion](https://plg.uwaterloo.ca/~gvcormac/cormacksigir09-rrf.pdf):
+    /// each document scores `1 / (k + rank)` in each ranking it appears in. Only ranks are
+    /// used, so the scores of the two searches don't need to be comparable.
+    ReciprocalRank { k: f64 },
+    /// Weighted sum of the scores of both searches, after scaling each ranking's scores to
+    /// `[0, 1]` (`1` being the best score of the ranking). Documents missing from a ranking
+    /// score `0` in it.
+    Weighted { vector_weight: f64 },
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Self::reciprocal_rank()
+    }
+}
+
+impl Fusion {
+    /// Reciprocal rank fusion with the usual `k = 60`.
+    pub fn reciprocal_rank() -> Self {
+        Self::ReciprocalRank { k: 60.0 }
+    }
+
+    /// Weighted fusion, where the vector search scores weigh `vector_weight` (between
+    /// `0` and `1`) and the keyword search scores `1 - vector_weight`.
+    pub fn weighted(vector_weight: f64) -> Self {
+        Self::Weighted {
+            vector_weight: vector_weight.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Merge two rankings of `(score, id)` sorted from best to worst into the `n` best
+    /// `(fused score, id)`, sorted by decreasing fused score.
+    pub fn fuse(
+        &self,
+        vector_results: &[(f64, String)],
+        keyword_results: &[(f64, String)],
+        n: usize,
+    ) -> Vec<(f64, String)> {
+        let mut scores = HashMap::<&str, f64>::new();
+
+        match *self {
+            Self::ReciprocalRank { k } => {
+                for results in [vector_results, keyword_results] {
+                    for (rank, (_, id)) in results.iter().enumerate() {
+                        *scores.entry(id).or_default() += 1.0 / (k + rank as f64 + 1.0);
+                    }
+                }
+            }
+            Self::Weighted { vector_weight } => {
+                for (results, weight) in [
+                    (vector_results, vector_weight),
+                    (keyword_results, 1.0 - vector_weight),
+                ] {
+                    for (score, id) in normalize(results) {
+                        *scores.entry(id).or_default() += weight * score;
+                    }
+                }
+            }
+        }
+
+        let mut results = scores
+            .into_iter()
+            .map(|(id, score)| (score, id.to_string()))
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        results.truncate(n);
+        results
+    }
+}
+
+/// Scale the scores of a ranking sorted from best to worst to `[0, 1]`, `1` being the best.
+/// Since the ranking is sorted, this works whether higher (e.g.: similarities) or lower
+/// (e.g.: distances) scores are better.
+fn normalize(results: &[(f64, String)]) -> impl Iterator<Item = (f64, &str)> {
+    let best = results.first().map(|(score, _)| *score).unwrap_or_default();
+    let worst = results.last().map(|(score, _)| *score).unwrap_or_default();
+
+    results.iter().map(move |(score, id)| {
+        let score = if best == worst {
+            1.0
+        } else {
+            (score - worst) / (best - worst)
+        };
+        (score, id.as_str())
+    })
+}
+
+/// A [VectorStoreIndex] returning the results of both the vector and keyword searches of
+/// an index, merged with a [Fusion] strategy. The scores of the results are the fused scores,
+/// higher being better.
+#[derive(Clone)]
+pub struct HybridIndex<I> {
+    index: I,
+    fusion: Fusion,
+    num_candidates: Option<usize>,
+}
+
+impl<I: VectorStoreIndex + KeywordSearchIndex> HybridIndex<I> {
+    /// Create a hybrid index using [Fusion::reciprocal_rank].
+    pub fn new(index: I) -> Self {
+        Self {
+            index,
+            fusion: Fusion::default(),
+            num_candidates: None,
+        }
+    }
+
+    /// Set the strategy used to merge the results of the vector and keyword searches.
+    pub fn fusion(mut self, fusion: Fusion) -> Self {
+        self.fusion = fusion;
+        self
+    }
+
+    /// Set the number of results fetched from each of the vector and keyword searches before
+    /// merging them. Defaults to twice the number of requested results.
+    pub fn num_candidates(mut self, num_candidates: usize) -> Self {
+        self.num_candidates = Some(num_candidates);
+        self
+    }
+
+    pub fn index(&self) -> &I {
+        &self.index
+    }
+
+    fn candidates(&self, n: usize) -> usize {
+        self.num_candidates.unwrap_or(2 * n).max(n)
+    }
+
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let candidates = self.candidates(n);
+        let (vector_results, keyword_results) = match filter {
+            Some(filter) => futures::try_join!(
+                self.index
+                    .top_n_with_filter::<T>(query, candidates, filter.clone()),
+                self.index
+                    .keyword_top_n_with_filter::<T>(query, candidates, filter)
+            )?,
+            None => futures::try_join!(
+                self.index.top_n::<T>(query, candidates),
+                self.index.keyword_top_n::<T>(query, candidates)
+            )?,
+        };
+
+        let ranking = self.fusion.fuse(
+            &without_documents(&vector_results),
+            &without_documents(&keyword_results),
+            n,
+        );
+
+        let mut documents = vector_results
+            .into_iter()
+            .chain(keyword_results)
+            .map(|(_, id, document)| (id, document))
+            .collect::<HashMap<_, _>>();
+
+        Ok(ranking
+            .into_iter()
+            .filter_map(|(score, id)| {
+                let document = documents.remove(&id)?;
+                Some((score, id, document))
+            })
+            .collect())
+    }
+
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let candidates = self.candidates(n);
+        let (vector_results, keyword_results) = match filter {
+            Some(filter) => futures::try_join!(
+                self.index
+                    .top_n_ids_with_filter(query, candidates, filter.clone()),
+                self.index
+                    .keyword_top_n_ids_with_filter(query, candidates, filter)
+            )?,
+            None => futures::try_join!(
+                self.index.top_n_ids(query, candidates),
+                self.index.keyword_top_n_ids(query, candidates)
+            )?,
+        };
+
+        Ok(self.fusion.fuse(&vector_results, &keyword_results, n))
+    }
+}
+
+fn without_documents<T>(results: &[(f64, String, T)]) -> Vec<(f64, String)> {
+    results
+        .iter()
+        .map(|(score, id, _)| (*score, id.clone()))
+        .collect()
+}
+
+impl<I: VectorStoreIndex + KeywordSearchIndex> VectorStoreIndex for HybridIndex<I> {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, None).await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
+
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(filter)).await
+    }
+
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(filter)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fusion;
+
+    fn ranking(ids: &[(f64, &str)]) -> Vec<(f64, String)> {
+        ids.iter()
+            .map(|(score, id)| (*score, id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let vector = ranking(&[(0.9, "a"), (0.8, "b"), (0.7, "c")]);
+        let keyword = ranking(&[(12.0, "c"), (3.0, "d")]);
+
+        let results = Fusion::ReciprocalRank { k: 1.0 }.fuse(&vector, &keyword, 10);
+
+        // c: 1/4 + 1/2, a: 1/2, b: 1/3, d: 1/3
+        assert_eq!(
+            results
+                .iter()
+                .map(|(_, id)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["c", "a", "b", "d"]
+        );
+        assert!((results[0].0 - 0.75).abs() < 1e-9);
+        assert_eq!(
+            Fusion::reciprocal_rank().fuse(&vector, &keyword, 2).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_weighted_fusion() {
+        // Distances: lower is better
+        let vector = ranking(&[(0.1, "a"), (0.3, "b"), (0.5, "c")]);
+        let keyword = ranking(&[(8.0, "c"), (2.0, "b")]);
+
+        let results = Fusion::weighted(0.5).fuse(&vector, &keyword, 10);
+        // a: 0.5 * 1, b: 0.5 * 0.5 + 0.5 * 0, c: 0.5 * 0 + 0.5 * 1
+        assert_eq!(
+            results,
+            vec![(0.5, "a".into()), (0.5, "c".into()), (0.25, "b".into())]
+        );
+
+        let results = Fusion::weighted(0.0).fuse(&vector, &keyword, 1);
+        assert_eq!(results, vec![(1.0, "c".into())]);
+    }
+}
@@ -7,20 +7,39 @@ use std::{
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
-use super::{VectorStoreError, VectorStoreIndex};
+use super::{
+    bm25::Bm25Index,
+    hnsw::{normalize, HnswIndex, HnswParams},
+    Filter, KeywordSearchIndex, VectorStoreError, VectorStoreIndex,
+};
 use crate::{
-    embeddings::{distance::VectorDistance, Embedding, EmbeddingModel},
+    embeddings::{distance::DistanceMetric, Embedding, EmbeddingModel},
     OneOrMany,
 };
 
 /// [InMemoryVectorStore] is a simple in-memory vector store that stores embeddings
 /// in-memory using a HashMap.
+///
+/// A [Bm25Index] of the embedded texts of the documents is maintained alongside the
+/// embeddings, for keyword and [hybrid](super::HybridIndex) searches.
+///
+/// By default, vector searches compare the query with every embedding using cosine
+/// similarity. The metric can be changed with [InMemoryVectorStore::with_metric], and for
+/// large stores, an approximate [HnswIndex] can be enabled with [InMemoryVectorStore::with_hnsw].
 #[derive(Clone, Default)]
 pub struct InMemoryVectorStore<D: Serialize> {
     /// The embeddings are stored in a HashMap.
     /// Hashmap key is the document id.
     /// Hashmap value is a tuple of the serializable document and its corresponding embeddings.
     embeddings: HashMap<String, (D, OneOrMany<Embedding>)>,
+    /// Keyword index of the embedded texts of the documents.
+    keywords: Bm25Index,
+    /// Approximate nearest neighbour index of the embeddings, if enabled.
+    ann: Option<HnswIndex>,
+    /// Metric used to compare embeddings.
+    metric: DistanceMetric,
+    /// Whether embeddings are normalized on insertion.
+    normalized: bool,
 }
 
 impl<D: Serialize + Eq> InMemoryVectorStore<D> {
@@ -28,27 +47,27 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
     /// Ids are automatically generated have will have the form `"doc{n}"` where `n`
     /// is the index of the document.
     pub fn from_documents(documents: impl IntoIterator<Item = (D, OneOrMany<Embedding>)>) -> Self {
-        let mut store = HashMap::new();
+        let mut store = Self::new();
         documents
             .into_iter()
             .enumerate()
             .for_each(|(i, (doc, embeddings))| {
-                store.insert(format!("doc{i}"), (doc, embeddings));
+                store.insert(format!("doc{i}"), doc, embeddings);
             });
 
-        Self { embeddings: store }
+        store
     }
 
     /// Create a new [InMemoryVectorStore] from documents and and their corresponding embeddings with ids.
     pub fn from_documents_with_ids(
         documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>)>,
     ) -> Self {
-        let mut store = HashMap::new();
+        let mut store = Self::new();
         documents.into_iter().for_each(|(i, doc, embeddings)| {
-            store.insert(i.to_string(), (doc, embeddings));
+            store.insert(i.to_string(), doc, embeddings);
         });
 
-        Self { embeddings: store }
+        store
     }
 
     /// Create a new [InMemoryVectorStore] from documents and their corresponding embeddings.
@@ -57,27 +76,195 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
         documents: impl IntoIterator<Item = (D, OneOrMany<Embedding>)>,
         f: fn(&D) -> String,
     ) -> Self {
-        let mut store = HashMap::new();
+        let mut store = Self::new();
         documents.into_iter().for_each(|(doc, embeddings)| {
-            store.insert(f(&doc), (doc, embeddings));
+            store.insert(f(&doc), doc, embeddings);
         });
 
-        Self { embeddings: store }
+        store
+    }
+
+    fn new() -> Self {
+        Self {
+            embeddings: HashMap::new(),
+            keywords: Bm25Index::new(),
+            ann: None,
+            metric: DistanceMetric::default(),
+            normalized: false,
+        }
+    }
+
+    /// Insert or replace a document, keeping the keyword and vector indexes in sync.
+    fn insert(&mut self, id: String, doc: D, mut embeddings: OneOrMany<Embedding>) {
+        if self.normalized {
+            for embedding in embeddings.iter_mut() {
+                embedding.vec = normalize(&embedding.vec);
+            }
+        }
+
+        let text = embeddings
+            .iter()
+            .map(|embedding| embedding.document.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.keywords.insert(id.clone(), &text);
+        if let Some(ann) = &mut self.ann {
+            ann.insert(
+                id.clone(),
+                embeddings.iter().map(|embedding| embedding.vec.as_slice()),
+            );
+        }
+        self.embeddings.insert(id, (doc, embeddings));
+    }
+
+    /// Use an approximate nearest neighbour index for vector searches, built from the
+    /// documents already in the store and updated as documents are added or deleted.
+    ///
+    /// Searches are much faster on large stores, but may miss some of the closest documents
+    /// (see [HnswParams] to tune the trade-off). Searches with a [Filter] still compare the
+    /// query with every matching document.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::vector_store::{hnsw::HnswParams, in_memory_store::InMemoryVectorStore};
+    ///
+    /// let store = InMemoryVectorStore::from_documents(embeddings)
+    ///     .with_hnsw(HnswParams::default().m(32).ef_search(128));
+    /// let index = store.index(model);
+    /// ```
+    pub fn with_hnsw(mut self, params: HnswParams) -> Self {
+        self.ann = Some(self.build_ann(params));
+        self
     }
 
-    /// Implement vector search on [InMemoryVectorStore].
+    /// Set the metric used to compare embeddings in vector searches. Defaults to
+    /// [DistanceMetric::Cosine]. See [InMemoryVectorIndex::with_metric] to use a different
+    /// metric for a single index.
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self.rebuild_ann();
+        self
+    }
+
+    /// Normalize the embeddings of the store to unit length, as well as the embeddings added
+    /// later on. Cosine similarity is then computed as a cheaper dot product.
+    ///
+    /// Note that metrics depending on the length of the embeddings (e.g.: euclidean distance)
+    /// then compare the normalized embeddings.
+    pub fn with_normalized_embeddings(mut self) -> Self {
+        self.normalized = true;
+        for (_, embeddings) in self.embeddings.values_mut() {
+            for embedding in embeddings.iter_mut() {
+                embedding.vec = normalize(&embedding.vec);
+            }
+        }
+        self.rebuild_ann();
+        self
+    }
+
+    /// The metric used to compare embeddings in vector searches.
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Rebuild the approximate index, if enabled, after changes to the embeddings or metric.
+    fn rebuild_ann(&mut self) {
+        if let Some(params) = self.ann.as_ref().map(|ann| *ann.params()) {
+            self.ann = Some(self.build_ann(params));
+        }
+    }
+
+    fn build_ann(&self, params: HnswParams) -> HnswIndex {
+        let mut ann = HnswIndex::with_metric(params, self.metric);
+
+        // Insert in a deterministic order so that the graph only depends on the documents
+        let mut ids = self.embeddings.keys().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let (_, embeddings) = &self.embeddings[id];
+            ann.insert(
+                id.clone(),
+                embeddings.iter().map(|embedding| embedding.vec.as_slice()),
+            );
+        }
+
+        ann
+    }
+
+    /// Implement keyword search on [InMemoryVectorStore], using BM25 scores of the embedded
+    /// texts of the documents. If a filter is given, documents not matching it are skipped.
+    fn keyword_search(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let matching = match filter {
+            Some(filter) => {
+                let mut matching = std::collections::HashSet::new();
+                for (id, (doc, _)) in self.embeddings.iter() {
+                    if filter.matches(&serde_json::to_value(doc)?) {
+                        matching.insert(id.as_str());
+                    }
+                }
+                Some(matching)
+            }
+            None => None,
+        };
+
+        Ok(self.keywords.search_by(query, n, |id| {
+            matching
+                .as_ref()
+                .is_none_or(|matching| matching.contains(id))
+        }))
+    }
+
+    /// Implement vector search on [InMemoryVectorStore] with the given metric.
     /// To be used by implementations of [VectorStoreIndex::top_n] and [VectorStoreIndex::top_n_ids] methods.
-    fn vector_search(&self, prompt_embedding: &Embedding, n: usize) -> EmbeddingRanking<D> {
+    /// If a filter is given, documents not matching it are skipped.
+    /// The ranking is ordered by [ranking_key] of the scores of the documents.
+    fn vector_search(
+        &self,
+        prompt_embedding: &Embedding,
+        n: usize,
+        filter: Option<&Filter>,
+        metric: DistanceMetric,
+    ) -> Result<EmbeddingRanking<D>, VectorStoreError> {
+        let normalized_embedding;
+        let prompt_embedding = if self.normalized {
+            normalized_embedding = Embedding {
+                document: prompt_embedding.document.clone(),
+                vec: normalize(&prompt_embedding.vec),
+            };
+            &normalized_embedding
+        } else {
+            prompt_embedding
+        };
+
+        // The approximate index only supports unfiltered searches with its own metric
+        if let (Some(ann), None) = (&self.ann, filter) {
+            if ann.metric() == metric {
+                return Ok(self.approximate_vector_search(ann, prompt_embedding, n, metric));
+            }
+        }
+
         // Sort documents by best embedding distance
         let mut docs = BinaryHeap::new();
 
         for (id, (doc, embeddings)) in self.embeddings.iter() {
+            if let Some(filter) = filter {
+                if !filter.matches(&serde_json::to_value(doc)?) {
+                    continue;
+                }
+            }
+
             // Get the best context for the document given the prompt
             if let Some((distance, embed_doc)) = embeddings
                 .iter()
                 .map(|embedding| {
+                    let score = metric.compute(embedding, prompt_embedding, self.normalized);
                     (
-                        OrderedFloat(embedding.cosine_similarity(prompt_embedding, false)),
+                        OrderedFloat(ranking_key(metric, score)),
                         &embedding.document,
                     )
                 })
@@ -92,14 +279,38 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
             }
         }
 
-        // Log selected tools with their distances
-        tracing::info!(target: "rig",
-            "Selected documents: {}",
-            docs.iter()
-                .map(|Reverse(RankingItem(distance, id, _, _))| format!("{} ({})", id, distance))
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
+        log_ranking(&docs, metric);
+
+        Ok(docs)
+    }
+
+    /// Vector search using the approximate nearest neighbour index. The scores of the
+    /// documents found are computed exactly, as in [Self::vector_search].
+    fn approximate_vector_search<'a>(
+        &'a self,
+        ann: &HnswIndex,
+        prompt_embedding: &Embedding,
+        n: usize,
+        metric: DistanceMetric,
+    ) -> EmbeddingRanking<'a, D> {
+        let docs = ann
+            .search(&prompt_embedding.vec, n)
+            .into_iter()
+            .filter_map(|(id, index)| {
+                let (id, (doc, embeddings)) = self.embeddings.get_key_value(id)?;
+                let embedding = embeddings.iter().nth(index)?;
+                let score = metric.compute(embedding, prompt_embedding, self.normalized);
+
+                Some(Reverse(RankingItem(
+                    OrderedFloat(ranking_key(metric, score)),
+                    id,
+                    doc,
+                    &embedding.document,
+                )))
+            })
+            .collect();
+
+        log_ranking(&docs, metric);
 
         docs
     }
@@ -111,23 +322,24 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
         &mut self,
         documents: impl IntoIterator<Item = (D, OneOrMany<Embedding>)>,
     ) {
-        let current_index = self.embeddings.len();
-        documents
-            .into_iter()
-            .enumerate()
-            .for_each(|(index, (doc, embeddings))| {
-                self.embeddings
-                    .insert(format!("doc{}", index + current_index), (doc, embeddings));
-            });
+        let mut index = self.embeddings.len();
+        for (doc, embeddings) in documents {
+            // Skip the ids still in use if documents were deleted
+            while self.embeddings.contains_key(&format!("doc{index}")) {
+                index += 1;
+            }
+            self.insert(format!("doc{index}"), doc, embeddings);
+        }
     }
 
     /// Add documents and their corresponding embeddings to the store with ids.
+    /// Documents that already exist with the same ids are replaced.
     pub fn add_documents_with_ids(
         &mut self,
         documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>)>,
     ) {
         documents.into_iter().for_each(|(id, doc, embeddings)| {
-            self.embeddings.insert(id.to_string(), (doc, embeddings));
+            self.insert(id.to_string(), doc, embeddings);
         });
     }
 
@@ -140,10 +352,36 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
     ) {
         for (doc, embeddings) in documents {
             let id = f(&doc);
-            self.embeddings.insert(id, (doc, embeddings));
+            self.insert(id, doc, embeddings);
         }
     }
 
+    /// Delete the documents with the given ids. Unknown ids are ignored.
+    pub fn delete_documents(&mut self, ids: impl IntoIterator<Item = impl AsRef<str>>) {
+        for id in ids {
+            self.embeddings.remove(id.as_ref());
+            self.keywords.remove(id.as_ref());
+            if let Some(ann) = &mut self.ann {
+                ann.remove(id.as_ref());
+            }
+        }
+    }
+
+    /// Delete the documents matching the filter.
+    pub fn delete_documents_with_filter(
+        &mut self,
+        filter: &Filter,
+    ) -> Result<(), VectorStoreError> {
+        let mut ids = Vec::new();
+        for (id, (doc, _)) in self.embeddings.iter() {
+            if filter.matches(&serde_json::to_value(doc)?) {
+                ids.push(id.clone());
+            }
+        }
+        self.delete_documents(ids);
+        Ok(())
+    }
+
     /// Get the document by its id and deserialize it into the given type.
     pub fn get_document<T: for<'a> Deserialize<'a>>(
         &self,
@@ -157,7 +395,7 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
     }
 }
 
-/// RankingItem(distance, document_id, serializable document, embeddings document)
+/// RankingItem(ranking key, document_id, serializable document, embeddings document)
 #[derive(Eq, PartialEq)]
 struct RankingItem<'a, D: Serialize>(OrderedFloat<f64>, &'a String, &'a D, &'a String);
 
@@ -175,6 +413,29 @@ impl<D: Serialize + Eq> PartialOrd for RankingItem<'_, D> {
 
 type EmbeddingRanking<'a, D> = BinaryHeap<Reverse<RankingItem<'a, D>>>;
 
+/// Key by which documents are ranked, higher being better: the score itself for
+/// similarities, the negated score for distances. Also converts a key back to the score.
+fn ranking_key(metric: DistanceMetric, score: f64) -> f64 {
+    if metric.higher_is_better() {
+        score
+    } else {
+        -score
+    }
+}
+
+/// Log selected documents with their distances
+fn log_ranking<D: Serialize + Eq>(docs: &EmbeddingRanking<D>, metric: DistanceMetric) {
+    tracing::info!(target: "rig",
+        "Selected documents: {}",
+        docs.iter()
+            .map(|Reverse(RankingItem(key, id, _, _))| {
+                format!("{} ({})", id, ranking_key(metric, key.0))
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+}
+
 impl<D: Serialize> InMemoryVectorStore<D> {
     pub fn index<M: EmbeddingModel>(self, model: M) -> InMemoryVectorIndex<M, D> {
         InMemoryVectorIndex::new(model, self)
@@ -196,11 +457,37 @@ impl<D: Serialize> InMemoryVectorStore<D> {
 pub struct InMemoryVectorIndex<M: EmbeddingModel, D: Serialize> {
     model: M,
     pub store: InMemoryVectorStore<D>,
+    /// Metric overriding the metric of the store, if any
+    metric: Option<DistanceMetric>,
 }
 
 impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
     pub fn new(model: M, store: InMemoryVectorStore<D>) -> Self {
-        Self { model, store }
+        Self {
+            model,
+            store,
+            metric: None,
+        }
+    }
+
+    /// Compare embeddings with the given metric instead of the metric of the store.
+    /// Scores are similarities (higher is better) or distances (smaller is better) depending
+    /// on the metric, and results are always ordered from best to worst.
+    ///
+    /// Searches with a metric other than the one of the store don't use its approximate index.
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    /// The metric used to compare embeddings.
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric.unwrap_or(self.store.metric)
+    }
+
+    /// The embedding model used to embed queries
+    pub fn model(&self) -> &M {
+        &self.model
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&String, &(D, OneOrMany<Embedding>))> {
@@ -216,23 +503,27 @@ impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
     }
 }
 
-impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
-    for InMemoryVectorIndex<M, D>
-{
-    async fn top_n<T: for<'a> Deserialize<'a>>(
+impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> InMemoryVectorIndex<M, D> {
+    /// Shared implementation of [VectorStoreIndex::top_n] and [VectorStoreIndex::top_n_with_filter].
+    async fn search<T: for<'a> Deserialize<'a>>(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
         let prompt_embedding = &self.model.embed_text(query).await?;
+        let metric = self.metric();
 
-        let docs = self.store.vector_search(prompt_embedding, n);
+        let docs = self
+            .store
+            .vector_search(prompt_embedding, n, filter, metric)?;
 
-        // Return n best
-        docs.into_iter()
-            .map(|Reverse(RankingItem(distance, id, doc, _))| {
+        // Return n best, best first
+        docs.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(RankingItem(key, id, doc, _))| {
                 Ok((
-                    distance.0,
+                    ranking_key(metric, key.0),
                     id.clone(),
                     serde_json::from_str(
                         &serde_json::to_string(doc).map_err(VectorStoreError::JsonError)?,
@@ -243,29 +534,141 @@ impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
             .collect::<Result<Vec<_>, _>>()
     }
 
-    async fn top_n_ids(
+    /// Shared implementation of [VectorStoreIndex::top_n_ids] and [VectorStoreIndex::top_n_ids_with_filter].
+    async fn search_ids(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
         let prompt_embedding = &self.model.embed_text(query).await?;
+        let metric = self.metric();
 
-        let docs = self.store.vector_search(prompt_embedding, n);
+        let docs = self
+            .store
+            .vector_search(prompt_embedding, n, filter, metric)?;
 
-        // Return n best
-        docs.into_iter()
-            .map(|Reverse(RankingItem(distance, id, _, _))| Ok((distance.0, id.clone())))
+        // Return n best, best first
+        docs.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(RankingItem(key, id, _, _))| Ok((ranking_key(metric, key.0), id.clone())))
             .collect::<Result<Vec<_>, _>>()
     }
 }
 
+impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
+    for InMemoryVectorIndex<M, D>
+{
+    async fn top_n<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, None).await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
+
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
+
+impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> InMemoryVectorIndex<M, D> {
+    /// Shared implementation of [KeywordSearchIndex::keyword_top_n] and
+    /// [KeywordSearchIndex::keyword_top_n_with_filter].
+    fn keyword_search<T: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.store
+            .keyword_search(query, n, filter)?
+            .into_iter()
+            .filter_map(|(score, id)| {
+                let (doc, _) = self.store.embeddings.get(&id)?;
+                Some(
+                    serde_json::to_value(doc)
+                        .and_then(serde_json::from_value)
+                        .map(|doc| (score, id, doc))
+                        .map_err(VectorStoreError::JsonError),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Keyword search using the BM25 scores of the embedded texts of the documents,
+/// higher scores being better.
+impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> KeywordSearchIndex
+    for InMemoryVectorIndex<M, D>
+{
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, None)
+    }
+
+    async fn keyword_top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.store.keyword_search(query, n, None)
+    }
+
+    async fn keyword_top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, Some(&filter))
+    }
+
+    async fn keyword_top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.store.keyword_search(query, n, Some(&filter))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::cmp::Reverse;
 
-    use crate::{embeddings::embedding::Embedding, OneOrMany};
+    use serde_json::json;
+
+    use crate::{embeddings::embedding::Embedding, vector_store::Filter, OneOrMany};
 
-    use super::{InMemoryVectorStore, RankingItem};
+    use super::{ranking_key, HnswParams, InMemoryVectorStore, RankingItem};
+    use crate::embeddings::distance::DistanceMetric;
 
     #[test]
     fn test_auto_ids() {
@@ -399,13 +802,17 @@ mod tests {
             ),
         ]);
 
-        let ranking = vector_store.vector_search(
-            &Embedding {
-                document: "glarby-glarble".to_string(),
-                vec: vec![0.0, 0.1, 0.6],
-            },
-            1,
-        );
+        let ranking = vector_store
+            .vector_search(
+                &Embedding {
+                    document: "glarby-glarble".to_string(),
+                    vec: vec![0.0, 0.1, 0.6],
+                },
+                1,
+                None,
+                DistanceMetric::Cosine,
+            )
+            .unwrap();
 
         assert_eq!(
             ranking
@@ -476,13 +883,17 @@ mod tests {
             ),
         ]);
 
-        let ranking = vector_store.vector_search(
-            &Embedding {
-                document: "glarby-glarble".to_string(),
-                vec: vec![0.0, 0.1, 0.6],
-            },
-            1,
-        );
+        let ranking = vector_store
+            .vector_search(
+                &Embedding {
+                    document: "glarby-glarble".to_string(),
+                    vec: vec![0.0, 0.1, 0.6],
+                },
+                1,
+                None,
+                DistanceMetric::Cosine,
+            )
+            .unwrap();
 
         assert_eq!(
             ranking
@@ -502,4 +913,255 @@ mod tests {
             )]
         )
     }
+
+    #[test]
+    fn test_filtered_search() {
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(vec![
+            (
+                "doc0",
+                json!({ "tenant": "acme", "text": "glarb-garb" }),
+                OneOrMany::one(Embedding {
+                    document: "glarb-garb".to_string(),
+                    vec: vec![0.1, 0.1, 0.5],
+                }),
+            ),
+            (
+                "doc1",
+                json!({ "tenant": "globex", "text": "marble-marble" }),
+                OneOrMany::one(Embedding {
+                    document: "marble-marble".to_string(),
+                    vec: vec![0.7, -0.3, 0.0],
+                }),
+            ),
+        ]);
+        let query = Embedding {
+            document: "glarby-glarble".to_string(),
+            vec: vec![0.0, 0.1, 0.6],
+        };
+
+        let ids = |filter: Option<&Filter>| {
+            vector_store
+                .vector_search(&query, 2, filter, DistanceMetric::Cosine)
+                .unwrap()
+                .into_iter()
+                .map(|Reverse(RankingItem(_, id, _, _))| id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(None).len(), 2);
+        assert_eq!(ids(Some(&Filter::eq("tenant", "globex"))), vec!["doc1"]);
+        assert!(ids(Some(&Filter::eq("tenant", "initech"))).is_empty());
+    }
+
+    #[test]
+    fn test_delete_documents() {
+        let mut vector_store =
+            InMemoryVectorStore::from_documents(["acme", "globex", "acme"].map(|tenant| {
+                (
+                    json!({ "tenant": tenant }),
+                    OneOrMany::one(Embedding {
+                        document: tenant.to_string(),
+                        vec: vec![0.1, 0.1, 0.5],
+                    }),
+                )
+            }));
+
+        vector_store.delete_documents(["doc1", "unknown"]);
+        assert_eq!(vector_store.len(), 2);
+
+        // Generated ids don't collide with the remaining documents
+        vector_store.add_documents([(
+            json!({ "tenant": "initech" }),
+            OneOrMany::one(Embedding {
+                document: "initech".to_string(),
+                vec: vec![0.1, 0.1, 0.5],
+            }),
+        )]);
+        assert_eq!(vector_store.len(), 3);
+
+        vector_store
+            .delete_documents_with_filter(&Filter::eq("tenant", "acme"))
+            .unwrap();
+        assert_eq!(
+            vector_store
+                .iter()
+                .map(|(id, _)| id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["doc3"]
+        );
+    }
+
+    #[test]
+    fn test_keyword_search() {
+        let mut vector_store = InMemoryVectorStore::from_documents_with_ids(
+            [
+                ("doc0", "acme", "Error E1042: connection refused"),
+                ("doc1", "globex", "Error E2001: connection timed out"),
+                ("doc2", "acme", "The connection was closed"),
+            ]
+            .map(|(id, tenant, text)| {
+                (
+                    id,
+                    json!({ "tenant": tenant }),
+                    OneOrMany::one(Embedding {
+                        document: text.to_string(),
+                        vec: vec![0.1, 0.1, 0.5],
+                    }),
+                )
+            }),
+        );
+
+        let ids = |vector_store: &InMemoryVectorStore<_>, query, filter: Option<&Filter>| {
+            vector_store
+                .keyword_search(query, 3, filter)
+                .unwrap()
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(&vector_store, "e1042", None), vec!["doc0"]);
+        assert_eq!(ids(&vector_store, "connection", None).len(), 3);
+        assert_eq!(
+            ids(
+                &vector_store,
+                "error timed out",
+                Some(&Filter::eq("tenant", "acme"))
+            ),
+            vec!["doc0"]
+        );
+
+        // The keyword index follows the changes of the store
+        vector_store.delete_documents(["doc0"]);
+        assert!(ids(&vector_store, "e1042", None).is_empty());
+    }
+
+    #[test]
+    fn test_distance_metrics() {
+        let documents = [
+            ("doc0", [2.0, 1.0]),
+            ("doc1", [10.0, 0.0]),
+            ("doc2", [0.0, 5.0]),
+        ]
+        .map(|(id, vec)| {
+            (
+                id,
+                id,
+                OneOrMany::one(Embedding {
+                    document: id.to_string(),
+                    vec: vec.to_vec(),
+                }),
+            )
+        });
+        let query = Embedding {
+            document: "query".to_string(),
+            vec: vec![3.0, 3.0],
+        };
+        fn ranking<'a>(
+            vector_store: &'a InMemoryVectorStore<&str>,
+            query: &Embedding,
+            metric: DistanceMetric,
+        ) -> Vec<(f64, &'a str)> {
+            vector_store
+                .vector_search(query, 3, None, metric)
+                .unwrap()
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse(RankingItem(key, id, _, _))| {
+                    (ranking_key(metric, key.0), id.as_str())
+                })
+                .collect()
+        }
+
+        let vector_store = InMemoryVectorStore::from_documents_with_ids(documents);
+        assert_eq!(
+            ranking(&vector_store, &query, DistanceMetric::DotProduct),
+            vec![(30.0, "doc1"), (15.0, "doc2"), (9.0, "doc0")]
+        );
+        // Smaller distances are better
+        assert_eq!(
+            ranking(&vector_store, &query, DistanceMetric::Manhattan),
+            vec![(3.0, "doc0"), (5.0, "doc2"), (10.0, "doc1")]
+        );
+        assert_eq!(
+            ranking(&vector_store, &query, DistanceMetric::Chebyshev),
+            vec![(2.0, "doc0"), (3.0, "doc2"), (7.0, "doc1")]
+        );
+
+        // Once normalized, the embeddings on the axes are at the same distance from the diagonal
+        let vector_store = vector_store
+            .with_metric(DistanceMetric::Euclidean)
+            .with_normalized_embeddings();
+        assert_eq!(vector_store.metric(), DistanceMetric::Euclidean);
+        let distances = ranking(&vector_store, &query, DistanceMetric::Euclidean);
+        assert_eq!(distances[0].1, "doc0");
+        assert!((distances[1].0 - distances[2].0).abs() < 1e-9);
+        let scores = ranking(&vector_store, &query, DistanceMetric::DotProduct);
+        assert!((scores[0].0 - 0.9f64.sqrt()).abs() < 1e-9);
+        assert!(scores[1..]
+            .iter()
+            .all(|(score, _)| (score - 0.5f64.sqrt()).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_hnsw_search() {
+        let documents = [
+            ("doc0", [0.1, 0.1, 0.5]),
+            ("doc1", [0.7, -0.3, 0.0]),
+            ("doc2", [0.3, 0.7, 0.1]),
+            ("doc3", [-0.5, 0.2, 0.2]),
+        ]
+        .map(|(id, vec)| {
+            (
+                id,
+                json!({ "id": id }),
+                OneOrMany::one(Embedding {
+                    document: id.to_string(),
+                    vec: vec.to_vec(),
+                }),
+            )
+        });
+        let exact = InMemoryVectorStore::from_documents_with_ids(documents.clone());
+        let mut approximate = InMemoryVectorStore::from_documents_with_ids(documents)
+            .with_hnsw(HnswParams::default());
+
+        let query = Embedding {
+            document: "query".to_string(),
+            vec: vec![0.2, 0.3, 0.4],
+        };
+        let ranking = |vector_store: &InMemoryVectorStore<_>, filter: Option<&Filter>| {
+            vector_store
+                .vector_search(&query, 3, filter, DistanceMetric::Cosine)
+                .unwrap()
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse(RankingItem(distance, id, _, _))| (distance.0, id.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ranking(&approximate, None), ranking(&exact, None));
+        let filter = Filter::ne("id", "doc2");
+        assert_eq!(
+            ranking(&approximate, Some(&filter)),
+            ranking(&exact, Some(&filter))
+        );
+
+        // The approximate index follows the changes of the store
+        approximate.delete_documents(["doc0"]);
+        approximate.add_documents_with_ids([(
+            "doc4",
+            json!({ "id": "doc4" }),
+            OneOrMany::one(Embedding {
+                document: "doc4".to_string(),
+                vec: vec![0.2, 0.3, 0.4],
+            }),
+        )]);
+        assert_eq!(
+            ranking(&approximate, None)
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect::<Vec<_>>(),
+            vec!["doc4", "doc2", "doc3"]
+        );
+    }
 }
@@ -2,9 +2,20 @@ use futures::future::BoxFuture;
 use serde::Deserialize;
 use serde_json::Value;
 
-use crate::embeddings::EmbeddingError;
+use crate::{
+    embeddings::{Embedding, EmbeddingError},
+    OneOrMany,
+};
 
+pub mod bm25;
+pub mod filter;
+pub mod hnsw;
+pub mod hybrid;
 pub mod in_memory_store;
+pub mod persistence;
+
+pub use filter::{Filter, FilteredIndex};
+pub use hybrid::{Fusion, HybridIndex, KeywordSearchIndex};
 
 #[derive(Debug, thiserror::Error)]
 pub enum VectorStoreError {
@@ -20,6 +31,18 @@ pub enum VectorStoreError {
 
     #[error("Missing Id: {0}")]
     MissingIdError(String),
+
+    /// The filter cannot be translated to the filter language of the vector store
+    #[error("Unsupported filter: {0}")]
+    UnsupportedFilter(String),
+
+    /// Io error (e.g.: reading or writing a saved store)
+    #[error("Io error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// The embeddings of a store don't match the expected embedding model
+    #[error("Model mismatch: {0}")]
+    ModelMismatch(String),
 }
 
 /// Trait for vector store indexes
@@ -38,6 +61,105 @@ pub trait VectorStoreIndex: Send + Sync {
         query: &str,
         n: usize,
     ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send;
+
+    /// Same as `top_n` but only documents matching the [Filter] are considered.
+    /// Vector stores which do not support filtering return [VectorStoreError::UnsupportedFilter].
+    fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send
+    {
+        let _ = (query, n);
+        std::future::ready(Err(VectorStoreError::UnsupportedFilter(format!(
+            "{filter:?} (filtering is not supported by this vector store)"
+        ))))
+    }
+
+    /// Same as `top_n_ids` but only documents matching the [Filter] are considered.
+    /// Vector stores which do not support filtering return [VectorStoreError::UnsupportedFilter].
+    fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send
+    {
+        let _ = (query, n);
+        std::future::ready(Err(VectorStoreError::UnsupportedFilter(format!(
+            "{filter:?} (filtering is not supported by this vector store)"
+        ))))
+    }
+}
+
+impl<I: VectorStoreIndex> VectorStoreIndex for std::sync::Arc<I> {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        (**self).top_n(query, n).await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        (**self).top_n_ids(query, n).await
+    }
+
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        (**self).top_n_with_filter(query, n, filter).await
+    }
+
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        (**self).top_n_ids_with_filter(query, n, filter).await
+    }
+}
+
+/// Trait for vector stores whose documents can be inserted, replaced and deleted, e.g.: to keep
+/// a vector store in sync with the source of its documents.
+///
+/// Documents are identified by the ids given when upserting them. Every embedding of a
+/// document is replaced or deleted along with the document.
+///
+/// [InMemoryVectorStore](in_memory_store::InMemoryVectorStore) provides the same operations as
+/// inherent methods taking `&mut self`.
+pub trait WritableVectorStore<D>: Send + Sync {
+    /// Insert the documents with their ids and embeddings, replacing the documents
+    /// that already exist with the same ids.
+    fn upsert_documents(
+        &self,
+        documents: Vec<(String, D, OneOrMany<Embedding>)>,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+
+    /// Delete the documents with the given ids. Unknown ids are ignored.
+    fn delete_documents(
+        &self,
+        ids: Vec<String>,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+
+    /// Delete the documents matching the [Filter].
+    fn delete_documents_with_filter(
+        &self,
+        filter: Filter,
+    ) -> impl std::future::Future<Output = Result<(), VectorStoreError>> + Send;
+
+    /// Count the documents in the vector store, i.e.: the number of distinct document ids.
+    /// Documents with several embeddings are counted once.
+    fn count(&self) -> impl std::future::Future<Output = Result<usize, VectorStoreError>> + Send;
 }
 
 pub type TopNResults = Result<Vec<(f64, String, Value)>, VectorStoreError>;
@@ -50,6 +172,20 @@ pub trait VectorStoreIndexDyn: Send + Sync {
         query: &'a str,
         n: usize,
     ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
+
+    fn top_n_with_filter<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, TopNResults>;
+
+    fn top_n_ids_with_filter<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
 }
 
 impl<I: VectorStoreIndex> VectorStoreIndexDyn for I {
@@ -75,6 +211,31 @@ impl<I: VectorStoreIndex> VectorStoreIndexDyn for I {
     ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
         Box::pin(self.top_n_ids(query, n))
     }
+
+    fn top_n_with_filter<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, TopNResults> {
+        Box::pin(async move {
+            Ok(self
+                .top_n_with_filter::<serde_json::Value>(query, n, filter)
+                .await?
+                .into_iter()
+                .map(|(score, id, doc)| (score, id, prune_document(doc).unwrap_or_default()))
+                .collect::<Vec<_>>())
+        })
+    }
+
+    fn top_n_ids_with_filter<'a>(
+        &'a self,
+        query: &'a str,
+        n: usize,
+        filter: Filter,
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
+        Box::pin(self.top_n_ids_with_filter(query, n, filter))
+    }
 }
 
 fn prune_document(document: serde_json::Value) -> Option<serde_json::Value> {
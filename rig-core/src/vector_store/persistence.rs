@@ -0,0 +1,439 @@
+//! Save and load [InMemoryVectorStore]s to and from files, to avoid embedding the same
+//! documents again every time a process starts.
+//!
+//! Stores can be saved in two formats (see [StoreFormat]):
+//! - [StoreFormat::Json]: human readable, suited to small stores.
+//! - [StoreFormat::Binary]: compact, with vectors stored as `f32`, suited to large stores.
+//!
+//! Files start with a [StoreHeader] holding the name of the embedding model and the number of
+//! dimensions of the embeddings, which are checked when loading the store so that a store
+//! is never queried with embeddings from another model. The format is detected on load.
+//!
+//! Only the documents and their embeddings are saved: the approximate index enabled with
+//! [InMemoryVectorStore::with_hnsw] must be enabled again after loading the store.
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     providers::openai,
+//!     vector_store::{
+//!         in_memory_store::InMemoryVectorIndex,
+//!         persistence::StoreFormat,
+//!     },
+//! };
+//!
+//! let model = openai_client.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+//!
+//! // Save the index built from freshly computed embeddings...
+//! index.save("store.bin", StoreFormat::Binary, openai::TEXT_EMBEDDING_3_SMALL)?;
+//!
+//! // ...and reload it on the next run
+//! let index = InMemoryVectorIndex::<_, Document>::load("store.bin", model, openai::TEXT_EMBEDDING_3_SMALL)?;
+//! ```
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore},
+    VectorStoreError,
+};
+use crate::{
+    embeddings::{Embedding, EmbeddingModel},
+    OneOrMany,
+};
+
+/// Magic bytes at the start of binary store files.
+const MAGIC: &[u8; 8] = b"RIGSTORE";
+/// Version of the file formats, bumped on incompatible changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// File format of a saved [InMemoryVectorStore].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StoreFormat {
+    /// JSON document, with vectors stored as `f64`.
+    #[default]
+    Json,
+    /// Binary format, with vectors stored as little-endian `f32`.
+    Binary,
+}
+
+/// Description of the embeddings of a saved store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreHeader {
+    /// Name of the embedding model which computed the embeddings
+    pub model: String,
+    /// Number of dimensions of the embeddings
+    pub ndims: usize,
+}
+
+impl StoreHeader {
+    pub fn new(model: &str, ndims: usize) -> Self {
+        Self {
+            model: model.to_string(),
+            ndims,
+        }
+    }
+
+    /// Check that a loaded store was created with the expected model.
+    fn check(&self, expected: &StoreHeader) -> Result<(), VectorStoreError> {
+        if self == expected {
+            Ok(())
+        } else {
+            Err(VectorStoreError::ModelMismatch(format!(
+                "store was created with model `{}` ({} dimensions), expected model `{}` ({} dimensions)",
+                self.model, self.ndims, expected.model, expected.ndims
+            )))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonStoreRef<'a, D> {
+    version: u32,
+    header: &'a StoreHeader,
+    documents: Vec<JsonDocumentRef<'a, D>>,
+}
+
+#[derive(Serialize)]
+struct JsonDocumentRef<'a, D> {
+    id: &'a str,
+    document: &'a D,
+    embeddings: &'a OneOrMany<Embedding>,
+}
+
+#[derive(Deserialize)]
+struct JsonStore<D> {
+    version: u32,
+    header: StoreHeader,
+    documents: Vec<JsonDocument<D>>,
+}
+
+#[derive(Deserialize)]
+struct JsonDocument<D> {
+    id: String,
+    document: D,
+    embeddings: OneOrMany<Embedding>,
+}
+
+/// Header of a JSON store, ignoring the documents.
+#[derive(Deserialize)]
+struct JsonStoreHeader {
+    version: u32,
+    header: StoreHeader,
+}
+
+impl<D: Serialize> InMemoryVectorStore<D> {
+    /// Save the store to a file in the given format. All the embeddings must have the number
+    /// of dimensions of the `header`.
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        format: StoreFormat,
+        header: &StoreHeader,
+    ) -> Result<(), VectorStoreError> {
+        if let Some((id, _)) = self.iter().find(|(_, (_, embeddings))| {
+            embeddings
+                .iter()
+                .any(|embedding| embedding.vec.len() != header.ndims)
+        }) {
+            return Err(VectorStoreError::ModelMismatch(format!(
+                "embeddings of document `{id}` don't have {} dimensions",
+                header.ndims
+            )));
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            StoreFormat::Json => serde_json::to_writer(
+                &mut writer,
+                &JsonStoreRef {
+                    version: FORMAT_VERSION,
+                    header,
+                    documents: self
+                        .iter()
+                        .map(|(id, (document, embeddings))| JsonDocumentRef {
+                            id,
+                            document,
+                            embeddings,
+                        })
+                        .collect(),
+                },
+            )?,
+            StoreFormat::Binary => self.write_binary(&mut writer, header)?,
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Binary format:
+    /// - magic bytes and format version
+    /// - json header and number of documents
+    /// - for each document: id, json document, number of embeddings and for each embedding,
+    ///   its text and `ndims` f32 values
+    ///
+    /// Strings are prefixed by their length, and all numbers are little-endian.
+    fn write_binary(&self, writer: &mut impl Write, header: &StoreHeader) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        write_bytes(writer, &serde_json::to_vec(header)?)?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+
+        for (id, (document, embeddings)) in self.iter() {
+            write_bytes(writer, id.as_bytes())?;
+            write_bytes(writer, &serde_json::to_vec(document)?)?;
+            writer.write_all(&(embeddings.len() as u32).to_le_bytes())?;
+            for embedding in embeddings.iter() {
+                write_bytes(writer, embedding.document.as_bytes())?;
+                for value in &embedding.vec {
+                    writer.write_all(&(*value as f32).to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<D: Serialize + for<'a> Deserialize<'a> + Eq> InMemoryVectorStore<D> {
+    /// Load a store saved with [InMemoryVectorStore::save], in any format.
+    /// Fails with [VectorStoreError::ModelMismatch] if the header of the file differs from the
+    /// `expected` header.
+    pub fn load(path: impl AsRef<Path>, expected: &StoreHeader) -> Result<Self, VectorStoreError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        if is_binary(&mut reader)? {
+            let header = read_binary_header(&mut reader)?;
+            header.check(expected)?;
+
+            let count = read_u64(&mut reader)?;
+            let mut documents = vec![];
+            for _ in 0..count {
+                let id = read_string(&mut reader)?;
+                let document = serde_json::from_slice(&read_bytes(&mut reader)?)?;
+
+                let num_embeddings = read_u32(&mut reader)?;
+                let mut embeddings = vec![];
+                for _ in 0..num_embeddings {
+                    let text = read_string(&mut reader)?;
+                    let mut buffer = vec![0; header.ndims * 4];
+                    reader.read_exact(&mut buffer)?;
+                    embeddings.push(Embedding {
+                        document: text,
+                        vec: buffer
+                            .chunks_exact(4)
+                            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()) as f64)
+                            .collect(),
+                    });
+                }
+
+                let embeddings = OneOrMany::many(embeddings)
+                    .map_err(|_| invalid_data(format!("document `{id}` has no embeddings")))?;
+                documents.push((id, document, embeddings));
+            }
+
+            Ok(Self::from_documents_with_ids(documents))
+        } else {
+            let store: JsonStore<D> = serde_json::from_reader(reader)?;
+            check_version(store.version)?;
+            store.header.check(expected)?;
+
+            Ok(Self::from_documents_with_ids(
+                store
+                    .documents
+                    .into_iter()
+                    .map(|document| (document.id, document.document, document.embeddings)),
+            ))
+        }
+    }
+}
+
+/// Read the header of a store saved with [InMemoryVectorStore::save], in any format.
+pub fn read_header(path: impl AsRef<Path>) -> Result<StoreHeader, VectorStoreError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    if is_binary(&mut reader)? {
+        Ok(read_binary_header(&mut reader)?)
+    } else {
+        // The documents are skipped, but still parsed
+        let store: JsonStoreHeader = serde_json::from_reader(reader)?;
+        check_version(store.version)?;
+        Ok(store.header)
+    }
+}
+
+impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
+    /// Save the store of the index to a file, with a header holding the name of the
+    /// embedding model of the index and its number of dimensions.
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        format: StoreFormat,
+        model_name: &str,
+    ) -> Result<(), VectorStoreError> {
+        self.store.save(
+            path,
+            format,
+            &StoreHeader::new(model_name, self.model().ndims()),
+        )
+    }
+}
+
+impl<M: EmbeddingModel, D: Serialize + for<'a> Deserialize<'a> + Eq> InMemoryVectorIndex<M, D> {
+    /// Load an index saved with [InMemoryVectorIndex::save]. Fails with
+    /// [VectorStoreError::ModelMismatch] if the store was created with another model.
+    pub fn load(
+        path: impl AsRef<Path>,
+        model: M,
+        model_name: &str,
+    ) -> Result<Self, VectorStoreError> {
+        let store = InMemoryVectorStore::load(path, &StoreHeader::new(model_name, model.ndims()))?;
+        Ok(Self::new(model, store))
+    }
+}
+
+/// Check whether the file starts with the magic bytes of the binary format, consuming
+/// them if so.
+fn is_binary(reader: &mut BufReader<File>) -> std::io::Result<bool> {
+    let buffer = reader.fill_buf()?;
+    if buffer.starts_with(MAGIC) {
+        reader.consume(MAGIC.len());
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+fn read_binary_header(reader: &mut impl Read) -> Result<StoreHeader, VectorStoreError> {
+    check_version(read_u32(reader)?)?;
+    Ok(serde_json::from_slice(&read_bytes(reader)?)?)
+}
+
+fn check_version(version: u32) -> Result<(), VectorStoreError> {
+    if version == FORMAT_VERSION {
+        Ok(())
+    } else {
+        Err(invalid_data(format!("unsupported store format version {version}")).into())
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn read_bytes(reader: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let mut buffer = vec![0; read_u32(reader)? as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn read_string(reader: &mut impl Read) -> std::io::Result<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|err| invalid_data(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{read_header, StoreFormat, StoreHeader};
+    use crate::{
+        embeddings::Embedding,
+        vector_store::{in_memory_store::InMemoryVectorStore, VectorStoreError},
+        OneOrMany,
+    };
+
+    fn store() -> InMemoryVectorStore<serde_json::Value> {
+        InMemoryVectorStore::from_documents_with_ids([
+            (
+                "doc0",
+                json!({ "title": "glarb-glarb" }),
+                OneOrMany::one(Embedding {
+                    document: "glarb-glarb".to_string(),
+                    vec: vec![0.5, -0.25, 1.0],
+                }),
+            ),
+            (
+                "doc1",
+                json!({ "title": "flurbo" }),
+                OneOrMany::many([
+                    Embedding {
+                        document: "flurbo".to_string(),
+                        vec: vec![0.125, 0.0, -2.0],
+                    },
+                    Embedding {
+                        document: "a green alien".to_string(),
+                        vec: vec![1.0, 2.0, 3.0],
+                    },
+                ])
+                .unwrap(),
+            ),
+        ])
+    }
+
+    /// Documents of the store as json, sorted by id
+    fn sorted(store: &InMemoryVectorStore<serde_json::Value>) -> Vec<serde_json::Value> {
+        let mut documents = store.iter().collect::<Vec<_>>();
+        documents.sort_by_key(|(id, _)| *id);
+        documents
+            .into_iter()
+            .map(|document| serde_json::to_value(document).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_save_load() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let header = StoreHeader::new("test-model", 3);
+
+        for format in [StoreFormat::Json, StoreFormat::Binary] {
+            let path = dir.path().join(format!("{format:?}"));
+            store().save(&path, format, &header).unwrap();
+
+            assert_eq!(read_header(&path).unwrap(), header);
+
+            // The values are exactly representable as f32
+            let loaded = InMemoryVectorStore::load(&path, &header).unwrap();
+            assert_eq!(sorted(&loaded), sorted(&store()));
+
+            assert!(matches!(
+                InMemoryVectorStore::<serde_json::Value>::load(
+                    &path,
+                    &StoreHeader::new("other-model", 3)
+                ),
+                Err(VectorStoreError::ModelMismatch(_))
+            ));
+        }
+
+        // Embeddings must have the dimensions of the header
+        assert!(matches!(
+            store().save(
+                dir.path().join("invalid"),
+                StoreFormat::Json,
+                &StoreHeader::new("test-model", 4)
+            ),
+            Err(VectorStoreError::ModelMismatch(_))
+        ));
+    }
+}
@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 pub use fastembed::EmbeddingModel as FastembedModel;
-use fastembed::{InitOptions, TextEmbedding};
+pub use fastembed::RerankerModel as FastembedRerankModel;
+use fastembed::{InitOptions, RerankInitOptions, TextEmbedding, TextRerank};
 use rig::{
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
+    rerank::{self, RerankError},
     Embed,
 };
 
@@ -63,6 +65,21 @@ impl Client {
     ) -> EmbeddingsBuilder<EmbeddingModel, D> {
         EmbeddingsBuilder::new(self.embedding_model(model))
     }
+
+    /// Create a local cross-encoder rerank model with the given name.
+    ///
+    /// # Example
+    /// ```
+    /// use rig_fastembed::{Client, FastembedRerankModel};
+    ///
+    /// // Initialize the Fastembed client
+    /// let fastembed_client = Client::new();
+    ///
+    /// let rerank_model = fastembed_client.rerank_model(&FastembedRerankModel::BGERerankerBase);
+    /// ```
+    pub fn rerank_model(&self, model: &FastembedRerankModel) -> RerankModel {
+        RerankModel::new(model)
+    }
 }
 
 #[derive(Clone)]
@@ -120,6 +137,63 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     }
 }
 
+/// Cross-encoder rerank model running locally, which scores each (query, document) pair.
+#[derive(Clone)]
+pub struct RerankModel {
+    reranker: Arc<TextRerank>,
+    pub model: FastembedRerankModel,
+}
+
+impl RerankModel {
+    pub fn new(model: &FastembedRerankModel) -> Self {
+        let reranker = Arc::new(
+            TextRerank::try_new(
+                RerankInitOptions::new(model.to_owned()).with_show_download_progress(true),
+            )
+            .unwrap(),
+        );
+
+        Self {
+            reranker,
+            model: model.to_owned(),
+        }
+    }
+}
+
+impl rerank::Reranker for RerankModel {
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<String>,
+        top_n: usize,
+    ) -> Result<Vec<rerank::RerankResult>, RerankError> {
+        if documents.is_empty() || top_n == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut results = self
+            .reranker
+            .rerank(
+                query,
+                documents.iter().map(String::as_str).collect(),
+                false,
+                None,
+            )
+            .map_err(|err| RerankError::ProviderError(err.to_string()))?;
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        Ok(results
+            .into_iter()
+            .take(top_n)
+            .map(|result| rerank::RerankResult {
+                index: result.index,
+                relevance_score: result.score as f64,
+            })
+            .collect())
+    }
+}
+
 /// As seen on the text embedding model cards file: <https://github.com/Anush008/fastembed-rs/blob/main/src/models/text_embedding.rs>
 pub fn fetch_model_ndims(model: &FastembedModel) -> usize {
     match model {
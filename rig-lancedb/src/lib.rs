@@ -1,14 +1,22 @@
+use arrow_array::RecordBatchIterator;
 use lancedb::{
-    query::{QueryBase, VectorQuery},
+    index::scalar::FullTextSearchQuery,
+    query::{Query, QueryBase, VectorQuery},
     DistanceType,
 };
 use rig::{
-    embeddings::embedding::EmbeddingModel,
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    embeddings::{embedding::EmbeddingModel, Embedding},
+    vector_store::{
+        Filter, KeywordSearchIndex, VectorStoreError, VectorStoreIndex, WritableVectorStore,
+    },
+    OneOrMany,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use utils::{FilterTableColumns, QueryToJson};
+use std::collections::HashSet;
+use utils::{
+    embedding_column, filter_to_sql, rows_to_record_batch, FilterTableColumns, QueryToJson,
+};
 
 mod utils;
 
@@ -100,6 +108,178 @@ impl<M: EmbeddingModel> LanceDbVectorIndex<M> {
 
         query
     }
+
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+
+        let mut query = self
+            .table
+            .vector_search(prompt_embedding.vec.clone())
+            .map_err(lancedb_to_rig_error)?
+            .limit(n)
+            .select(lancedb::query::Select::Columns(
+                self.table
+                    .schema()
+                    .await
+                    .map_err(lancedb_to_rig_error)?
+                    .filter_embeddings(),
+            ));
+
+        if let Some(filter) = filter {
+            query = query.only_if(filter_to_sql(filter)?);
+        }
+
+        self.build_query(query)
+            .execute_query()
+            .await?
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                Ok((
+                    match value.get("_distance") {
+                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
+                        _ => 0.0,
+                    },
+                    match value.get(self.id_field.clone()) {
+                        Some(Value::String(id)) => id.to_string(),
+                        _ => format!("unknown{i}"),
+                    },
+                    serde_json::from_value(value).map_err(serde_to_rig_error)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_text(query).await?;
+
+        let mut query = self
+            .table
+            .query()
+            .select(lancedb::query::Select::Columns(vec![self.id_field.clone()]))
+            .nearest_to(prompt_embedding.vec.clone())
+            .map_err(lancedb_to_rig_error)?
+            .limit(n);
+
+        if let Some(filter) = filter {
+            query = query.only_if(filter_to_sql(filter)?);
+        }
+
+        self.build_query(query)
+            .execute_query()
+            .await?
+            .into_iter()
+            .map(|value| {
+                Ok((
+                    match value.get("distance") {
+                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
+                        _ => 0.0,
+                    },
+                    match value.get(self.id_field.clone()) {
+                        Some(Value::String(id)) => id.to_string(),
+                        _ => "".to_string(),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Build a full-text search query on the table, selecting the given columns.
+    fn keyword_query(
+        &self,
+        query: &str,
+        n: usize,
+        columns: Vec<String>,
+        filter: Option<&Filter>,
+    ) -> Result<Query, VectorStoreError> {
+        let mut query = self
+            .table
+            .query()
+            .full_text_search(FullTextSearchQuery::new(query.to_string()))
+            .select(lancedb::query::Select::Columns(columns))
+            .limit(n);
+
+        if let Some(filter) = filter {
+            query = query.only_if(filter_to_sql(filter)?);
+        }
+
+        Ok(query)
+    }
+
+    /// Get the BM25 score of a row returned by a full-text search.
+    fn keyword_score(value: &Value) -> f64 {
+        match value.get("_score") {
+            Some(Value::Number(score)) => score.as_f64().unwrap_or_default(),
+            _ => 0.0,
+        }
+    }
+
+    /// Shared implementation of the [KeywordSearchIndex] methods returning documents.
+    async fn keyword_search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let columns = self
+            .table
+            .schema()
+            .await
+            .map_err(lancedb_to_rig_error)?
+            .filter_embeddings();
+
+        self.keyword_query(query, n, columns, filter)?
+            .execute_query()
+            .await?
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                Ok((
+                    Self::keyword_score(&value),
+                    match value.get(self.id_field.clone()) {
+                        Some(Value::String(id)) => id.to_string(),
+                        _ => format!("unknown{i}"),
+                    },
+                    serde_json::from_value(value).map_err(serde_to_rig_error)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Shared implementation of the [KeywordSearchIndex] methods returning ids.
+    async fn keyword_search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_query(query, n, vec![self.id_field.clone()], filter)?
+            .execute_query()
+            .await?
+            .into_iter()
+            .map(|value| {
+                Ok((
+                    Self::keyword_score(&value),
+                    match value.get(self.id_field.clone()) {
+                        Some(Value::String(id)) => id.to_string(),
+                        _ => "".to_string(),
+                    },
+                ))
+            })
+            .collect()
+    }
 }
 
 /// See [LanceDB vector search](https://lancedb.github.io/lancedb/search/) for more information.
@@ -199,40 +379,7 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(query).await?;
-
-        let query = self
-            .table
-            .vector_search(prompt_embedding.vec.clone())
-            .map_err(lancedb_to_rig_error)?
-            .limit(n)
-            .select(lancedb::query::Select::Columns(
-                self.table
-                    .schema()
-                    .await
-                    .map_err(lancedb_to_rig_error)?
-                    .filter_embeddings(),
-            ));
-
-        self.build_query(query)
-            .execute_query()
-            .await?
-            .into_iter()
-            .enumerate()
-            .map(|(i, value)| {
-                Ok((
-                    match value.get("_distance") {
-                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
-                        _ => 0.0,
-                    },
-                    match value.get(self.id_field.clone()) {
-                        Some(Value::String(id)) => id.to_string(),
-                        _ => format!("unknown{i}"),
-                    },
-                    serde_json::from_value(value).map_err(serde_to_rig_error)?,
-                ))
-            })
-            .collect()
+        self.search(query, n, None).await
     }
 
     /// Implement the `top_n_ids` method of the `VectorStoreIndex` trait for `LanceDbVectorIndex`.
@@ -257,32 +404,173 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(query).await?;
+        self.search_ids(query, n, None).await
+    }
 
-        let query = self
+    /// Same as `top_n` but only records matching the filter are considered.
+    /// Filter fields refer to the columns of the table. Whether the filter is applied
+    /// before or after the vector search is set by [SearchParams::post_filter].
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    /// Same as `top_n_ids` but only records matching the filter are considered.
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
+
+/// Keyword search using the native [full-text search](https://lancedb.github.io/lancedb/fts/)
+/// of LanceDB, on the columns with a full-text search index. Scores are BM25 scores, higher
+/// being better.
+/// # Example
+/// ```
+/// use lancedb::index::{scalar::FtsIndexBuilder, Index};
+///
+/// // Create the full-text search index on the columns containing text
+/// table.create_index(&["definition"], Index::FTS(FtsIndexBuilder::default())).execute().await?;
+///
+/// let results = vector_store_index.keyword_top_n_ids("E1042", 3).await?;
+/// ```
+impl<M: EmbeddingModel + Sync + Send> KeywordSearchIndex for LanceDbVectorIndex<M> {
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, None).await
+    }
+
+    async fn keyword_top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, None).await
+    }
+
+    /// Same as `keyword_top_n` but only records matching the filter are considered.
+    /// Filter fields refer to the columns of the table.
+    async fn keyword_top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, Some(&filter)).await
+    }
+
+    async fn keyword_top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, Some(&filter)).await
+    }
+}
+
+impl<M: EmbeddingModel + Sync + Send, Doc: Serialize + Send + Sync> WritableVectorStore<Doc>
+    for LanceDbVectorIndex<M>
+{
+    /// Insert or replace documents by id, one row per embedding.
+    /// The fields of the documents are mapped to the columns of the table, the id being
+    /// stored in the id column and the embedding in the embedding column (the one set with
+    /// [SearchParams::column], if any).
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        let schema = self.table.schema().await.map_err(lancedb_to_rig_error)?;
+        let embedding_column = match &self.search_params.column {
+            Some(column) => column.clone(),
+            None => embedding_column(&schema)?,
+        };
+
+        let ids = documents.iter().map(|(id, ..)| id.clone()).collect();
+        let rows = documents
+            .into_iter()
+            .map(|(id, document, embeddings)| {
+                let mut row = match serde_json::to_value(&document)? {
+                    Value::Object(row) => row,
+                    _ => {
+                        return Err(VectorStoreError::DatastoreError(
+                            "Documents must serialize to json objects".into(),
+                        ))
+                    }
+                };
+                row.insert(self.id_field.clone(), Value::String(id));
+                let row = Value::Object(row);
+
+                Ok(embeddings
+                    .into_iter()
+                    .map(|embedding| (row.clone(), embedding.vec))
+                    .collect::<Vec<_>>())
+            })
+            .collect::<Result<Vec<_>, VectorStoreError>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let batch = rows_to_record_batch(schema.clone(), &embedding_column, rows)?;
+
+        <Self as WritableVectorStore<Doc>>::delete_documents(self, ids).await?;
+
+        self.table
+            .add(RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .map_err(lancedb_to_rig_error)
+    }
+
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.table
+            .delete(&filter_to_sql(&Filter::is_in(self.id_field.clone(), ids))?)
+            .await
+            .map_err(lancedb_to_rig_error)
+    }
+
+    /// Delete the rows matching the filter. Filter fields refer to the columns of the table.
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        self.table
+            .delete(&filter_to_sql(&filter)?)
+            .await
+            .map_err(lancedb_to_rig_error)
+    }
+
+    /// Count the distinct values of the id column, since documents have one row per embedding.
+    /// The ids of all the rows are fetched and held in memory, so the cost of counting grows
+    /// with the size of the table.
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        let ids = self
             .table
             .query()
             .select(lancedb::query::Select::Columns(vec![self.id_field.clone()]))
-            .nearest_to(prompt_embedding.vec.clone())
-            .map_err(lancedb_to_rig_error)?
-            .limit(n);
-
-        self.build_query(query)
             .execute_query()
             .await?
             .into_iter()
-            .map(|value| {
-                Ok((
-                    match value.get("distance") {
-                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
-                        _ => 0.0,
-                    },
-                    match value.get(self.id_field.clone()) {
-                        Some(Value::String(id)) => id.to_string(),
-                        _ => "".to_string(),
-                    },
-                ))
-            })
-            .collect()
+            .filter_map(|row| row.get(&self.id_field).cloned())
+            .map(|id| id.to_string())
+            .collect::<HashSet<_>>();
+
+        Ok(ids.len())
     }
 }
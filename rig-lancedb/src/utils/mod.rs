@@ -2,13 +2,15 @@ mod deserializer;
 
 use std::sync::Arc;
 
+use arrow_array::{ArrayRef, FixedSizeListArray, Float64Array, RecordBatch};
 use deserializer::RecordBatchDeserializer;
 use futures::TryStreamExt;
 use lancedb::{
-    arrow::arrow_schema::{DataType, Schema},
+    arrow::arrow_schema::{ArrowError, DataType, Schema, SchemaRef},
     query::ExecutableQuery,
 };
-use rig::vector_store::VectorStoreError;
+use rig::vector_store::{Filter, VectorStoreError};
+use serde_json::Value;
 
 use crate::lancedb_to_rig_error;
 
@@ -32,6 +34,20 @@ impl QueryToJson for lancedb::query::VectorQuery {
     }
 }
 
+impl QueryToJson for lancedb::query::Query {
+    async fn execute_query(&self) -> Result<Vec<serde_json::Value>, VectorStoreError> {
+        let record_batches = self
+            .execute()
+            .await
+            .map_err(lancedb_to_rig_error)?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(lancedb_to_rig_error)?;
+
+        record_batches.deserialize()
+    }
+}
+
 /// Filter out the columns from a table that do not include embeddings. Return the vector of column names.
 pub(crate) trait FilterTableColumns {
     fn filter_embeddings(self) -> Vec<String>;
@@ -52,13 +68,190 @@ impl FilterTableColumns for Arc<Schema> {
     }
 }
 
+/// Get the name of the embedding column of a table: the only `FixedSizeList` of `Float64` column.
+pub(crate) fn embedding_column(schema: &Schema) -> Result<String, VectorStoreError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .filter(|field| {
+            matches!(field.data_type(), DataType::FixedSizeList(inner, ..) if inner.data_type() == &DataType::Float64)
+        })
+        .map(|field| field.name().to_string())
+        .collect::<Vec<_>>();
+
+    match columns.as_slice() {
+        [column] => Ok(column.clone()),
+        _ => Err(VectorStoreError::DatastoreError(
+            format!(
+                "Expected exactly one embedding column, found {}. Set it with `SearchParams::column`",
+                columns.len()
+            )
+            .into(),
+        )),
+    }
+}
+
+/// Convert rows, each made of a json object holding the non-embedding columns and an
+/// embedding, to a record batch with the given table schema.
+pub(crate) fn rows_to_record_batch(
+    schema: SchemaRef,
+    embedding_column: &str,
+    rows: Vec<(Value, Vec<f64>)>,
+) -> Result<RecordBatch, VectorStoreError> {
+    let arrow_to_rig_error = |e: ArrowError| VectorStoreError::DatastoreError(Box::new(e));
+
+    let (embedding_field, (inner_field, ndims)) = schema
+        .fields()
+        .iter()
+        .find(|field| field.name() == embedding_column)
+        .and_then(|field| match field.data_type() {
+            DataType::FixedSizeList(inner, ndims) => Some((field, (inner.clone(), *ndims))),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            VectorStoreError::DatastoreError(
+                format!("Column {embedding_column} is not an embedding column").into(),
+            )
+        })?;
+
+    // The non-embedding columns are decoded from json
+    let columns_schema = Arc::new(Schema::new(
+        schema
+            .fields()
+            .iter()
+            .filter(|field| field.name() != embedding_field.name())
+            .cloned()
+            .collect::<Vec<_>>(),
+    ));
+    let (documents, embeddings): (Vec<_>, Vec<_>) = rows.into_iter().unzip();
+
+    let mut decoder = arrow_json::ReaderBuilder::new(columns_schema)
+        .with_batch_size(documents.len().max(1))
+        .build_decoder()
+        .map_err(arrow_to_rig_error)?;
+    decoder.serialize(&documents).map_err(arrow_to_rig_error)?;
+    let batch = decoder
+        .flush()
+        .map_err(arrow_to_rig_error)?
+        .ok_or_else(|| VectorStoreError::DatastoreError("No rows to insert".into()))?;
+
+    if let Some(embedding) = embeddings.iter().find(|e| e.len() != ndims as usize) {
+        return Err(VectorStoreError::DatastoreError(
+            format!(
+                "Embedding has {} dimensions, expected {ndims}",
+                embedding.len()
+            )
+            .into(),
+        ));
+    }
+    let embeddings: ArrayRef = Arc::new(
+        FixedSizeListArray::try_new(
+            inner_field,
+            ndims,
+            Arc::new(Float64Array::from(
+                embeddings.into_iter().flatten().collect::<Vec<_>>(),
+            )),
+            None,
+        )
+        .map_err(arrow_to_rig_error)?,
+    );
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if field.name() == embedding_field.name() {
+                Ok(embeddings.clone())
+            } else {
+                batch.column_by_name(field.name()).cloned().ok_or_else(|| {
+                    VectorStoreError::DatastoreError(
+                        format!("Missing column {}", field.name()).into(),
+                    )
+                })
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    RecordBatch::try_new(schema, columns).map_err(arrow_to_rig_error)
+}
+
+/// Translate a [Filter] to a LanceDB SQL filter on the columns of a table.
+/// Nested fields (i.e.: struct columns) are separated by `.`.
+pub(crate) fn filter_to_sql(filter: &Filter) -> Result<String, VectorStoreError> {
+    fn column(field: &str) -> String {
+        field
+            .split('.')
+            .map(|part| format!("`{}`", part.replace('`', "``")))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn literal(value: &Value) -> Result<String, VectorStoreError> {
+        match value {
+            Value::String(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+            Value::Number(number) => Ok(number.to_string()),
+            Value::Bool(b) => Ok(b.to_string().to_uppercase()),
+            value => Err(VectorStoreError::UnsupportedFilter(format!(
+                "Cannot compare a column with {value}"
+            ))),
+        }
+    }
+
+    Ok(match filter {
+        Filter::Eq(field, Value::Null) => format!("{} IS NULL", column(field)),
+        Filter::Ne(field, Value::Null) => format!("{} IS NOT NULL", column(field)),
+        Filter::Eq(field, value) => format!("{} = {}", column(field), literal(value)?),
+        Filter::Ne(field, value) => format!(
+            "({0} IS NULL OR {0} <> {1})",
+            column(field),
+            literal(value)?
+        ),
+        Filter::Gt(field, value) => format!("{} > {}", column(field), literal(value)?),
+        Filter::Gte(field, value) => format!("{} >= {}", column(field), literal(value)?),
+        Filter::Lt(field, value) => format!("{} < {}", column(field), literal(value)?),
+        Filter::Lte(field, value) => format!("{} <= {}", column(field), literal(value)?),
+        Filter::In(_, values) if values.is_empty() => "FALSE".to_string(),
+        Filter::In(field, values) => format!(
+            "{} IN ({})",
+            column(field),
+            values
+                .iter()
+                .map(literal)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ")
+        ),
+        Filter::And(filters) if filters.is_empty() => "TRUE".to_string(),
+        Filter::Or(filters) if filters.is_empty() => "FALSE".to_string(),
+        Filter::And(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(filter_to_sql)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" AND ")
+        ),
+        Filter::Or(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(filter_to_sql)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" OR ")
+        ),
+        Filter::Not(filter) => format!("NOT ({})", filter_to_sql(filter)?),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
+    use arrow_array::{Array, FixedSizeListArray, StringArray};
     use lancedb::arrow::arrow_schema::{DataType, Field, Schema};
+    use rig::vector_store::Filter;
+    use serde_json::json;
 
-    use super::FilterTableColumns;
+    use super::{embedding_column, filter_to_sql, rows_to_record_batch, FilterTableColumns};
 
     #[tokio::test]
     async fn test_column_filtering() {
@@ -110,4 +303,69 @@ mod tests {
 
         assert_eq!(columns, vec!["id".to_string(), "my_bool".to_string()])
     }
+
+    #[test]
+    fn test_filter_to_sql() {
+        let filter = Filter::eq("tenant", "o'reilly")
+            .and(Filter::is_in("metadata.year", [1994, 1995]))
+            .and(!Filter::gt("score", 0.5))
+            .and(Filter::ne("draft", true));
+
+        assert_eq!(
+            filter_to_sql(&filter).unwrap(),
+            "(`tenant` = 'o''reilly' AND `metadata`.`year` IN (1994, 1995) AND NOT (`score` > 0.5) AND (`draft` IS NULL OR `draft` <> TRUE))"
+        );
+
+        assert!(filter_to_sql(&Filter::eq("tags", serde_json::json!(["a"]))).is_err());
+    }
+
+    #[test]
+    fn test_rows_to_record_batch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new(
+                "embedding",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 2),
+                false,
+            ),
+            Field::new("definition", DataType::Utf8, true),
+        ]));
+
+        assert_eq!(embedding_column(&schema).unwrap(), "embedding");
+
+        let batch = rows_to_record_batch(
+            schema.clone(),
+            "embedding",
+            vec![
+                (json!({"id": "doc0", "definition": "a"}), vec![0.0, 1.0]),
+                (json!({"id": "doc1"}), vec![2.0, 3.0]),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema(), schema);
+
+        let definitions = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(definitions.value(0), "a");
+        assert!(definitions.is_null(1));
+
+        let embeddings = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<FixedSizeListArray>()
+            .unwrap();
+        assert_eq!(embeddings.value_length(), 2);
+
+        assert!(rows_to_record_batch(
+            schema,
+            "embedding",
+            vec![(json!({"id": "doc0"}), vec![0.0])]
+        )
+        .is_err());
+    }
 }
@@ -3,7 +3,8 @@ use mongodb::bson::{self, doc};
 
 use rig::{
     embeddings::embedding::{Embedding, EmbeddingModel},
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{Filter, VectorStoreError, VectorStoreIndex, WritableVectorStore},
+    OneOrMany,
 };
 use serde::{Deserialize, Serialize};
 
@@ -104,14 +105,26 @@ pub struct MongoDbVectorIndex<M: EmbeddingModel, C: Send + Sync> {
 impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
     /// Vector search stage of aggregation pipeline of mongoDB collection.
     /// To be used by implementations of top_n and top_n_ids methods on VectorStoreIndex trait for MongoDbVectorIndex.
-    fn pipeline_search_stage(&self, prompt_embedding: &Embedding, n: usize) -> bson::Document {
+    /// The filter given per query is combined with the pre-filter of the search params.
+    fn pipeline_search_stage(
+        &self,
+        prompt_embedding: &Embedding,
+        n: usize,
+        query_filter: Option<&Filter>,
+    ) -> Result<bson::Document, VectorStoreError> {
         let SearchParams {
             filter,
             exact,
             num_candidates,
         } = &self.search_params;
 
-        doc! {
+        let filter = match query_filter {
+            Some(query_filter) if filter.is_empty() => mongodb_filter(query_filter)?,
+            Some(query_filter) => doc! { "$and": [filter.clone(), mongodb_filter(query_filter)?] },
+            None => filter.clone(),
+        };
+
+        Ok(doc! {
           "$vectorSearch": {
             "index": &self.index_name,
             "path": self.embedded_field.clone(),
@@ -121,7 +134,7 @@ impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
             "filter": filter,
             "exact": exact.unwrap_or(false)
           }
-        }
+        })
     }
 
     /// Score declaration stage of aggregation pipeline of mongoDB collection.
@@ -220,21 +233,20 @@ impl SearchParams {
     }
 }
 
-impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
-    for MongoDbVectorIndex<M, C>
-{
-    /// Implement the `top_n` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
-    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> MongoDbVectorIndex<M, C> {
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
         let prompt_embedding = self.model.embed_text(query).await?;
 
         let mut cursor = self
             .collection
             .aggregate([
-                self.pipeline_search_stage(&prompt_embedding, n),
+                self.pipeline_search_stage(&prompt_embedding, n, filter)?,
                 self.pipeline_score_stage(),
                 {
                     doc! {
@@ -268,18 +280,19 @@ impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
         Ok(results)
     }
 
-    /// Implement the `top_n_ids` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
-    async fn top_n_ids(
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
         let prompt_embedding = self.model.embed_text(query).await?;
 
         let mut cursor = self
             .collection
             .aggregate([
-                self.pipeline_search_stage(&prompt_embedding, n),
+                self.pipeline_search_stage(&prompt_embedding, n, filter)?,
                 self.pipeline_score_stage(),
                 doc! {
                     "$project": {
@@ -311,3 +324,155 @@ impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
         Ok(results)
     }
 }
+
+/// Translate a [Filter] to a MongoDB query filter.
+/// See [MongoDB vector search pre-filter](https://www.mongodb.com/docs/atlas/atlas-vector-search/vector-search-stage/#atlas-vector-search-pre-filter)
+/// for the supported operators. Filtered fields must be indexed as `filter` fields in the vector index.
+fn mongodb_filter(filter: &Filter) -> Result<bson::Document, VectorStoreError> {
+    fn comparison(
+        field: &str,
+        op: &str,
+        value: &serde_json::Value,
+    ) -> Result<bson::Document, VectorStoreError> {
+        let value =
+            bson::to_bson(value).map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+        Ok(doc! { field: { op: value } })
+    }
+
+    Ok(match filter {
+        Filter::Eq(field, value) => comparison(field, "$eq", value)?,
+        Filter::Ne(field, value) => comparison(field, "$ne", value)?,
+        Filter::In(field, values) => comparison(field, "$in", &values.clone().into())?,
+        Filter::Gt(field, value) => comparison(field, "$gt", value)?,
+        Filter::Gte(field, value) => comparison(field, "$gte", value)?,
+        Filter::Lt(field, value) => comparison(field, "$lt", value)?,
+        Filter::Lte(field, value) => comparison(field, "$lte", value)?,
+        Filter::And(filters) if filters.is_empty() => doc! {},
+        Filter::Or(filters) if filters.is_empty() => {
+            return Err(VectorStoreError::UnsupportedFilter(
+                "MongoDB does not support an empty $or".to_string(),
+            ))
+        }
+        Filter::And(filters) => doc! {
+            "$and": filters.iter().map(mongodb_filter).collect::<Result<Vec<_>, _>>()?
+        },
+        Filter::Or(filters) => doc! {
+            "$or": filters.iter().map(mongodb_filter).collect::<Result<Vec<_>, _>>()?
+        },
+        Filter::Not(filter) => doc! { "$nor": [mongodb_filter(filter)?] },
+    })
+}
+
+impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
+    for MongoDbVectorIndex<M, C>
+{
+    /// Implement the `top_n` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, None).await
+    }
+
+    /// Implement the `top_n_ids` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
+
+    /// Same as `top_n` but only documents matching the filter are considered.
+    /// The filter is combined with the pre-filter of the [SearchParams].
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    /// Same as `top_n_ids` but only documents matching the filter are considered.
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
+
+impl<M: EmbeddingModel + Sync + Send, C: Sync + Send, Doc: Serialize + Send + Sync>
+    WritableVectorStore<Doc> for MongoDbVectorIndex<M, C>
+{
+    /// Insert or replace documents by `_id`. The embedding is stored in the embedded field
+    /// of the vector index, so each document must have exactly one embedding.
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        let collection = self.collection.clone_with_type::<bson::Document>();
+
+        for (id, document, embeddings) in documents {
+            if embeddings.len() > 1 {
+                return Err(VectorStoreError::DatastoreError(
+                    format!("Document {id} has more than one embedding").into(),
+                ));
+            }
+
+            let mut document = bson::to_document(&document)
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            document.insert("_id", id.clone());
+            document.insert(self.embedded_field.clone(), embeddings.first().vec);
+
+            collection
+                .replace_one(doc! { "_id": id }, document)
+                .upsert(true)
+                .await
+                .map_err(mongodb_to_rig_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.collection
+            .delete_many(doc! { "_id": { "$in": ids } })
+            .await
+            .map_err(mongodb_to_rig_error)?;
+
+        Ok(())
+    }
+
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        // An empty `$or` is rejected by MongoDB, and matches no document anyway
+        if matches!(&filter, Filter::Or(filters) if filters.is_empty()) {
+            return Ok(());
+        }
+
+        self.collection
+            .delete_many(mongodb_filter(&filter)?)
+            .await
+            .map_err(mongodb_to_rig_error)?;
+
+        Ok(())
+    }
+
+    /// Count the documents of the collection. Each document holds a single embedding and is
+    /// identified by its `_id`, so this is the number of distinct document ids.
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        self.collection
+            .count_documents(doc! {})
+            .await
+            .map(|count| count as usize)
+            .map_err(mongodb_to_rig_error)
+    }
+}
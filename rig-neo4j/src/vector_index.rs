@@ -7,11 +7,17 @@
 use neo4rs::{Graph, Query};
 use rig::{
     embeddings::{Embedding, EmbeddingModel},
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{Filter, VectorStoreError, VectorStoreIndex, WritableVectorStore},
+    OneOrMany,
 };
 use serde::{de::Error, Deserialize, Serialize};
 
-use crate::Neo4jClient;
+use crate::{neo4j_to_rig_error, Neo4jClient, ToBoltType};
+
+/// Node property holding the id of the document a node was created from, for nodes inserted
+/// with [WritableVectorStore::upsert_documents]. Searches return this id instead of the
+/// element id of the node.
+pub const DOCUMENT_ID_PROPERTY: &str = "_document_id";
 
 pub struct Neo4jVectorIndex<M: EmbeddingModel> {
     graph: Graph,
@@ -101,6 +107,13 @@ const BASE_VECTOR_SEARCH_QUERY: &str = "
     YIELD node, score
 ";
 
+const GET_INDEX_LABEL_QUERY: &str = "
+    SHOW VECTOR INDEXES
+    YIELD name, labelsOrTypes
+    WHERE name=$index_name
+    RETURN labelsOrTypes[0] as label
+";
+
 impl<M: EmbeddingModel> Neo4jVectorIndex<M> {
     pub fn new(
         graph: Graph,
@@ -124,17 +137,48 @@ impl<M: EmbeddingModel> Neo4jVectorIndex<M> {
     /// CALL db.index.vector.queryNodes($index_name, $num_candidates, $queryVector)
     /// YIELD node, score
     /// WHERE {where_clause}
-    /// RETURN score, ID(node) as element_id, node {.*, embedding:null } as node
+    /// RETURN score, ID(node) as element_id, node.`_document_id` as document_id, node {.*, embedding:null } as node
+    /// ORDER BY score DESC
+    /// LIMIT $limit
     /// ```
+    ///
+    /// Without filter, `n` candidates are fetched from the index. With the filter of the
+    /// [SearchParams], `n` times [SearchParams::candidates_multiplier] candidates are fetched,
+    /// since the filter is applied after the vector search.
     pub fn build_vector_search_query(
         &self,
         prompt_embedding: Embedding,
         return_node: bool,
         n: usize,
     ) -> Query {
-        let where_clause = match &self.search_params.post_vector_search_filter {
-            Some(filter) => format!("WHERE {}", filter),
-            None => "".to_string(),
+        self.build_filtered_vector_search_query(prompt_embedding, return_node, n, None, vec![])
+    }
+
+    /// Same as [Self::build_vector_search_query], with an additional condition (a translated
+    /// [Filter] and its parameters, see [cypher_condition]) combined with the post-filter of
+    /// the search params.
+    fn build_filtered_vector_search_query(
+        &self,
+        prompt_embedding: Embedding,
+        return_node: bool,
+        n: usize,
+        filter: Option<String>,
+        params: Vec<serde_json::Value>,
+    ) -> Query {
+        let num_candidates =
+            if filter.is_some() || self.search_params.post_vector_search_filter.is_some() {
+                n.saturating_mul(self.search_params.candidates_multiplier.max(1))
+            } else {
+                n
+            };
+
+        let where_clause = match (&self.search_params.post_vector_search_filter, filter) {
+            (Some(search_filter), Some(filter)) => {
+                format!("WHERE ({}) AND {}", search_filter, filter)
+            }
+            (Some(filter), None) => format!("WHERE {}", filter),
+            (None, Some(filter)) => format!("WHERE {}", filter),
+            (None, None) => "".to_string(),
         };
 
         // Propertiy containing the embedding vectors are excluded from the returned node
@@ -142,10 +186,13 @@ impl<M: EmbeddingModel> Neo4jVectorIndex<M> {
             "\
             {}\
             \t{}\n\
-            \tRETURN score, ID(node) as element_id {}
+            \tRETURN score, ID(node) as element_id, node.`{}` as document_id {}\n\
+            \tORDER BY score DESC\n\
+            \tLIMIT $limit
             ",
             BASE_VECTOR_SEARCH_QUERY,
             where_clause,
+            DOCUMENT_ID_PROPERTY,
             if return_node {
                 format!(
                     ", node {{.*, {}:null }} as node",
@@ -158,11 +205,174 @@ impl<M: EmbeddingModel> Neo4jVectorIndex<M> {
 
         tracing::debug!("Query before params: {}", query);
 
-        Query::new(query)
+        bind_filter_params(Query::new(query), params)
             .param("queryVector", prompt_embedding.vec)
-            .param("num_candidates", n as i64)
+            .param("num_candidates", num_candidates as i64)
+            .param("limit", n as i64)
             .param("index_name", self.index_config.index_name.clone())
     }
+
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + std::marker::Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = self.embedding_model.embed_text(query).await?;
+        let mut params = Vec::new();
+        let filter = filter
+            .map(|filter| cypher_condition(filter, &mut params))
+            .transpose()?;
+        let query =
+            self.build_filtered_vector_search_query(prompt_embedding, true, n, filter, params);
+
+        let rows = Neo4jClient::execute_and_collect::<RowResultNode<T>>(&self.graph, query).await?;
+
+        let results = rows
+            .into_iter()
+            .map(|row| {
+                let id = row
+                    .document_id
+                    .unwrap_or_else(|| row.element_id.to_string());
+                (row.score, id, row.node)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(results)
+    }
+
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let prompt_embedding = self.embedding_model.embed_text(query).await?;
+
+        let mut params = Vec::new();
+        let filter = filter
+            .map(|filter| cypher_condition(filter, &mut params))
+            .transpose()?;
+        let query =
+            self.build_filtered_vector_search_query(prompt_embedding, false, n, filter, params);
+
+        let rows = Neo4jClient::execute_and_collect::<RowResult>(&self.graph, query).await?;
+
+        let results = rows
+            .into_iter()
+            .map(|row| {
+                let id = row
+                    .document_id
+                    .unwrap_or_else(|| row.element_id.to_string());
+                (row.score, id)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(results)
+    }
+
+    /// Get the label of the nodes covered by the vector index.
+    async fn index_label(&self) -> Result<String, VectorStoreError> {
+        Neo4jClient::execute_and_collect::<String>(
+            &self.graph,
+            neo4rs::query(GET_INDEX_LABEL_QUERY)
+                .param("index_name", self.index_config.index_name.clone()),
+        )
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            VectorStoreError::DatastoreError(
+                format!("Index `{}` not found", self.index_config.index_name).into(),
+            )
+        })
+    }
+}
+
+/// Escape a label or property name to be used in a Cypher query.
+fn escape_name(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// Translate a [Filter] to a Cypher condition on the properties of the returned `node`.
+/// The values of the filter are pushed to `params` and referenced as `$filter_<index>`.
+fn cypher_condition(
+    filter: &Filter,
+    params: &mut Vec<serde_json::Value>,
+) -> Result<String, VectorStoreError> {
+    fn property(field: &str) -> String {
+        field.split('.').fold("node".to_string(), |property, part| {
+            format!("{}.`{}`", property, part.replace('`', "``"))
+        })
+    }
+
+    fn param(
+        value: &serde_json::Value,
+        params: &mut Vec<serde_json::Value>,
+    ) -> Result<String, VectorStoreError> {
+        if value.is_object() {
+            return Err(VectorStoreError::UnsupportedFilter(format!(
+                "Cannot compare a property with {value}"
+            )));
+        }
+        params.push(value.clone());
+        Ok(format!("$filter_{}", params.len() - 1))
+    }
+
+    fn join(
+        filters: &[Filter],
+        separator: &str,
+        params: &mut Vec<serde_json::Value>,
+    ) -> Result<String, VectorStoreError> {
+        Ok(format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| cypher_condition(filter, params))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(separator)
+        ))
+    }
+
+    Ok(match filter {
+        Filter::Eq(field, serde_json::Value::Null) => format!("{} IS NULL", property(field)),
+        Filter::Ne(field, serde_json::Value::Null) => format!("{} IS NOT NULL", property(field)),
+        Filter::Eq(field, value) => format!("{} = {}", property(field), param(value, params)?),
+        Filter::Ne(field, value) => format!(
+            "({0} IS NULL OR {0} <> {1})",
+            property(field),
+            param(value, params)?
+        ),
+        Filter::In(field, values) => format!(
+            "{} IN {}",
+            property(field),
+            param(&values.clone().into(), params)?
+        ),
+        Filter::Gt(field, value) => format!("{} > {}", property(field), param(value, params)?),
+        Filter::Gte(field, value) => format!("{} >= {}", property(field), param(value, params)?),
+        Filter::Lt(field, value) => format!("{} < {}", property(field), param(value, params)?),
+        Filter::Lte(field, value) => format!("{} <= {}", property(field), param(value, params)?),
+        Filter::And(filters) if filters.is_empty() => "true".to_string(),
+        Filter::Or(filters) if filters.is_empty() => "false".to_string(),
+        Filter::And(filters) => join(filters, " AND ", params)?,
+        Filter::Or(filters) => join(filters, " OR ", params)?,
+        // Comparisons with missing properties are null, which should not match the negated filter either
+        Filter::Not(filter) => {
+            format!("NOT coalesce({}, false)", cypher_condition(filter, params)?)
+        }
+    })
+}
+
+/// Bind the parameters of a condition built by [cypher_condition] to a query.
+fn bind_filter_params(query: Query, params: Vec<serde_json::Value>) -> Query {
+    params
+        .into_iter()
+        .enumerate()
+        .fold(query, |query, (index, value)| {
+            query.param(&format!("filter_{index}"), value.to_bolt_type())
+        })
 }
 
 /// Search parameters for a vector search. Neo4j currently only supports post-vector-search filtering.
@@ -170,13 +380,20 @@ pub struct SearchParams {
     /// Sets the **post-filter** field of the search params. Uses a WHERE clause.
     /// See [Neo4j WHERE clause](https://neo4j.com/docs/cypher-manual/current/clauses/where/) for more information.
     post_vector_search_filter: Option<String>,
+    /// Number of candidates fetched from the index per requested result when the search is
+    /// filtered. Defaults to [SearchParams::DEFAULT_CANDIDATES_MULTIPLIER].
+    candidates_multiplier: usize,
 }
 
 impl SearchParams {
+    /// Default number of candidates fetched per requested result by filtered searches.
+    pub const DEFAULT_CANDIDATES_MULTIPLIER: usize = 10;
+
     /// Initializes a new `SearchParams` with default values.
     pub fn new(filter: Option<String>) -> Self {
         Self {
             post_vector_search_filter: filter,
+            candidates_multiplier: Self::DEFAULT_CANDIDATES_MULTIPLIER,
         }
     }
 
@@ -184,6 +401,15 @@ impl SearchParams {
         self.post_vector_search_filter = Some(filter);
         self
     }
+
+    /// Set the number of candidates fetched from the index per requested result when the
+    /// search is filtered. Filters are applied to the candidates, so a higher multiplier
+    /// makes it more likely to find `n` matching nodes with selective filters, at the cost
+    /// of a slower search.
+    pub fn candidates_multiplier(mut self, candidates_multiplier: usize) -> Self {
+        self.candidates_multiplier = candidates_multiplier;
+        self
+    }
 }
 
 impl Default for SearchParams {
@@ -196,6 +422,7 @@ impl Default for SearchParams {
 pub struct RowResultNode<T> {
     score: f64,
     element_id: i64,
+    document_id: Option<String>,
     node: T,
 }
 
@@ -203,6 +430,7 @@ pub struct RowResultNode<T> {
 struct RowResult {
     score: f64,
     element_id: i64,
+    document_id: Option<String>,
 }
 
 impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for Neo4jVectorIndex<M> {
@@ -225,17 +453,7 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for Neo4jVec
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = self.embedding_model.embed_text(query).await?;
-        let query = self.build_vector_search_query(prompt_embedding, true, n);
-
-        let rows = Neo4jClient::execute_and_collect::<RowResultNode<T>>(&self.graph, query).await?;
-
-        let results = rows
-            .into_iter()
-            .map(|row| (row.score, row.element_id.to_string(), row.node))
-            .collect::<Vec<_>>();
-
-        Ok(results)
+        self.search(query, n, None).await
     }
 
     /// Get the top n ids and scores matching the query. Runs faster than top_n since it doesn't need to transfer and parse
@@ -245,17 +463,144 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for Neo4jVec
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = self.embedding_model.embed_text(query).await?;
+        self.search_ids(query, n, None).await
+    }
 
-        let query = self.build_vector_search_query(prompt_embedding, false, n);
+    /// Same as `top_n` but only nodes matching the filter are returned.
+    /// Like the filter of the [SearchParams], it is applied after the vector search to
+    /// `n` times [SearchParams::candidates_multiplier] candidates, so fewer than `n` nodes
+    /// may be returned when few nodes match the filter.
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + std::marker::Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
 
-        let rows = Neo4jClient::execute_and_collect::<RowResult>(&self.graph, query).await?;
+    /// Same as `top_n_ids` but only nodes matching the filter are returned. The filter is
+    /// applied after the vector search, as with [Self::top_n_with_filter].
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
 
-        let results = rows
+impl<M: EmbeddingModel + std::marker::Sync + Send, Doc: Serialize + Send + Sync>
+    WritableVectorStore<Doc> for Neo4jVectorIndex<M>
+{
+    /// Insert or replace documents by id, as nodes with the label of the vector index.
+    /// The fields of a document are stored as properties of its node, which holds a single
+    /// embedding, so each document must have exactly one embedding.
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        let rows = documents
             .into_iter()
-            .map(|row| (row.score, row.element_id.to_string()))
-            .collect::<Vec<_>>();
+            .map(|(id, document, embeddings)| {
+                if embeddings.len() > 1 {
+                    return Err(VectorStoreError::DatastoreError(
+                        format!("Document {id} has more than one embedding").into(),
+                    ));
+                }
+
+                Ok(serde_json::json!({
+                    "id": id,
+                    "properties": serde_json::to_value(&document)?,
+                    "embedding": embeddings.first().vec,
+                }))
+            })
+            .collect::<Result<Vec<_>, VectorStoreError>>()?;
 
-        Ok(results)
+        let query = format!(
+            "
+            UNWIND $rows AS row
+            MERGE (node:{label} {{{id}: row.id}})
+            SET node = row.properties, node.{id} = row.id, node.{embedding} = row.embedding
+            ",
+            label = escape_name(&self.index_label().await?),
+            id = escape_name(DOCUMENT_ID_PROPERTY),
+            embedding = escape_name(&self.index_config.embedding_property),
+        );
+
+        self.graph
+            .run(neo4rs::query(&query).param("rows", rows.to_bolt_type()))
+            .await
+            .map_err(neo4j_to_rig_error)
+    }
+
+    /// Delete nodes by document id, or by element id for nodes not inserted with
+    /// [WritableVectorStore::upsert_documents].
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let query = format!(
+            "
+            MATCH (node:{label})
+            WHERE node.{id} IN $ids OR (node.{id} IS NULL AND toString(ID(node)) IN $ids)
+            DETACH DELETE node
+            ",
+            label = escape_name(&self.index_label().await?),
+            id = escape_name(DOCUMENT_ID_PROPERTY),
+        );
+
+        self.graph
+            .run(neo4rs::query(&query).param("ids", ids))
+            .await
+            .map_err(neo4j_to_rig_error)
+    }
+
+    /// Delete the nodes of the vector index whose properties match the filter.
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        let mut params = Vec::new();
+        let query = format!(
+            "
+            MATCH (node:{label})
+            WHERE {condition}
+            DETACH DELETE node
+            ",
+            label = escape_name(&self.index_label().await?),
+            condition = cypher_condition(&filter, &mut params)?,
+        );
+
+        self.graph
+            .run(bind_filter_params(neo4rs::query(&query), params))
+            .await
+            .map_err(neo4j_to_rig_error)
+    }
+
+    /// Count the distinct document ids of the nodes of the vector index, i.e.: the nodes with
+    /// its label and embedding property. Nodes without a document id are identified by their id.
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        let query = format!(
+            "
+            MATCH (node:{label})
+            WHERE node.{embedding} IS NOT NULL
+            RETURN count(DISTINCT coalesce(node.{id}, ID(node))) as count
+            ",
+            label = escape_name(&self.index_label().await?),
+            embedding = escape_name(&self.index_config.embedding_property),
+            id = escape_name(DOCUMENT_ID_PROPERTY),
+        );
+
+        Ok(
+            Neo4jClient::execute_and_collect::<i64>(&self.graph, neo4rs::query(&query))
+                .await?
+                .into_iter()
+                .next()
+                .unwrap_or_default() as usize,
+        )
     }
 }
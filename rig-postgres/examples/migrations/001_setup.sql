@@ -4,6 +4,7 @@ CREATE EXTENSION IF NOT EXISTS vector;
 -- create table with embeddings using 1536 dimensions (text-embedding-3-small)
 CREATE TABLE documents (
   id uuid DEFAULT gen_random_uuid(), -- we can have repeated entries
+  document_id text, -- id of the documents inserted with `upsert_documents`
   document jsonb NOT NULL,
   embedded_text text NOT NULL,
   embedding vector(1536)
@@ -2,12 +2,14 @@ use std::fmt::Display;
 
 use rig::{
     embeddings::{Embedding, EmbeddingModel},
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{
+        Filter, KeywordSearchIndex, VectorStoreError, VectorStoreIndex, WritableVectorStore,
+    },
     Embed, OneOrMany,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::PgPool;
+use sqlx::{postgres::PgArguments, query::QueryAs, types::Json, PgPool, Postgres};
 use uuid::Uuid;
 
 pub struct PostgresVectorStore<Model: EmbeddingModel> {
@@ -15,6 +17,7 @@ pub struct PostgresVectorStore<Model: EmbeddingModel> {
     pg_pool: PgPool,
     documents_table: String,
     distance_function: PgVectorDistanceFunction,
+    text_search_config: String,
 }
 
 /* PgVector supported distances
@@ -47,9 +50,13 @@ impl Display for PgVectorDistanceFunction {
     }
 }
 
+/// Id of the document of a row returned by searches: the `document_id` of the rows inserted
+/// with [WritableVectorStore::upsert_documents], the `id` of the other rows.
+const DOCUMENT_ID: &str = "coalesce(document_id, id::text)";
+
 #[derive(Debug, Deserialize, sqlx::FromRow)]
 pub struct SearchResult {
-    id: Uuid,
+    id: String,
     document: Value,
     //embedded_text: String,
     distance: f64,
@@ -57,7 +64,7 @@ pub struct SearchResult {
 
 #[derive(Debug, Deserialize, sqlx::FromRow)]
 pub struct SearchResultOnlyId {
-    id: Uuid,
+    id: String,
     distance: f64,
 }
 
@@ -65,7 +72,7 @@ impl SearchResult {
     pub fn into_result<T: DeserializeOwned>(self) -> Result<(f64, String, T), VectorStoreError> {
         let document: T =
             serde_json::from_value(self.document).map_err(VectorStoreError::JsonError)?;
-        Ok((self.distance, self.id.to_string(), document))
+        Ok((self.distance, self.id, document))
     }
 }
 
@@ -81,6 +88,7 @@ impl<Model: EmbeddingModel> PostgresVectorStore<Model> {
             pg_pool,
             documents_table: documents_table.unwrap_or(String::from("documents")),
             distance_function,
+            text_search_config: String::from("english"),
         }
     }
 
@@ -88,28 +96,185 @@ impl<Model: EmbeddingModel> PostgresVectorStore<Model> {
         Self::new(model, pg_pool, None, PgVectorDistanceFunction::Cosine)
     }
 
-    fn search_query_full(&self) -> String {
-        self.search_query(true)
+    /// Set the [text search configuration](https://www.postgresql.org/docs/current/textsearch-configuration.html)
+    /// used by keyword searches on the `embedded_text` column. Defaults to `english`.
+    pub fn with_text_search_config(mut self, text_search_config: &str) -> Self {
+        self.text_search_config = text_search_config.to_string();
+        self
+    }
+
+    fn search_query_full(&self, condition: Option<&str>) -> String {
+        self.search_query(true, condition)
     }
-    fn search_query_only_ids(&self) -> String {
-        self.search_query(false)
+    fn search_query_only_ids(&self, condition: Option<&str>) -> String {
+        self.search_query(false, condition)
     }
 
-    fn search_query(&self, with_document: bool) -> String {
+    fn search_query(&self, with_document: bool, condition: Option<&str>) -> String {
         let document = if with_document { ", document" } else { "" };
+        let condition = condition
+            .map(|condition| format!("WHERE {condition} "))
+            .unwrap_or_default();
         format!(
             "
             SELECT id{}, distance FROM ( \
-              SELECT DISTINCT ON (id) id{}, embedding {} $1 as distance \
+              SELECT DISTINCT ON (id) {DOCUMENT_ID} as id{}, embedding {} $1 as distance \
               FROM {} \
-              ORDER BY id, distance \
+              {}ORDER BY id, distance \
             ) as d \
             ORDER BY distance \
             LIMIT $2",
-            document, document, self.distance_function, self.documents_table
+            document, document, self.distance_function, self.documents_table, condition
+        )
+    }
+
+    /// Full-text search query on the `embedded_text` column, ranking the documents containing
+    /// any of the terms of the query (bound as `$1`) with `ts_rank`. The rank is returned in
+    /// the `distance` column to share the search result types, higher being better.
+    fn keyword_search_query(&self, with_document: bool, condition: Option<&str>) -> String {
+        let document = if with_document { ", document" } else { "" };
+        let condition = condition
+            .map(|condition| format!("AND {condition} "))
+            .unwrap_or_default();
+        let config = format!(
+            "'{}'::regconfig",
+            self.text_search_config.replace('\'', "''")
+        );
+        // The terms of the query are OR-ed rather than AND-ed as with `plainto_tsquery`
+        let ts_query = format!("replace(plainto_tsquery({config}, $1)::text, '&', '|')::tsquery");
+        let ts_vector = format!("to_tsvector({config}, embedded_text)");
+        format!(
+            "
+            SELECT id{document}, distance FROM ( \
+              SELECT DISTINCT ON (id) {DOCUMENT_ID} as id{document}, ts_rank({ts_vector}, {ts_query})::float8 as distance \
+              FROM {} \
+              WHERE {ts_vector} @@ {ts_query} {condition}\
+              ORDER BY id, distance DESC \
+            ) as d \
+            ORDER BY distance DESC \
+            LIMIT $2",
+            self.documents_table
         )
     }
 
+    /// Shared implementation of the [KeywordSearchIndex] methods returning documents.
+    async fn keyword_search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let mut params = Vec::new();
+        let condition = filter.map(|filter| filter_condition(filter, 2, &mut params));
+        let sql = self.keyword_search_query(true, condition.as_deref());
+
+        let rows: Vec<SearchResult> = bind_filter_params(
+            sqlx::query_as(sql.as_str()).bind(query).bind(n as i64),
+            params,
+        )
+        .fetch_all(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .flat_map(SearchResult::into_result)
+            .collect())
+    }
+
+    /// Shared implementation of the [KeywordSearchIndex] methods returning ids.
+    async fn keyword_search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let mut params = Vec::new();
+        let condition = filter.map(|filter| filter_condition(filter, 2, &mut params));
+        let sql = self.keyword_search_query(false, condition.as_deref());
+
+        let rows: Vec<SearchResultOnlyId> = bind_filter_params(
+            sqlx::query_as(sql.as_str()).bind(query).bind(n as i64),
+            params,
+        )
+        .fetch_all(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(rows.into_iter().map(|row| (row.distance, row.id)).collect())
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<pgvector::Vector, VectorStoreError> {
+        Ok(self
+            .model
+            .embed_text(query)
+            .await?
+            .vec
+            .iter()
+            .map(|&x| x as f32)
+            .collect::<Vec<f32>>()
+            .into())
+    }
+
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let embedded_query = self.embed_query(query).await?;
+
+        let mut params = Vec::new();
+        let condition = filter.map(|filter| filter_condition(filter, 2, &mut params));
+        let sql = self.search_query_full(condition.as_deref());
+
+        let rows: Vec<SearchResult> = bind_filter_params(
+            sqlx::query_as(sql.as_str())
+                .bind(embedded_query)
+                .bind(n as i64),
+            params,
+        )
+        .fetch_all(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        let rows: Vec<(f64, String, T)> = rows
+            .into_iter()
+            .flat_map(SearchResult::into_result)
+            .collect();
+
+        Ok(rows)
+    }
+
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let embedded_query = self.embed_query(query).await?;
+
+        let mut params = Vec::new();
+        let condition = filter.map(|filter| filter_condition(filter, 2, &mut params));
+        let sql = self.search_query_only_ids(condition.as_deref());
+
+        let rows: Vec<SearchResultOnlyId> = bind_filter_params(
+            sqlx::query_as(sql.as_str())
+                .bind(embedded_query)
+                .bind(n as i64),
+            params,
+        )
+        .fetch_all(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        let rows: Vec<(f64, String)> = rows.into_iter().map(|row| (row.distance, row.id)).collect();
+
+        Ok(rows)
+    }
+
     pub async fn insert_documents<Doc: Serialize + Embed + Send>(
         &self,
         documents: Vec<(Doc, OneOrMany<Embedding>)>,
@@ -143,6 +308,96 @@ impl<Model: EmbeddingModel> PostgresVectorStore<Model> {
     }
 }
 
+/// Query parameter used by a filter condition.
+enum FilterParam {
+    /// Path of a field of the `document` column, bound as `text[]`.
+    Path(Vec<String>),
+    /// Value compared with a field, bound as `jsonb`.
+    Value(Value),
+}
+
+/// Translate a [Filter] to a SQL condition on the `document` column.
+/// Fields and values are bound as query parameters, which are numbered after
+/// the `offset` parameters of the query (e.g.: the query embedding and limit).
+fn filter_condition(filter: &Filter, offset: usize, params: &mut Vec<FilterParam>) -> String {
+    fn comparison(
+        field: &str,
+        op: &str,
+        value: &Value,
+        offset: usize,
+        params: &mut Vec<FilterParam>,
+    ) -> String {
+        params.push(FilterParam::Path(
+            field.split('.').map(str::to_string).collect(),
+        ));
+        let path = format!("(document #> ${})", params.len() + offset);
+        params.push(FilterParam::Value(value.clone()));
+        let value = format!("${}", params.len() + offset);
+
+        match op {
+            "=" => format!("{path} = {value}"),
+            "<>" => format!("{path} IS DISTINCT FROM {value}"),
+            // jsonb orders values of different types, so only same type values are compared
+            op => format!("(jsonb_typeof({path}) = jsonb_typeof({value}) AND {path} {op} {value})"),
+        }
+    }
+
+    match filter {
+        Filter::Eq(field, value) => comparison(field, "=", value, offset, params),
+        Filter::Ne(field, value) => comparison(field, "<>", value, offset, params),
+        Filter::Gt(field, value) => comparison(field, ">", value, offset, params),
+        Filter::Gte(field, value) => comparison(field, ">=", value, offset, params),
+        Filter::Lt(field, value) => comparison(field, "<", value, offset, params),
+        Filter::Lte(field, value) => comparison(field, "<=", value, offset, params),
+        Filter::In(field, values) => filter_condition(
+            &Filter::Or(
+                values
+                    .iter()
+                    .map(|value| Filter::Eq(field.clone(), value.clone()))
+                    .collect(),
+            ),
+            offset,
+            params,
+        ),
+        Filter::And(filters) if filters.is_empty() => "TRUE".to_string(),
+        Filter::Or(filters) if filters.is_empty() => "FALSE".to_string(),
+        Filter::And(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| filter_condition(filter, offset, params))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        ),
+        Filter::Or(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| filter_condition(filter, offset, params))
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        ),
+        // Missing fields make comparisons NULL, which should not match the negated filter either
+        Filter::Not(filter) => format!(
+            "NOT COALESCE({}, FALSE)",
+            filter_condition(filter, offset, params)
+        ),
+    }
+}
+
+fn bind_filter_params<'q, O>(
+    mut query: QueryAs<'q, Postgres, O, PgArguments>,
+    params: Vec<FilterParam>,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    for param in params {
+        query = match param {
+            FilterParam::Path(path) => query.bind(path),
+            FilterParam::Value(value) => query.bind(Json(value)),
+        };
+    }
+    query
+}
+
 impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
     /// Get the top n documents based on the distance to the given query.
     /// The result is a list of tuples of the form (score, id, document)
@@ -151,59 +406,184 @@ impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let embedded_query: pgvector::Vector = self
-            .model
-            .embed_text(query)
-            .await?
-            .vec
-            .iter()
-            .map(|&x| x as f32)
-            .collect::<Vec<f32>>()
-            .into();
+        self.search(query, n, None).await
+    }
 
-        let rows: Vec<SearchResult> = sqlx::query_as(self.search_query_full().as_str())
-            .bind(embedded_query)
-            .bind(n as i64)
-            .fetch_all(&self.pg_pool)
-            .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+    /// Same as `top_n` but returns the document ids only.
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
 
-        let rows: Vec<(f64, String, T)> = rows
-            .into_iter()
-            .flat_map(SearchResult::into_result)
-            .collect();
+    /// Same as `top_n` but only documents matching the filter are considered.
+    /// Filter fields refer to (nested) fields of the `document` column.
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
 
-        Ok(rows)
+    /// Same as `top_n_ids` but only documents matching the filter are considered.
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
     }
+}
 
-    /// Same as `top_n` but returns the document ids only.
-    async fn top_n_ids(
+/// Keyword search using the native full-text search of Postgres on the `embedded_text` column.
+/// Scores are `ts_rank` ranks, higher being better.
+///
+/// To speed up keyword searches, create a GIN index matching the text search configuration:
+/// ```sql
+/// CREATE INDEX IF NOT EXISTS document_text_idx ON documents
+/// USING gin(to_tsvector('english', embedded_text));
+/// ```
+impl<Model: EmbeddingModel> KeywordSearchIndex for PostgresVectorStore<Model> {
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, None).await
+    }
+
+    async fn keyword_top_n_ids(
         &self,
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let embedded_query: pgvector::Vector = self
-            .model
-            .embed_text(query)
-            .await?
-            .vec
-            .iter()
-            .map(|&x| x as f32)
-            .collect::<Vec<f32>>()
-            .into();
+        self.keyword_search_ids(query, n, None).await
+    }
 
-        let rows: Vec<SearchResultOnlyId> = sqlx::query_as(self.search_query_only_ids().as_str())
-            .bind(embedded_query)
-            .bind(n as i64)
-            .fetch_all(&self.pg_pool)
+    async fn keyword_top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, Some(&filter)).await
+    }
+
+    async fn keyword_top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, Some(&filter)).await
+    }
+}
+
+/// Namespace of the uuids derived from the ids of the documents which are not uuids.
+const DOCUMENT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x9a4e_27c3_51d8_4f06_b3e2_7c18_e5f0_4d69);
+
+/// Convert a document id to the uuid stored in the `id` column. Ids which are not uuids are
+/// mapped to a name-based (v5) uuid, so the same id always refers to the same document.
+fn document_uuid(id: &str) -> Uuid {
+    Uuid::parse_str(id).unwrap_or_else(|_| Uuid::new_v5(&DOCUMENT_ID_NAMESPACE, id.as_bytes()))
+}
+
+impl<Model: EmbeddingModel, Doc: Serialize + Send + Sync> WritableVectorStore<Doc>
+    for PostgresVectorStore<Model>
+{
+    /// Insert or replace documents by id. One row is stored per embedding, all with the
+    /// same `id`. Ids which are not uuids are converted to uuids deterministically, while the
+    /// original id is stored in the `document_id` column and returned by searches.
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        let mut tx = self
+            .pg_pool
+            .begin()
             .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
 
-        let rows: Vec<(f64, String)> = rows
-            .into_iter()
-            .map(|row| (row.distance, row.id.to_string()))
-            .collect();
+        for (document_id, document, embeddings) in documents {
+            let id = document_uuid(&document_id);
+            let json_document = serde_json::to_value(&document)?;
 
-        Ok(rows)
+            sqlx::query(format!("DELETE FROM {} WHERE id = $1", self.documents_table).as_str())
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+
+            for embedding in embeddings {
+                sqlx::query(
+                    format!(
+                        "INSERT INTO {} (id, document_id, document, embedded_text, embedding) VALUES ($1, $2, $3, $4, $5)",
+                        self.documents_table
+                    )
+                    .as_str(),
+                )
+                .bind(id)
+                .bind(&document_id)
+                .bind(&json_document)
+                .bind(&embedding.document)
+                .bind(&embedding.vec)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+            }
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(e.into()))
+    }
+
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        let ids = ids.iter().map(|id| document_uuid(id)).collect::<Vec<_>>();
+
+        sqlx::query(format!("DELETE FROM {} WHERE id = ANY($1)", self.documents_table).as_str())
+            .bind(ids)
+            .execute(&self.pg_pool)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+
+        Ok(())
+    }
+
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        let mut params = Vec::new();
+        let condition = filter_condition(&filter, 0, &mut params);
+        let sql = format!("DELETE FROM {} WHERE {}", self.documents_table, condition);
+
+        let mut query = sqlx::query(sql.as_str());
+        for param in params {
+            query = match param {
+                FilterParam::Path(path) => query.bind(path),
+                FilterParam::Value(value) => query.bind(Json(value)),
+            };
+        }
+
+        query
+            .execute(&self.pg_pool)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        let count: i64 = sqlx::query_scalar(
+            format!("SELECT COUNT(DISTINCT id) FROM {}", self.documents_table).as_str(),
+        )
+        .fetch_one(&self.pg_pool)
+        .await
+        .map_err(|e| VectorStoreError::DatastoreError(e.into()))?;
+
+        Ok(count as usize)
     }
 }
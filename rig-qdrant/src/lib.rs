@@ -1,17 +1,36 @@
 use qdrant_client::{
     qdrant::{
-        point_id::PointIdOptions, PointId, PointStruct, Query, QueryPoints, UpsertPointsBuilder,
+        self, point_id::PointIdOptions, with_payload_selector::SelectorOptions, Condition,
+        DeletePointsBuilder, PayloadIncludeSelector, PointId, PointStruct, Query, QueryPoints,
+        Range, ScrollPointsBuilder, UpsertPointsBuilder, WithPayloadSelector,
     },
     Payload, Qdrant,
 };
 use rig::{
     embeddings::{Embedding, EmbeddingModel},
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{Filter, VectorStoreError, VectorStoreIndex, WritableVectorStore},
     Embed, OneOrMany,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
 use uuid::Uuid;
 
+/// Payload field holding the id of the document a point belongs to, for points inserted with
+/// [WritableVectorStore::upsert_documents]. Searches return this id instead of the point id.
+pub const DOCUMENT_ID_FIELD: &str = "_document_id";
+
+/// Namespace of the ids of the points inserted with [WritableVectorStore::upsert_documents].
+const POINT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x3b0f_5c1e_9d42_4a7b_8e61_2f4c_d0a9_7e15);
+
+/// Id of the point holding the embedding at `index` of the document `id`.
+fn point_id(id: &str, index: usize) -> String {
+    Uuid::new_v5(&POINT_ID_NAMESPACE, format!("{index}:{id}").as_bytes()).to_string()
+}
+
+/// Number of points fetched per request when counting documents.
+const COUNT_PAGE_SIZE: u32 = 1000;
+
 /// Represents a vector store implementation using Qdrant - <https://qdrant.tech/> as the backend.
 pub struct QdrantVectorStore<M: EmbeddingModel> {
     /// Model used to generate embeddings for the vector store
@@ -48,12 +67,92 @@ impl<M: EmbeddingModel> QdrantVectorStore<M> {
         Ok(embedding.vec.iter().map(|&x| x as f32).collect())
     }
 
-    /// Fill in query parameters with the given query and limit.
-    fn prepare_query_params(&self, query: Option<Query>, limit: usize) -> QueryPoints {
+    /// Fill in query parameters with the given query, limit and filter.
+    /// The filter is combined with the filter of the default search parameters, if any.
+    fn prepare_query_params(
+        &self,
+        query: Option<Query>,
+        limit: usize,
+        filter: Option<&Filter>,
+    ) -> Result<QueryPoints, VectorStoreError> {
         let mut params = self.query_params.clone();
         params.query = query;
         params.limit = Some(limit as u64);
-        params
+        if let Some(filter) = filter {
+            let filter = qdrant_filter(filter)?;
+            params.filter = Some(match params.filter.take() {
+                Some(default) => qdrant::Filter::must([default.into(), filter.into()]),
+                None => filter,
+            });
+        }
+        Ok(params)
+    }
+
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let query = match self.query_params.query {
+            Some(ref q) => Some(q.clone()),
+            None => Some(Query::new_nearest(self.generate_query_vector(query).await?)),
+        };
+
+        let params = self.prepare_query_params(query, n, filter)?;
+        let result = self
+            .client
+            .query(params)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        result
+            .result
+            .into_iter()
+            .map(|item| {
+                let payload = serde_json::to_value(item.payload)?;
+                let id = document_id(item.id, &payload)?;
+                let score = item.score as f64;
+                let payload = serde_json::from_value(payload)?;
+                Ok((score, id, payload))
+            })
+            .collect()
+    }
+
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let query = match self.query_params.query {
+            Some(ref q) => Some(q.clone()),
+            None => Some(Query::new_nearest(self.generate_query_vector(query).await?)),
+        };
+
+        let mut params = self.prepare_query_params(query, n, filter)?;
+        // Only the document id is needed from the payload
+        params.with_payload = Some(WithPayloadSelector {
+            selector_options: Some(SelectorOptions::Include(PayloadIncludeSelector {
+                fields: vec![DOCUMENT_ID_FIELD.to_string()],
+            })),
+        });
+        let points = self
+            .client
+            .query(params)
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .result;
+
+        points
+            .into_iter()
+            .map(|point| {
+                let id = document_id(point.id, &serde_json::to_value(point.payload)?)?;
+                Ok((point.score as f64, id))
+            })
+            .collect()
     }
 
     pub async fn insert_documents<Doc: Serialize + Embed + Send>(
@@ -100,6 +199,117 @@ fn stringify_id(id: PointId) -> Result<String, VectorStoreError> {
     }
 }
 
+/// Get the id of the document a point belongs to: the [DOCUMENT_ID_FIELD] of its payload
+/// if set, the point id otherwise.
+fn document_id(point_id: Option<PointId>, payload: &Value) -> Result<String, VectorStoreError> {
+    match payload.get(DOCUMENT_ID_FIELD).and_then(Value::as_str) {
+        Some(id) => Ok(id.to_string()),
+        None => stringify_id(
+            point_id.ok_or_else(|| VectorStoreError::DatastoreError("Missing point ID".into()))?,
+        ),
+    }
+}
+
+/// Translate a [Filter] to a Qdrant filter on the payload of the points.
+/// Qdrant only supports ranges over numbers and exact matches on strings, integers and booleans.
+fn qdrant_filter(filter: &Filter) -> Result<qdrant::Filter, VectorStoreError> {
+    Ok(match filter {
+        // An empty `should` clause matches every point, so a filter matching nothing is used instead
+        Filter::Or(filters) if filters.is_empty() => {
+            qdrant::Filter::must_not([qdrant::Filter::default().into()])
+        }
+        Filter::And(filters) => qdrant::Filter::must(
+            filters
+                .iter()
+                .map(|filter| qdrant_filter(filter).map(Condition::from))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Filter::Or(filters) => qdrant::Filter::should(
+            filters
+                .iter()
+                .map(|filter| qdrant_filter(filter).map(Condition::from))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Filter::Not(filter) => qdrant::Filter::must_not([qdrant_filter(filter)?.into()]),
+        Filter::Ne(field, value) => {
+            qdrant::Filter::must_not([qdrant_condition(&Filter::Eq(field.clone(), value.clone()))?])
+        }
+        filter => qdrant::Filter::must([qdrant_condition(filter)?]),
+    })
+}
+
+/// Translate a field comparison to a Qdrant condition.
+fn qdrant_condition(filter: &Filter) -> Result<Condition, VectorStoreError> {
+    let unsupported = || VectorStoreError::UnsupportedFilter(format!("{filter:?}"));
+
+    let number = |value: &Value| value.as_f64().ok_or_else(unsupported);
+
+    Ok(match filter {
+        Filter::Eq(field, Value::String(s)) => Condition::matches(field.clone(), s.clone()),
+        Filter::Eq(field, Value::Bool(b)) => Condition::matches(field.clone(), *b),
+        Filter::Eq(field, Value::Number(n)) => match n.as_i64() {
+            Some(i) => Condition::matches(field.clone(), i),
+            // Floats can't be matched exactly, so a single value range is used instead
+            None => Condition::range(
+                field.clone(),
+                Range {
+                    gte: n.as_f64(),
+                    lte: n.as_f64(),
+                    ..Default::default()
+                },
+            ),
+        },
+        Filter::Eq(field, Value::Null) => Condition::is_null(field.clone()),
+        Filter::In(field, values) if values.iter().all(Value::is_string) => Condition::matches(
+            field.clone(),
+            values
+                .iter()
+                .filter_map(|value| value.as_str().map(str::to_string))
+                .collect::<Vec<_>>(),
+        ),
+        Filter::In(field, values) if values.iter().all(Value::is_i64) => Condition::matches(
+            field.clone(),
+            values.iter().filter_map(Value::as_i64).collect::<Vec<_>>(),
+        ),
+        Filter::In(field, values) => qdrant::Filter::should(
+            values
+                .iter()
+                .map(|value| qdrant_condition(&Filter::Eq(field.clone(), value.clone())))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+        .into(),
+        Filter::Gt(field, value) => Condition::range(
+            field.clone(),
+            Range {
+                gt: Some(number(value)?),
+                ..Default::default()
+            },
+        ),
+        Filter::Gte(field, value) => Condition::range(
+            field.clone(),
+            Range {
+                gte: Some(number(value)?),
+                ..Default::default()
+            },
+        ),
+        Filter::Lt(field, value) => Condition::range(
+            field.clone(),
+            Range {
+                lt: Some(number(value)?),
+                ..Default::default()
+            },
+        ),
+        Filter::Lte(field, value) => Condition::range(
+            field.clone(),
+            Range {
+                lte: Some(number(value)?),
+                ..Default::default()
+            },
+        ),
+        filter => qdrant_filter(filter)?.into(),
+    })
+}
+
 impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for QdrantVectorStore<M> {
     /// Search for the top `n` nearest neighbors to the given query within the Qdrant vector store.
     /// Returns a vector of tuples containing the score, ID, and payload of the nearest neighbors.
@@ -108,31 +318,7 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for QdrantVe
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let query = match self.query_params.query {
-            Some(ref q) => Some(q.clone()),
-            None => Some(Query::new_nearest(self.generate_query_vector(query).await?)),
-        };
-
-        let params = self.prepare_query_params(query, n);
-        let result = self
-            .client
-            .query(params)
-            .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
-
-        result
-            .result
-            .into_iter()
-            .map(|item| {
-                let id =
-                    stringify_id(item.id.ok_or_else(|| {
-                        VectorStoreError::DatastoreError("Missing point ID".into())
-                    })?)?;
-                let score = item.score as f64;
-                let payload = serde_json::from_value(serde_json::to_value(item.payload)?)?;
-                Ok((score, id, payload))
-            })
-            .collect()
+        self.search(query, n, None).await
     }
 
     /// Search for the top `n` nearest neighbors to the given query within the Qdrant vector store.
@@ -142,28 +328,177 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for QdrantVe
         query: &str,
         n: usize,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let query = match self.query_params.query {
-            Some(ref q) => Some(q.clone()),
-            None => Some(Query::new_nearest(self.generate_query_vector(query).await?)),
-        };
+        self.search_ids(query, n, None).await
+    }
 
-        let params = self.prepare_query_params(query, n);
-        let points = self
-            .client
-            .query(params)
-            .await
-            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
-            .result;
+    /// Same as `top_n` but only points whose payload matches the filter are considered.
+    /// The filter is combined with the filter of the default query parameters.
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
 
-        points
+    /// Same as `top_n_ids` but only points whose payload matches the filter are considered.
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
+
+impl<M: EmbeddingModel + std::marker::Sync + Send, Doc: Serialize + Send + Sync>
+    WritableVectorStore<Doc> for QdrantVectorStore<M>
+{
+    /// Insert or replace documents by id. One point is stored per embedding, with the document
+    /// as payload along with its id in the [DOCUMENT_ID_FIELD] field.
+    ///
+    /// Points have deterministic ids derived from the document id and the index of the
+    /// embedding, so the points of a document are overwritten in place. The points left over
+    /// from a previous version with more embeddings are deleted afterwards, so a failed upsert
+    /// never loses the previous version of a document.
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let collection_name = self.query_params.collection_name.clone();
+        let ids = documents
+            .iter()
+            .map(|(id, ..)| id.clone())
+            .collect::<Vec<_>>();
+
+        let points = documents
             .into_iter()
-            .map(|point| {
-                let id =
-                    stringify_id(point.id.ok_or_else(|| {
-                        VectorStoreError::DatastoreError("Missing point ID".into())
-                    })?)?;
-                Ok((point.score as f64, id))
+            .map(|(id, document, embeddings)| {
+                let mut payload = Payload::try_from(serde_json::to_value(&document)?)
+                    .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+                payload.insert(DOCUMENT_ID_FIELD, id.clone());
+
+                Ok(embeddings
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, embedding)| {
+                        PointStruct::new(
+                            point_id(&id, index),
+                            embedding
+                                .vec
+                                .into_iter()
+                                .map(|x| x as f32)
+                                .collect::<Vec<_>>(),
+                            payload.clone(),
+                        )
+                    })
+                    .collect::<Vec<_>>())
             })
-            .collect()
+            .collect::<Result<Vec<_>, VectorStoreError>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let point_ids = points
+            .iter()
+            .filter_map(|point| point.id.clone())
+            .collect::<Vec<_>>();
+
+        self.client
+            .upsert_points(UpsertPointsBuilder::new(&collection_name, points).wait(true))
+            .await
+            .map_err(|err| {
+                VectorStoreError::DatastoreError(format!("Error while upserting: {err}").into())
+            })?;
+
+        // Delete the points of the documents which were not overwritten
+        let stale = qdrant::Filter {
+            must: vec![Condition::matches(DOCUMENT_ID_FIELD, ids)],
+            must_not: vec![Condition::has_id(point_ids)],
+            ..Default::default()
+        };
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(&collection_name)
+                    .points(stale)
+                    .wait(true),
+            )
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let filter = qdrant::Filter::must([Condition::matches(DOCUMENT_ID_FIELD, ids)]);
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(&self.query_params.collection_name)
+                    .points(filter)
+                    .wait(true),
+            )
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// Delete the points whose payload matches the filter.
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(&self.query_params.collection_name)
+                    .points(qdrant_filter(&filter)?)
+                    .wait(true),
+            )
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// Count the distinct document ids of the points (see [DOCUMENT_ID_FIELD]), scrolling
+    /// through the whole collection. The ids of all the points are fetched and held in memory,
+    /// so the cost of counting grows with the size of the collection.
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        let mut ids = HashSet::new();
+        let mut offset = None;
+
+        loop {
+            let mut request = ScrollPointsBuilder::new(&self.query_params.collection_name)
+                .limit(COUNT_PAGE_SIZE)
+                .with_payload(SelectorOptions::Include(PayloadIncludeSelector {
+                    fields: vec![DOCUMENT_ID_FIELD.to_string()],
+                }));
+            if let Some(offset) = offset {
+                request = request.offset(offset);
+            }
+
+            let response = self
+                .client
+                .scroll(request)
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+            for point in response.result {
+                ids.insert(document_id(
+                    point.id,
+                    &serde_json::to_value(point.payload)?,
+                )?);
+            }
+
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => return Ok(ids.len()),
+            }
+        }
     }
 }
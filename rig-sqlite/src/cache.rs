@@ -0,0 +1,121 @@
+use rig::embeddings::{
+    cache::{CacheKey, EmbeddingCache},
+    EmbeddingError,
+};
+use rusqlite::OptionalExtension;
+use tokio_rusqlite::Connection;
+
+/// [EmbeddingCache] storing embeddings in a table of a SQLite database, for use with
+/// [CachedEmbeddingModel](rig::embeddings::cache::CachedEmbeddingModel).
+///
+/// Unlike [SqliteVectorStore](crate::SqliteVectorStore), the cache does not require the
+/// `sqlite-vec` extension. Embeddings are stored as little-endian `f64` values.
+///
+/// # Example
+/// ```rust
+/// use rig::{embeddings::cache::CachedEmbeddingModel, providers::openai};
+/// use rig_sqlite::SqliteEmbeddingCache;
+/// use tokio_rusqlite::Connection;
+///
+/// let conn = Connection::open("embeddings_cache.db").await?;
+/// let cache = SqliteEmbeddingCache::new(conn).await?;
+///
+/// let openai = openai::Client::from_env();
+/// let model = CachedEmbeddingModel::new(
+///     openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL),
+///     openai::TEXT_EMBEDDING_3_SMALL,
+///     cache,
+/// );
+/// ```
+#[derive(Clone)]
+pub struct SqliteEmbeddingCache {
+    conn: Connection,
+    table: String,
+}
+
+impl SqliteEmbeddingCache {
+    /// Create a cache in the `embedding_cache` table of the database, if it does not exist.
+    pub async fn new(conn: Connection) -> Result<Self, EmbeddingError> {
+        Self::with_table(conn, "embedding_cache").await
+    }
+
+    /// Create a cache in the given table of the database, if it does not exist.
+    pub async fn with_table(conn: Connection, table: &str) -> Result<Self, EmbeddingError> {
+        let create_table = format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                model TEXT NOT NULL,
+                text_hash TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                PRIMARY KEY (model, text_hash)
+            )"
+        );
+
+        conn.call(move |conn| {
+            conn.execute_batch(&create_table)?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| EmbeddingError::CacheError(Box::new(e)))?;
+
+        Ok(Self {
+            conn,
+            table: table.to_string(),
+        })
+    }
+}
+
+impl EmbeddingCache for SqliteEmbeddingCache {
+    async fn get(&self, keys: &[CacheKey]) -> Result<Vec<Option<Vec<f64>>>, EmbeddingError> {
+        let keys = keys.to_vec();
+        let query = format!(
+            "SELECT embedding FROM {} WHERE model = ?1 AND text_hash = ?2",
+            self.table
+        );
+
+        self.conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare_cached(&query)?;
+                let mut vecs = Vec::with_capacity(keys.len());
+                for key in &keys {
+                    let bytes = stmt
+                        .query_row((&key.model, &key.text_hash), |row| row.get::<_, Vec<u8>>(0))
+                        .optional()?;
+                    vecs.push(bytes.map(|bytes| {
+                        bytes
+                            .chunks_exact(8)
+                            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+                            .collect()
+                    }));
+                }
+                Ok(vecs)
+            })
+            .await
+            .map_err(|e| EmbeddingError::CacheError(Box::new(e)))
+    }
+
+    async fn insert(&self, entries: Vec<(CacheKey, Vec<f64>)>) -> Result<(), EmbeddingError> {
+        let query = format!(
+            "INSERT OR REPLACE INTO {} (model, text_hash, embedding) VALUES (?1, ?2, ?3)",
+            self.table
+        );
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::from)?;
+                {
+                    let mut stmt = tx.prepare_cached(&query)?;
+                    for (key, vec) in &entries {
+                        let bytes = vec
+                            .iter()
+                            .flat_map(|value| value.to_le_bytes())
+                            .collect::<Vec<_>>();
+                        stmt.execute((&key.model, &key.text_hash, bytes))?;
+                    }
+                }
+                tx.commit().map_err(tokio_rusqlite::Error::from)?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| EmbeddingError::CacheError(Box::new(e)))
+    }
+}
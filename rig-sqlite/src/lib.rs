@@ -1,6 +1,13 @@
+mod cache;
+
+pub use cache::SqliteEmbeddingCache;
+
 use rig::embeddings::{Embedding, EmbeddingModel};
-use rig::vector_store::{VectorStoreError, VectorStoreIndex};
+use rig::vector_store::{
+    Filter, KeywordSearchIndex, VectorStoreError, VectorStoreIndex, WritableVectorStore,
+};
 use rig::OneOrMany;
+use rusqlite::types::Value;
 use serde::Deserialize;
 use std::marker::PhantomData;
 use tokio_rusqlite::Connection;
@@ -144,6 +151,12 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
                 table_name, dims
             ))?;
 
+            // Create full-text index of the embedded texts
+            conn.execute_batch(&format!(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS {}_fts USING fts5(content)",
+                table_name
+            ))?;
+
             conn.execute_batch("COMMIT")?;
             Ok(())
         })
@@ -197,6 +210,18 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
                 table_name
             );
 
+            let content = embeddings
+                .iter()
+                .map(|embedding| embedding.document.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            txn.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {table_name}_fts (rowid, content) VALUES (?1, ?2)"
+                ),
+                rusqlite::params![last_id, content],
+            )?;
+
             let mut stmt = txn.prepare(&embeddings_sql)?;
             for (i, embedding) in embeddings.iter().enumerate() {
                 let vec = serialize_embedding(embedding);
@@ -231,6 +256,113 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> SqliteVec
             .await
             .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
     }
+
+    /// Delete the rows selected by a query returning their rowids, along with their embeddings.
+    fn delete_rows_with_txn(
+        txn: &rusqlite::Transaction<'_>,
+        rowids_sql: &str,
+        params: &[Value],
+    ) -> Result<usize, tokio_rusqlite::Error> {
+        let table_name = T::name();
+
+        txn.execute(
+            &format!("DELETE FROM {table_name}_embeddings WHERE rowid IN ({rowids_sql})"),
+            rusqlite::params_from_iter(params),
+        )?;
+        txn.execute(
+            &format!("DELETE FROM {table_name}_fts WHERE rowid IN ({rowids_sql})"),
+            rusqlite::params_from_iter(params),
+        )?;
+        let deleted = txn.execute(
+            &format!("DELETE FROM {table_name} WHERE rowid IN ({rowids_sql})"),
+            rusqlite::params_from_iter(params),
+        )?;
+
+        Ok(deleted)
+    }
+}
+
+impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable + 'static> WritableVectorStore<T>
+    for SqliteVectorStore<E, T>
+{
+    /// Insert or replace rows by id. The ids must be the ones returned by
+    /// [SqliteVectorStoreTable::id] for the rows.
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, T, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        if let Some((id, doc, _)) = documents.iter().find(|(id, doc, _)| *id != doc.id()) {
+            return Err(VectorStoreError::MissingIdError(format!(
+                "Upserted id {} doesn't match the row id {}",
+                id,
+                doc.id()
+            )));
+        }
+        let this = self.clone();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::from)?;
+                let rowids_sql = format!("SELECT rowid FROM {} WHERE id = ?1", T::name());
+                for (id, doc, embeddings) in documents {
+                    Self::delete_rows_with_txn(&tx, &rowids_sql, &[Value::Text(id)])?;
+                    this.add_rows_with_txn(&tx, vec![(doc, embeddings)])?;
+                }
+                tx.commit().map_err(tokio_rusqlite::Error::from)?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::from)?;
+                let rowids_sql = format!("SELECT rowid FROM {} WHERE id = ?1", T::name());
+                for id in ids {
+                    Self::delete_rows_with_txn(&tx, &rowids_sql, &[Value::Text(id)])?;
+                }
+                tx.commit().map_err(tokio_rusqlite::Error::from)?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    /// Delete the rows matching the filter. Filter fields refer to the columns of the table.
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        let columns = T::schema();
+        let columns = columns.iter().map(|column| column.name).collect::<Vec<_>>();
+        let mut params = Vec::new();
+        let condition = filter_condition(&filter, &columns, &mut params)?;
+        let rowids_sql = format!("SELECT d.rowid FROM {} d WHERE {}", T::name(), condition);
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::from)?;
+                let deleted = Self::delete_rows_with_txn(&tx, &rowids_sql, &params)?;
+                tx.commit().map_err(tokio_rusqlite::Error::from)?;
+                debug!("Deleted {} documents matching filter", deleted);
+                Ok(())
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
+
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        self.conn
+            .call(|conn| {
+                let count: i64 = conn.query_row(
+                    &format!("SELECT COUNT(DISTINCT id) FROM {}", T::name()),
+                    [],
+                    |row| row.get(0),
+                )?;
+                Ok(count as usize)
+            })
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))
+    }
 }
 
 /// SQLite vector store implementation for Rig.
@@ -329,40 +461,157 @@ impl<E: EmbeddingModel + 'static, T: SqliteVectorStoreTable> SqliteVectorIndex<E
     }
 }
 
-impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorStoreIndex
-    for SqliteVectorIndex<E, T>
-{
-    async fn top_n<D: for<'a> Deserialize<'a>>(
+impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> SqliteVectorIndex<E, T> {
+    /// Build the search query selecting the given columns, along with its parameters.
+    /// Without a filter, the `sqlite-vec` KNN query is used. With a filter, the distances
+    /// of the matching rows are computed exhaustively, since the KNN query would only
+    /// return the matching rows among the `n` nearest ones.
+    async fn search_query(
         &self,
         query: &str,
         n: usize,
-    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
-        debug!("Finding top {} matches for query", n);
+        select_cols: &str,
+        filter: Option<&Filter>,
+    ) -> Result<(String, Vec<Value>), VectorStoreError> {
         let embedding = self.embedding_model.embed_text(query).await?;
-        let query_vec: Vec<f32> = serialize_embedding(&embedding);
+        let query_vec = serialize_embedding(&embedding);
+        let table_name = T::name();
+
+        let mut params = vec![
+            Value::Blob(query_vec.as_bytes().to_vec()),
+            Value::Integer(n as i64),
+        ];
+
+        let sql = match filter {
+            None => format!(
+                "SELECT {select_cols}, e.distance
+                FROM {table_name}_embeddings e
+                JOIN {table_name} d ON e.rowid = d.rowid
+                WHERE e.embedding MATCH ?1 AND k = ?2
+                ORDER BY e.distance"
+            ),
+            Some(filter) => {
+                let columns = T::schema();
+                let columns = columns.iter().map(|column| column.name).collect::<Vec<_>>();
+                let condition = filter_condition(filter, &columns, &mut params)?;
+
+                format!(
+                    "SELECT {select_cols}, vec_distance_l2(e.embedding, ?1) AS distance
+                    FROM {table_name}_embeddings e
+                    JOIN {table_name} d ON e.rowid = d.rowid
+                    WHERE {condition}
+                    ORDER BY distance
+                    LIMIT ?2"
+                )
+            }
+        };
+
+        Ok((sql, params))
+    }
+
+    /// Build the FTS5 full-text search query selecting the given columns, along with its
+    /// parameters. Rows containing any of the terms of the query are ranked by BM25 score.
+    /// Returns `None` if the query has no terms.
+    fn keyword_search_query(
+        query: &str,
+        n: usize,
+        select_cols: &str,
+        filter: Option<&Filter>,
+    ) -> Result<Option<(String, Vec<Value>)>, VectorStoreError> {
+        // Terms are quoted so the query isn't parsed as a FTS5 query expression
+        let terms = query
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{term}\""))
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return Ok(None);
+        }
         let table_name = T::name();
 
-        // Get all column names from SqliteVectorStoreTable
+        let mut params = vec![Value::Text(terms.join(" OR ")), Value::Integer(n as i64)];
+        let condition = match filter {
+            Some(filter) => {
+                let columns = T::schema();
+                let columns = columns.iter().map(|column| column.name).collect::<Vec<_>>();
+                format!("AND {}", filter_condition(filter, &columns, &mut params)?)
+            }
+            None => String::new(),
+        };
+
+        // bm25() is lower for better matches, so it is negated to have higher scores be better
+        let sql = format!(
+            "SELECT {select_cols}, -bm25({table_name}_fts) AS score
+            FROM {table_name}_fts
+            JOIN {table_name} d ON {table_name}_fts.rowid = d.rowid
+            WHERE {table_name}_fts MATCH ?1 {condition}
+            ORDER BY score DESC
+            LIMIT ?2"
+        );
+
+        Ok(Some((sql, params)))
+    }
+
+    /// Columns of the table, and the SELECT expression of the columns of the document table.
+    fn select_columns() -> (Vec<&'static str>, String) {
         let columns = T::schema();
         let column_names: Vec<&str> = columns.iter().map(|column| column.name).collect();
 
+        let select_cols = column_names
+            .iter()
+            .map(|column| format!("d.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        (column_names, select_cols)
+    }
+
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<D: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        debug!("Finding top {} matches for query", n);
+
+        let (column_names, select_cols) = Self::select_columns();
+        let (sql, params) = self.search_query(query, n, &select_cols, filter).await?;
+
+        self.query_documents(sql, params, column_names).await
+    }
+
+    /// Shared implementation of the [KeywordSearchIndex] methods returning documents.
+    async fn keyword_search<D: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        debug!("Finding top {} keyword matches for query", n);
+
+        let (column_names, select_cols) = Self::select_columns();
+        match Self::keyword_search_query(query, n, &select_cols, filter)? {
+            Some((sql, params)) => self.query_documents(sql, params, column_names).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Run a search query selecting the columns of the document table followed by the score.
+    async fn query_documents<D: for<'a> Deserialize<'a>>(
+        &self,
+        sql: String,
+        params: Vec<Value>,
+        column_names: Vec<&'static str>,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
         let rows = self
             .store
             .conn
             .call(move |conn| {
-                // Build SELECT statement with all columns
-                let select_cols = column_names.join(", ");
-                let mut stmt = conn.prepare(&format!(
-                    "SELECT d.{}, e.distance 
-                    FROM {}_embeddings e
-                    JOIN {} d ON e.rowid = d.rowid
-                    WHERE e.embedding MATCH ?1 AND k = ?2
-                    ORDER BY e.distance",
-                    select_cols, table_name, table_name
-                ))?;
+                let mut stmt = conn.prepare(&sql)?;
 
                 let rows = stmt
-                    .query_map(rusqlite::params![query_vec.as_bytes().to_vec(), n], |row| {
+                    .query_map(rusqlite::params_from_iter(params), |row| {
                         // Create a map of column names to values
                         let mut map = serde_json::Map::new();
                         for (i, col_name) in column_names.iter().enumerate() {
@@ -398,40 +647,50 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
         Ok(top_n)
     }
 
-    async fn top_n_ids(
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
         debug!("Finding top {} document IDs for query", n);
-        let embedding = self.embedding_model.embed_text(query).await?;
-        let query_vec = serialize_embedding(&embedding);
-        let table_name = T::name();
+        let (sql, params) = self.search_query(query, n, "d.id", filter).await?;
+
+        self.query_ids(sql, params).await
+    }
 
+    /// Shared implementation of the [KeywordSearchIndex] methods returning ids.
+    async fn keyword_search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        debug!("Finding top {} keyword matching document IDs for query", n);
+
+        match Self::keyword_search_query(query, n, "d.id", filter)? {
+            Some((sql, params)) => self.query_ids(sql, params).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Run a search query selecting the id of the documents followed by the score.
+    async fn query_ids(
+        &self,
+        sql: String,
+        params: Vec<Value>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
         let results = self
             .store
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare(&format!(
-                    "SELECT d.id, e.distance 
-                     FROM {0}_embeddings e
-                     JOIN {0} d ON e.rowid = d.rowid
-                     WHERE e.embedding MATCH ?1 AND k = ?2
-                     ORDER BY e.distance",
-                    table_name
-                ))?;
+                let mut stmt = conn.prepare(&sql)?;
 
                 let results = stmt
-                    .query_map(
-                        rusqlite::params![
-                            query_vec
-                                .iter()
-                                .flat_map(|x| x.to_le_bytes())
-                                .collect::<Vec<u8>>(),
-                            n
-                        ],
-                        |row| Ok((row.get::<_, f64>(1)?, row.get::<_, String>(0)?)),
-                    )?
+                    .query_map(rusqlite::params_from_iter(params), |row| {
+                        Ok((row.get::<_, f64>(1)?, row.get::<_, String>(0)?))
+                    })?
                     .collect::<Result<Vec<_>, _>>()?;
                 Ok(results)
             })
@@ -443,6 +702,182 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
     }
 }
 
+impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorStoreIndex
+    for SqliteVectorIndex<E, T>
+{
+    async fn top_n<D: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        self.search(query, n, None).await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
+
+    /// Same as `top_n` but only rows matching the filter are considered.
+    /// Filter fields refer to the columns of the [SqliteVectorStoreTable].
+    async fn top_n_with_filter<D: for<'a> Deserialize<'a>>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
+
+/// Keyword search using a FTS5 full-text index of the embedded texts of the rows, which is
+/// created along with the table. Scores are negated BM25 scores, higher being better.
+///
+/// Rows added before the full-text index was introduced are not indexed.
+impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> KeywordSearchIndex
+    for SqliteVectorIndex<E, T>
+{
+    async fn keyword_top_n<D: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        self.keyword_search(query, n, None).await
+    }
+
+    async fn keyword_top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, None).await
+    }
+
+    /// Same as `keyword_top_n` but only rows matching the filter are considered.
+    /// Filter fields refer to the columns of the [SqliteVectorStoreTable].
+    async fn keyword_top_n_with_filter<D: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
+        self.keyword_search(query, n, Some(&filter)).await
+    }
+
+    async fn keyword_top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, Some(&filter)).await
+    }
+}
+
+/// Translate a [Filter] to a SQL condition on the columns of the document table.
+/// Values are bound as query parameters, numbered after the ones already in `params`.
+fn filter_condition(
+    filter: &Filter,
+    columns: &[&str],
+    params: &mut Vec<Value>,
+) -> Result<String, VectorStoreError> {
+    fn comparison(
+        field: &str,
+        op: &str,
+        value: &serde_json::Value,
+        columns: &[&str],
+        params: &mut Vec<Value>,
+    ) -> Result<String, VectorStoreError> {
+        // Column names can't be bound, so only the columns of the table are accepted
+        if !columns.contains(&field) {
+            return Err(VectorStoreError::UnsupportedFilter(format!(
+                "Unknown column: {field}"
+            )));
+        }
+
+        let value = match value {
+            serde_json::Value::Null => {
+                return Ok(match op {
+                    "=" => format!("d.{field} IS NULL"),
+                    "<>" => format!("d.{field} IS NOT NULL"),
+                    _ => "FALSE".to_string(),
+                })
+            }
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            serde_json::Value::Bool(b) => Value::Text(b.to_string()),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(number.as_f64().unwrap_or_default()),
+            },
+            value => {
+                return Err(VectorStoreError::UnsupportedFilter(format!(
+                    "Cannot compare column {field} with {value}"
+                )))
+            }
+        };
+        params.push(value);
+
+        Ok(match op {
+            "<>" => format!("d.{field} IS NOT ?{}", params.len()),
+            op => format!("d.{field} {op} ?{}", params.len()),
+        })
+    }
+
+    Ok(match filter {
+        Filter::Eq(field, value) => comparison(field, "=", value, columns, params)?,
+        Filter::Ne(field, value) => comparison(field, "<>", value, columns, params)?,
+        Filter::Gt(field, value) => comparison(field, ">", value, columns, params)?,
+        Filter::Gte(field, value) => comparison(field, ">=", value, columns, params)?,
+        Filter::Lt(field, value) => comparison(field, "<", value, columns, params)?,
+        Filter::Lte(field, value) => comparison(field, "<=", value, columns, params)?,
+        Filter::In(field, values) => filter_condition(
+            &Filter::Or(
+                values
+                    .iter()
+                    .map(|value| Filter::Eq(field.clone(), value.clone()))
+                    .collect(),
+            ),
+            columns,
+            params,
+        )?,
+        Filter::And(filters) if filters.is_empty() => "TRUE".to_string(),
+        Filter::Or(filters) if filters.is_empty() => "FALSE".to_string(),
+        Filter::And(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| filter_condition(filter, columns, params))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" AND ")
+        ),
+        Filter::Or(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| filter_condition(filter, columns, params))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" OR ")
+        ),
+        // Comparisons with NULL columns are NULL, which should not match the negated filter either
+        Filter::Not(filter) => format!(
+            "NOT COALESCE({}, FALSE)",
+            filter_condition(filter, columns, params)?
+        ),
+    })
+}
+
 fn serialize_embedding(embedding: &Embedding) -> Vec<f32> {
     embedding.vec.iter().map(|x| *x as f32).collect()
 }
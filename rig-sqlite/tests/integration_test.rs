@@ -1,6 +1,6 @@
 use serde_json::json;
 
-use rig::vector_store::VectorStoreIndex;
+use rig::vector_store::{KeywordSearchIndex, VectorStoreIndex, WritableVectorStore};
 use rig::{
     embeddings::{Embedding, EmbeddingsBuilder},
     providers::openai,
@@ -148,6 +148,13 @@ async fn vector_search_test() {
         .await
         .expect("Could not add embeddings to vector store");
 
+    assert_eq!(
+        WritableVectorStore::<Word>::count(&vector_store)
+            .await
+            .expect("Could not count documents"),
+        3
+    );
+
     // Create a vector index on our vector store
     let index = vector_store.index(model);
 
@@ -165,6 +172,14 @@ async fn vector_search_test() {
             "id": "doc1",
             "definition": "Definition of a *glarb-glarb*: A glarb-glarb is a ancient tool used by the ancestors of the inhabitants of planet Jiro to farm the land.",
         })
+    );
+
+    // Query the full-text index
+    let results = index.keyword_top_n_ids("linglingdong", 3).await.expect("");
+
+    assert_eq!(
+        results.into_iter().map(|(_, id)| id).collect::<Vec<_>>(),
+        vec!["doc2".to_string()]
     )
 }
 
@@ -2,7 +2,9 @@ use std::fmt::Display;
 
 use rig::{
     embeddings::{Embedding, EmbeddingModel},
-    vector_store::{VectorStoreError, VectorStoreIndex},
+    vector_store::{
+        Filter, KeywordSearchIndex, VectorStoreError, VectorStoreIndex, WritableVectorStore,
+    },
     Embed, OneOrMany,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -42,13 +44,20 @@ impl Display for SurrealDistanceFunction {
 #[derive(Debug, Deserialize)]
 struct SearchResult {
     id: Thing,
+    #[serde(default)]
+    document_id: Option<String>,
     document: String,
     distance: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateRecord {
+    /// The id of the document, for records inserted with [WritableVectorStore::upsert_documents].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    document_id: Option<String>,
     document: String,
+    /// The document as an object, on which filters are evaluated.
+    metadata: serde_json::Value,
     embedded_text: String,
     embedding: Vec<f64>,
 }
@@ -56,6 +65,8 @@ pub struct CreateRecord {
 #[derive(Debug, Deserialize)]
 pub struct SearchResultOnlyId {
     id: Thing,
+    #[serde(default)]
+    document_id: Option<String>,
     distance: f64,
 }
 
@@ -63,8 +74,9 @@ impl SearchResult {
     pub fn into_result<T: DeserializeOwned>(self) -> Result<(f64, String, T), VectorStoreError> {
         let document: T =
             serde_json::from_str(&self.document).map_err(VectorStoreError::JsonError)?;
+        let id = self.document_id.unwrap_or_else(|| self.id.id.to_string());
 
-        Ok((self.distance, self.id.id.to_string(), document))
+        Ok((self.distance, id, document))
     }
 }
 
@@ -91,28 +103,119 @@ impl<Model: EmbeddingModel, C: Connection> SurrealVectorStore<Model, C> {
         Self::new(model, surreal, None, SurrealDistanceFunction::Cosine)
     }
 
-    fn search_query_full(&self) -> String {
-        self.search_query(true)
+    fn search_query_full(&self, condition: Option<&str>) -> String {
+        self.search_query(true, condition)
     }
 
-    fn search_query_only_ids(&self) -> String {
-        self.search_query(false)
+    fn search_query_only_ids(&self, condition: Option<&str>) -> String {
+        self.search_query(false, condition)
     }
 
-    fn search_query(&self, with_document: bool) -> String {
+    fn search_query(&self, with_document: bool, condition: Option<&str>) -> String {
         let document = if with_document { ", document" } else { "" };
         let embedded_text = if with_document { ", embedded_text" } else { "" };
+        let condition = condition
+            .map(|condition| format!("where {condition} "))
+            .unwrap_or_default();
         let Self {
             distance_function, ..
         } = self;
         format!(
             "
-               SELECT id {document} {embedded_text}, {distance_function}($vec, embedding) as distance \
-              from type::table($tablename) order by distance desc \
+               SELECT id, document_id {document} {embedded_text}, {distance_function}($vec, embedding) as distance \
+              from type::table($tablename) {condition}order by distance desc \
             LIMIT $limit",
         )
     }
 
+    /// Full-text search query on the `embedded_text` field, ranking the matching records by
+    /// BM25 score. The score is returned as `distance` to share the search result types,
+    /// higher being better.
+    fn keyword_search_query(&self, with_document: bool, condition: Option<&str>) -> String {
+        let document = if with_document { ", document" } else { "" };
+        let condition = condition
+            .map(|condition| format!("AND {condition} "))
+            .unwrap_or_default();
+        format!(
+            "
+               SELECT id, document_id {document}, search::score(1) as distance \
+              from type::table($tablename) where embedded_text @1@ $query {condition}\
+            order by distance desc \
+            LIMIT $limit",
+        )
+    }
+
+    /// Shared implementation of the [KeywordSearchIndex] methods returning documents.
+    async fn keyword_search<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let mut params = Vec::new();
+        let condition = filter
+            .map(|filter| filter_condition(filter, &mut params))
+            .transpose()?;
+
+        let mut request = self
+            .surreal
+            .query(self.keyword_search_query(true, condition.as_deref()))
+            .bind(("query", query.to_string()))
+            .bind(("tablename", self.documents_table.clone()))
+            .bind(("limit", n));
+        for param in params {
+            request = request.bind(param);
+        }
+
+        let rows: Vec<SearchResult> = request
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .take(0)
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .flat_map(SearchResult::into_result)
+            .collect())
+    }
+
+    /// Shared implementation of the [KeywordSearchIndex] methods returning ids.
+    async fn keyword_search_ids(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let mut params = Vec::new();
+        let condition = filter
+            .map(|filter| filter_condition(filter, &mut params))
+            .transpose()?;
+
+        let mut request = self
+            .surreal
+            .query(self.keyword_search_query(false, condition.as_deref()))
+            .bind(("query", query.to_string()))
+            .bind(("tablename", self.documents_table.clone()))
+            .bind(("limit", n));
+        for param in params {
+            request = request.bind(param);
+        }
+
+        let rows: Vec<SearchResultOnlyId> = request
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .take(0)
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let id = row.document_id.unwrap_or_else(|| row.id.id.to_string());
+                (row.distance, id)
+            })
+            .collect())
+    }
+
     pub async fn insert_documents<Doc: Serialize + Embed + Send>(
         &self,
         documents: Vec<(Doc, OneOrMany<Embedding>)>,
@@ -126,7 +229,9 @@ impl<Model: EmbeddingModel, C: Connection> SurrealVectorStore<Model, C> {
                 let embedding: Vec<f64> = embedding.vec;
 
                 let record = CreateRecord {
+                    document_id: None,
                     document: json_document_as_string.clone(),
+                    metadata: json_document.clone(),
                     embedded_text,
                     embedding,
                 };
@@ -143,22 +248,32 @@ impl<Model: EmbeddingModel, C: Connection> SurrealVectorStore<Model, C> {
     }
 }
 
-impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorStore<Model, C> {
-    /// Get the top n documents based on the distance to the given query.
-    /// The result is a list of tuples of the form (score, id, document)
-    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+impl<Model: EmbeddingModel, C: Connection> SurrealVectorStore<Model, C> {
+    /// Shared implementation of `top_n` and `top_n_with_filter`.
+    async fn search<T: for<'a> Deserialize<'a> + Send>(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
         let embedded_query: Vec<f64> = self.model.embed_text(query).await?.vec;
 
-        let mut response = self
+        let mut params = Vec::new();
+        let condition = filter
+            .map(|filter| filter_condition(filter, &mut params))
+            .transpose()?;
+
+        let mut request = self
             .surreal
-            .query(self.search_query_full().as_str())
+            .query(self.search_query_full(condition.as_deref()).as_str())
             .bind(("vec", embedded_query))
             .bind(("tablename", self.documents_table.clone()))
-            .bind(("limit", n))
+            .bind(("limit", n));
+        for param in params {
+            request = request.bind(param);
+        }
+
+        let mut response = request
             .await
             .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
 
@@ -174,11 +289,12 @@ impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorSto
         Ok(rows)
     }
 
-    /// Same as `top_n` but returns the document ids only.
-    async fn top_n_ids(
+    /// Shared implementation of `top_n_ids` and `top_n_ids_with_filter`.
+    async fn search_ids(
         &self,
         query: &str,
         n: usize,
+        filter: Option<&Filter>,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
         let embedded_query: Vec<f32> = self
             .model
@@ -189,12 +305,22 @@ impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorSto
             .map(|&x| x as f32)
             .collect();
 
-        let mut response = self
+        let mut params = Vec::new();
+        let condition = filter
+            .map(|filter| filter_condition(filter, &mut params))
+            .transpose()?;
+
+        let mut request = self
             .surreal
-            .query(self.search_query_only_ids().as_str())
+            .query(self.search_query_only_ids(condition.as_deref()).as_str())
             .bind(("vec", embedded_query))
             .bind(("tablename", self.documents_table.clone()))
-            .bind(("limit", n))
+            .bind(("limit", n));
+        for param in params {
+            request = request.bind(param);
+        }
+
+        let mut response = request
             .await
             .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
 
@@ -202,9 +328,261 @@ impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorSto
             .take::<Vec<SearchResultOnlyId>>(0)
             .unwrap()
             .into_iter()
-            .map(|row| (row.distance, row.id.id.to_string()))
+            .map(|row| {
+                let id = row.document_id.unwrap_or_else(|| row.id.id.to_string());
+                (row.distance, id)
+            })
             .collect();
 
         Ok(rows)
     }
 }
+
+/// Translate a [Filter] to a SurrealQL condition on the `metadata` field of the records.
+/// Values are bound as query parameters named `$filter_{n}`.
+fn filter_condition(
+    filter: &Filter,
+    params: &mut Vec<(String, serde_json::Value)>,
+) -> Result<String, VectorStoreError> {
+    fn comparison(
+        field: &str,
+        op: &str,
+        value: serde_json::Value,
+        params: &mut Vec<(String, serde_json::Value)>,
+    ) -> Result<String, VectorStoreError> {
+        // Field names can't be bound, so only plain identifiers are accepted
+        if !field.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }) {
+            return Err(VectorStoreError::UnsupportedFilter(format!(
+                "Invalid field name: {field}"
+            )));
+        }
+
+        let name = format!("filter_{}", params.len());
+        let condition = format!("metadata.{field} {op} ${name}");
+        params.push((name, value));
+        Ok(condition)
+    }
+
+    Ok(match filter {
+        Filter::Eq(field, value) => comparison(field, "=", value.clone(), params)?,
+        Filter::Ne(field, value) => comparison(field, "!=", value.clone(), params)?,
+        Filter::Gt(field, value) => comparison(field, ">", value.clone(), params)?,
+        Filter::Gte(field, value) => comparison(field, ">=", value.clone(), params)?,
+        Filter::Lt(field, value) => comparison(field, "<", value.clone(), params)?,
+        Filter::Lte(field, value) => comparison(field, "<=", value.clone(), params)?,
+        Filter::In(field, values) => comparison(field, "IN", values.clone().into(), params)?,
+        Filter::And(filters) if filters.is_empty() => "true".to_string(),
+        Filter::Or(filters) if filters.is_empty() => "false".to_string(),
+        Filter::And(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| filter_condition(filter, params))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" AND ")
+        ),
+        Filter::Or(filters) => format!(
+            "({})",
+            filters
+                .iter()
+                .map(|filter| filter_condition(filter, params))
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" OR ")
+        ),
+        Filter::Not(filter) => format!("!({})", filter_condition(filter, params)?),
+    })
+}
+
+impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorStore<Model, C> {
+    /// Get the top n documents based on the distance to the given query.
+    /// The result is a list of tuples of the form (score, id, document)
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, None).await
+    }
+
+    /// Same as `top_n` but returns the document ids only.
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, None).await
+    }
+
+    /// Same as `top_n` but only documents matching the filter are considered.
+    /// Filter fields refer to fields of the documents, which are stored in the `metadata`
+    /// field of the records. Records inserted before `metadata` was introduced never match.
+    async fn top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(query, n, Some(&filter)).await
+    }
+
+    /// Same as `top_n_ids` but only documents matching the filter are considered.
+    async fn top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.search_ids(query, n, Some(&filter)).await
+    }
+}
+
+/// Keyword search using a SurrealDB full-text search index on the `embedded_text` field,
+/// returning the records matching the terms of the query. Scores are BM25 scores, higher
+/// being better.
+///
+/// The index must be defined with a search analyzer and BM25 scoring, e.g.:
+/// ```surql
+/// DEFINE ANALYZER IF NOT EXISTS documents_analyzer TOKENIZERS class FILTERS lowercase, ascii;
+/// DEFINE INDEX IF NOT EXISTS documents_text_index ON documents
+///     FIELDS embedded_text
+///     SEARCH ANALYZER documents_analyzer BM25;
+/// ```
+impl<Model: EmbeddingModel, C: Connection> KeywordSearchIndex for SurrealVectorStore<Model, C> {
+    async fn keyword_top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, None).await
+    }
+
+    async fn keyword_top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, None).await
+    }
+
+    async fn keyword_top_n_with_filter<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.keyword_search(query, n, Some(&filter)).await
+    }
+
+    async fn keyword_top_n_ids_with_filter(
+        &self,
+        query: &str,
+        n: usize,
+        filter: Filter,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        self.keyword_search_ids(query, n, Some(&filter)).await
+    }
+}
+
+impl<Model: EmbeddingModel, C: Connection, Doc: Serialize + Send + Sync> WritableVectorStore<Doc>
+    for SurrealVectorStore<Model, C>
+{
+    /// Insert or replace documents by id, one record per embedding.
+    /// The id of the document is stored in the `document_id` field of its records.
+    async fn upsert_documents(
+        &self,
+        documents: Vec<(String, Doc, OneOrMany<Embedding>)>,
+    ) -> Result<(), VectorStoreError> {
+        <Self as WritableVectorStore<Doc>>::delete_documents(
+            self,
+            documents.iter().map(|(id, ..)| id.clone()).collect(),
+        )
+        .await?;
+
+        for (id, document, embeddings) in documents {
+            let json_document = serde_json::to_value(&document)?;
+            let json_document_as_string = serde_json::to_string(&json_document)?;
+
+            for embedding in embeddings {
+                let record = CreateRecord {
+                    document_id: Some(id.clone()),
+                    document: json_document_as_string.clone(),
+                    metadata: json_document.clone(),
+                    embedded_text: embedding.document,
+                    embedding: embedding.vec,
+                };
+
+                self.surreal
+                    .create::<Option<CreateRecord>>(self.documents_table.clone())
+                    .content(record)
+                    .await
+                    .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete records by document id, or by record id for records not inserted with
+    /// [WritableVectorStore::upsert_documents].
+    async fn delete_documents(&self, ids: Vec<String>) -> Result<(), VectorStoreError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.surreal
+            .query(
+                "DELETE type::table($tablename) \
+                WHERE document_id IN $ids OR (document_id = NONE AND record::id(id) IN $ids)",
+            )
+            .bind(("tablename", self.documents_table.clone()))
+            .bind(("ids", ids))
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .check()
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// Delete the records whose `metadata` matches the filter.
+    async fn delete_documents_with_filter(&self, filter: Filter) -> Result<(), VectorStoreError> {
+        let mut params = Vec::new();
+        let condition = filter_condition(&filter, &mut params)?;
+
+        let mut request = self
+            .surreal
+            .query(format!("DELETE type::table($tablename) WHERE {condition}"))
+            .bind(("tablename", self.documents_table.clone()));
+        for param in params {
+            request = request.bind(param);
+        }
+
+        request
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .check()
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// Count the distinct document ids of the records, using the record id for records not
+    /// inserted with [WritableVectorStore::upsert_documents].
+    async fn count(&self) -> Result<usize, VectorStoreError> {
+        let count: Option<usize> = self
+            .surreal
+            .query(
+                "RETURN array::len(array::distinct(\
+                (SELECT VALUE document_id ?? record::id(id) FROM type::table($tablename))))",
+            )
+            .bind(("tablename", self.documents_table.clone()))
+            .await
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?
+            .take(0)
+            .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+
+        Ok(count.unwrap_or_default())
+    }
+}